@@ -0,0 +1,111 @@
+//! Compares this crate with `rustc-demangle` and, on the Itanium corpus,
+//! where this crate only handles legacy Rust symbols, `cpp_demangle`, and prints the results as a table. Run with
+//!
+//! ```text
+//! cargo bench --features compare --bench compare
+//! ```
+//!
+//! The v0 corpora use this crate's draft of the mangling scheme, which
+//! `rustc-demangle` only partially understands, so the table reports how many
+//! symbols each demangler accepted next to its speed.
+
+extern crate cpp_demangle;
+extern crate rustc_demangle;
+extern crate std_mangle_rs;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use std_mangle_rs::ast::Symbol;
+
+const V0_CORPORA: &[(&str, &str)] = &[
+    ("small", include_str!("corpus/small.txt")),
+    ("median", include_str!("corpus/median.txt")),
+    ("pathological", include_str!("corpus/pathological.txt")),
+];
+
+const ITANIUM_CORPUS: (&str, &str) = ("itanium", include_str!("corpus/itanium.txt"));
+
+type Demangler = fn(&str) -> Option<String>;
+
+const V0_DEMANGLERS: &[(&str, Demangler)] = &[
+    ("std-mangle-rs", std_mangle),
+    ("rustc-demangle", rustc_demangle),
+];
+
+const ITANIUM_DEMANGLERS: &[(&str, Demangler)] = &[
+    ("std-mangle-rs", std_mangle_legacy),
+    ("rustc-demangle", rustc_demangle),
+    ("cpp_demangle", cpp_demangle),
+];
+
+/// How long each demangler runs over each corpus.
+const MEASUREMENT_TIME: Duration = Duration::from_millis(500);
+
+fn std_mangle(mangled: &str) -> Option<String> {
+    Symbol::parse(mangled).ok().map(|symbol| symbol.demangle())
+}
+
+fn std_mangle_legacy(mangled: &str) -> Option<String> {
+    std_mangle_rs::legacy::demangle(mangled).ok()
+}
+
+fn rustc_demangle(mangled: &str) -> Option<String> {
+    rustc_demangle::try_demangle(mangled)
+        .ok()
+        .map(|demangled| demangled.to_string())
+}
+
+fn cpp_demangle(mangled: &str) -> Option<String> {
+    cpp_demangle::Symbol::new(mangled)
+        .ok()
+        .map(|symbol| symbol.to_string())
+}
+
+fn symbols(corpus: &str) -> Vec<&str> {
+    corpus.lines().filter(|line| !line.is_empty()).collect()
+}
+
+/// Returns how many of `symbols` were demangled and the mean time per
+/// symbol.
+fn measure(symbols: &[&str], demangle: Demangler) -> (usize, Duration) {
+    let accepted = symbols.iter().filter(|s| demangle(s).is_some()).count();
+
+    let start = Instant::now();
+    let mut rounds = 0;
+    while start.elapsed() < MEASUREMENT_TIME {
+        for &mangled in symbols {
+            black_box(demangle(black_box(mangled)));
+        }
+        rounds += 1;
+    }
+
+    (accepted, start.elapsed() / (rounds * symbols.len() as u32))
+}
+
+fn main() {
+    println!(
+        "{:<14} {:<16} {:>9} {:>12}",
+        "corpus", "demangler", "accepted", "per symbol"
+    );
+
+    let runs = V0_CORPORA
+        .iter()
+        .map(|&corpus| (corpus, V0_DEMANGLERS))
+        .chain(Some((ITANIUM_CORPUS, ITANIUM_DEMANGLERS)));
+
+    for ((corpus_name, corpus), demanglers) in runs {
+        let symbols = symbols(corpus);
+
+        for &(demangler_name, demangle) in demanglers {
+            let (accepted, time) = measure(&symbols, demangle);
+            println!(
+                "{:<14} {:<16} {:>4}/{:<4} {:>10.2?}",
+                corpus_name,
+                demangler_name,
+                accepted,
+                symbols.len(),
+                time
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Throughput benchmarks over the symbols in `benches/corpus`.
+//!
+//! `small` and `median` are taken from the demangling test data,
+//! `pathological` holds deeply nested, long, and backref-heavy symbols.
+
+#[macro_use]
+extern crate criterion;
+extern crate std_mangle_rs;
+
+use criterion::{black_box, Criterion, Throughput};
+use std_mangle_rs::ast::Symbol;
+
+const CORPORA: &[(&str, &str)] = &[
+    ("small", include_str!("corpus/small.txt")),
+    ("median", include_str!("corpus/median.txt")),
+    ("pathological", include_str!("corpus/pathological.txt")),
+];
+
+fn symbols(corpus: &str) -> Vec<&str> {
+    corpus.lines().filter(|line| !line.is_empty()).collect()
+}
+
+fn bench_corpora<F>(c: &mut Criterion, name: &str, mut f: F)
+where
+    F: FnMut(&str),
+{
+    let mut group = c.benchmark_group(name);
+
+    for &(corpus_name, corpus) in CORPORA {
+        let symbols = symbols(corpus);
+        let bytes = symbols.iter().map(|s| s.len() as u64).sum();
+
+        group.throughput(Throughput::Bytes(bytes));
+        group.bench_function(corpus_name, |b| {
+            b.iter(|| {
+                for &mangled in &symbols {
+                    f(black_box(mangled));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    bench_corpora(c, "parse", |mangled| {
+        black_box(Symbol::parse(mangled).unwrap());
+    });
+}
+
+fn demangle(c: &mut Criterion) {
+    bench_corpora(c, "demangle", |mangled| {
+        black_box(Symbol::parse(mangled).unwrap().demangle());
+    });
+}
+
+fn mangle(c: &mut Criterion) {
+    let parsed: Vec<Vec<Symbol>> = CORPORA
+        .iter()
+        .map(|&(_, corpus)| {
+            symbols(corpus)
+                .into_iter()
+                .map(|s| Symbol::parse(s).unwrap())
+                .collect()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("mangle");
+
+    for (&(corpus_name, _), symbols) in CORPORA.iter().zip(parsed.iter()) {
+        let bytes = symbols.iter().map(|s| s.mangle().len() as u64).sum();
+
+        group.throughput(Throughput::Bytes(bytes));
+        group.bench_function(corpus_name, |b| {
+            b.iter(|| {
+                for symbol in symbols {
+                    black_box(black_box(symbol).mangle());
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn round_trip(c: &mut Criterion) {
+    bench_corpora(c, "round_trip", |mangled| {
+        let mangled = Symbol::parse(mangled).unwrap().mangle();
+        black_box(Symbol::parse(&mangled).unwrap());
+    });
+}
+
+criterion_group!(benches, parse, demangle, mangle, round_trip);
+criterion_main!(benches);
@@ -1,3 +1,6 @@
+#[cfg(feature = "cxx")]
+extern crate cxx_build;
+
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
@@ -24,6 +27,16 @@ fn main() {
             emit_test_case(spec_line, title_line, &mut output);
         }
     }
+
+    // The C++ side of the `cxx` bridge, linked into the library so that C++
+    // code only needs the generated header.
+    #[cfg(feature = "cxx")]
+    {
+        println!("cargo:rerun-if-changed=src/cxx_bridge.rs");
+        cxx_build::bridge("src/cxx_bridge.rs")
+            .std("c++14")
+            .compile("std_mangle_cxx");
+    }
 }
 
 fn emit_test_case(spec_line: &str, title_line: &str, output: &mut impl Write) {
@@ -56,5 +69,10 @@ fn emit_test_case(spec_line: &str, title_line: &str, output: &mut impl Write) {
         "  assert_eq!(demangled_expected, demangled_actual);"
     )
     .unwrap();
+    writeln!(
+        output,
+        "  assert_eq!(ast, ::mangled_symbol_to_ast(&ast.mangle()).unwrap());"
+    )
+    .unwrap();
     writeln!(output, "}}").unwrap();
 }
@@ -0,0 +1,128 @@
+//! A shared library with the Rust demangling entry points of libiberty, the
+//! demangler library of GDB and binutils, so that debuggers and tools that
+//! load it from a shared library can demangle v0 symbols before their own
+//! copy is updated. Preload `libstd_mangle_debugger.so`, or link against it
+//! in place of libiberty's `rust-demangle.o`.
+//!
+//! As in libiberty, strings returned by `rust_demangle()` are allocated with
+//! `malloc()` and freed by the caller with `free()`, and hashes and
+//! disambiguators are only shown with `DMGL_VERBOSE`.
+
+extern crate std_mangle_rs;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic;
+use std::ptr;
+use std_mangle_rs::compat;
+
+/// libiberty's option for showing everything the symbol encodes.
+pub const DMGL_VERBOSE: c_int = 1 << 3;
+
+/// The callback of `rust_demangle_callback()`, which receives the output in
+/// pieces that are not NUL-terminated.
+pub type DemangleCallback = unsafe extern "C" fn(*const c_char, usize, *mut c_void);
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+}
+
+unsafe fn demangle(mangled: *const c_char, options: c_int) -> Option<String> {
+    if mangled.is_null() {
+        return None;
+    }
+    let mangled = CStr::from_ptr(mangled).to_str().ok()?;
+
+    // Unwinding into C is undefined behavior.
+    panic::catch_unwind(|| {
+        let demangled = compat::try_demangle(mangled).ok()?;
+        Some(if options & DMGL_VERBOSE != 0 {
+            demangled.to_string()
+        } else {
+            format!("{:#}", demangled)
+        })
+    })
+    .ok()?
+}
+
+/// Demangles `mangled` into a string allocated with `malloc()`. Returns NULL
+/// if `mangled` is not a Rust symbol or the allocation fails.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rust_demangle(mangled: *const c_char, options: c_int) -> *mut c_char {
+    let demangled = match demangle(mangled, options) {
+        Some(demangled) => demangled,
+        None => return ptr::null_mut(),
+    };
+
+    let out = malloc(demangled.len() + 1) as *mut u8;
+    if !out.is_null() {
+        ptr::copy_nonoverlapping(demangled.as_ptr(), out, demangled.len());
+        *out.add(demangled.len()) = 0;
+    }
+    out as *mut c_char
+}
+
+/// Demangles `mangled` and passes the output to `callback` together with
+/// `opaque`. Returns 1 on success and 0 if `mangled` is not a Rust symbol.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string, and
+/// `callback` must be safe to call with `opaque`.
+#[no_mangle]
+pub unsafe extern "C" fn rust_demangle_callback(
+    mangled: *const c_char,
+    options: c_int,
+    callback: DemangleCallback,
+    opaque: *mut c_void,
+) -> c_int {
+    match demangle(mangled, options) {
+        Some(demangled) => {
+            callback(demangled.as_ptr() as *const c_char, demangled.len(), opaque);
+            1
+        }
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rust_demangle, rust_demangle_callback, DMGL_VERBOSE};
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_void};
+    use std::slice;
+
+    extern "C" {
+        fn free(ptr: *mut c_void);
+    }
+
+    unsafe extern "C" fn append(piece: *const c_char, len: usize, opaque: *mut c_void) {
+        let out = &mut *(opaque as *mut Vec<u8>);
+        out.extend_from_slice(slice::from_raw_parts(piece as *const u8, len));
+    }
+
+    #[test]
+    fn libiberty_entry_points() {
+        let mangled = CString::new("_RNvCs0_7mycrate3foo").unwrap();
+        unsafe {
+            let demangled = rust_demangle(mangled.as_ptr(), 0);
+            assert_eq!("mycrate::foo", CStr::from_ptr(demangled).to_str().unwrap());
+            free(demangled as *mut c_void);
+
+            let mut out = Vec::new();
+            let opaque = &mut out as *mut Vec<u8> as *mut c_void;
+            assert_eq!(
+                1,
+                rust_demangle_callback(mangled.as_ptr(), DMGL_VERBOSE, append, opaque)
+            );
+            assert_eq!(b"mycrate[2]::foo", &out[..]);
+
+            let main = CString::new("main").unwrap();
+            assert!(rust_demangle(main.as_ptr(), 0).is_null());
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! The C interface of `std_mangle_rs::ffi` as a shared and a static
+//! library, `libstd_mangle.so` and `libstd_mangle.a` (or their equivalents
+//! on other platforms), for C and C++ programs to link against with the
+//! header `include/std_mangle.h`. The interface lives in the main crate,
+//! whose own crate types stay `rlib` only so that it still builds without
+//! `std`; this package only links it into libraries.
+
+extern crate std_mangle_rs;
+
+pub use std_mangle_rs::ffi::*;
@@ -0,0 +1,155 @@
+#ifndef STD_MANGLE_H
+#define STD_MANGLE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Options for `smr_demangle_opts()`.
+ */
+typedef struct SmrOptions {
+  /**
+   * Leave out disambiguators other than the indices of closures, and the
+   * instantiating crate, of v0 symbols.
+   */
+  bool strip_disambiguators;
+  /**
+   * Replace the generic arguments of v0 symbols by `_`, which implies
+   * `strip_disambiguators`.
+   */
+  bool strip_generics;
+  /**
+   * Demangle legacy symbols as well as v0 symbols.
+   */
+  bool legacy;
+} SmrOptions;
+
+/**
+ * The functions of the library as a table, for profilers that load it as a
+ * plugin with `dlopen()` and look up `smr_plugin_v1` with `dlsym()`. Later
+ * versions only add fields at the end, so a plugin with a larger `size` or
+ * `version` can be used through this definition.
+ *
+ * All functions accept v0 and legacy symbols and return NULL for anything
+ * else. Their results must be freed with this table's `free` function.
+ */
+typedef struct SmrPluginV1 {
+  /**
+   * The size of this struct in bytes.
+   */
+  size_t size;
+  /**
+   * The version of the interface, `SMR_PLUGIN_VERSION`, which is 1 for
+   * this definition.
+   */
+  uint32_t version;
+  /**
+   * The demangled symbol, like `smr_demangle()`.
+   */
+  char *(*demangle)(const char*);
+  /**
+   * The demangled symbol without hashes, disambiguators and the
+   * instantiating crate.
+   */
+  char *(*demangle_short)(const char*);
+  /**
+   * The demangled symbol with generic arguments replaced by `_`, which
+   * all instances of a generic function share.
+   */
+  char *(*skeleton)(const char*);
+  /**
+   * Frees a string returned by `demangle`, `demangle_short` or `skeleton`
+   * of this table, which the caller owns until then, like `smr_free()`.
+   * Does nothing for NULL. It must not be called on any other memory,
+   * such as strings from `malloc()` or from another copy of the library.
+   */
+  void (*free)(char*);
+} SmrPluginV1;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The plugin table of this library.
+ */
+extern const struct SmrPluginV1 smr_plugin_v1;
+
+/**
+ * Demangles the v0 or legacy Rust symbol `mangled`. Returns NULL if
+ * `mangled` is NULL or not a valid symbol.
+ *
+ * # Safety
+ *
+ * `mangled` must be NULL or point to a NUL-terminated string.
+ */
+char *smr_demangle(const char *mangled);
+
+/**
+ * Demangles the Rust symbol `mangled` as given by `options`, or with all
+ * options off if `options` is NULL. Returns NULL if `mangled` is NULL or
+ * not a valid symbol.
+ *
+ * # Safety
+ *
+ * `mangled` must be NULL or point to a NUL-terminated string, and
+ * `options` must be NULL or point to an `SmrOptions`.
+ */
+char *smr_demangle_opts(const char *mangled, const struct SmrOptions *options);
+
+/**
+ * Frees a string returned by this library. Does nothing for NULL.
+ *
+ * # Safety
+ *
+ * `s` must be NULL or a string returned by this library that has not been
+ * freed yet.
+ */
+void smr_free(char *s);
+
+/**
+ * Analyzes the v0 or legacy symbol `mangled` into a
+ * `std_mangle.v1.SymbolInfo` protobuf message, whose length is stored in
+ * `*len`. Returns NULL if `mangled` is NULL or not a valid symbol. The
+ * message must be freed with `smr_free_bytes()`.
+ *
+ * # Safety
+ *
+ * `mangled` must be NULL or point to a NUL-terminated string, and `len`
+ * must point to a writable `size_t`.
+ */
+uint8_t *smr_analyze_protobuf(const char *mangled, size_t *len);
+
+/**
+ * Frees a message of `len` bytes returned by `smr_analyze_protobuf()`.
+ * Does nothing for NULL.
+ *
+ * # Safety
+ *
+ * `bytes` must be NULL or a message returned by this library that has not
+ * been freed yet, and `len` its length.
+ */
+void smr_free_bytes(uint8_t *bytes, size_t len);
+
+/**
+ * Demangles `mangled` into the buffer `out` of `len` bytes, with the
+ * signature of the `rustc_demangle` C shim that native toolchains look
+ * for. The output is that of `compat::demangle()` with `{}`. Returns `out`,
+ * or NULL if `mangled` is not a Rust symbol or its demangled form and the
+ * terminating NUL do not fit into `out`.
+ *
+ * # Safety
+ *
+ * `mangled` must be NULL or point to a NUL-terminated string, and `out`
+ * must be NULL or point to `len` writable bytes.
+ */
+char *rustc_demangle(const char *mangled, char *out, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* STD_MANGLE_H */
@@ -0,0 +1,683 @@
+//! Attribution of code size to generic items and crates, for finding out
+//! which generics bloat a binary and which instantiations are emitted by
+//! several crates, and detection of patterns that are known to cause bloat.
+
+use ast::*;
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use visit::{self, Visitor};
+
+/// The sizes of symbols grouped by skeleton, by crate, and by instance.
+#[derive(Clone, Default, Debug)]
+pub struct BloatAnalysis {
+    /// The number of symbols that were added.
+    pub symbols: usize,
+    /// The number of those that could not be parsed.
+    pub invalid: usize,
+    /// The total size of the symbols that could be parsed.
+    pub size: u64,
+    skeletons: HashMap<String, SkeletonSize>,
+    crates: HashMap<String, CrateSize>,
+    /// The instantiating crates of every instance, by the instance without
+    /// its instantiating crate.
+    instances: HashMap<String, Instance>,
+    /// The nesting depth and size of every symbol with a nested pattern, by
+    /// the pattern and the skeleton of the symbol.
+    nestings: HashMap<(BloatPattern, String), Vec<(usize, u64)>>,
+}
+
+/// The instances of one generic item.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct SkeletonSize {
+    pub instances: usize,
+    pub size: u64,
+}
+
+/// The size of the code that a crate defines and instantiates.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct CrateSize {
+    /// The size of the symbols of items defined in the crate.
+    pub defined: u64,
+    /// The size of the symbols instantiated by the crate, i.e. those whose
+    /// instantiating crate it is, or whose defining crate it is if they have
+    /// no instantiating crate.
+    pub instantiated: u64,
+}
+
+/// One instance emitted by several crates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Duplicate {
+    /// The demangled instance without its instantiating crate.
+    pub instance: String,
+    /// The crates that emitted it, sorted.
+    pub crates: Vec<String>,
+    /// The size of all copies.
+    pub size: u64,
+    /// The size of all copies but the largest, which one shared copy would
+    /// save.
+    pub wasted: u64,
+}
+
+/// A pattern in symbols that is known to cause bloat.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "kebab-case"))]
+pub enum BloatPattern {
+    /// Iterator adapters of `core` or `std`, like `Map` and `Filter`, nested
+    /// in each other. Every level instantiates all the methods again.
+    IteratorAdapterChain,
+    /// `Box<dyn Future>` nested in each other, e.g. by boxing a future that
+    /// awaits a boxed future.
+    NestedBoxedFuture,
+    /// One instance emitted by many crates.
+    CrossCrateDuplicate,
+}
+
+impl BloatPattern {
+    pub fn name(self) -> &'static str {
+        match self {
+            BloatPattern::IteratorAdapterChain => "iterator-adapter-chain",
+            BloatPattern::NestedBoxedFuture => "nested-boxed-future",
+            BloatPattern::CrossCrateDuplicate => "cross-crate-duplicate",
+        }
+    }
+}
+
+/// When `BloatAnalysis::findings()` reports a pattern.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BloatThresholds {
+    /// The number of nested iterator adapters.
+    pub adapter_depth: usize,
+    /// The number of nested `Box<dyn Future>`.
+    pub boxed_future_depth: usize,
+    /// The number of crates that emit an instance.
+    pub duplicate_crates: usize,
+}
+
+impl Default for BloatThresholds {
+    fn default() -> BloatThresholds {
+        BloatThresholds {
+            adapter_depth: 4,
+            boxed_future_depth: 2,
+            duplicate_crates: 3,
+        }
+    }
+}
+
+/// The symbols with one bloat pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct Finding {
+    pub pattern: BloatPattern,
+    /// The skeleton of the symbols, or the duplicated instance without its
+    /// instantiating crate.
+    pub item: String,
+    /// The number of symbols with the pattern.
+    pub count: usize,
+    /// Their total size.
+    pub size: u64,
+    /// The deepest nesting among the symbols, or the number of crates that
+    /// emit the instance.
+    pub degree: usize,
+}
+
+#[derive(Clone, Default, Debug)]
+struct Instance {
+    crates: BTreeSet<String>,
+    size: u64,
+    largest: u64,
+}
+
+impl BloatAnalysis {
+    pub fn new() -> BloatAnalysis {
+        BloatAnalysis::default()
+    }
+
+    /// Adds the symbol `mangled` of `size` bytes.
+    pub fn add(&mut self, mangled: &str, size: u64) {
+        self.symbols += 1;
+
+        let mut symbol = match Symbol::parse(mangled) {
+            Ok(symbol) => symbol,
+            Err(_) => {
+                self.invalid += 1;
+                return;
+            }
+        };
+        self.size += size;
+
+        let defining = symbol.defining_crate().u_ident.0.to_string();
+        let instantiating = take_instantiating_crate(&mut symbol);
+
+        let skeleton = self
+            .skeletons
+            .entry(symbol.skeleton().demangle())
+            .or_default();
+        skeleton.instances += 1;
+        skeleton.size += size;
+
+        self.crates.entry(defining).or_default().defined += size;
+        self.crates
+            .entry(instantiating.clone())
+            .or_default()
+            .instantiated += size;
+
+        let instance = self.instances.entry(symbol.demangle()).or_default();
+        instance.crates.insert(instantiating);
+        instance.size += size;
+        instance.largest = instance.largest.max(size);
+
+        let mut counter = NestingCounter::default();
+        counter.visit_symbol(&symbol);
+        for &(pattern, depth) in &[
+            (BloatPattern::IteratorAdapterChain, counter.adapters.max),
+            (BloatPattern::NestedBoxedFuture, counter.boxed_futures.max),
+        ] {
+            if depth > 0 {
+                self.nestings
+                    .entry((pattern, symbol.skeleton().demangle()))
+                    .or_default()
+                    .push((depth, size));
+            }
+        }
+    }
+
+    /// The skeletons in order of decreasing total size, ties broken by name.
+    pub fn by_skeleton(&self) -> Vec<(&str, SkeletonSize)> {
+        let mut skeletons: Vec<_> = self
+            .skeletons
+            .iter()
+            .map(|(skeleton, &size)| (&skeleton[..], size))
+            .collect();
+        skeletons.sort_by(|a, b| b.1.size.cmp(&a.1.size).then(a.0.cmp(b.0)));
+        skeletons
+    }
+
+    /// The crates in order of decreasing instantiated size, ties broken by
+    /// name.
+    pub fn by_crate(&self) -> Vec<(&str, CrateSize)> {
+        let mut crates: Vec<_> = self
+            .crates
+            .iter()
+            .map(|(name, &size)| (&name[..], size))
+            .collect();
+        crates.sort_by(|a, b| b.1.instantiated.cmp(&a.1.instantiated).then(a.0.cmp(b.0)));
+        crates
+    }
+
+    /// The instances emitted by more than one crate, in order of decreasing
+    /// wasted size.
+    pub fn duplicates(&self) -> Vec<Duplicate> {
+        let mut duplicates: Vec<_> = self
+            .instances
+            .iter()
+            .filter(|&(_, instance)| instance.crates.len() > 1)
+            .map(|(name, instance)| Duplicate {
+                instance: name.clone(),
+                crates: instance.crates.iter().cloned().collect(),
+                size: instance.size,
+                wasted: instance.size - instance.largest,
+            })
+            .collect();
+        duplicates.sort_by(|a, b| b.wasted.cmp(&a.wasted).then(a.instance.cmp(&b.instance)));
+        duplicates
+    }
+
+    /// The bloat patterns that reach `thresholds`, one finding per skeleton
+    /// or duplicated instance, in order of decreasing size.
+    pub fn findings(&self, thresholds: &BloatThresholds) -> Vec<Finding> {
+        let mut findings: Vec<_> = self
+            .nestings
+            .iter()
+            .filter_map(|((pattern, skeleton), symbols)| {
+                let threshold = match *pattern {
+                    BloatPattern::IteratorAdapterChain => thresholds.adapter_depth,
+                    _ => thresholds.boxed_future_depth,
+                };
+                let matching: Vec<_> = symbols
+                    .iter()
+                    .filter(|&&(depth, _)| depth >= threshold)
+                    .collect();
+                if matching.is_empty() {
+                    return None;
+                }
+                Some(Finding {
+                    pattern: *pattern,
+                    item: skeleton.clone(),
+                    count: matching.len(),
+                    size: matching.iter().map(|&&(_, size)| size).sum(),
+                    degree: matching.iter().map(|&&(depth, _)| depth).max().unwrap(),
+                })
+            })
+            .collect();
+
+        findings.extend(
+            self.instances
+                .iter()
+                .filter(|&(_, instance)| instance.crates.len() >= thresholds.duplicate_crates)
+                .map(|(name, instance)| Finding {
+                    pattern: BloatPattern::CrossCrateDuplicate,
+                    item: name.clone(),
+                    count: instance.crates.len(),
+                    size: instance.size,
+                    degree: instance.crates.len(),
+                }),
+        );
+
+        findings.sort_by(|a, b| {
+            b.size
+                .cmp(&a.size)
+                .then(a.pattern.cmp(&b.pattern))
+                .then(a.item.cmp(&b.item))
+        });
+        findings
+    }
+}
+
+/// The deepest nesting of the patterns in one symbol.
+#[derive(Default)]
+struct NestingCounter {
+    adapters: Nesting,
+    boxed_futures: Nesting,
+}
+
+#[derive(Default)]
+struct Nesting {
+    depth: usize,
+    max: usize,
+}
+
+impl Nesting {
+    fn enter(&mut self, matches: bool) {
+        if matches {
+            self.depth += 1;
+            self.max = self.max.max(self.depth);
+        }
+    }
+
+    fn exit(&mut self, matches: bool) {
+        if matches {
+            self.depth -= 1;
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for NestingCounter {
+    fn visit_type(&mut self, ty: &'ast Type) {
+        let adapter = is_iterator_adapter(ty);
+        let boxed_future = is_boxed_future(ty);
+
+        self.adapters.enter(adapter);
+        self.boxed_futures.enter(boxed_future);
+        visit::walk_type(self, ty);
+        self.adapters.exit(adapter);
+        self.boxed_futures.exit(boxed_future);
+    }
+}
+
+/// The names of the crate and the items along `path`, if it names an item
+/// outside an impl.
+fn item_names(mut path: &Path) -> Option<Vec<&str>> {
+    let mut names = Vec::new();
+    loop {
+        path = match *path {
+            Path::CrateRoot { ref id } => {
+                names.push(&*id.u_ident.0);
+                names.reverse();
+                return Some(names);
+            }
+            Path::Nested {
+                ref inner,
+                ref ident,
+                ..
+            } => {
+                names.push(&*ident.u_ident.0);
+                inner
+            }
+            Path::Generic { ref inner, .. } => inner,
+            _ => return None,
+        }
+    }
+}
+
+/// Whether `names` is an item of `core` or `std` in the module `module`.
+fn in_std_module(names: &[&str], module: &str) -> bool {
+    names.len() > 2 && (names[0] == "core" || names[0] == "std") && names[1] == module
+}
+
+fn is_iterator_adapter(ty: &Type) -> bool {
+    match *ty {
+        Type::Named(ref path) => match **path {
+            Path::Generic { .. } => {
+                item_names(path).is_some_and(|names| in_std_module(&names, "iter"))
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_boxed_future(ty: &Type) -> bool {
+    let args = match *ty {
+        Type::Named(ref path) => match **path {
+            Path::Generic { ref args, .. }
+                if item_names(path).is_some_and(|names| names == ["alloc", "boxed", "Box"]) =>
+            {
+                args
+            }
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    match args.first() {
+        Some(GenericArg::Type(Type::DynTrait(bounds, _))) => bounds.traits.iter().any(|t| {
+            item_names(&t.path).is_some_and(|names| {
+                in_std_module(&names, "future") && names.last() == Some(&"Future")
+            })
+        }),
+        _ => false,
+    }
+}
+
+/// The instantiations of one generic item, by their demangled form without
+/// the instantiating crate.
+pub type Instantiations = BTreeMap<String, Instantiation>;
+
+/// How often one instance occurs, and in which crates.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Instantiation {
+    pub count: usize,
+    /// The instantiating crates of the symbols, or their defining crates if
+    /// they have none.
+    pub crates: BTreeSet<String>,
+}
+
+impl Instantiation {
+    /// Whether several crates emitted this instance, which sharing generics
+    /// would avoid.
+    pub fn in_multiple_crates(&self) -> bool {
+        self.crates.len() > 1
+    }
+}
+
+/// Groups `symbols` by their skeleton, with the instantiations of each.
+/// Symbols that do not parse are left out.
+pub fn group_by_skeleton<'a, I>(symbols: I) -> BTreeMap<String, Instantiations>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut groups = BTreeMap::<String, Instantiations>::new();
+
+    for mangled in symbols {
+        let mut symbol = match Symbol::parse(mangled) {
+            Ok(symbol) => symbol,
+            Err(_) => continue,
+        };
+        let instantiating = take_instantiating_crate(&mut symbol);
+
+        let instantiation = groups
+            .entry(symbol.skeleton().demangle())
+            .or_default()
+            .entry(symbol.demangle())
+            .or_default();
+        instantiation.count += 1;
+        instantiation.crates.insert(instantiating);
+    }
+
+    groups
+}
+
+/// The instantiations in `groups` that several crates emitted, with their
+/// skeletons, in order.
+pub fn multi_crate_instantiations(
+    groups: &BTreeMap<String, Instantiations>,
+) -> Vec<(&str, &str, &Instantiation)> {
+    groups
+        .iter()
+        .flat_map(|(skeleton, instantiations)| {
+            instantiations
+                .iter()
+                .filter(|&(_, instantiation)| instantiation.in_multiple_crates())
+                .map(move |(instance, instantiation)| (&skeleton[..], &instance[..], instantiation))
+        })
+        .collect()
+}
+
+/// Removes the instantiating crate from `symbol` and returns its name, or
+/// the name of the defining crate if there is none.
+fn take_instantiating_crate(symbol: &mut Symbol) -> String {
+    let name = symbol
+        .instantiating_crate_ident()
+        .unwrap_or_else(|| symbol.defining_crate())
+        .u_ident
+        .0
+        .to_string();
+    symbol.instantiating_crate = None;
+    name
+}
+
+/// Prints a report with the ten largest entries of every list.
+impl fmt::Display for BloatAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "symbols: {} ({} invalid), {} bytes",
+            self.symbols, self.invalid, self.size
+        )?;
+
+        writeln!(f, "\nbytes per skeleton:")?;
+        for (skeleton, size) in self.by_skeleton().into_iter().take(10) {
+            writeln!(f, "{:>10}  {:>6}  {}", size.size, size.instances, skeleton)?;
+        }
+
+        writeln!(f, "\nbytes per crate (instantiated, defined):")?;
+        for (name, size) in self.by_crate().into_iter().take(10) {
+            writeln!(
+                f,
+                "{:>10}  {:>10}  {}",
+                size.instantiated, size.defined, name
+            )?;
+        }
+
+        writeln!(f, "\nduplicate instantiations (wasted bytes):")?;
+        for duplicate in self.duplicates().into_iter().take(10) {
+            writeln!(
+                f,
+                "{:>10}  {} in {}",
+                duplicate.wasted,
+                duplicate.instance,
+                duplicate.crates.join(", ")
+            )?;
+        }
+
+        writeln!(f, "\nbloat patterns (bytes, symbols, degree):")?;
+        for finding in self
+            .findings(&BloatThresholds::default())
+            .into_iter()
+            .take(10)
+        {
+            writeln!(
+                f,
+                "{:>10}  {:>6}  {:>3}  {}: {}",
+                finding.size,
+                finding.count,
+                finding.degree,
+                finding.pattern.name(),
+                finding.item
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        group_by_skeleton, multi_crate_instantiations, BloatAnalysis, BloatPattern,
+        BloatThresholds, CrateSize, Duplicate, Finding, SkeletonSize,
+    };
+
+    #[test]
+    fn attribute_sizes() {
+        let mut analysis = BloatAnalysis::new();
+        for &(mangled, size) in &[
+            ("_RINxC3std4swaphEC1a", 10),
+            ("_RINxC3std4swaphEC1b", 12),
+            ("_RINxC3std4swapmEC1a", 30),
+            ("_RNxC1a4main", 5),
+            ("_RXX", 100),
+        ] {
+            analysis.add(mangled, size);
+        }
+
+        assert_eq!(
+            (5, 1, 57),
+            (analysis.symbols, analysis.invalid, analysis.size)
+        );
+        assert_eq!(
+            vec![
+                (
+                    "std::swap<_>",
+                    SkeletonSize {
+                        instances: 3,
+                        size: 52
+                    }
+                ),
+                (
+                    "a::main",
+                    SkeletonSize {
+                        instances: 1,
+                        size: 5
+                    }
+                ),
+            ],
+            analysis.by_skeleton()
+        );
+        assert_eq!(
+            vec![
+                (
+                    "a",
+                    CrateSize {
+                        defined: 5,
+                        instantiated: 45
+                    }
+                ),
+                (
+                    "b",
+                    CrateSize {
+                        defined: 0,
+                        instantiated: 12
+                    }
+                ),
+                (
+                    "std",
+                    CrateSize {
+                        defined: 52,
+                        instantiated: 0
+                    }
+                ),
+            ],
+            analysis.by_crate()
+        );
+        assert_eq!(
+            vec![Duplicate {
+                instance: "std::swap<u8>".to_string(),
+                crates: vec!["a".to_string(), "b".to_string()],
+                size: 22,
+                wasted: 10,
+            }],
+            analysis.duplicates()
+        );
+    }
+
+    #[test]
+    fn group_instantiations() {
+        let groups = group_by_skeleton(vec![
+            "_RINxC3std4swaphEC1a",
+            "_RINxC3std4swaphEC1b",
+            "_RINxC3std4swaphEC1b",
+            "_RINxC3std4swapmEC1a",
+            "_RNxC1a4main",
+            "_RXX",
+        ]);
+
+        assert_eq!(
+            vec!["a::main", "std::swap<_>"],
+            groups.keys().collect::<Vec<_>>()
+        );
+        let swap = &groups["std::swap<_>"];
+        assert_eq!(3, swap["std::swap<u8>"].count);
+        assert_eq!(1, swap["std::swap<u32>"].count);
+
+        let multi = multi_crate_instantiations(&groups);
+        assert_eq!(1, multi.len());
+        assert_eq!(("std::swap<_>", "std::swap<u8>"), (multi[0].0, multi[0].1));
+        assert_eq!(vec!["a", "b"], multi[0].2.crates.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn find_bloat_patterns() {
+        let adapter = "INyNyC4core4iter3Map";
+        let boxed = concat!(
+            "INyNyC5alloc5boxed3Box",
+            "DG_NyNyNyC4core6future6future6Futurep6Output"
+        );
+        // foo::run<core::iter::Map<...<u8>>>, with `depth` nested adapters
+        let chain = |depth: usize| {
+            format!(
+                "_RINxC3foo3run{}h{}E",
+                adapter.repeat(depth),
+                "E".repeat(depth)
+            )
+        };
+
+        let mut analysis = BloatAnalysis::new();
+        analysis.add(&chain(5), 100);
+        analysis.add(&chain(4), 50);
+        analysis.add(&chain(2), 10);
+        // foo::spawn<Box<dyn Future<Output = Box<dyn Future<Output = ()>>>>>
+        let future = format!("_RINxC3foo5spawn{}{}uEL_EEL_EE", boxed, boxed);
+        analysis.add(&future, 30);
+        for krate in &["a", "b", "c"] {
+            analysis.add(&format!("_RINxC3std4swaphEC1{}", krate), 5);
+        }
+
+        assert_eq!(
+            vec![
+                Finding {
+                    pattern: BloatPattern::IteratorAdapterChain,
+                    item: "foo::run<_>".to_string(),
+                    count: 2,
+                    size: 150,
+                    degree: 5,
+                },
+                Finding {
+                    pattern: BloatPattern::NestedBoxedFuture,
+                    item: "foo::spawn<_>".to_string(),
+                    count: 1,
+                    size: 30,
+                    degree: 2,
+                },
+                Finding {
+                    pattern: BloatPattern::CrossCrateDuplicate,
+                    item: "std::swap<u8>".to_string(),
+                    count: 3,
+                    size: 15,
+                    degree: 3,
+                },
+            ],
+            analysis.findings(&BloatThresholds::default())
+        );
+        assert!(analysis
+            .findings(&BloatThresholds {
+                adapter_depth: 6,
+                boxed_future_depth: 3,
+                duplicate_crates: 4,
+            })
+            .is_empty());
+    }
+}
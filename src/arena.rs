@@ -0,0 +1,540 @@
+//! An AST allocated in an arena.
+//!
+//! When a large batch of symbols is parsed and dropped together, the reference
+//! counting of the shared subtrees in `ast` is pure overhead. The nodes in
+//! this module are allocated in a `bumpalo::Bump` instead and refer to each
+//! other with plain references, so dropping the arena frees all symbols at
+//! once.
+//! Identifiers are copied into the arena as well, so the parsed symbols do not
+//! borrow from the input.
+
+use ast::{self, BasicType, Binder, DecimalNumber, Disambiguator, Lifetime, Namespace, Ptr};
+use bumpalo::Bump;
+use grammar::{self, Builder};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Symbol<'a> {
+    pub version: Option<DecimalNumber>,
+    pub path: &'a Path<'a>,
+    pub instantiating_crate: Option<&'a Path<'a>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Ident<'a> {
+    pub dis: Disambiguator,
+    pub u_ident: &'a str,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Path<'a> {
+    CrateRoot {
+        id: Ident<'a>,
+    },
+    InherentImpl {
+        impl_path: ImplPath<'a>,
+        self_type: &'a Type<'a>,
+    },
+    TraitImpl {
+        impl_path: ImplPath<'a>,
+        self_type: &'a Type<'a>,
+        trait_name: &'a Path<'a>,
+    },
+    TraitDef {
+        self_type: &'a Type<'a>,
+        trait_name: &'a Path<'a>,
+    },
+    Nested {
+        ns: u8,
+        inner: &'a Path<'a>,
+        ident: Ident<'a>,
+    },
+    Generic {
+        inner: &'a Path<'a>,
+        args: &'a [GenericArg<'a>],
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ImplPath<'a> {
+    pub dis: Option<Disambiguator>,
+    pub path: &'a Path<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum GenericArg<'a> {
+    Lifetime(u64),
+    Type(&'a Type<'a>),
+    Const(&'a Const<'a>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Type<'a> {
+    BasicType(BasicType),
+    Array(&'a Type<'a>, &'a Const<'a>),
+    Slice(&'a Type<'a>),
+    Named(&'a Path<'a>),
+    Tuple(&'a [Type<'a>]),
+    /// A shared reference; the lifetime is the De Bruijn index, if any.
+    Ref(Option<u64>, &'a Type<'a>),
+    RefMut(Option<u64>, &'a Type<'a>),
+    RawPtrConst(&'a Type<'a>),
+    RawPtrMut(&'a Type<'a>),
+    Fn(&'a FnSig<'a>),
+    DynTrait(&'a DynBounds<'a>, u64),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct FnSig<'a> {
+    pub bound_lifetimes: u64,
+    pub is_unsafe: bool,
+    pub abi: Option<Abi<'a>>,
+    pub param_types: &'a [Type<'a>],
+    pub return_type: &'a Type<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Abi<'a> {
+    C,
+    Named(&'a str),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynBounds<'a> {
+    pub bound_lifetimes: u64,
+    pub traits: &'a [DynTrait<'a>],
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynTrait<'a> {
+    pub path: &'a Path<'a>,
+    pub assoc_type_bindings: &'a [DynTraitAssocBinding<'a>],
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynTraitAssocBinding<'a> {
+    pub ident: &'a str,
+    pub ty: &'a Type<'a>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Const<'a> {
+    Value(&'a Type<'a>, u64),
+    Placeholder(&'a Type<'a>),
+}
+
+/// Parses a mangled symbol, allocating all nodes in `arena`.
+pub fn parse<'a>(arena: &'a Bump, input: &[u8]) -> Result<Symbol<'a>, String> {
+    grammar::parse(input, ArenaBuilder { arena })
+}
+
+struct ArenaBuilder<'a> {
+    arena: &'a Bump,
+}
+
+impl<'a> ArenaBuilder<'a> {
+    fn path(&self, path: Path<'a>) -> &'a Path<'a> {
+        self.arena.alloc(path)
+    }
+
+    fn ty(&self, ty: Type<'a>) -> &'a Type<'a> {
+        self.arena.alloc(ty)
+    }
+
+    fn types(&self, types: Vec<&'a Type<'a>>) -> &'a [Type<'a>] {
+        self.arena.alloc_slice_fill_iter(types.into_iter().copied())
+    }
+}
+
+impl<'a, 'i> Builder<'i> for ArenaBuilder<'a> {
+    type Symbol = Symbol<'a>;
+    type Path = &'a Path<'a>;
+    type ImplPath = ImplPath<'a>;
+    type Ident = Ident<'a>;
+    type UIdent = &'a str;
+    type GenericArg = GenericArg<'a>;
+    type Type = &'a Type<'a>;
+    type FnSig = &'a FnSig<'a>;
+    type DynBounds = &'a DynBounds<'a>;
+    type DynTrait = DynTrait<'a>;
+    type Const = &'a Const<'a>;
+
+    fn symbol(
+        &mut self,
+        version: Option<DecimalNumber>,
+        path: &'a Path<'a>,
+        instantiating_crate: Option<&'a Path<'a>>,
+    ) -> Symbol<'a> {
+        Symbol {
+            version,
+            path,
+            instantiating_crate,
+        }
+    }
+
+    fn crate_root(&mut self, id: Ident<'a>) -> &'a Path<'a> {
+        self.path(Path::CrateRoot { id })
+    }
+
+    fn inherent_impl(&mut self, impl_path: ImplPath<'a>, self_type: &'a Type<'a>) -> &'a Path<'a> {
+        self.path(Path::InherentImpl {
+            impl_path,
+            self_type,
+        })
+    }
+
+    fn trait_impl(
+        &mut self,
+        impl_path: ImplPath<'a>,
+        self_type: &'a Type<'a>,
+        trait_name: &'a Path<'a>,
+    ) -> &'a Path<'a> {
+        self.path(Path::TraitImpl {
+            impl_path,
+            self_type,
+            trait_name,
+        })
+    }
+
+    fn trait_def(&mut self, self_type: &'a Type<'a>, trait_name: &'a Path<'a>) -> &'a Path<'a> {
+        self.path(Path::TraitDef {
+            self_type,
+            trait_name,
+        })
+    }
+
+    fn nested(&mut self, ns: Namespace, inner: &'a Path<'a>, ident: Ident<'a>) -> &'a Path<'a> {
+        self.path(Path::Nested {
+            ns: ns.0,
+            inner,
+            ident,
+        })
+    }
+
+    fn generic(&mut self, inner: &'a Path<'a>, args: Vec<GenericArg<'a>>) -> &'a Path<'a> {
+        self.path(Path::Generic {
+            inner,
+            args: self.arena.alloc_slice_fill_iter(args),
+        })
+    }
+
+    fn impl_path(&mut self, dis: Option<Disambiguator>, path: &'a Path<'a>) -> ImplPath<'a> {
+        ImplPath { dis, path }
+    }
+
+    fn ident(&mut self, dis: Disambiguator, u_ident: &'a str) -> Ident<'a> {
+        Ident { dis, u_ident }
+    }
+
+    fn uident(&mut self, raw: &'i str, decoded: Option<String>) -> &'a str {
+        match decoded {
+            Some(decoded) => self.arena.alloc_str(&decoded),
+            None => self.arena.alloc_str(raw),
+        }
+    }
+
+    fn lifetime_arg(&mut self, lifetime: Lifetime) -> GenericArg<'a> {
+        GenericArg::Lifetime(lifetime.debruijn_index.value())
+    }
+
+    fn type_arg(&mut self, ty: &'a Type<'a>) -> GenericArg<'a> {
+        GenericArg::Type(ty)
+    }
+
+    fn const_arg(&mut self, k: &'a Const<'a>) -> GenericArg<'a> {
+        GenericArg::Const(k)
+    }
+
+    fn basic_type(&mut self, bt: BasicType) -> &'a Type<'a> {
+        self.ty(Type::BasicType(bt))
+    }
+
+    fn named(&mut self, path: &'a Path<'a>) -> &'a Type<'a> {
+        self.ty(Type::Named(path))
+    }
+
+    fn array(&mut self, inner: &'a Type<'a>, len: &'a Const<'a>) -> &'a Type<'a> {
+        self.ty(Type::Array(inner, len))
+    }
+
+    fn slice(&mut self, inner: &'a Type<'a>) -> &'a Type<'a> {
+        self.ty(Type::Slice(inner))
+    }
+
+    fn tuple(&mut self, components: Vec<&'a Type<'a>>) -> &'a Type<'a> {
+        let components = self.types(components);
+        self.ty(Type::Tuple(components))
+    }
+
+    fn reference(
+        &mut self,
+        mutable: bool,
+        lt: Option<Lifetime>,
+        inner: &'a Type<'a>,
+    ) -> &'a Type<'a> {
+        let lt = lt.map(|lt| lt.debruijn_index.value());
+        if mutable {
+            self.ty(Type::RefMut(lt, inner))
+        } else {
+            self.ty(Type::Ref(lt, inner))
+        }
+    }
+
+    fn raw_ptr(&mut self, mutable: bool, inner: &'a Type<'a>) -> &'a Type<'a> {
+        if mutable {
+            self.ty(Type::RawPtrMut(inner))
+        } else {
+            self.ty(Type::RawPtrConst(inner))
+        }
+    }
+
+    fn fn_type(&mut self, fn_sig: &'a FnSig<'a>) -> &'a Type<'a> {
+        self.ty(Type::Fn(fn_sig))
+    }
+
+    fn dyn_trait_type(&mut self, bounds: &'a DynBounds<'a>, lt: Lifetime) -> &'a Type<'a> {
+        self.ty(Type::DynTrait(bounds, lt.debruijn_index.value()))
+    }
+
+    fn fn_sig(
+        &mut self,
+        binder: Binder,
+        is_unsafe: bool,
+        abi: Option<grammar::Abi<&'a str>>,
+        param_types: Vec<&'a Type<'a>>,
+        return_type: &'a Type<'a>,
+    ) -> &'a FnSig<'a> {
+        let param_types = self.types(param_types);
+        self.arena.alloc(FnSig {
+            bound_lifetimes: binder.count.value(),
+            is_unsafe,
+            abi: abi.map(|abi| match abi {
+                grammar::Abi::C => Abi::C,
+                grammar::Abi::Named(name) => Abi::Named(name),
+            }),
+            param_types,
+            return_type,
+        })
+    }
+
+    fn dyn_bounds(&mut self, binder: Binder, traits: Vec<DynTrait<'a>>) -> &'a DynBounds<'a> {
+        self.arena.alloc(DynBounds {
+            bound_lifetimes: binder.count.value(),
+            traits: self.arena.alloc_slice_fill_iter(traits),
+        })
+    }
+
+    fn dyn_trait(
+        &mut self,
+        path: &'a Path<'a>,
+        assoc_type_bindings: Vec<(&'a str, &'a Type<'a>)>,
+    ) -> DynTrait<'a> {
+        DynTrait {
+            path,
+            assoc_type_bindings: self.arena.alloc_slice_fill_iter(
+                assoc_type_bindings
+                    .into_iter()
+                    .map(|(ident, ty)| DynTraitAssocBinding { ident, ty }),
+            ),
+        }
+    }
+
+    fn const_value(&mut self, ty: &'a Type<'a>, value: u64) -> &'a Const<'a> {
+        self.arena.alloc(Const::Value(ty, value))
+    }
+
+    fn const_placeholder(&mut self, ty: &'a Type<'a>) -> &'a Const<'a> {
+        self.arena.alloc(Const::Placeholder(ty))
+    }
+}
+
+fn lifetime(debruijn_index: u64) -> Lifetime {
+    Lifetime {
+        debruijn_index: ast::BinderIndex(debruijn_index),
+    }
+}
+
+fn binder(count: u64) -> Binder {
+    Binder {
+        count: ast::LifetimeCount(count),
+    }
+}
+
+fn uident(ident: &str) -> ast::UIdent {
+    ast::UIdent(ident.into())
+}
+
+impl<'a, 'b> From<&'b Symbol<'a>> for ast::Symbol {
+    fn from(symbol: &'b Symbol<'a>) -> ast::Symbol {
+        ast::Symbol {
+            version: symbol.version,
+            path: symbol.path.into(),
+            instantiating_crate: symbol.instantiating_crate.map(Into::into),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Ident<'a>> for ast::Ident {
+    fn from(ident: &'b Ident<'a>) -> ast::Ident {
+        ast::Ident {
+            dis: ident.dis,
+            u_ident: uident(ident.u_ident),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Path<'a>> for ast::Path {
+    fn from(path: &'b Path<'a>) -> ast::Path {
+        fn convert_impl_path(impl_path: &ImplPath) -> ast::ImplPath {
+            ast::ImplPath {
+                dis: impl_path.dis,
+                path: Ptr::new(impl_path.path.into()),
+            }
+        }
+
+        match *path {
+            Path::CrateRoot { ref id } => ast::Path::CrateRoot { id: id.into() },
+            Path::InherentImpl {
+                ref impl_path,
+                self_type,
+            } => ast::Path::InherentImpl {
+                impl_path: convert_impl_path(impl_path),
+                self_type: self_type.into(),
+            },
+            Path::TraitImpl {
+                ref impl_path,
+                self_type,
+                trait_name,
+            } => ast::Path::TraitImpl {
+                impl_path: convert_impl_path(impl_path),
+                self_type: self_type.into(),
+                trait_name: Ptr::new(trait_name.into()),
+            },
+            Path::TraitDef {
+                self_type,
+                trait_name,
+            } => ast::Path::TraitDef {
+                self_type: self_type.into(),
+                trait_name: Ptr::new(trait_name.into()),
+            },
+            Path::Nested {
+                ns,
+                inner,
+                ref ident,
+            } => ast::Path::Nested {
+                ns: Namespace(ns),
+                inner: Ptr::new(inner.into()),
+                ident: ident.into(),
+            },
+            Path::Generic { inner, args } => ast::Path::Generic {
+                inner: Ptr::new(inner.into()),
+                args: args.iter().map(Into::into).collect(),
+            },
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b GenericArg<'a>> for ast::GenericArg {
+    fn from(arg: &'b GenericArg<'a>) -> ast::GenericArg {
+        match *arg {
+            GenericArg::Lifetime(index) => ast::GenericArg::Lifetime(lifetime(index)),
+            GenericArg::Type(ty) => ast::GenericArg::Type(ty.into()),
+            GenericArg::Const(k) => ast::GenericArg::Const(k.into()),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Type<'a>> for ast::Type {
+    fn from(ty: &'b Type<'a>) -> ast::Type {
+        fn ptr(ty: &Type) -> Ptr<ast::Type> {
+            Ptr::new(ty.into())
+        }
+
+        match *ty {
+            Type::BasicType(bt) => ast::Type::BasicType(bt),
+            Type::Array(inner, len) => ast::Type::Array(ptr(inner), Ptr::new(len.into())),
+            Type::Slice(inner) => ast::Type::Slice(ptr(inner)),
+            Type::Named(path) => ast::Type::Named(Ptr::new(path.into())),
+            Type::Tuple(components) => {
+                ast::Type::Tuple(components.iter().map(Into::into).collect())
+            }
+            Type::Ref(lt, inner) => ast::Type::Ref(lt.map(lifetime), ptr(inner)),
+            Type::RefMut(lt, inner) => ast::Type::RefMut(lt.map(lifetime), ptr(inner)),
+            Type::RawPtrConst(inner) => ast::Type::RawPtrConst(ptr(inner)),
+            Type::RawPtrMut(inner) => ast::Type::RawPtrMut(ptr(inner)),
+            Type::Fn(fn_sig) => ast::Type::Fn(Ptr::new(fn_sig.into())),
+            Type::DynTrait(bounds, lt) => {
+                ast::Type::DynTrait(Ptr::new(bounds.into()), lifetime(lt))
+            }
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b FnSig<'a>> for ast::FnSig {
+    fn from(fn_sig: &'b FnSig<'a>) -> ast::FnSig {
+        ast::FnSig {
+            binder: binder(fn_sig.bound_lifetimes),
+            is_unsafe: fn_sig.is_unsafe,
+            abi: fn_sig.abi.map(|abi| match abi {
+                Abi::C => ast::Abi::C,
+                Abi::Named(name) => ast::Abi::Named(uident(name)),
+            }),
+            param_types: fn_sig.param_types.iter().map(Into::into).collect(),
+            return_type: fn_sig.return_type.into(),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b DynBounds<'a>> for ast::DynBounds {
+    fn from(bounds: &'b DynBounds<'a>) -> ast::DynBounds {
+        ast::DynBounds {
+            binder: binder(bounds.bound_lifetimes),
+            traits: bounds
+                .traits
+                .iter()
+                .map(|tr| ast::DynTrait {
+                    path: tr.path.into(),
+                    assoc_type_bindings: tr
+                        .assoc_type_bindings
+                        .iter()
+                        .map(|binding| ast::DynTraitAssocBinding {
+                            ident: uident(binding.ident),
+                            ty: binding.ty.into(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Const<'a>> for ast::Const {
+    fn from(k: &'b Const<'a>) -> ast::Const {
+        match *k {
+            Const::Value(ty, value) => ast::Const::Value(ty.into(), value),
+            Const::Placeholder(ty) => ast::Const::Placeholder(ty.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use ast::Symbol;
+    use bumpalo::Bump;
+
+    #[test]
+    fn arena_matches_owned() {
+        let arena = Bump::new();
+
+        for &mangled in &[
+            "_RNxXC3stdNyNyB2_3foo3FooB9_3bar",
+            "_RINxC7mycrate3bazFG_mEcE",
+            "_RINxC7mycrate3bazAsj309_E",
+            "_RNxMs1_C3fooFG_UKCEu4quux",
+            "_RINxC7mycrate3barmEC16some_other_crate",
+        ] {
+            let symbol = parse(&arena, mangled.as_bytes()).unwrap();
+            assert_eq!(Symbol::parse(mangled).unwrap(), Symbol::from(&symbol));
+        }
+    }
+}
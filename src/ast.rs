@@ -1,24 +1,97 @@
-use std::sync::Arc;
+use ast_demangle::AstDemangle;
+use ast_mangle::{AstMangle, Compress};
+use parse;
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+/// The pointer used for shared subtrees. Enabling the `rc` feature swaps the
+/// atomically reference counted `Arc` for `Rc`, which is cheaper but makes the
+/// AST `!Send`.
+///
+/// Unlike the other features, `rc` takes something away: code that sends
+/// symbols to other threads stops compiling as soon as any crate in the
+/// build enables it. Only a final binary that handles all symbols on one
+/// thread should enable it, and it cannot be combined with `rayon`.
+#[cfg(feature = "rc")]
+pub use std::rc::Rc as Ptr;
+#[cfg(not(feature = "rc"))]
+pub use std::sync::Arc as Ptr;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Base62Number(pub u64);
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct DecimalNumber(pub u64);
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+/// Defines a `u64` newtype that prints as its number.
+macro_rules! number_newtype {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+        #[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+        pub struct $name(pub u64);
+
+        impl $name {
+            pub const fn new(value: u64) -> $name {
+                $name(value)
+            }
+
+            pub fn value(self) -> u64 {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+number_newtype! {
+    /// Distinguishes otherwise identically named items. A value of zero means
+    /// "no disambiguator" and is not printed.
+    Disambiguator
+}
+
+number_newtype! {
+    /// The De Bruijn index of a lifetime, i.e. which enclosing binder it
+    /// refers to.
+    BinderIndex
+}
+
+number_newtype! {
+    /// The number of lifetimes introduced by a binder.
+    LifetimeCount
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Ident {
-    pub dis: Base62Number,
+    pub dis: Disambiguator,
     pub u_ident: UIdent,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
-pub struct UIdent(pub String);
+/// A decoded identifier. The owned AST does not borrow from its input, so
+/// identifiers can be shared between symbols; use `borrowed::Symbol` to
+/// avoid allocating for identifiers that need no decoding.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct UIdent(pub Ptr<str>);
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Namespace(pub u8);
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum Path {
     CrateRoot {
         id: Ident,
@@ -30,102 +103,177 @@ pub enum Path {
     TraitImpl {
         impl_path: ImplPath,
         self_type: Type,
-        trait_name: Arc<Path>,
+        trait_name: Ptr<Path>,
     },
     TraitDef {
         self_type: Type,
-        trait_name: Arc<Path>,
+        trait_name: Ptr<Path>,
     },
     Nested {
         ns: Namespace,
-        inner: Arc<Path>,
+        inner: Ptr<Path>,
         ident: Ident,
     },
     Generic {
-        inner: Arc<Path>,
-        args: Vec<GenericArg>,
+        inner: Ptr<Path>,
+        args: GenericArgs,
     },
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct ImplPath {
     // Should this be optional?
-    pub dis: Option<Base62Number>,
-    pub path: Arc<Path>,
+    pub dis: Option<Disambiguator>,
+    pub path: Ptr<Path>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum GenericArg {
     Lifetime(Lifetime),
     Type(Type),
     Const(Const),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+/// Most argument lists are short, so they are stored inline up to this length.
+const INLINE_LIST_LEN: usize = 3;
+
+pub type GenericArgs = SmallVec<[GenericArg; INLINE_LIST_LEN]>;
+
+/// An empty generic argument list.
+pub const EMPTY_GENERIC_ARGS: GenericArgs = SmallVec::new_const();
+
+/// The parameter types of a `FnSig`. Tuple components stay a `Vec` because
+/// storing them inline would make `Type` infinitely large.
+pub type TypeList = SmallVec<[Type; INLINE_LIST_LEN]>;
+
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Lifetime {
-    pub debruijn_index: Base62Number,
+    pub debruijn_index: BinderIndex,
+}
+
+impl Lifetime {
+    /// The erased lifetime `'_`.
+    pub const ERASED: Lifetime = Lifetime {
+        debruijn_index: BinderIndex(0),
+    };
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Binder {
-    pub count: Base62Number,
+    pub count: LifetimeCount,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+impl Binder {
+    /// A binder that introduces no lifetimes.
+    pub const EMPTY: Binder = Binder {
+        count: LifetimeCount(0),
+    };
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum Type {
     BasicType(BasicType),
-    Array(Arc<Type>, Arc<Const>),
-    Slice(Arc<Type>),
-    Named(Arc<Path>),
+    Array(Ptr<Type>, Ptr<Const>),
+    Slice(Ptr<Type>),
+    Named(Ptr<Path>),
     Tuple(Vec<Type>),
-    Ref(Option<Lifetime>, Arc<Type>),
-    RefMut(Option<Lifetime>, Arc<Type>),
-    RawPtrConst(Arc<Type>),
-    RawPtrMut(Arc<Type>),
-    Fn(Arc<FnSig>),
-    DynTrait(Arc<DynBounds>, Lifetime),
+    Ref(Option<Lifetime>, Ptr<Type>),
+    RefMut(Option<Lifetime>, Ptr<Type>),
+    RawPtrConst(Ptr<Type>),
+    RawPtrMut(Ptr<Type>),
+    Fn(Ptr<FnSig>),
+    DynTrait(Ptr<DynBounds>, Lifetime),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+impl Type {
+    pub const UNIT: Type = Type::BasicType(BasicType::Unit);
+
+    pub const fn basic(bt: BasicType) -> Type {
+        Type::BasicType(bt)
+    }
+
+    /// A tuple type; unlike `UNIT` this is not encoded as a basic type.
+    pub const fn empty_tuple() -> Type {
+        Type::Tuple(Vec::new())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct FnSig {
     pub binder: Binder,
     pub is_unsafe: bool,
     pub abi: Option<Abi>,
-    pub param_types: Vec<Type>,
+    pub param_types: TypeList,
     pub return_type: Type,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+impl FnSig {
+    /// The signature of a safe Rust `fn()`.
+    pub const EMPTY: FnSig = FnSig {
+        binder: Binder::EMPTY,
+        is_unsafe: false,
+        abi: None,
+        param_types: SmallVec::new_const(),
+        return_type: Type::UNIT,
+    };
+}
+
+impl Default for FnSig {
+    fn default() -> FnSig {
+        FnSig::EMPTY
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum Abi {
     C,
     Named(UIdent),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct DynBounds {
     pub binder: Binder,
     pub traits: Vec<DynTrait>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+impl DynBounds {
+    pub const EMPTY: DynBounds = DynBounds {
+        binder: Binder::EMPTY,
+        traits: Vec::new(),
+    };
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct DynTrait {
     pub path: Path,
     pub assoc_type_bindings: Vec<DynTraitAssocBinding>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct DynTraitAssocBinding {
     pub ident: UIdent,
     pub ty: Type,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum Const {
     Value(Type, u64),
     Placeholder(Type),
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub enum BasicType {
     Bool,
     Char,
@@ -150,9 +298,37 @@ pub enum BasicType {
     Placeholder,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct Symbol {
     pub version: Option<DecimalNumber>,
     pub path: Path,
     pub instantiating_crate: Option<Path>,
 }
+
+impl Symbol {
+    /// Parses a mangled symbol name, resolving all backrefs.
+    pub fn parse(mangled: &str) -> Result<Symbol, String> {
+        parse::parse(mangled.as_bytes())
+    }
+
+    /// Produces the compressed, mangled form of this symbol. Panics if an
+    /// identifier cannot be punycode-encoded, which can only happen for
+    /// symbols that were not parsed; `Compress::mangle()` fails instead.
+    pub fn mangle(&self) -> String {
+        AstMangle::mangle(self)
+    }
+
+    /// Produces the mangled form of this symbol without any backrefs. Panics
+    /// like `mangle()`.
+    pub fn mangle_uncompressed(&self) -> String {
+        let mut c = Compress::uncompressed();
+        self.mangle_to_string(&mut c);
+        c.finish()
+    }
+
+    /// Produces the human readable form of this symbol.
+    pub fn demangle(&self) -> String {
+        AstDemangle::demangle(self)
+    }
+}
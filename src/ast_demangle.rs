@@ -1,5 +1,9 @@
 use ast::*;
-use std::fmt::Write;
+use std::cmp;
+use std::fmt::{self, Write};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use visit::{self, Visitor};
 
 pub trait AstDemangle {
     fn demangle_to_string(&self, out: &mut String);
@@ -11,44 +15,290 @@ pub trait AstDemangle {
     }
 }
 
+/// Writes the demangled form of a node to any `Output`, which
+/// `AstDemangle` forwards to with a `String`.
+trait DemangleTo {
+    fn demangle_to<O: Output>(&self, out: &mut O);
+}
+
+macro_rules! forward_to_demangle_to {
+    ($($node:ty),*) => {
+        $(
+            impl AstDemangle for $node {
+                fn demangle_to_string(&self, out: &mut String) {
+                    self.demangle_to(out);
+                }
+            }
+        )*
+    };
+}
+
+forward_to_demangle_to!(
+    Ident,
+    UIdent,
+    Path,
+    DynBounds,
+    GenericArg,
+    Lifetime,
+    Type,
+    FnSig,
+    Abi,
+    DynTrait,
+    DynTraitAssocBinding,
+    Const,
+    BasicType
+);
+
+/// The operations of `String` that the demangler uses.
+trait Output: Write {
+    fn push(&mut self, c: char);
+    fn push_str(&mut self, s: &str);
+    fn pop(&mut self);
+    /// Whether the rest of the output would be thrown away anyway.
+    fn is_full(&self) -> bool;
+}
+
+impl Output for String {
+    fn push(&mut self, c: char) {
+        String::push(self, c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s);
+    }
+
+    fn pop(&mut self) {
+        String::pop(self);
+    }
+
+    fn is_full(&self) -> bool {
+        false
+    }
+}
+
+/// A `String` that stops growing, and is marked as full, once writing to it
+/// would make it longer than `max_len` bytes.
+struct Bounded {
+    out: String,
+    max_len: usize,
+    full: bool,
+}
+
+impl Write for Bounded {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl Output for Bounded {
+    fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if self.full || s.len() > self.max_len - self.out.len() {
+            self.full = true;
+        } else {
+            self.out.push_str(s);
+        }
+    }
+
+    fn pop(&mut self) {
+        if !self.full {
+            self.out.pop();
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.full
+    }
+}
+
+/// Demangles `symbol`, or returns `None` as soon as the output would be
+/// longer than `max_len` bytes. Never allocates more than `max_len` bytes.
+pub(crate) fn demangle_bounded(symbol: &Symbol, max_len: usize) -> Option<String> {
+    let mut out = Bounded {
+        out: String::with_capacity(cmp::min(estimate_len(symbol), max_len)),
+        max_len,
+        full: false,
+    };
+    symbol.demangle_to(&mut out);
+
+    if out.full {
+        None
+    } else {
+        Some(out.out)
+    }
+}
+
 impl AstDemangle for Symbol {
     fn demangle_to_string(&self, out: &mut String) {
-        self.path.demangle_to_string(out);
+        self.demangle_to(out);
+    }
+
+    fn demangle(&self) -> String {
+        let mut out = String::with_capacity(estimate_len(self));
+        self.demangle_to(&mut out);
+        out
+    }
+}
+
+impl DemangleTo for Symbol {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        self.path.demangle_to(out);
 
         if let Some(ref instantiating_crate) = self.instantiating_crate {
             out.push_str(" @ ");
-            instantiating_crate.demangle_to_string(out);
+            instantiating_crate.demangle_to(out);
         }
     }
 }
 
-impl AstDemangle for Ident {
-    fn demangle_to_string(&self, out: &mut String) {
-        self.u_ident.demangle_to_string(out);
-        if self.dis != Base62Number(0) {
-            write!(out, "[{}]", self.dis.0).unwrap();
+/// Estimates the length of the demangled form of `symbol` in a single cheap
+/// pass, so that the output can be allocated once instead of growing
+/// repeatedly. Identifiers and disambiguators are counted exactly and most
+/// punctuation generously, so the estimate errs on the long side, except for
+/// symbols with many long basic types, consts or ABI names, which count as a
+/// few characters each.
+pub fn estimate_len(symbol: &Symbol) -> usize {
+    let mut estimate = LenEstimate(0);
+    estimate.visit_symbol(symbol);
+    estimate.0
+}
+
+struct LenEstimate(usize);
+
+impl<'ast> Visitor<'ast> for LenEstimate {
+    fn visit_symbol(&mut self, symbol: &'ast Symbol) {
+        if symbol.instantiating_crate.is_some() {
+            self.0 += " @ ".len();
+        }
+        visit::walk_symbol(self, symbol);
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        self.0 += match *path {
+            Path::CrateRoot { .. } => 0,
+            Path::InherentImpl { .. } => "<>".len(),
+            Path::TraitImpl { .. } | Path::TraitDef { .. } => "< as >".len(),
+            // The name of a closure is not printed, only its disambiguator.
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } if *ns == Namespace(b'C') => {
+                self.0 += "::{closure}[]".len() + decimal_len(ident.dis.0);
+                self.visit_path(inner);
+                return;
+            }
+            Path::Nested { .. } => "::".len(),
+            Path::Generic { .. } => "<>".len(),
+        };
+        visit::walk_path(self, path);
+    }
+
+    fn visit_impl_path(&mut self, _impl_path: &'ast ImplPath) {
+        // The path of the impl is not part of the demangled form.
+    }
+
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        self.0 += ident.u_ident.0.len();
+        if ident.dis != Disambiguator(0) {
+            self.0 += "[]".len() + decimal_len(ident.dis.0);
+        }
+    }
+
+    fn visit_generic_arg(&mut self, arg: &'ast GenericArg) {
+        self.0 += ",".len();
+        visit::walk_generic_arg(self, arg);
+    }
+
+    fn visit_lifetime(&mut self, _lifetime: &'ast Lifetime) {
+        self.0 += "'_".len();
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        self.0 += match *ty {
+            // Most basic types are spelled with two to five characters.
+            Type::BasicType(_) => 4,
+            Type::Array(..) => "[; ]".len(),
+            Type::Slice(_) => "[]".len(),
+            Type::Named(_) => 0,
+            Type::Tuple(ref components) => "()".len() + components.len(),
+            Type::Ref(..) => "&".len(),
+            Type::RefMut(..) => "&mut ".len(),
+            Type::RawPtrConst(_) => "*const ".len(),
+            Type::RawPtrMut(_) => "*mut ".len(),
+            Type::Fn(_) => "fn() -> ".len(),
+            Type::DynTrait(..) => "dyn ".len(),
+        };
+        visit::walk_type(self, ty);
+    }
+
+    fn visit_fn_sig(&mut self, fn_sig: &'ast FnSig) {
+        if fn_sig.is_unsafe {
+            self.0 += "unsafe ".len();
+        }
+        if fn_sig.abi.is_some() {
+            self.0 += "extern \"C\" ".len();
         }
+        self.0 += fn_sig.param_types.len();
+        visit::walk_fn_sig(self, fn_sig);
+    }
+
+    fn visit_dyn_trait_assoc_binding(&mut self, binding: &'ast DynTraitAssocBinding) {
+        self.0 += binding.ident.0.len() + " = ".len();
+        visit::walk_dyn_trait_assoc_binding(self, binding);
+    }
+
+    fn visit_dyn_trait(&mut self, dyn_trait: &'ast DynTrait) {
+        self.0 += " + ".len();
+        visit::walk_dyn_trait(self, dyn_trait);
+    }
+
+    fn visit_const(&mut self, _k: &'ast Const) {
+        // Most constants are short integers; their type is not printed.
+        self.0 += 4;
     }
 }
 
-impl AstDemangle for UIdent {
-    fn demangle_to_string(&self, out: &mut String) {
+/// The number of decimal digits of `n`.
+fn decimal_len(n: u64) -> usize {
+    n.checked_ilog10().map_or(1, |digits| digits as usize + 1)
+}
+
+impl DemangleTo for Ident {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        self.u_ident.demangle_to(out);
+        if self.dis != Disambiguator(0) {
+            write!(out, "[{}]", self.dis).unwrap();
+        }
+    }
+}
+
+impl DemangleTo for UIdent {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         out.push_str(&self.0[..]);
     }
 }
 
-impl AstDemangle for Path {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for Path {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        if out.is_full() {
+            return;
+        }
+
         match *self {
             Path::CrateRoot { ref id } => {
-                id.demangle_to_string(out);
+                id.demangle_to(out);
             }
             Path::InherentImpl {
                 impl_path: _,
                 ref self_type,
             } => {
                 out.push('<');
-                self_type.demangle_to_string(out);
+                self_type.demangle_to(out);
                 out.push('>');
             }
             Path::TraitImpl {
@@ -61,9 +311,9 @@ impl AstDemangle for Path {
                 ref trait_name,
             } => {
                 out.push('<');
-                self_type.demangle_to_string(out);
+                self_type.demangle_to(out);
                 out.push_str(" as ");
-                trait_name.demangle_to_string(out);
+                trait_name.demangle_to(out);
                 out.push('>');
             }
             Path::Nested {
@@ -71,23 +321,23 @@ impl AstDemangle for Path {
                 ref inner,
                 ref ident,
             } => {
-                inner.demangle_to_string(out);
+                inner.demangle_to(out);
 
                 if *ns == Namespace(b'C') {
-                    write!(out, "::{{closure}}[{}]", ident.dis.0).unwrap();
+                    write!(out, "::{{closure}}[{}]", ident.dis).unwrap();
                 } else if !ident.u_ident.0.is_empty() {
                     out.push_str("::");
-                    ident.demangle_to_string(out);
+                    ident.demangle_to(out);
                 }
             }
             Path::Generic {
                 ref inner,
                 ref args,
             } => {
-                inner.demangle_to_string(out);
+                inner.demangle_to(out);
                 out.push('<');
                 for arg in args {
-                    arg.demangle_to_string(out);
+                    arg.demangle_to(out);
                     out.push(',');
                 }
                 out.pop();
@@ -97,10 +347,10 @@ impl AstDemangle for Path {
     }
 }
 
-impl AstDemangle for DynBounds {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for DynBounds {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         for tr in self.traits.iter() {
-            tr.demangle_to_string(out);
+            tr.demangle_to(out);
             out.push('+');
         }
 
@@ -108,53 +358,57 @@ impl AstDemangle for DynBounds {
     }
 }
 
-impl AstDemangle for GenericArg {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for GenericArg {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         match *self {
             GenericArg::Lifetime(ref lt) => {
-                lt.demangle_to_string(out);
+                lt.demangle_to(out);
             }
             GenericArg::Type(ref ty) => {
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
             }
             GenericArg::Const(ref k) => {
-                k.demangle_to_string(out);
+                k.demangle_to(out);
             }
         }
     }
 }
 
-impl AstDemangle for Lifetime {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for Lifetime {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         out.push_str("'_");
     }
 }
 
-impl AstDemangle for Type {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for Type {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        if out.is_full() {
+            return;
+        }
+
         match *self {
             Type::BasicType(bt) => {
-                bt.demangle_to_string(out);
+                bt.demangle_to(out);
             }
             Type::Array(ref inner, ref len) => {
                 out.push('[');
-                inner.demangle_to_string(out);
+                inner.demangle_to(out);
                 out.push_str("; ");
-                len.demangle_to_string(out);
+                len.demangle_to(out);
                 out.push(']');
             }
             Type::Slice(ref inner) => {
                 out.push('[');
-                inner.demangle_to_string(out);
+                inner.demangle_to(out);
                 out.push(']');
             }
             Type::Named(ref path) => {
-                path.demangle_to_string(out);
+                path.demangle_to(out);
             }
             Type::Tuple(ref inner) => {
                 out.push('(');
                 for ty in inner {
-                    ty.demangle_to_string(out);
+                    ty.demangle_to(out);
                     out.push(',');
                 }
                 out.pop();
@@ -162,39 +416,39 @@ impl AstDemangle for Type {
             }
             Type::Ref(_, ref ty) => {
                 out.push('&');
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
             }
             Type::RefMut(_, ref ty) => {
                 out.push_str("&mut ");
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
             }
             Type::RawPtrConst(ref ty) => {
                 out.push_str("*const ");
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
             }
             Type::RawPtrMut(ref ty) => {
                 out.push_str("*mut ");
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
             }
             Type::Fn(ref fn_sig) => {
-                fn_sig.demangle_to_string(out);
+                fn_sig.demangle_to(out);
             }
             Type::DynTrait(ref bounds, _) => {
-                bounds.demangle_to_string(out);
+                bounds.demangle_to(out);
             }
         }
     }
 }
 
-impl AstDemangle for FnSig {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for FnSig {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         if self.is_unsafe {
             out.push_str("unsafe ");
         }
 
         if let Some(ref abi) = self.abi {
             out.push_str("extern ");
-            abi.demangle_to_string(out);
+            abi.demangle_to(out);
             out.push(' ');
         }
 
@@ -202,7 +456,7 @@ impl AstDemangle for FnSig {
 
         if !self.param_types.is_empty() {
             for param_type in self.param_types.iter() {
-                param_type.demangle_to_string(out);
+                param_type.demangle_to(out);
                 out.push(',');
             }
             out.pop();
@@ -212,35 +466,35 @@ impl AstDemangle for FnSig {
 
         if self.return_type != Type::BasicType(BasicType::Unit) {
             out.push_str(" -> ");
-            self.return_type.demangle_to_string(out);
+            self.return_type.demangle_to(out);
         }
     }
 }
 
-impl AstDemangle for Abi {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for Abi {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         out.push('"');
         match *self {
             Abi::C => {
                 out.push('C');
             }
             Abi::Named(ref ident) => {
-                ident.demangle_to_string(out);
+                ident.demangle_to(out);
             }
         }
         out.push('"');
     }
 }
 
-impl AstDemangle for DynTrait {
-    fn demangle_to_string(&self, out: &mut String) {
-        self.path.demangle_to_string(out);
+impl DemangleTo for DynTrait {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        self.path.demangle_to(out);
 
         if !self.assoc_type_bindings.is_empty() {
             out.push('<');
 
             for binding in self.assoc_type_bindings.iter() {
-                binding.demangle_to_string(out);
+                binding.demangle_to(out);
                 out.push_str(", ");
             }
 
@@ -250,16 +504,16 @@ impl AstDemangle for DynTrait {
     }
 }
 
-impl AstDemangle for DynTraitAssocBinding {
-    fn demangle_to_string(&self, out: &mut String) {
-        self.ident.demangle_to_string(out);
+impl DemangleTo for DynTraitAssocBinding {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
+        self.ident.demangle_to(out);
         out.push('=');
-        self.ty.demangle_to_string(out);
+        self.ty.demangle_to(out);
     }
 }
 
-impl AstDemangle for Const {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for Const {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         match *self {
             Const::Value(Type::BasicType(BasicType::I8), i)
             | Const::Value(Type::BasicType(BasicType::I16), i)
@@ -277,15 +531,15 @@ impl AstDemangle for Const {
             }
             Const::Placeholder(ref ty) | Const::Value(ref ty, _) => {
                 out.push_str("{const ");
-                ty.demangle_to_string(out);
+                ty.demangle_to(out);
                 out.push('}');
             }
         }
     }
 }
 
-impl AstDemangle for BasicType {
-    fn demangle_to_string(&self, out: &mut String) {
+impl DemangleTo for BasicType {
+    fn demangle_to<O: Output>(&self, out: &mut O) {
         out.push_str(match *self {
             BasicType::Bool => "bool",
             BasicType::Char => "char",
@@ -311,3 +565,29 @@ impl AstDemangle for BasicType {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{decimal_len, estimate_len};
+    use ast::Symbol;
+
+    #[test]
+    fn estimate_covers_disambiguators() {
+        assert_eq!(
+            vec![1, 1, 2, 20],
+            [0, 9, 10, u64::MAX]
+                .iter()
+                .map(|&n| decimal_len(n))
+                .collect::<Vec<_>>()
+        );
+
+        // foo[18446744073709551615]::bar::{closure}[18446744073709551615]
+        let symbol = Symbol::parse("_RNCNvCslYGhA16ahyd_3foo3barslYGhA16ahyd_0").unwrap();
+        let demangled = symbol.demangle();
+        assert_eq!(
+            "foo[18446744073709551615]::bar::{closure}[18446744073709551615]",
+            demangled
+        );
+        assert_eq!(demangled.len(), estimate_len(&symbol));
+    }
+}
@@ -0,0 +1,776 @@
+use ast::*;
+use ast_demangle::AstDemangle;
+use charset;
+use collections::{Map, RandomState};
+use int_radix::radix;
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::hash::{BuildHasher, Hash, Hasher};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The compression state used while mangling a single symbol. Every path,
+/// type, and const that has been emitted before is replaced by a backref to
+/// the position of its first occurrence.
+///
+/// `Compress::new().mangle(&symbol)` is what `mangle()` does, and
+/// `Compress::uncompressed()` mangles without backrefs instead. Each value
+/// mangles one symbol.
+pub struct Compress {
+    out: String,
+    paths: SubstTable<Path>,
+    types: SubstTable<Type>,
+    consts: SubstTable<Const>,
+    // Subtrees that are shared within the AST are found by their address
+    // before falling back to hashing their whole structure.
+    shared_paths: Map<ByAddress<Path>, usize>,
+    shared_types: Map<ByAddress<Type>, (EntryKind, usize)>,
+    dictionary: Option<Dictionary>,
+    /// Whether to emit backrefs at all.
+    compress: bool,
+    /// The first identifier that could not be punycode-encoded.
+    error: Option<String>,
+}
+
+/// The positions of the substitutions of one kind, by their structure.
+///
+/// Hashing a node hashes its whole subtree, so the table is keyed by the
+/// hash of each node, which is computed once per lookup and reused when the
+/// node is inserted afterwards. Growing the table then only rehashes `u64`s.
+struct SubstTable<T> {
+    hasher: RandomState,
+    buckets: Map<u64, SmallVec<[(T, usize); 1]>>,
+}
+
+impl<T: Hash + Eq> SubstTable<T> {
+    fn new() -> SubstTable<T> {
+        SubstTable {
+            hasher: RandomState::default(),
+            buckets: Map::new(),
+        }
+    }
+
+    fn hash(&self, node: &T) -> u64 {
+        self.hasher.hash_one(node)
+    }
+
+    fn get(&self, hash: u64, node: &T) -> Option<usize> {
+        self.buckets
+            .get(&hash)?
+            .iter()
+            .find(|entry| entry.0 == *node)
+            .map(|entry| entry.1)
+    }
+
+    fn insert(&mut self, hash: u64, node: T, pos: usize) {
+        self.buckets.entry(hash).or_default().push((node, pos));
+    }
+}
+
+/// A `Ptr` that is compared by address. Holding on to the `Ptr` keeps the
+/// address from being reused by another node.
+struct ByAddress<T>(Ptr<T>);
+
+impl<T> PartialEq for ByAddress<T> {
+    fn eq(&self, other: &ByAddress<T>) -> bool {
+        Ptr::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Eq for ByAddress<T> {}
+
+impl<T> PartialOrd for ByAddress<T> {
+    fn partial_cmp(&self, other: &ByAddress<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ByAddress<T> {
+    fn cmp(&self, other: &ByAddress<T>) -> Ordering {
+        Ptr::as_ptr(&self.0).cmp(&Ptr::as_ptr(&other.0))
+    }
+}
+
+impl<T> Hash for ByAddress<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Ptr::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// Whether `node` may be reached more than once while mangling. Nodes with a
+/// single owner are only ever reached through it, so looking them up by
+/// address is not worth the cost.
+fn is_shared<T>(node: &Ptr<T>) -> bool {
+    Ptr::strong_count(node) > 1
+}
+
+/// Which substitution table a dictionary entry lives in.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "json", serde(rename_all = "lowercase"))]
+pub enum EntryKind {
+    Path,
+    Type,
+    Const,
+}
+
+/// A record of every substitution candidate emitted while mangling a symbol
+/// and of every backref that was used instead of re-emitting one.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+    pub entries: Vec<DictionaryEntry>,
+    pub backrefs: Vec<DictionaryBackref>,
+    open: Vec<usize>,
+    by_start: Map<(EntryKind, usize), usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DictionaryEntry {
+    pub kind: EntryKind,
+    /// Byte range of the entry within the mangled symbol.
+    pub start: usize,
+    pub end: usize,
+    pub demangled: String,
+    /// The innermost entry this one is nested in, if any.
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DictionaryBackref {
+    /// Byte offset of the `B` within the mangled symbol.
+    pub pos: usize,
+    /// The innermost entry the backref occurs in, if any.
+    pub from: Option<usize>,
+    /// The entry the backref points to.
+    pub to: usize,
+}
+
+impl Compress {
+    pub fn new() -> Compress {
+        Compress {
+            out: String::new(),
+            paths: SubstTable::new(),
+            types: SubstTable::new(),
+            consts: SubstTable::new(),
+            shared_paths: Map::new(),
+            shared_types: Map::new(),
+            dictionary: None,
+            compress: true,
+            error: None,
+        }
+    }
+
+    /// Like `new()` but never emits backrefs, so every repeated path, type,
+    /// and const is spelled out in full.
+    pub fn uncompressed() -> Compress {
+        Compress {
+            compress: false,
+            ..Compress::new()
+        }
+    }
+
+    /// Like `new()` but additionally records the substitution dictionary.
+    pub(crate) fn recording() -> Compress {
+        Compress {
+            dictionary: Some(Dictionary::default()),
+            ..Compress::new()
+        }
+    }
+
+    /// Mangles `symbol`, e.g. `Compress::uncompressed().mangle(&symbol)` for
+    /// the symbol without backrefs. Fails if an identifier cannot be
+    /// punycode-encoded, which can only happen for symbols that were not
+    /// parsed.
+    pub fn mangle(mut self, symbol: &Symbol) -> Result<String, String> {
+        symbol.mangle_to_string(&mut self);
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.out),
+        }
+    }
+
+    /// Returns the mangled symbol. Panics if an identifier could not be
+    /// punycode-encoded.
+    pub(crate) fn finish(self) -> String {
+        self.finish_recording().0
+    }
+
+    /// Returns the mangled symbol and, if recording, the dictionary.
+    pub(crate) fn finish_recording(self) -> (String, Option<Dictionary>) {
+        if let Some(error) = self.error {
+            panic!("{}", error);
+        }
+        (self.out, self.dictionary)
+    }
+
+    fn base62_number(&mut self, n: u64) {
+        if n > 0 {
+            write!(self.out, "{}", radix(62, n - 1)).unwrap();
+        }
+        self.out.push('_');
+    }
+
+    fn disambiguator(&mut self, dis: Disambiguator) {
+        if dis.0 > 0 {
+            self.out.push('s');
+            self.base62_number(dis.0 - 1);
+        }
+    }
+
+    fn backref(&mut self, kind: EntryKind, pos: usize) {
+        if let Some(ref mut dictionary) = self.dictionary {
+            dictionary.backrefs.push(DictionaryBackref {
+                pos: self.out.len(),
+                from: dictionary.open.last().cloned(),
+                to: dictionary.by_start[&(kind, pos)],
+            });
+        }
+
+        self.out.push('B');
+        // Backrefs are relative to the end of the `_R` prefix.
+        self.base62_number(pos as u64 - 2);
+    }
+
+    fn enter<T: AstDemangle>(&mut self, kind: EntryKind, node: &T) -> usize {
+        let start = self.out.len();
+
+        if let Some(ref mut dictionary) = self.dictionary {
+            let index = dictionary.entries.len();
+            dictionary.entries.push(DictionaryEntry {
+                kind,
+                start,
+                end: start,
+                demangled: node.demangle(),
+                parent: dictionary.open.last().cloned(),
+            });
+            dictionary.by_start.insert((kind, start), index);
+            dictionary.open.push(index);
+        }
+
+        start
+    }
+
+    fn exit(&mut self) {
+        let end = self.out.len();
+
+        if let Some(ref mut dictionary) = self.dictionary {
+            let index = dictionary.open.pop().unwrap();
+            dictionary.entries[index].end = end;
+        }
+    }
+}
+
+impl Default for Compress {
+    fn default() -> Compress {
+        Compress::new()
+    }
+}
+
+pub trait AstMangle {
+    fn mangle_to_string(&self, c: &mut Compress);
+
+    fn mangle(&self) -> String {
+        let mut c = Compress::new();
+        self.mangle_to_string(&mut c);
+        c.finish()
+    }
+}
+
+impl AstMangle for Symbol {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.out.push_str("_R");
+
+        // Version 0 is the one without a number.
+        if let Some(version) = self.version.and_then(|DecimalNumber(v)| v.checked_sub(1)) {
+            write!(c.out, "{}", version).unwrap();
+        }
+
+        self.path.mangle_to_string(c);
+
+        if let Some(ref instantiating_crate) = self.instantiating_crate {
+            instantiating_crate.mangle_to_string(c);
+        }
+    }
+}
+
+impl AstMangle for Ident {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.disambiguator(self.dis);
+        self.u_ident.mangle_to_string(c);
+    }
+}
+
+impl AstMangle for UIdent {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        if self.0.is_ascii() {
+            write!(c.out, "{}{}", self.0.len(), self.0).unwrap();
+        } else {
+            match charset::encode_punycode_ident(&self.0) {
+                Ok(encoded) => write!(c.out, "u{}{}", encoded.len(), encoded).unwrap(),
+                Err(error) => {
+                    c.error.get_or_insert(error);
+                }
+            }
+        }
+    }
+}
+
+impl AstMangle for Path {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        mangle_path(self, c);
+    }
+}
+
+impl AstMangle for Ptr<Path> {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        mangle_shared_path(self, c);
+    }
+}
+
+fn mangle_shared_path(path: &Ptr<Path>, c: &mut Compress) -> usize {
+    if !c.compress || !is_shared(path) {
+        return mangle_path(path, c);
+    }
+
+    let key = ByAddress(path.clone());
+
+    if let Some(&pos) = c.shared_paths.get(&key) {
+        c.backref(EntryKind::Path, pos);
+        return pos;
+    }
+
+    let pos = mangle_path(path, c);
+    c.shared_paths.insert(key, pos);
+    pos
+}
+
+/// Mangles `path` and returns the position its backrefs point to.
+fn mangle_path(path: &Path, c: &mut Compress) -> usize {
+    let hash = c.paths.hash(path);
+    if c.compress {
+        if let Some(pos) = c.paths.get(hash, path) {
+            c.backref(EntryKind::Path, pos);
+            return pos;
+        }
+    }
+
+    let start = c.enter(EntryKind::Path, path);
+
+    match *path {
+        Path::CrateRoot { ref id } => {
+            c.out.push('C');
+            id.mangle_to_string(c);
+        }
+        Path::InherentImpl {
+            ref impl_path,
+            ref self_type,
+        } => {
+            c.out.push('M');
+            impl_path.mangle_to_string(c);
+            self_type.mangle_to_string(c);
+        }
+        Path::TraitImpl {
+            ref impl_path,
+            ref self_type,
+            ref trait_name,
+        } => {
+            c.out.push('X');
+            impl_path.mangle_to_string(c);
+            self_type.mangle_to_string(c);
+            trait_name.mangle_to_string(c);
+        }
+        Path::TraitDef {
+            ref self_type,
+            ref trait_name,
+        } => {
+            c.out.push('Y');
+            self_type.mangle_to_string(c);
+            trait_name.mangle_to_string(c);
+        }
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            c.out.push('N');
+            c.out.push(ns.0 as char);
+            inner.mangle_to_string(c);
+            ident.mangle_to_string(c);
+        }
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => {
+            c.out.push('I');
+            inner.mangle_to_string(c);
+            for arg in args {
+                arg.mangle_to_string(c);
+            }
+            c.out.push('E');
+        }
+    }
+
+    c.exit();
+    if c.compress {
+        c.paths.insert(hash, path.clone(), start);
+    }
+    start
+}
+
+impl AstMangle for ImplPath {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        if let Some(dis) = self.dis {
+            c.disambiguator(dis);
+        }
+        self.path.mangle_to_string(c);
+    }
+}
+
+impl AstMangle for GenericArg {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        match *self {
+            GenericArg::Lifetime(ref lt) => {
+                lt.mangle_to_string(c);
+            }
+            GenericArg::Type(ref ty) => {
+                ty.mangle_to_string(c);
+            }
+            GenericArg::Const(ref k) => {
+                c.out.push('K');
+                k.mangle_to_string(c);
+            }
+        }
+    }
+}
+
+impl AstMangle for Lifetime {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.out.push('L');
+        c.base62_number(self.debruijn_index.0);
+    }
+}
+
+impl AstMangle for Binder {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.out.push('G');
+        c.base62_number(self.count.0);
+    }
+}
+
+impl AstMangle for Type {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        mangle_type(self, c);
+    }
+}
+
+impl AstMangle for Ptr<Type> {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        if !c.compress || !is_shared(self) {
+            mangle_type(self, c);
+            return;
+        }
+
+        let key = ByAddress(self.clone());
+
+        if let Some(&(kind, pos)) = c.shared_types.get(&key) {
+            c.backref(kind, pos);
+            return;
+        }
+
+        if let Some(substitution) = mangle_type(self, c) {
+            c.shared_types.insert(key, substitution);
+        }
+    }
+}
+
+/// Mangles `ty` and returns the kind and position of the substitution its
+/// backrefs point to, or `None` for basic types, which are never
+/// substituted.
+fn mangle_type(ty: &Type, c: &mut Compress) -> Option<(EntryKind, usize)> {
+    match *ty {
+        Type::BasicType(bt) => {
+            bt.mangle_to_string(c);
+            return None;
+        }
+        // Named types share their encoding (and thus their backrefs) with
+        // the path they consist of.
+        Type::Named(ref path) => {
+            return Some((EntryKind::Path, mangle_shared_path(path, c)));
+        }
+        _ => {}
+    }
+
+    let hash = c.types.hash(ty);
+    if c.compress {
+        if let Some(pos) = c.types.get(hash, ty) {
+            c.backref(EntryKind::Type, pos);
+            return Some((EntryKind::Type, pos));
+        }
+    }
+
+    let start = c.enter(EntryKind::Type, ty);
+
+    match *ty {
+        Type::BasicType(_) | Type::Named(_) => unreachable!(),
+        Type::Array(ref inner, ref len) => {
+            c.out.push('A');
+            inner.mangle_to_string(c);
+            len.mangle_to_string(c);
+        }
+        Type::Slice(ref inner) => {
+            c.out.push('S');
+            inner.mangle_to_string(c);
+        }
+        Type::Tuple(ref components) => {
+            c.out.push('T');
+            for ty in components {
+                ty.mangle_to_string(c);
+            }
+            c.out.push('E');
+        }
+        Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+            let tag = if let Type::Ref(..) = *ty { 'R' } else { 'Q' };
+            c.out.push(tag);
+            if let Some(ref lifetime) = *lifetime {
+                lifetime.mangle_to_string(c);
+            }
+            inner.mangle_to_string(c);
+        }
+        Type::RawPtrConst(ref ty) => {
+            c.out.push('P');
+            ty.mangle_to_string(c);
+        }
+        Type::RawPtrMut(ref ty) => {
+            c.out.push('O');
+            ty.mangle_to_string(c);
+        }
+        Type::Fn(ref fn_sig) => {
+            c.out.push('F');
+            fn_sig.mangle_to_string(c);
+        }
+        Type::DynTrait(ref bounds, ref lifetime) => {
+            c.out.push('D');
+            bounds.mangle_to_string(c);
+            lifetime.mangle_to_string(c);
+        }
+    }
+
+    c.exit();
+    if c.compress {
+        c.types.insert(hash, ty.clone(), start);
+    }
+    Some((EntryKind::Type, start))
+}
+
+impl AstMangle for FnSig {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        self.binder.mangle_to_string(c);
+
+        if self.is_unsafe {
+            c.out.push('U');
+        }
+
+        if let Some(ref abi) = self.abi {
+            c.out.push('K');
+            abi.mangle_to_string(c);
+        }
+
+        for param_type in self.param_types.iter() {
+            param_type.mangle_to_string(c);
+        }
+
+        c.out.push('E');
+
+        self.return_type.mangle_to_string(c);
+    }
+}
+
+impl AstMangle for Abi {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        match *self {
+            Abi::C => {
+                c.out.push('C');
+            }
+            Abi::Named(ref ident) => {
+                ident.mangle_to_string(c);
+            }
+        }
+    }
+}
+
+impl AstMangle for DynBounds {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        self.binder.mangle_to_string(c);
+
+        for tr in self.traits.iter() {
+            tr.mangle_to_string(c);
+        }
+
+        c.out.push('E');
+    }
+}
+
+impl AstMangle for DynTrait {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        self.path.mangle_to_string(c);
+
+        for binding in self.assoc_type_bindings.iter() {
+            binding.mangle_to_string(c);
+        }
+    }
+}
+
+impl AstMangle for DynTraitAssocBinding {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.out.push('p');
+        self.ident.mangle_to_string(c);
+        self.ty.mangle_to_string(c);
+    }
+}
+
+impl AstMangle for Const {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        let hash = c.consts.hash(self);
+        if c.compress {
+            if let Some(pos) = c.consts.get(hash, self) {
+                c.backref(EntryKind::Const, pos);
+                return;
+            }
+        }
+
+        let start = c.enter(EntryKind::Const, self);
+
+        match *self {
+            Const::Value(ref ty, value) => {
+                ty.mangle_to_string(c);
+                write!(c.out, "{}_", radix(16, value)).unwrap();
+            }
+            Const::Placeholder(ref ty) => {
+                ty.mangle_to_string(c);
+                c.out.push('p');
+            }
+        }
+
+        c.exit();
+        if c.compress {
+            c.consts.insert(hash, self.clone(), start);
+        }
+    }
+}
+
+impl AstMangle for BasicType {
+    fn mangle_to_string(&self, c: &mut Compress) {
+        c.out.push(match *self {
+            BasicType::I8 => 'a',
+            BasicType::Bool => 'b',
+            BasicType::Char => 'c',
+            BasicType::F64 => 'd',
+            BasicType::Str => 'e',
+            BasicType::F32 => 'f',
+            BasicType::U8 => 'h',
+            BasicType::Isize => 'i',
+            BasicType::Usize => 'j',
+            BasicType::I32 => 'l',
+            BasicType::U32 => 'm',
+            BasicType::I128 => 'n',
+            BasicType::U128 => 'o',
+            BasicType::Placeholder => 'p',
+            BasicType::I16 => 's',
+            BasicType::U16 => 't',
+            BasicType::Unit => 'u',
+            BasicType::Ellipsis => 'v',
+            BasicType::I64 => 'x',
+            BasicType::U64 => 'y',
+            BasicType::Never => 'z',
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compress;
+    use ast::*;
+
+    #[test]
+    fn punycode_ident_round_trip() {
+        for name in &["gödel", "föö_bär", "ℝ", "abc"] {
+            let symbol = Symbol {
+                version: None,
+                path: Path::CrateRoot {
+                    id: Ident {
+                        dis: Disambiguator(0),
+                        u_ident: UIdent(name.to_string().into()),
+                    },
+                },
+                instantiating_crate: None,
+            };
+
+            let mangled = symbol.mangle();
+            assert_eq!(name.is_ascii(), !mangled.starts_with("_RCu"));
+            assert_eq!(symbol, Symbol::parse(&mangled).unwrap());
+        }
+    }
+
+    #[test]
+    fn shared_subtrees_become_backrefs() {
+        let tuple = Ptr::new(Type::Tuple(vec![Type::basic(BasicType::U8); 2]));
+        let symbol = Symbol {
+            version: None,
+            path: Path::Generic {
+                inner: Ptr::new(Path::CrateRoot {
+                    id: Ident {
+                        dis: Disambiguator(0),
+                        u_ident: UIdent("foo".into()),
+                    },
+                }),
+                args: vec![
+                    GenericArg::Type(Type::Slice(tuple.clone())),
+                    GenericArg::Type(Type::Ref(None, tuple)),
+                ]
+                .into(),
+            },
+            instantiating_crate: None,
+        };
+
+        let mangled = symbol.mangle();
+        assert_eq!("_RIC3fooSThhERB6_E", mangled);
+        assert_eq!(symbol, Symbol::parse(&mangled).unwrap());
+
+        let uncompressed = symbol.mangle_uncompressed();
+        assert_eq!("_RIC3fooSThhERThhEE", uncompressed);
+        assert_eq!(symbol, Symbol::parse(&uncompressed).unwrap());
+    }
+
+    #[test]
+    fn mangle_hand_built_symbols_without_panicking() {
+        let crate_root = |name: String| Path::CrateRoot {
+            id: Ident {
+                dis: Disambiguator(0),
+                u_ident: UIdent(name.into()),
+            },
+        };
+
+        // Version 0 is the one without a number.
+        let symbol = Symbol {
+            version: Some(DecimalNumber(0)),
+            path: crate_root("foo".to_string()),
+            instantiating_crate: None,
+        };
+        assert_eq!(Ok("_RC3foo".to_string()), Compress::new().mangle(&symbol));
+
+        // The punycode delta of the last character does not fit into 32 bits.
+        let symbol = Symbol {
+            version: None,
+            path: crate_root("a".repeat(4000) + "\u{10ffff}"),
+            instantiating_crate: None,
+        };
+        assert!(Compress::new()
+            .mangle(&symbol)
+            .unwrap_err()
+            .contains("punycode"));
+        assert!(::mangle(&symbol).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Glue for demangling the symbol names of the `backtrace` crate with this
+//! crate instead of `rustc-demangle`.
+//!
+//! `demangle()` has the signature of the function `backtrace` demangles
+//! symbol names with, for pipelines that call it themselves.
+//! `DemangledBacktrace` formats a whole `Backtrace` like `backtrace` does,
+//! and `install_panic_hook()` makes panics print one:
+//!
+//! ```no_run
+//! std_mangle_rs::backtraces::install_panic_hook();
+//! ```
+
+use backtrace::{Backtrace, SymbolName};
+use compat::{self, Demangle};
+use std::fmt;
+use std::panic;
+use std::str;
+
+/// Demangles the raw symbol name `bytes`, or returns `None` if it is not a
+/// Rust symbol.
+pub fn demangle(bytes: &[u8]) -> Option<Demangle<'_>> {
+    str::from_utf8(bytes)
+        .ok()
+        .and_then(|name| compat::try_demangle(name).ok())
+}
+
+/// Formats `name` as `SymbolName`'s `Display` implementation does: demangled
+/// if it is a Rust symbol, with `{:#}` leaving out hashes, and as it is
+/// otherwise.
+pub fn format_symbol_name(name: &SymbolName, alternate: bool) -> String {
+    match demangle(name.as_bytes()) {
+        Some(ref demangled) if alternate => format!("{:#}", demangled),
+        Some(ref demangled) => demangled.to_string(),
+        None => String::from_utf8_lossy(name.as_bytes()).into_owned(),
+    }
+}
+
+/// Formats a resolved backtrace with one numbered line per symbol and its
+/// source location below it, like `backtrace` does with `{:?}`. With `{:#}`,
+/// hashes and disambiguators are left out.
+pub struct DemangledBacktrace<'a>(pub &'a Backtrace);
+
+impl<'a> fmt::Display for DemangledBacktrace<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut index = 0;
+        for frame in self.0.frames() {
+            for symbol in frame.symbols() {
+                let name = match symbol.name() {
+                    Some(name) => format_symbol_name(&name, f.alternate()),
+                    None => "<unknown>".to_string(),
+                };
+                writeln!(f, "{:4}: {}", index, name)?;
+                if let (Some(file), Some(line)) = (symbol.filename(), symbol.lineno()) {
+                    writeln!(f, "             at {}:{}", file.display(), line)?;
+                }
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the panic hook by one that prints the panic message followed by
+/// a backtrace demangled with this crate.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        eprintln!("{}", info);
+        eprintln!("stack backtrace:");
+        eprint!("{:#}", DemangledBacktrace(&Backtrace::new()));
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, DemangledBacktrace};
+    use backtrace::Backtrace;
+
+    #[test]
+    fn demangle_own_backtrace() {
+        assert_eq!(
+            "mycrate::foo",
+            demangle(b"_RNvC7mycrate3foo").unwrap().to_string()
+        );
+        assert!(demangle(b"main").is_none());
+        assert!(demangle(b"\xff").is_none());
+
+        let backtrace = format!("{:#}", DemangledBacktrace(&Backtrace::new()));
+        assert!(backtrace.contains("backtraces::tests::demangle_own_backtrace"));
+    }
+}
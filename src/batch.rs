@@ -0,0 +1,267 @@
+//! Demangling of many symbols with a shared output buffer.
+
+use ast::Symbol;
+use ast_demangle::{estimate_len, AstDemangle};
+use lru::LruCache;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::ops::Range;
+use std::slice;
+
+/// Demangles symbols into a buffer that is reused from one call to the next,
+/// so demangling a large symbol table does not allocate a `String` per symbol.
+/// Optionally, recent results are cached by their mangled form.
+#[derive(Default, Debug)]
+pub struct Demangler {
+    out: String,
+    results: Vec<Result<Range<usize>, String>>,
+    cache: Option<Cache>,
+}
+
+#[derive(Debug)]
+struct Cache {
+    entries: LruCache<Result<String, String>>,
+    stats: CacheStats,
+}
+
+/// How often a `Demangler` found a symbol in its cache.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of symbols currently in the cache.
+    pub entries: usize,
+}
+
+impl Demangler {
+    pub fn new() -> Demangler {
+        Demangler::default()
+    }
+
+    /// Creates a demangler that remembers the results for the `capacity`
+    /// most recently demangled symbols.
+    pub fn with_cache(capacity: usize) -> Demangler {
+        Demangler {
+            cache: Some(Cache {
+                entries: LruCache::new(capacity),
+                stats: CacheStats::default(),
+            }),
+            ..Demangler::default()
+        }
+    }
+
+    /// The cache statistics, or `None` if this demangler has no cache.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| CacheStats {
+            entries: cache.entries.len(),
+            ..cache.stats
+        })
+    }
+
+    /// Demangles a single symbol. The result is valid until the next call.
+    pub fn demangle(&mut self, mangled: &str) -> Result<&str, String> {
+        self.out.clear();
+        demangle_into(&mut self.cache, &mut self.out, mangled)?;
+        Ok(&self.out)
+    }
+
+    /// Demangles all `symbols`. The results are in the same order as the input
+    /// and are valid until the next call.
+    pub fn demangle_all<'a, I>(&mut self, symbols: I) -> Demangled<'_>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        self.out.clear();
+        self.results.clear();
+
+        for mangled in symbols {
+            let start = self.out.len();
+            let result = demangle_into(&mut self.cache, &mut self.out, mangled);
+            self.results.push(result.map(|()| start..self.out.len()));
+        }
+
+        Demangled {
+            out: &self.out,
+            results: &self.results,
+        }
+    }
+}
+
+/// Appends the demangled form of `mangled` to `out`, consulting `cache` first.
+fn demangle_into(cache: &mut Option<Cache>, out: &mut String, mangled: &str) -> Result<(), String> {
+    let cache = match *cache {
+        Some(ref mut cache) => cache,
+        None => return Symbol::parse(mangled).map(|symbol| demangle_symbol(&symbol, out)),
+    };
+
+    if let Some(result) = cache.entries.get(mangled) {
+        cache.stats.hits += 1;
+        return match *result {
+            Ok(ref demangled) => {
+                out.push_str(demangled);
+                Ok(())
+            }
+            Err(ref e) => Err(e.clone()),
+        };
+    }
+
+    cache.stats.misses += 1;
+
+    let start = out.len();
+    let result = Symbol::parse(mangled).map(|symbol| demangle_symbol(&symbol, out));
+    let cached = match result {
+        Ok(()) => Ok(out[start..].to_string()),
+        Err(ref e) => Err(e.clone()),
+    };
+    cache.entries.insert(mangled, cached);
+
+    result
+}
+
+fn demangle_symbol(symbol: &Symbol, out: &mut String) {
+    out.reserve(estimate_len(symbol));
+    symbol.demangle_to_string(out);
+}
+
+/// Demangles `symbols` on all cores, with one `Demangler` per thread. The
+/// results are in the same order as the input.
+#[cfg(feature = "rayon")]
+pub fn par_demangle_all<S>(symbols: &[S]) -> Vec<Result<String, String>>
+where
+    S: AsRef<str> + Sync,
+{
+    symbols
+        .par_iter()
+        .map_init(Demangler::new, |demangler, mangled| {
+            demangler.demangle(mangled.as_ref()).map(str::to_string)
+        })
+        .collect()
+}
+
+/// The results of `Demangler::demangle_all()`.
+#[derive(Copy, Clone, Debug)]
+pub struct Demangled<'d> {
+    out: &'d str,
+    results: &'d [Result<Range<usize>, String>],
+}
+
+impl<'d> Demangled<'d> {
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The demangled form of the `index`th symbol, or the error that
+    /// prevented demangling it.
+    pub fn get(&self, index: usize) -> Option<Result<&'d str, &'d str>> {
+        self.results
+            .get(index)
+            .map(|result| resolve(self.out, result))
+    }
+
+    pub fn iter(&self) -> DemangledIter<'d> {
+        DemangledIter {
+            out: self.out,
+            results: self.results.iter(),
+        }
+    }
+}
+
+impl<'d> IntoIterator for Demangled<'d> {
+    type Item = Result<&'d str, &'d str>;
+    type IntoIter = DemangledIter<'d>;
+
+    fn into_iter(self) -> DemangledIter<'d> {
+        self.iter()
+    }
+}
+
+pub struct DemangledIter<'d> {
+    out: &'d str,
+    results: slice::Iter<'d, Result<Range<usize>, String>>,
+}
+
+impl<'d> Iterator for DemangledIter<'d> {
+    type Item = Result<&'d str, &'d str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let out = self.out;
+        self.results.next().map(|result| resolve(out, result))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.results.size_hint()
+    }
+}
+
+fn resolve<'d>(out: &'d str, result: &'d Result<Range<usize>, String>) -> Result<&'d str, &'d str> {
+    match *result {
+        Ok(ref range) => Ok(&out[range.clone()]),
+        Err(ref e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Demangler;
+
+    #[test]
+    fn demangle_all_reuses_buffer() {
+        let mut demangler = Demangler::new();
+
+        let results: Vec<_> = demangler
+            .demangle_all(vec!["_RNxC7mycrate4quux", "_RXX", "_RNxC7mycrate3baz"])
+            .iter()
+            .map(|result| result.map(str::to_string).map_err(|_| ()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                Ok("mycrate::quux".to_string()),
+                Err(()),
+                Ok("mycrate::baz".to_string())
+            ],
+            results
+        );
+
+        let capacity = demangler.out.capacity();
+        demangler.demangle_all(vec!["_RNxC7mycrate4quux"]);
+        assert_eq!(capacity, demangler.out.capacity());
+    }
+
+    #[test]
+    fn cache_hits_and_misses() {
+        let mut demangler = Demangler::with_cache(2);
+
+        for &mangled in &["_RNxC7mycrate4quux", "_RNxC7mycrate4quux", "_RXX", "_RXX"] {
+            let _ = demangler.demangle(mangled);
+        }
+        assert_eq!(
+            "mycrate::quux",
+            demangler.demangle("_RNxC7mycrate4quux").unwrap()
+        );
+
+        let stats = demangler.cache_stats().unwrap();
+        assert_eq!((3, 2, 2), (stats.hits, stats.misses, stats.entries));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let symbols: Vec<_> = (0..1000)
+            .map(|i| format!("_RNxC7mycrate{}f{}", i.to_string().len() + 1, i))
+            .collect();
+
+        let mut demangler = Demangler::new();
+        let sequential: Vec<_> = demangler
+            .demangle_all(symbols.iter().map(|s| &s[..]))
+            .iter()
+            .map(|result| result.map(str::to_string).map_err(str::to_string))
+            .collect();
+
+        assert_eq!(sequential, super::par_demangle_all(&symbols));
+    }
+}
@@ -0,0 +1,274 @@
+//! `cargo symbols`: lists the demangled symbols of the binaries and dynamic
+//! libraries built for the current workspace.
+//!
+//! The artifacts are located with `cargo metadata` and their symbol tables
+//! are read with `nm`. Nothing is built; run `cargo build` first.
+
+extern crate serde_json;
+extern crate std_mangle_rs;
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std_mangle_rs::ast::Symbol;
+use std_mangle_rs::legacy;
+
+const USAGE: &str = "\
+usage: cargo symbols [options]
+
+options:
+    --release                 use the artifacts of the release profile
+    --profile <name>          use the artifacts of the given profile
+    --manifest-path <path>    the Cargo.toml of the workspace
+    --crate <name>            only list items defined in the given crate
+    --skeleton                list generic items once, with the number and
+                              total size of their instances
+    --sort-size               sort by size, largest first, instead of by name
+    --nm <path>               the nm to use, by default $NM or `nm`";
+
+struct Options {
+    profile: String,
+    manifest_path: Option<String>,
+    krate: Option<String>,
+    skeleton: bool,
+    sort_size: bool,
+    nm: String,
+}
+
+/// A Rust symbol of an artifact.
+struct Entry {
+    size: u64,
+    demangled: String,
+    krate: String,
+    skeleton: String,
+}
+
+fn main() {
+    let options = parse_args();
+
+    for artifact in artifacts(&options) {
+        if !artifact.exists() {
+            eprintln!("skipping {}: not built", artifact.display());
+            continue;
+        }
+
+        let mut entries: Vec<Entry> = symbols(&options.nm, &artifact)
+            .into_iter()
+            .filter(|entry| {
+                options
+                    .krate
+                    .as_ref()
+                    .is_none_or(|krate| entry.krate == *krate)
+            })
+            .collect();
+
+        println!("{}:", artifact.display());
+
+        if options.skeleton {
+            let mut skeletons: BTreeMap<&str, (u64, usize)> = BTreeMap::new();
+            for entry in &entries {
+                let skeleton = skeletons.entry(&entry.skeleton).or_insert((0, 0));
+                skeleton.0 += entry.size;
+                skeleton.1 += 1;
+            }
+
+            let mut skeletons: Vec<_> = skeletons.into_iter().collect();
+            if options.sort_size {
+                skeletons.sort_by_key(|&(_, (size, _))| std::cmp::Reverse(size));
+            }
+            for (skeleton, (size, count)) in skeletons {
+                println!("{:>10} {:>6}x  {}", size, count, skeleton);
+            }
+        } else {
+            if options.sort_size {
+                entries.sort_by(|a, b| b.size.cmp(&a.size).then(a.demangled.cmp(&b.demangled)));
+            } else {
+                entries.sort_by(|a, b| a.demangled.cmp(&b.demangled));
+            }
+            for entry in &entries {
+                println!("{:>10}  {}", entry.size, entry.demangled);
+            }
+        }
+    }
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        profile: "debug".to_string(),
+        manifest_path: None,
+        krate: None,
+        skeleton: false,
+        sort_size: false,
+        nm: env::var("NM").unwrap_or_else(|_| "nm".to_string()),
+    };
+
+    // Cargo passes the name of the subcommand as the first argument.
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(|arg| &arg[..]) == Some("symbols") {
+        args.next();
+    }
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next().unwrap_or_else(|| {
+                eprintln!("{} needs a value\n\n{}", arg, USAGE);
+                process::exit(2);
+            })
+        };
+
+        match &arg[..] {
+            "--release" => options.profile = "release".to_string(),
+            "--profile" => options.profile = value(),
+            "--manifest-path" => options.manifest_path = Some(value()),
+            "--crate" => options.krate = Some(value()),
+            "--skeleton" => options.skeleton = true,
+            "--sort-size" => options.sort_size = true,
+            "--nm" => options.nm = value(),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => {
+                eprintln!("unexpected argument `{}`\n\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    // Like cargo itself, the `dev` profile puts its artifacts into `debug`.
+    if options.profile == "dev" {
+        options.profile = "debug".to_string();
+    }
+
+    options
+}
+
+/// The paths of the binaries and dynamic libraries of all workspace members.
+fn artifacts(options: &Options) -> Vec<PathBuf> {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command.args(["metadata", "--format-version", "1", "--no-deps"]);
+    if let Some(ref manifest_path) = options.manifest_path {
+        command.args(["--manifest-path", manifest_path]);
+    }
+
+    let output = command
+        .output()
+        .unwrap_or_else(|e| fail("failed to run cargo metadata", e));
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        process::exit(1);
+    }
+
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .unwrap_or_else(|e| fail("failed to parse cargo metadata", e));
+    let dir =
+        Path::new(metadata["target_directory"].as_str().unwrap_or("target")).join(&options.profile);
+
+    let mut artifacts = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        for target in package["targets"].as_array().into_iter().flatten() {
+            let name = target["name"].as_str().unwrap_or_default();
+            let kinds = target["kind"].as_array().into_iter().flatten();
+
+            for kind in kinds.filter_map(Value::as_str) {
+                let file = match kind {
+                    "bin" => format!("{}{}", name, env::consts::EXE_SUFFIX),
+                    "cdylib" | "dylib" => format!(
+                        "{}{}{}",
+                        env::consts::DLL_PREFIX,
+                        name.replace('-', "_"),
+                        env::consts::DLL_SUFFIX
+                    ),
+                    _ => continue,
+                };
+                artifacts.push(dir.join(file));
+            }
+        }
+    }
+
+    artifacts
+}
+
+/// Reads the defined Rust symbols of `artifact` with `nm`.
+fn symbols(nm: &str, artifact: &Path) -> Vec<Entry> {
+    let output = Command::new(nm)
+        .args(["--defined-only", "-S"])
+        .arg(artifact)
+        .output()
+        .unwrap_or_else(|e| fail(&format!("failed to run {}", nm), e));
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        process::exit(1);
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            // `address [size] type name`, with the size missing for symbols
+            // that have none.
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (size, name) = match fields[..] {
+                [_, size, _, name] => (u64::from_str_radix(size, 16).unwrap_or(0), name),
+                [_, _, name] => (0, name),
+                _ => return None,
+            };
+            entry(size, name)
+        })
+        .collect()
+}
+
+fn entry(size: u64, name: &str) -> Option<Entry> {
+    // macOS prefixes every symbol with an extra `_`.
+    let name = if name.starts_with("__R") || name.starts_with("__ZN") {
+        &name[1..]
+    } else {
+        name
+    };
+
+    if let Ok(symbol) = Symbol::parse(name) {
+        return Some(Entry {
+            size,
+            demangled: symbol.demangle(),
+            krate: symbol.defining_crate().u_ident.0.to_string(),
+            skeleton: symbol.skeleton().demangle(),
+        });
+    }
+
+    // Legacy symbols do not encode generic arguments, so they are their own
+    // skeleton.
+    let demangled = legacy::demangle(name).ok()?;
+    Some(Entry {
+        size,
+        krate: demangled.split("::").next().unwrap_or_default().to_string(),
+        skeleton: demangled.clone(),
+        demangled,
+    })
+}
+
+fn fail<E: std::fmt::Display>(message: &str, e: E) -> ! {
+    eprintln!("{}: {}", message, e);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::entry;
+
+    #[test]
+    fn strip_mach_o_prefix() {
+        for &(name, demangled) in &[
+            ("_RNvC7mycrate3foo", "mycrate::foo"),
+            ("__RNvC7mycrate3foo", "mycrate::foo"),
+            ("_ZN7mycrate3foo17h0123456789abcdefE", "mycrate::foo"),
+            ("__ZN7mycrate3foo17h0123456789abcdefE", "mycrate::foo"),
+        ] {
+            let entry = entry(8, name).unwrap();
+            assert_eq!(demangled, entry.demangled);
+            assert_eq!("mycrate", entry.krate);
+        }
+        assert!(entry(8, "__Zfoo").is_none());
+    }
+}
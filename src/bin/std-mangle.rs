@@ -0,0 +1,1870 @@
+//! Demangles, compresses, and decompresses symbols.
+//!
+//! Without a subcommand, copies stdin to stdout, replacing every v0 and
+//! legacy Rust symbol with its demangled form.
+//!
+//! Also builds for `wasm32-wasip1`, e.g. for sandboxed CI jobs, with
+//! `cargo build --release --bin std-mangle --target wasm32-wasip1`. There,
+//! `--jobs` has no effect and colors are only used with `--color always`.
+
+#[cfg(feature = "grep")]
+extern crate regex;
+extern crate std_mangle_rs;
+
+use host::CheckStdout;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::env;
+use std::hint::black_box;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process;
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std_mangle_rs::ast::Symbol;
+#[cfg(feature = "object")]
+use std_mangle_rs::binary;
+use std_mangle_rs::dictionary::DictionaryDump;
+use std_mangle_rs::diff::SymbolDiff;
+use std_mangle_rs::explain::explain;
+#[cfg(feature = "json")]
+use std_mangle_rs::filter::replace_json_line;
+use std_mangle_rs::filter::{
+    replace_backtrace_line, replace_folded_line, replace_nm_line, replace_objdump_line,
+    replace_symbols, StreamingFilter,
+};
+use std_mangle_rs::generate::{GeneratorConfig, SymbolGenerator};
+use std_mangle_rs::highlight::{highlight, Theme};
+use std_mangle_rs::info::analyze;
+use std_mangle_rs::legacy;
+use std_mangle_rs::parse::STACK_SIZE;
+use std_mangle_rs::pattern::Pattern;
+#[cfg(feature = "profile")]
+use std_mangle_rs::profile::{self, CountingAllocator};
+use std_mangle_rs::stats::SymbolStats;
+use std_mangle_rs::verify::verify;
+
+/// Counts allocations for `bench`.
+#[cfg(feature = "profile")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const USAGE: &str = "\
+usage: std-mangle [--format <format>] [--fields <fields>] [--unbuffered]
+       std-mangle <command> [--json | --output <output>] [--columns <columns>]
+                  [--color <when>] [--theme <theme>]
+                  [--jobs <n>] [--unique-skeleton] [--crate [!]<name>]...
+                  [--match <pattern>]... [-0] [--strip | --strip-generics]
+                  [<symbol> | -f <file> | -]...
+       std-mangle grep [--skeleton] <regex> [<file>...]
+       std-mangle stats [--json] [--crate [!]<name>]... [--match <pattern>]...
+                  [<symbol> | -f <file>]...
+       std-mangle top [-n <n>] [--by <key>] [--json] [--color <when>]
+                  [--theme <theme>] [--crate [!]<name>]...
+                  [--match <pattern>]... [<symbol> | -f <file>]...
+       std-mangle diff [--ignore-disambiguators] [--json] [--crate [!]<name>]...
+                  [--match <pattern>]... [-0] <old> <new>
+       std-mangle explain [--json] [--crate [!]<name>]... [--match <pattern>]...
+                  [<symbol> | -f <file>]...
+       std-mangle verify [--json] [<symbol> | -f <file>]...
+       std-mangle analyze [<symbol> | -f <file>]...
+       std-mangle dictionary [--json] [<symbol> | -f <file>]...
+       std-mangle dictionary --check <dump>
+       std-mangle bench [--warmup <n>] [--repetitions <n>]
+                  [<symbol> | -f <file>]...
+       std-mangle generate [-n <n>] [--max-depth <depth>] [--seed <seed>]
+
+Without a command, copies stdin to stdout, demangling all symbols in it. The
+output is flushed whenever the input pauses, so `my_server 2>&1 | std-mangle`
+shows demangled panics right away. With --unbuffered, the text format does
+not wait for whole lines either.
+
+formats:
+    text          demangle symbols anywhere (the default)
+    nm            only demangle the last column, as printed by `nm`
+    objdump       only demangle `<symbol+offset>` labels, as printed by
+                  `objdump -d`
+    folded        demangle every frame of `frame;frame;frame count` lines,
+                  as used by FlameGraph and `perf script` collapsers
+    backtrace     demangle the frames of pasted Rust backtraces, keeping frame
+                  numbers, addresses and `at <file>:<line>` lines as they are
+    json-lines    demangle the string values of lines of JSON, only within
+                  the comma-separated <fields> if given, like `msg` or
+                  `error.stack` (needs the `json` feature)
+
+commands:
+    demangle      print the demangled form of each v0 or legacy symbol
+    decompress    print each symbol with all backrefs expanded
+    compress      print each symbol with all possible backrefs
+    normalize     print each symbol without crate and impl disambiguators
+                  and without the instantiating crate, which is the same
+                  for the symbols of one item in any build
+    mangle        print the symbol for each JSON AST (needs the `json` feature)
+
+`grep` prints the lines of the files, or of stdin, whose demangled form
+matches <regex>, in their demangled form. With --skeleton, the regex is
+matched against the line with every symbol replaced by its skeleton instead,
+so that generic arguments do not have to be matched. Needs the `grep` feature.
+
+`stats` prints the number of symbols per crate and per skeleton, the most
+instantiated generic items, the longest symbols, and the average mangled and
+demangled lengths.
+
+`top` prints the <n> symbols, by default 10, with the highest <key> in order,
+each with its value. The keys are `demangled-len` (the default), `depth` for
+the depth of the most deeply nested path, type or const, and `args` for the
+total number of generic arguments.
+
+`diff` compares the symbols listed in the files <old> and <new>, one per line,
+by their structure and prints the added and removed ones grouped by skeleton.
+With --ignore-disambiguators, symbols that only differ in disambiguators
+other than the indices of closures count as the same. Exits with 1 if there
+are differences.
+
+`explain` prints the tokens of each symbol, its fully expanded AST, and every
+backref with the byte range of the substitution it points to.
+
+`verify` checks that each symbol parses (V001), that mangling the parsed
+symbol with and without backrefs gives the same symbol again (V002), and that
+the symbol is in canonical form, i.e. uses backrefs wherever possible and has
+nothing after its end (V003). It prints each failed check with its code and,
+where there is one, the byte offset of the problem, and exits with 1 if any
+check failed. With --json, prints one JSON object per failed check instead,
+with the fields `input`, `code`, `check`, `offset`, and `message`.
+
+`analyze` writes the crate, path, skeleton, generic arguments, namespace and
+suffixes of each v0 or legacy symbol as a `std_mangle.v1.SymbolInfo`
+protobuf message, each preceded by its length as a varint, to stdout. The
+schema is in `schema/symbol_info.proto`. Symbols that do not parse are
+reported on stderr, and the exit status is 1 if there are any.
+
+`dictionary` prints the substitution dictionary of each symbol as mangled by
+this build: every path, type and const a backref could point to, with its
+byte range and demangled form, and every backref with the entry it points
+to. With --json, it prints the same as JSON, which needs the `json`
+feature. With --check, it reads a dump in either form, e.g. one attached to a
+bug report, mangles its symbol again, and prints every difference to the
+dump. Exits with 1 if there are any.
+
+`bench` demangles all symbols <n> times for warmup, by default once, and then
+<n> more times, by default 5, and prints the fastest, median and slowest time
+for demangling all of them along with the throughput in symbols and in MB of
+mangled symbols per second. With the `profile` feature, it also prints the
+number of allocations and allocated bytes per symbol.
+
+`generate` prints <n> random but valid symbols, by default 10, for use as a
+fuzzing corpus. Paths, types and consts are nested at most <depth> levels
+deep, by default 6. The same <seed> always gives the same symbols; without
+one, the seed is taken from the clock and printed to stderr.
+
+Symbols are taken from the arguments and, one per line, from the files given
+with -f and from stdin for `-`. Without any, they are read from stdin. With
+-0, the symbols in files and stdin are separated by NUL instead. An argument
+naming a text file stands for its lines, and one naming an ELF, Mach-O or PE
+file for the v0 symbols in its symbol and dynamic symbol tables, which
+`demangle` and the others print with their sizes. Reading object files needs
+the `object` feature.
+
+With --crate <name>, all commands but `grep` only consider the symbols whose
+defining or instantiating crate is one of the given crates, and with
+--crate !<name>, only those where neither is. Symbols that do not parse are
+only kept if no crate is given without `!`.
+
+With --match <pattern>, they only consider the symbols whose path matches one
+of the patterns, like `core::ptr::drop_in_place<*>`. `*` stands for any one
+path segment or generic argument and `**` for any number of them, and a
+segment without `<>` matches with any generic arguments. Disambiguators are
+ignored. Symbols that do not parse are left out.
+
+With --strip, `demangle` prints symbols without disambiguators, except for
+the indices of closures, and without the instantiating crate, so that the
+output for two builds can be compared line by line with `diff`.
+--strip-generics also replaces generic arguments with `_`.
+
+With --color always, `demangle` and `top` highlight the demangled symbols
+with ANSI escape sequences, and with --color never, they do not. By default,
+they do if stdout is a terminal, `TERM` is not `dumb`, and `NO_COLOR` is not
+set. --theme selects `dark` (the default) for dark terminal backgrounds,
+`light` for light ones, or `mono` for bold and dim text only.
+
+With --jobs, the commands above process the symbols on <n> threads, or on
+all cores for 0. The output is in the same order as the input either way.
+
+With --unique-skeleton, the commands only print the first symbol of every
+skeleton, with the number of symbols that share it and their total size. This
+collapses all instances of a generic item into one line.
+
+With --json, prints one JSON object per symbol instead, with the fields
+`input`, `output`, `demangled`, `crate`, `skeleton`, `error`, `size`, and
+`count`. Fields that do not apply are null.
+
+With --output csv or --output tsv, prints a header and one row per symbol
+instead, with the comma-separated list of <columns>, by default
+`mangled,demangled,crate,skeleton`. The columns are `mangled`, `output`,
+`demangled`, `crate`, `skeleton`, `size`, `count`, and `error`; those that do
+not apply are empty. --output text and --output json are the same as the
+default and --json.";
+
+#[derive(Copy, Clone)]
+enum Command {
+    Demangle(Strip),
+    Decompress,
+    Compress,
+    Normalize,
+    Mangle,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        Some(match name {
+            "demangle" => Command::Demangle(Strip::Nothing),
+            "decompress" => Command::Decompress,
+            "compress" => Command::Compress,
+            "normalize" => Command::Normalize,
+            "mangle" => Command::Mangle,
+            _ => return None,
+        })
+    }
+
+    /// Returns the symbol given by `input`, which is a JSON AST for `mangle`.
+    /// Like the stdin filter, `demangle` also takes legacy symbols, which it
+    /// converts to v0 ones without their hash.
+    fn parse(self, input: &str) -> Result<Symbol, String> {
+        match self {
+            Command::Mangle => symbol_from_json(input),
+            Command::Demangle(_) => Symbol::parse(input).or_else(|e| {
+                legacy::to_v0(input)
+                    .map(|symbol| symbol.erase_disambiguators())
+                    .map_err(|_| e)
+            }),
+            _ => Symbol::parse(input),
+        }
+    }
+
+    /// Returns the symbol given by `input` along with the command's output.
+    fn run(self, input: &str) -> Result<(Symbol, String), String> {
+        let symbol = self.parse(input)?;
+
+        let output = match self {
+            Command::Demangle(Strip::Nothing) => symbol.demangle(),
+            Command::Demangle(Strip::Disambiguators) => {
+                let mut symbol = symbol.erase_disambiguators();
+                symbol.instantiating_crate = None;
+                symbol.demangle()
+            }
+            Command::Demangle(Strip::Generics) => {
+                symbol.erase_disambiguators().skeleton().demangle()
+            }
+            Command::Decompress => symbol.mangle_uncompressed(),
+            Command::Compress => symbol.mangle(),
+            Command::Mangle => std_mangle_rs::mangle(&symbol)?,
+            Command::Normalize => symbol.normalized_mangle(),
+        };
+
+        Ok((symbol, output))
+    }
+}
+
+/// What `demangle` leaves out of its output, as given by `--strip` and
+/// `--strip-generics`.
+#[derive(Copy, Clone)]
+enum Strip {
+    Nothing,
+    /// Disambiguators and the instantiating crate.
+    Disambiguators,
+    /// Generic arguments as well.
+    Generics,
+}
+
+#[cfg(feature = "json")]
+fn symbol_from_json(json: &str) -> Result<Symbol, String> {
+    Symbol::from_json(json)
+}
+
+#[cfg(not(feature = "json"))]
+fn symbol_from_json(_json: &str) -> Result<Symbol, String> {
+    Err("std-mangle was built without the `json` feature".to_string())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match args.first() {
+        None => {
+            filter(replace_symbols);
+            return;
+        }
+        Some(arg) if arg == "--format" => {
+            let replace = match args.get(1).map(|format| &format[..]) {
+                Some("text") => replace_symbols,
+                Some("nm") => replace_nm_line,
+                Some("objdump") => replace_objdump_line,
+                Some("folded") => replace_folded_line,
+                Some("backtrace") => replace_backtrace_line,
+                Some("json-lines") => {
+                    filter_json_lines(&args[2..]);
+                    return;
+                }
+                _ => usage_error(args.get(1).unwrap_or(arg)),
+            };
+            match args.get(2) {
+                Some(flag) if flag == "--unbuffered" && args[1] == "text" && args.len() == 3 => {
+                    stream()
+                }
+                Some(arg) => usage_error(arg),
+                None => filter(replace),
+            }
+            return;
+        }
+        Some(arg) if arg == "--unbuffered" => {
+            if let Some(arg) = args.get(1) {
+                usage_error(arg);
+            }
+            stream();
+            return;
+        }
+        Some(arg) if arg == "grep" => grep(&args[1..]),
+        Some(arg) if arg == "diff" => diff(&args[1..]),
+        Some(arg) if arg == "explain" => explain_all(&args[1..]),
+        Some(arg) if arg == "verify" => verify_all(&args[1..]),
+        Some(arg) if arg == "analyze" => analyze_all(&args[1..]),
+        Some(arg) if arg == "dictionary" => dictionary_all(&args[1..]),
+        Some(arg) if arg == "stats" => {
+            stats(&args[1..]);
+            return;
+        }
+        Some(arg) if arg == "top" => {
+            top(&args[1..]);
+            return;
+        }
+        Some(arg) if arg == "bench" => {
+            bench(&args[1..]);
+            return;
+        }
+        Some(arg) if arg == "generate" => {
+            generate(&args[1..]);
+            return;
+        }
+        Some(arg) if arg == "-h" || arg == "--help" => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some(arg) => Command::from_name(arg).unwrap_or_else(|| usage_error(arg)),
+    };
+
+    let (strip, inputs) = split_flag(&args[1..], "--strip");
+    let (strip_generics, inputs) = split_flag(&inputs, "--strip-generics");
+    let command = match command {
+        Command::Demangle(_) if strip_generics => Command::Demangle(Strip::Generics),
+        Command::Demangle(_) if strip => Command::Demangle(Strip::Disambiguators),
+        _ if strip || strip_generics => usage_error("--strip"),
+        command => command,
+    };
+
+    let (format, inputs) = split_format_flags(&inputs);
+    let (jobs, inputs) = split_jobs_flag(&inputs);
+    let (unique, inputs) = split_flag(&inputs, "--unique-skeleton");
+    let (crates, inputs) = split_filter_flags(&inputs);
+
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    let mut failed = false;
+    let mut batch = Vec::new();
+
+    if let Format::Table {
+        separator,
+        ref columns,
+    } = format
+    {
+        let header: Vec<_> = columns.iter().map(|column| column.name()).collect();
+        writeln!(stdout, "{}", header.join(&separator.to_string())).check_stdout();
+    }
+
+    let mut representatives = Representatives::default();
+
+    for_each_sized_input(&inputs, |input, size| {
+        if !crates.is_empty() && !crates.keeps(command.parse(input).ok().as_ref()) {
+            return;
+        }
+
+        let input = Input {
+            symbol: input.to_string(),
+            size,
+            count: None,
+        };
+        if unique {
+            representatives.add(command, input);
+            return;
+        }
+
+        batch.push(input);
+        if batch.len() == BATCH_LEN {
+            failed |= run_batch(command, &format, jobs, &batch, &mut stdout);
+            batch.clear();
+        }
+    });
+
+    if unique {
+        batch = representatives.inputs;
+    }
+    for batch in batch.chunks(BATCH_LEN) {
+        failed |= run_batch(command, &format, jobs, batch, &mut stdout);
+    }
+
+    stdout.flush().check_stdout();
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// The crates given by `--crate <name>` and `--crate !<name>`, and the
+/// patterns given by `--match <pattern>`.
+#[derive(Default)]
+struct SymbolFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    patterns: Vec<Pattern>,
+}
+
+impl SymbolFilter {
+    fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Whether to keep `symbol`, or a symbol that does not parse for `None`.
+    /// A symbol is kept if its defining or instantiating crate is one of the
+    /// included crates, if any, neither is one of the excluded crates, and it
+    /// matches one of the patterns, if any.
+    fn keeps(&self, symbol: Option<&Symbol>) -> bool {
+        let symbol = match symbol {
+            Some(symbol) => symbol,
+            None => return self.include.is_empty() && self.patterns.is_empty(),
+        };
+
+        if !self.patterns.is_empty() && !self.patterns.iter().any(|p| p.matches(symbol)) {
+            return false;
+        }
+
+        let defining = &symbol.defining_crate().u_ident.0;
+        let instantiating = symbol
+            .instantiating_crate_ident()
+            .map(|id| &id.u_ident.0[..]);
+        let named = |names: &[String]| {
+            names
+                .iter()
+                .any(|name| **defining == **name || instantiating == Some(&name[..]))
+        };
+
+        (self.include.is_empty() || named(&self.include)) && !named(&self.exclude)
+    }
+
+    fn keeps_input(&self, input: &str) -> bool {
+        self.is_empty() || self.keeps(Symbol::parse(input).ok().as_ref())
+    }
+}
+
+/// The number of symbols read before they are processed and printed. Large
+/// inputs are processed batch by batch to bound memory usage.
+const BATCH_LEN: usize = 1 << 16;
+
+/// A symbol to process, with its size if it was read from an object file
+/// that records one. With `--unique-skeleton`, it stands for `count` symbols
+/// with the same skeleton, and `size` is their total size.
+struct Input {
+    symbol: String,
+    size: Option<u64>,
+    count: Option<usize>,
+}
+
+/// The first symbol of every skeleton, in the order of their first
+/// occurrence. Symbols that do not parse are kept as they are.
+#[derive(Default)]
+struct Representatives {
+    inputs: Vec<Input>,
+    by_skeleton: HashMap<String, usize>,
+}
+
+impl Representatives {
+    fn add(&mut self, command: Command, mut input: Input) {
+        input.count = Some(1);
+
+        let skeleton = match command.parse(&input.symbol) {
+            Ok(symbol) => symbol.skeleton().demangle(),
+            Err(_) => {
+                self.inputs.push(input);
+                return;
+            }
+        };
+
+        match self.by_skeleton.get(&skeleton) {
+            Some(&index) => {
+                let representative = &mut self.inputs[index];
+                representative.count = representative.count.map(|count| count + 1);
+                representative.size = match (representative.size, input.size) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+            None => {
+                self.by_skeleton.insert(skeleton, self.inputs.len());
+                self.inputs.push(input);
+            }
+        }
+    }
+}
+
+/// How the results of a command are printed.
+enum Format {
+    /// Highlighted with `theme` if there is one.
+    Text {
+        theme: Option<&'static Theme>,
+    },
+    Json,
+    /// One row per symbol, after a header row, with the fields of `columns`
+    /// separated by `separator`.
+    Table {
+        separator: char,
+        columns: Vec<Column>,
+    },
+}
+
+#[derive(Copy, Clone)]
+enum Column {
+    Mangled,
+    Output,
+    Demangled,
+    Crate,
+    Skeleton,
+    Size,
+    Count,
+    Error,
+}
+
+impl Column {
+    const ALL: [Column; 8] = [
+        Column::Mangled,
+        Column::Output,
+        Column::Demangled,
+        Column::Crate,
+        Column::Skeleton,
+        Column::Size,
+        Column::Count,
+        Column::Error,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Column::Mangled => "mangled",
+            Column::Output => "output",
+            Column::Demangled => "demangled",
+            Column::Crate => "crate",
+            Column::Skeleton => "skeleton",
+            Column::Size => "size",
+            Column::Count => "count",
+            Column::Error => "error",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Column> {
+        Column::ALL
+            .iter()
+            .cloned()
+            .find(|column| column.name() == name)
+    }
+
+    /// The value of this column for `input`, empty if it does not apply.
+    fn value(self, input: &Input, result: &Result<(Symbol, String), String>) -> String {
+        match (self, result) {
+            (Column::Mangled, _) => input.symbol.clone(),
+            (Column::Size, _) => input.size.map_or(String::new(), |size| size.to_string()),
+            (Column::Count, _) => input.count.map_or(String::new(), |count| count.to_string()),
+            (Column::Output, Ok((_, output))) => output.clone(),
+            (Column::Demangled, Ok((symbol, _))) => symbol.demangle(),
+            (Column::Crate, Ok((symbol, _))) => symbol.defining_crate().u_ident.0.to_string(),
+            (Column::Skeleton, Ok((symbol, _))) => symbol.skeleton().demangle(),
+            (Column::Error, Err(e)) => e.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The output for one input symbol.
+struct Output {
+    line: String,
+    failed: bool,
+}
+
+fn process_one(command: Command, format: &Format, input: &Input) -> Output {
+    let result = command.run(&input.symbol);
+    let failed = result.is_err();
+
+    let line = match (format, result) {
+        (&Format::Json, ref result) => json_record(input, result),
+        (
+            &Format::Table {
+                separator,
+                ref columns,
+            },
+            ref result,
+        ) => table_row(input, result, separator, columns),
+        (&Format::Text { theme }, Ok((_, output))) => {
+            let output = match theme {
+                Some(theme) if matches!(command, Command::Demangle(_)) => {
+                    let mut highlighted = String::new();
+                    highlight(&output, theme, &mut highlighted);
+                    highlighted
+                }
+                _ => output,
+            };
+            let output = match input.size {
+                Some(size) => format!("{:>10}  {}", size, output),
+                None => output,
+            };
+            match input.count {
+                Some(count) => format!("{:>8}  {}", count, output),
+                None => output,
+            }
+        }
+        (&Format::Text { .. }, Err(e)) => format!("{}: {}", input.symbol, e),
+    };
+
+    Output { line, failed }
+}
+
+/// Runs `command` on every symbol of `batch` on `jobs` threads and prints
+/// the results in order. Returns whether any symbol failed.
+fn run_batch<W: Write>(
+    command: Command,
+    format: &Format,
+    jobs: usize,
+    batch: &[Input],
+    out: &mut W,
+) -> bool {
+    let outputs: Vec<Output> = if jobs <= 1 || batch.len() < 2 {
+        batch
+            .iter()
+            .map(|input| process_one(command, format, input))
+            .collect()
+    } else {
+        let chunk_len = batch.len().div_ceil(jobs);
+        thread::scope(|scope| {
+            let chunks: Vec<_> = batch
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    thread::Builder::new()
+                        .stack_size(STACK_SIZE)
+                        .spawn_scoped(scope, move || {
+                            chunk
+                                .iter()
+                                .map(|input| process_one(command, format, input))
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_else(|e| {
+                            host::fail(format_args!("failed to spawn thread: {}", e))
+                        })
+                })
+                .collect();
+
+            chunks
+                .into_iter()
+                .flat_map(|chunk| {
+                    chunk
+                        .join()
+                        .unwrap_or_else(|_| host::fail(format_args!("worker thread panicked")))
+                })
+                .collect()
+        })
+    };
+
+    let mut failed = false;
+    for output in outputs {
+        failed |= output.failed;
+        if output.failed && matches!(*format, Format::Text { .. }) {
+            eprintln!("{}", output.line);
+        } else {
+            writeln!(out, "{}", output.line).check_stdout();
+        }
+    }
+    failed
+}
+
+/// Returns the number of threads given by `--jobs` or `-j`, 1 if there is
+/// none or the host has no threads, and the remaining arguments.
+fn split_jobs_flag(args: &[String]) -> (usize, Vec<String>) {
+    let mut jobs = 1;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg != "--jobs" && arg != "-j" {
+            rest.push(arg.clone());
+            continue;
+        }
+
+        jobs = match args.next().map(|n| n.parse::<usize>()) {
+            Some(Ok(0)) => host::available_parallelism(),
+            Some(Ok(n)) => n,
+            _ => usage_error(arg),
+        };
+    }
+
+    (if host::THREADS { jobs } else { 1 }, rest)
+}
+
+/// Returns the output format given by `--json`, `--output` and `--columns`,
+/// and the remaining arguments.
+fn split_format_flags(args: &[String]) -> (Format, Vec<String>) {
+    let (theme, args) = split_color_flags(args);
+    let mut format = None;
+    let mut columns = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--json" => format = Some("json"),
+            "--output" => match args.next().map(|format| &format[..]) {
+                Some(name @ "text") | Some(name @ "json") | Some(name @ "csv")
+                | Some(name @ "tsv") => format = Some(name),
+                _ => usage_error(arg),
+            },
+            "--columns" => {
+                let list = args.next().unwrap_or_else(|| usage_error(arg));
+                columns = Some(
+                    list.split(',')
+                        .map(|name| Column::from_name(name).unwrap_or_else(|| usage_error(name)))
+                        .collect(),
+                );
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let separator = match format {
+        Some("csv") => ',',
+        Some("tsv") => '\t',
+        _ if columns.is_some() => usage_error("--columns"),
+        Some("json") => return (Format::Json, rest),
+        _ => return (Format::Text { theme }, rest),
+    };
+    let columns = columns.unwrap_or_else(|| {
+        vec![
+            Column::Mangled,
+            Column::Demangled,
+            Column::Crate,
+            Column::Skeleton,
+        ]
+    });
+
+    (Format::Table { separator, columns }, rest)
+}
+
+/// Returns the theme to highlight demangled symbols with as given by `--color`
+/// and `--theme`, and the remaining arguments. With `--color auto`, the
+/// default, symbols are highlighted if stdout is a terminal, `TERM` is not
+/// `dumb`, and `NO_COLOR` is not set.
+fn split_color_flags(args: &[String]) -> (Option<&'static Theme>, Vec<String>) {
+    let mut color = None;
+    let mut theme = &Theme::DARK;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--color" => match args.next().map(|color| &color[..]) {
+                Some("auto") => color = None,
+                Some("always") => color = Some(true),
+                Some("never") => color = Some(false),
+                _ => usage_error(arg),
+            },
+            "--theme" => {
+                theme = args
+                    .next()
+                    .and_then(|name| Theme::named(name))
+                    .unwrap_or_else(|| usage_error(arg))
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let color = color.unwrap_or_else(|| {
+        host::stdout_is_terminal()
+            && env::var_os("NO_COLOR").is_none()
+            && env::var_os("TERM").is_some_and(|term| term != "dumb")
+    });
+
+    (if color { Some(theme) } else { None }, rest)
+}
+
+/// Returns the crates and patterns given by `--crate` and `--match`, and the
+/// remaining arguments.
+fn split_filter_flags(args: &[String]) -> (SymbolFilter, Vec<String>) {
+    let mut filter = SymbolFilter::default();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--match" {
+            let pattern = args.next().unwrap_or_else(|| usage_error(arg));
+            match Pattern::parse(pattern) {
+                Ok(pattern) => filter.patterns.push(pattern),
+                Err(e) => {
+                    eprintln!("{}: {}", pattern, e);
+                    process::exit(2);
+                }
+            }
+            continue;
+        }
+        if arg != "--crate" {
+            rest.push(arg.clone());
+            continue;
+        }
+
+        match args.next() {
+            Some(name) if name.starts_with('!') => filter.exclude.push(name[1..].to_string()),
+            Some(name) => filter.include.push(name.clone()),
+            None => usage_error(arg),
+        }
+    }
+
+    (filter, rest)
+}
+
+/// Returns whether `args` contain `--json`, and the remaining arguments.
+fn split_json_flag(args: &[String]) -> (bool, Vec<String>) {
+    split_flag(args, "--json")
+}
+
+/// Returns whether `args` contain `flag`, and the remaining arguments.
+fn split_flag(args: &[String], flag: &str) -> (bool, Vec<String>) {
+    let found = args.iter().any(|arg| arg == flag);
+    let rest = args.iter().filter(|arg| *arg != flag).cloned().collect();
+    (found, rest)
+}
+
+fn explain_all(args: &[String]) -> ! {
+    let (json, inputs) = split_json_flag(args);
+    let (crates, inputs) = split_filter_flags(&inputs);
+    let mut failed = false;
+    let mut first = true;
+
+    for_each_input(&inputs, |input| {
+        let explanation = match explain(input) {
+            Ok(explanation) if crates.keeps(Some(&explanation.symbol)) => explanation,
+            Ok(_) => return,
+            Err(_) if !crates.keeps(None) => return,
+            Err(e) => {
+                eprintln!("{}: {}", input, e);
+                failed = true;
+                return;
+            }
+        };
+
+        if !json {
+            if !first {
+                println!();
+            }
+            first = false;
+            print!("{}", explanation);
+            return;
+        }
+
+        let text = |start: usize, end: usize| {
+            let mut quoted = String::new();
+            json_string(&explanation.mangled[start..end], &mut quoted);
+            quoted
+        };
+        let tokens: Vec<String> = explanation
+            .tokens
+            .iter()
+            .map(|token| {
+                let mut object = format!(
+                    "{{\"start\":{},\"end\":{},\"text\":{},\"description\":",
+                    token.start,
+                    token.end,
+                    text(token.start, token.end)
+                );
+                json_string(&token.description, &mut object);
+                object + "}"
+            })
+            .collect();
+        let backrefs: Vec<String> = explanation
+            .backrefs
+            .iter()
+            .map(|backref| {
+                let mut object = format!(
+                    "{{\"pos\":{},\"target_start\":{},\"target_end\":{},\"target\":{},\
+                     \"demangled\":",
+                    backref.pos,
+                    backref.target_start,
+                    backref.target_end,
+                    text(backref.target_start, backref.target_end)
+                );
+                json_string(&backref.demangled, &mut object);
+                object + "}"
+            })
+            .collect();
+
+        let mut object = String::from("{\"input\":");
+        json_string(input, &mut object);
+        object.push_str(",\"demangled\":");
+        json_string(&explanation.symbol.demangle(), &mut object);
+        object.push_str(",\"decompressed\":");
+        json_string(&explanation.symbol.mangle_uncompressed(), &mut object);
+        println!(
+            "{},\"tokens\":[{}],\"backrefs\":[{}]}}",
+            object,
+            tokens.join(","),
+            backrefs.join(",")
+        );
+    });
+
+    process::exit(if failed { 1 } else { 0 });
+}
+
+fn verify_all(args: &[String]) -> ! {
+    let (json, inputs) = split_json_flag(args);
+    let mut total = 0;
+    let mut failed = 0;
+
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    for_each_input(&inputs, |input| {
+        let failures = verify(input);
+        total += 1;
+        if !failures.is_empty() {
+            failed += 1;
+        }
+
+        for failure in failures {
+            let line = if json {
+                let mut object = String::from("{\"input\":");
+                json_string(input, &mut object);
+                object.push_str(&format!(
+                    ",\"code\":\"{}\",\"check\":\"{}\",\"offset\":",
+                    failure.check.code(),
+                    failure.check.name()
+                ));
+                match failure.offset {
+                    Some(offset) => object.push_str(&offset.to_string()),
+                    None => object.push_str("null"),
+                }
+                object.push_str(",\"message\":");
+                json_string(&failure.message, &mut object);
+                object + "}"
+            } else {
+                format!("{}: {}", input, failure)
+            };
+            writeln!(stdout, "{}", line).check_stdout();
+        }
+    });
+    drop(stdout);
+
+    eprintln!("{} of {} symbols failed verification", failed, total);
+    process::exit(if failed == 0 { 0 } else { 1 });
+}
+
+fn analyze_all(args: &[String]) -> ! {
+    let mut failed = false;
+    let mut message = Vec::new();
+
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    for_each_input(args, |input| match analyze(input) {
+        Ok(info) => {
+            message.clear();
+            info.write_protobuf_delimited(&mut message);
+            stdout.write_all(&message).check_stdout();
+        }
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            failed = true;
+        }
+    });
+    stdout.flush().check_stdout();
+
+    process::exit(if failed { 1 } else { 0 });
+}
+
+fn dictionary_all(args: &[String]) -> ! {
+    if let Some(flag) = args.first().filter(|&arg| arg == "--check") {
+        match args.get(1..) {
+            Some([path]) => check_dictionary(path),
+            _ => usage_error(flag),
+        }
+    }
+
+    let (json, inputs) = split_json_flag(args);
+    let mut failed = false;
+
+    for_each_input(&inputs, |input| {
+        let dump = match Symbol::parse(input) {
+            Ok(symbol) => symbol.dump_dictionary(),
+            Err(e) => {
+                eprintln!("{}: {}", input, e);
+                failed = true;
+                return;
+            }
+        };
+
+        if !json {
+            print!("{}", dump.to_text());
+            return;
+        }
+        match dictionary_to_json(&dump) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    });
+
+    process::exit(if failed { 1 } else { 0 });
+}
+
+/// Compares the dump in the file `path` with what this build does.
+fn check_dictionary(path: &str) -> ! {
+    let text = String::from_utf8_lossy(&host::read(path)).into_owned();
+    let dump = if text.trim_start().starts_with('{') {
+        dictionary_from_json(&text)
+    } else {
+        DictionaryDump::from_text(&text)
+    };
+
+    let differences = dump
+        .and_then(|dump| dump.differences())
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        });
+    for difference in &differences {
+        println!("{}", difference);
+    }
+
+    process::exit(if differences.is_empty() { 0 } else { 1 });
+}
+
+#[cfg(feature = "json")]
+fn dictionary_to_json(dump: &DictionaryDump) -> Result<String, String> {
+    Ok(dump.to_json())
+}
+
+#[cfg(not(feature = "json"))]
+fn dictionary_to_json(_dump: &DictionaryDump) -> Result<String, String> {
+    Err("std-mangle was built without the `json` feature".to_string())
+}
+
+#[cfg(feature = "json")]
+fn dictionary_from_json(json: &str) -> Result<DictionaryDump, String> {
+    DictionaryDump::from_json(json)
+}
+
+#[cfg(not(feature = "json"))]
+fn dictionary_from_json(_json: &str) -> Result<DictionaryDump, String> {
+    Err("std-mangle was built without the `json` feature".to_string())
+}
+
+fn stats(args: &[String]) {
+    let (json, inputs) = split_json_flag(args);
+    let (crates, inputs) = split_filter_flags(&inputs);
+    let mut stats = SymbolStats::new(10);
+    for_each_input(&inputs, |input| {
+        if crates.keeps_input(input) {
+            stats.add(input);
+        }
+    });
+
+    if !json {
+        print!("{}", stats);
+        return;
+    }
+
+    let counts = |entries: Vec<(&str, usize)>| {
+        let entries: Vec<String> = entries
+            .into_iter()
+            .map(|(name, count)| {
+                let mut entry = "{\"name\":".to_string();
+                json_string(name, &mut entry);
+                entry + &format!(",\"count\":{}}}", count)
+            })
+            .collect();
+        format!("[{}]", entries.join(","))
+    };
+    let longest: Vec<String> = stats
+        .longest()
+        .iter()
+        .map(|&(len, ref demangled)| {
+            let mut entry = "{\"demangled\":".to_string();
+            json_string(demangled, &mut entry);
+            entry + &format!(",\"len\":{}}}", len)
+        })
+        .collect();
+    let (mangled_len, demangled_len) = stats.average_len();
+
+    println!(
+        "{{\"symbols\":{},\"invalid\":{},\"average_mangled_len\":{:.1},\
+         \"average_demangled_len\":{:.1},\"crates\":{},\"skeletons\":{},\
+         \"most_instantiated\":{},\"longest\":[{}]}}",
+        stats.symbols,
+        stats.invalid,
+        mangled_len,
+        demangled_len,
+        counts(stats.top_crates(10)),
+        counts(stats.top_skeletons(10)),
+        counts(stats.most_instantiated(10)),
+        longest.join(",")
+    );
+}
+
+fn top(args: &[String]) {
+    let (json, args) = split_json_flag(args);
+    let (theme, args) = split_color_flags(&args);
+    let (crates, args) = split_filter_flags(&args);
+    let mut n = 10;
+    let mut key: fn(&Symbol) -> usize = |symbol| symbol.demangle().len();
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "-n" => {
+                n = match args.next().map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => usage_error(arg),
+                }
+            }
+            "--by" => {
+                key = match args.next().map(|key| &key[..]) {
+                    Some("demangled-len") => |symbol| symbol.demangle().len(),
+                    Some("depth") => |symbol| symbol.metrics().max_depth,
+                    Some("args") => |symbol| symbol.metrics().generic_arg_count,
+                    _ => usage_error(arg),
+                }
+            }
+            _ => inputs.push(arg.clone()),
+        }
+    }
+
+    // A min-heap of the best `n` symbols so far. Of symbols with the same
+    // value, the one that came first ranks higher.
+    let mut best = BinaryHeap::new();
+    let mut index = 0;
+    for_each_input(&inputs, |input| {
+        let symbol = match Symbol::parse(input) {
+            Ok(symbol) if crates.keeps(Some(&symbol)) => symbol,
+            _ => return,
+        };
+
+        best.push(Reverse((key(&symbol), Reverse(index), input.to_string())));
+        if best.len() > n {
+            best.pop();
+        }
+        index += 1;
+    });
+
+    for Reverse((value, _, input)) in best.into_sorted_vec() {
+        let demangled = Symbol::parse(&input).unwrap().demangle();
+        if json {
+            let mut object = String::from("{\"input\":");
+            json_string(&input, &mut object);
+            object.push_str(",\"demangled\":");
+            json_string(&demangled, &mut object);
+            println!("{},\"value\":{}}}", object, value);
+        } else if let Some(theme) = theme {
+            let mut highlighted = String::new();
+            highlight(&demangled, theme, &mut highlighted);
+            println!("{:>8}  {}", value, highlighted);
+        } else {
+            println!("{:>8}  {}", value, demangled);
+        }
+    }
+}
+
+fn bench(args: &[String]) {
+    let mut warmup = 1;
+    let mut repetitions = 5;
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let count = match &arg[..] {
+            "--warmup" => &mut warmup,
+            "--repetitions" => &mut repetitions,
+            _ => {
+                inputs.push(arg.clone());
+                continue;
+            }
+        };
+        *count = match args.next().map(|n| n.parse()) {
+            Some(Ok(n)) => n,
+            _ => usage_error(arg),
+        };
+    }
+    if repetitions == 0 {
+        usage_error("--repetitions");
+    }
+
+    let mut symbols = Vec::new();
+    for_each_input(&inputs, |input| symbols.push(input.to_string()));
+    if symbols.is_empty() {
+        eprintln!("no symbols to demangle");
+        process::exit(1);
+    }
+    let bytes: usize = symbols.iter().map(String::len).sum();
+
+    let run = || {
+        let mut failed = 0;
+        for symbol in &symbols {
+            match Symbol::parse(symbol) {
+                Ok(symbol) => {
+                    black_box(symbol.demangle());
+                }
+                Err(_) => failed += 1,
+            }
+        }
+        failed
+    };
+
+    let mut failed = 0;
+    for _ in 0..warmup {
+        failed = run();
+    }
+
+    let allocations = allocation_counts();
+    let mut times: Vec<Duration> = (0..repetitions)
+        .map(|_| {
+            let start = Instant::now();
+            failed = run();
+            start.elapsed()
+        })
+        .collect();
+    let allocations = allocation_counts().map(|(count, bytes)| {
+        let (start_count, start_bytes) = allocations.unwrap();
+        let per_symbol = (repetitions * symbols.len()) as f64;
+        (
+            (count - start_count) as f64 / per_symbol,
+            (bytes - start_bytes) as f64 / per_symbol,
+        )
+    });
+    times.sort();
+
+    println!(
+        "{} symbols, {} bytes, {} of them not valid",
+        symbols.len(),
+        bytes,
+        failed
+    );
+    println!("{} repetitions after {} for warmup\n", repetitions, warmup);
+    println!(
+        "{:<8} {:>12} {:>14} {:>10}",
+        "", "time", "symbols/s", "MB/s"
+    );
+    for &(name, time) in &[
+        ("fastest", times[0]),
+        ("median", times[times.len() / 2]),
+        ("slowest", times[times.len() - 1]),
+    ] {
+        let seconds = time.as_secs_f64();
+        println!(
+            "{:<8} {:>12.2?} {:>14.0} {:>10.2}",
+            name,
+            time,
+            symbols.len() as f64 / seconds,
+            bytes as f64 / seconds / 1e6
+        );
+    }
+
+    match allocations {
+        Some((count, bytes)) => println!(
+            "\n{:.1} allocations and {:.1} allocated bytes per symbol",
+            count, bytes
+        ),
+        None => println!("\nallocations are only counted with the `profile` feature"),
+    }
+}
+
+/// The allocations made so far and their total size, or `None` if they are
+/// not counted.
+#[cfg(feature = "profile")]
+fn allocation_counts() -> Option<(u64, u64)> {
+    Some(profile::allocation_counts())
+}
+
+#[cfg(not(feature = "profile"))]
+fn allocation_counts() -> Option<(u64, u64)> {
+    None
+}
+
+fn generate(args: &[String]) {
+    let mut n = 10;
+    let mut config = GeneratorConfig::default();
+    let mut seed = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let value = args.next().map(|value| value.parse());
+        match (&arg[..], value) {
+            ("-n", Some(Ok(value))) => n = value,
+            ("--max-depth", Some(Ok(value))) => config.max_depth = value as usize,
+            ("--seed", Some(Ok(value))) => seed = Some(value),
+            _ => usage_error(arg),
+        }
+    }
+
+    let seed = seed.unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let seed = now.as_secs() ^ u64::from(now.subsec_nanos());
+        eprintln!("seed: {}", seed);
+        seed
+    });
+
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    for mangled in SymbolGenerator::new(config)
+        .with_seed(seed)
+        .mangled()
+        .take(n as usize)
+    {
+        writeln!(stdout, "{}", mangled).check_stdout();
+    }
+}
+
+fn diff(args: &[String]) -> ! {
+    let (json, args) = split_json_flag(args);
+    let (crates, args) = split_filter_flags(&args);
+    let ignore_disambiguators = args.iter().any(|arg| arg == "--ignore-disambiguators");
+    let delimiter = if args.iter().any(|arg| arg == "-0") {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    let paths: Vec<&String> = args
+        .iter()
+        .filter(|arg| *arg != "--ignore-disambiguators" && *arg != "-0")
+        .collect();
+
+    if paths.len() != 2 {
+        usage_error(paths.get(2).map_or("diff", |arg| &arg[..]));
+    }
+
+    let read = |path: &str| {
+        let file = host::open(path, 2);
+        let mut symbols = Vec::new();
+        for_each_line(
+            BufReader::new(file),
+            path,
+            delimiter,
+            &mut |line: &str, _| {
+                if crates.keeps_input(line) {
+                    symbols.push(line.to_string());
+                }
+            },
+        );
+        symbols
+    };
+    let old = read(paths[0]);
+    let new = read(paths[1]);
+
+    let diff = SymbolDiff::new(
+        old.iter().map(|s| &s[..]),
+        new.iter().map(|s| &s[..]),
+        ignore_disambiguators,
+    );
+
+    if json {
+        let list = |names: &[String]| {
+            let names: Vec<String> = names
+                .iter()
+                .map(|name| {
+                    let mut quoted = String::new();
+                    json_string(name, &mut quoted);
+                    quoted
+                })
+                .collect();
+            format!("[{}]", names.join(","))
+        };
+        let skeletons: Vec<String> = diff
+            .skeletons
+            .iter()
+            .map(|(skeleton, entry)| {
+                let mut object = "{\"skeleton\":".to_string();
+                json_string(skeleton, &mut object);
+                object
+                    + &format!(
+                        ",\"added\":{},\"removed\":{}}}",
+                        list(&entry.added),
+                        list(&entry.removed)
+                    )
+            })
+            .collect();
+
+        println!(
+            "{{\"added\":{},\"removed\":{},\"invalid\":{},\"skeletons\":[{}]}}",
+            diff.added(),
+            diff.removed(),
+            diff.invalid,
+            skeletons.join(",")
+        );
+    } else {
+        print!("{}", diff);
+    }
+
+    process::exit(if diff.skeletons.is_empty() { 0 } else { 1 });
+}
+
+/// Prints the demangled form of every line that matches, then exits with the
+/// status of `grep`: 0 if any line matched, 1 if none did.
+#[cfg(feature = "grep")]
+fn grep(args: &[String]) -> ! {
+    use regex::Regex;
+    use std_mangle_rs::filter::replace_skeletons;
+
+    let skeleton = args.first().is_some_and(|arg| arg == "--skeleton");
+    let args = if skeleton { &args[1..] } else { args };
+
+    let pattern = args.first().unwrap_or_else(|| usage_error("grep"));
+    let regex = Regex::new(pattern).unwrap_or_else(|e| {
+        eprintln!("invalid regex: {}", e);
+        process::exit(2);
+    });
+
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    let mut demangled = String::new();
+    let mut skeletons = String::new();
+    let mut matched = false;
+
+    let mut grep_lines = |reader: &mut dyn BufRead, name: &str| {
+        for line in reader.lines() {
+            let line = line.unwrap_or_else(|e| {
+                eprintln!("failed to read {}: {}", name, e);
+                process::exit(2);
+            });
+
+            demangled.clear();
+            replace_symbols(&line, &mut demangled);
+
+            let haystack = if skeleton {
+                skeletons.clear();
+                replace_skeletons(&line, &mut skeletons);
+                &skeletons
+            } else {
+                &demangled
+            };
+
+            if regex.is_match(haystack) {
+                matched = true;
+                writeln!(stdout, "{}", demangled).check_stdout();
+            }
+        }
+    };
+
+    if args.len() == 1 {
+        let stdin = io::stdin();
+        grep_lines(&mut stdin.lock(), "stdin");
+    }
+
+    for path in &args[1..] {
+        let file = host::open(path, 2);
+        grep_lines(&mut BufReader::new(file), path);
+    }
+
+    stdout.flush().check_stdout();
+    process::exit(if matched { 0 } else { 1 });
+}
+
+#[cfg(not(feature = "grep"))]
+fn grep(_args: &[String]) -> ! {
+    eprintln!("std-mangle was built without the `grep` feature");
+    process::exit(2);
+}
+
+/// Formats the result for `input` as a single-line JSON object.
+fn json_record(input: &Input, result: &Result<(Symbol, String), String>) -> String {
+    let mut record = String::new();
+    let mut field = |name: &str, value: Option<&str>| {
+        record.push(if record.is_empty() { '{' } else { ',' });
+        json_string(name, &mut record);
+        record.push(':');
+        match value {
+            Some(value) => json_string(value, &mut record),
+            None => record.push_str("null"),
+        }
+    };
+
+    match *result {
+        Ok((ref symbol, ref output)) => {
+            field("input", Some(&input.symbol));
+            field("output", Some(output));
+            field("demangled", Some(&symbol.demangle()));
+            field("crate", Some(&symbol.defining_crate().u_ident.0));
+            field("skeleton", Some(&symbol.skeleton().demangle()));
+            field("error", None);
+        }
+        Err(ref e) => {
+            field("input", Some(&input.symbol));
+            for &name in &["output", "demangled", "crate", "skeleton"] {
+                field(name, None);
+            }
+            field("error", Some(e));
+        }
+    }
+
+    for &(name, value) in &[
+        ("size", input.size),
+        ("count", input.count.map(|n| n as u64)),
+    ] {
+        record.push_str(&format!(",\"{}\":", name));
+        match value {
+            Some(value) => record.push_str(&value.to_string()),
+            None => record.push_str("null"),
+        }
+    }
+
+    record.push('}');
+    record
+}
+
+/// Formats the result for `input` as a CSV or TSV row. CSV fields are quoted
+/// as needed, and tabs and line breaks in TSV fields replaced by spaces.
+fn table_row(
+    input: &Input,
+    result: &Result<(Symbol, String), String>,
+    separator: char,
+    columns: &[Column],
+) -> String {
+    let mut row = String::new();
+
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            row.push(separator);
+        }
+
+        let value = column.value(input, result);
+        if separator == '\t' {
+            row.extend(value.chars().map(|c| match c {
+                '\t' | '\n' | '\r' => ' ',
+                c => c,
+            }));
+        } else if value.contains([',', '"', '\n', '\r']) {
+            row.push('"');
+            row.push_str(&value.replace('"', "\"\""));
+            row.push('"');
+        } else {
+            row.push_str(&value);
+        }
+    }
+
+    row
+}
+
+fn json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn usage_error(arg: &str) -> ! {
+    eprintln!("unexpected argument `{}`\n\n{}", arg, USAGE);
+    process::exit(2);
+}
+
+fn for_each_input<F: FnMut(&str)>(args: &[String], mut f: F) {
+    for_each_sized_input(args, |input, _| f(input));
+}
+
+/// Calls `f` for every symbol given by `args`, or for every line of stdin if
+/// `args` names none. Both `-` and `-f -` stand for the lines of stdin, and
+/// an argument naming a file stands for its lines if it is text, or else for
+/// the v0 symbols in it, which must then be an object file. Only the symbols
+/// of object files can have a size. With
+/// `-0`, the symbols in files and stdin are separated by NUL instead of by
+/// line breaks.
+fn for_each_sized_input<F: FnMut(&str, Option<u64>)>(args: &[String], mut f: F) {
+    let delimiter = if args.iter().any(|arg| arg == "-0") {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "-0").collect();
+
+    if args.is_empty() {
+        let stdin = io::stdin();
+        for_each_line(stdin.lock(), "stdin", delimiter, &mut f);
+        return;
+    }
+
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "-" {
+            let stdin = io::stdin();
+            for_each_line(stdin.lock(), "stdin", delimiter, &mut f);
+            continue;
+        }
+
+        if arg != "-f" && arg != "--file" {
+            if arg.starts_with('-') {
+                usage_error(arg);
+            }
+            if Path::new(arg).is_file() {
+                for_each_file_symbol(arg, delimiter, &mut f);
+            } else {
+                f(arg, None);
+            }
+            continue;
+        }
+
+        let path = args.next().unwrap_or_else(|| usage_error(arg));
+        if path == "-" {
+            let stdin = io::stdin();
+            for_each_line(stdin.lock(), "stdin", delimiter, &mut f);
+            continue;
+        }
+
+        let file = host::open(path, 1);
+        for_each_line(BufReader::new(file), path, delimiter, &mut f);
+    }
+}
+
+/// Calls `f` for every record of the file `path` if it is UTF-8, which object
+/// files never are, or else for the v0 symbols of the object file.
+fn for_each_file_symbol<F: FnMut(&str, Option<u64>)>(path: &str, delimiter: u8, f: &mut F) {
+    let data = host::read(path);
+
+    match String::from_utf8(data) {
+        Ok(text) => for_each_line(text.as_bytes(), path, delimiter, f),
+        Err(e) => for_each_binary_symbol(path, e.as_bytes(), f),
+    }
+}
+
+#[cfg(feature = "object")]
+fn for_each_binary_symbol<F: FnMut(&str, Option<u64>)>(path: &str, data: &[u8], f: &mut F) {
+    let symbols = binary::symbols(data).unwrap_or_else(|e| {
+        eprintln!("failed to read {}: {}", path, e);
+        process::exit(1);
+    });
+
+    for symbol in symbols
+        .iter()
+        .filter(|symbol| symbol.name.starts_with("_R"))
+    {
+        f(&symbol.name, symbol.size);
+    }
+}
+
+#[cfg(not(feature = "object"))]
+fn for_each_binary_symbol<F: FnMut(&str, Option<u64>)>(path: &str, _data: &[u8], _f: &mut F) {
+    eprintln!(
+        "cannot read {}: std-mangle was built without the `object` feature",
+        path
+    );
+    process::exit(1);
+}
+
+/// Calls `f` for every non-empty record of `reader`, split at `delimiter`
+/// and trimmed.
+fn for_each_line<R: BufRead, F: FnMut(&str, Option<u64>)>(
+    reader: R,
+    name: &str,
+    delimiter: u8,
+    f: &mut F,
+) {
+    for line in reader.split(delimiter) {
+        let line = line
+            .and_then(|line| {
+                String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .unwrap_or_else(|e| host::fail(format_args!("failed to read {}: {}", name, e)));
+
+        let line = line.trim();
+        if !line.is_empty() {
+            f(line, None);
+        }
+    }
+}
+
+/// Filters stdin as JSON lines, demangling the fields given by `--fields`.
+#[cfg(feature = "json")]
+fn filter_json_lines(args: &[String]) {
+    let fields: Vec<&str> = match args {
+        [] => Vec::new(),
+        [flag, fields] if flag == "--fields" => fields.split(',').collect(),
+        [arg, ..] => usage_error(arg),
+    };
+
+    filter(|line: &str, out: &mut String| replace_json_line(line, &fields, out));
+}
+
+#[cfg(not(feature = "json"))]
+fn filter_json_lines(_args: &[String]) {
+    eprintln!("std-mangle was built without the `json` feature");
+    process::exit(2);
+}
+
+/// Copies stdin to stdout, passing every line through `replace`. The output
+/// is flushed whenever no more input is available yet, so that the output of
+/// a running program shows up as it is produced.
+fn filter<F: Fn(&str, &mut String)>(replace: F) {
+    let stdin = io::stdin();
+    let mut stdin = BufReader::new(stdin.lock());
+    let stdout = io::stdout();
+    let mut stdout = io::BufWriter::new(stdout.lock());
+    let mut line = Vec::new();
+    let mut demangled = String::new();
+
+    loop {
+        line.clear();
+        let len = stdin
+            .read_until(b'\n', &mut line)
+            .unwrap_or_else(|e| host::fail(format_args!("failed to read stdin: {}", e)));
+        if len == 0 {
+            break;
+        }
+
+        // The last line may not end in a line break, and gets none.
+        let newline = line.last() == Some(&b'\n');
+        if newline {
+            line.pop();
+        }
+
+        // Lines that are not UTF-8 are copied unchanged.
+        match str::from_utf8(&line) {
+            Ok(line) => {
+                demangled.clear();
+                replace(line, &mut demangled);
+                stdout.write_all(demangled.as_bytes())
+            }
+            Err(_) => stdout.write_all(&line),
+        }
+        .check_stdout();
+
+        if newline {
+            stdout.write_all(b"\n").check_stdout();
+        }
+        if stdin.buffer().is_empty() {
+            stdout.flush().check_stdout();
+        }
+    }
+
+    stdout.flush().check_stdout();
+}
+
+/// Copies stdin to stdout with all symbols demangled as soon as the input
+/// arrives, without waiting for whole lines.
+fn stream() {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut filter = StreamingFilter::new();
+    let mut input = vec![0; 1 << 16];
+    let mut output = Vec::new();
+
+    loop {
+        let len = match stdin.read(&mut input) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => host::fail(format_args!("failed to read stdin: {}", e)),
+        };
+
+        output.clear();
+        filter.push(&input[..len], &mut output);
+        stdout.write_all(&output).check_stdout();
+        stdout.flush().check_stdout();
+    }
+
+    output.clear();
+    filter.finish(&mut output);
+    stdout.write_all(&output).check_stdout();
+    stdout.flush().check_stdout();
+}
+
+/// The file system, terminal and thread support of the host. Under WASI, the
+/// runtime only grants access to the directories it is given, terminals
+/// cannot be detected reliably, and there are no threads.
+mod host {
+    use std::fmt;
+    use std::fs::{self, File};
+    use std::io::{self, IsTerminal};
+    use std::process;
+    use std::thread;
+
+    /// Whether worker threads can be spawned.
+    pub const THREADS: bool = !cfg!(target_os = "wasi");
+
+    /// Opens `path`, or exits with `status` if that fails.
+    pub fn open(path: &str, status: i32) -> File {
+        File::open(path).unwrap_or_else(|e| {
+            eprintln!("failed to open {}: {}{}", path, e, hint(&e));
+            process::exit(status);
+        })
+    }
+
+    /// Reads the whole file `path`, or exits with status 1 if that fails.
+    pub fn read(path: &str) -> Vec<u8> {
+        fs::read(path).unwrap_or_else(|e| {
+            eprintln!("failed to read {}: {}{}", path, e, hint(&e));
+            process::exit(1);
+        })
+    }
+
+    /// Whether stdout is a terminal. Always false under WASI, where runtimes
+    /// report every inherited stream as one.
+    pub fn stdout_is_terminal() -> bool {
+        !cfg!(target_os = "wasi") && io::stdout().is_terminal()
+    }
+
+    /// Prints `message` to stderr and exits with status 1.
+    pub fn fail(message: fmt::Arguments) -> ! {
+        eprintln!("std-mangle: {}", message);
+        process::exit(1);
+    }
+
+    pub fn available_parallelism() -> usize {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    /// Checks the result of a write to stdout.
+    pub trait CheckStdout {
+        /// Exits quietly with status 0 if the write failed because the reader
+        /// of stdout went away, as with `std-mangle | head`, and with status
+        /// 1 and a message on any other error.
+        fn check_stdout(self);
+    }
+
+    impl CheckStdout for io::Result<()> {
+        fn check_stdout(self) {
+            match self {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+                Err(e) => fail(format_args!("failed to write stdout: {}", e)),
+            }
+        }
+    }
+
+    fn hint(e: &io::Error) -> &'static str {
+        match e.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                if cfg!(target_os = "wasi") =>
+            {
+                " (is its directory passed to the WASI runtime, e.g. with `wasmtime --dir`?)"
+            }
+            _ => "",
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Reading symbol names from ELF, Mach-O, PE and other object files.
+
+use ast::Symbol;
+use object::{BinaryFormat, Object, ObjectSection, ObjectSymbol};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// A named entry of the symbol table or the dynamic symbol table of an
+/// object file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinarySymbol {
+    pub name: String,
+    /// The size in bytes, if the object file records one.
+    pub size: Option<u64>,
+}
+
+/// A named entry of the symbol table or the dynamic symbol table, with the
+/// metadata that `read_symbols()` returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawSymbol {
+    pub name: String,
+    /// The name of the section the symbol is defined in, if any.
+    pub section: Option<String>,
+    pub address: u64,
+    /// The size in bytes, if the object file records one.
+    pub size: Option<u64>,
+}
+
+/// The object file `read_symbols()` reads from.
+#[derive(Clone, Copy, Debug)]
+pub enum Source<'a> {
+    Path(&'a Path),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> From<&'a Path> for Source<'a> {
+    fn from(path: &'a Path) -> Source<'a> {
+        Source::Path(path)
+    }
+}
+
+impl<'a> From<&'a str> for Source<'a> {
+    fn from(path: &'a str) -> Source<'a> {
+        Source::Path(Path::new(path))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Source<'a> {
+    fn from(data: &'a [u8]) -> Source<'a> {
+        Source::Bytes(data)
+    }
+}
+
+/// Returns the named symbols of the object file `data`, first those of the
+/// symbol table and then those only in the dynamic symbol table. On Mach-O,
+/// the leading `_` that is added to every symbol is removed again.
+pub fn symbols(data: &[u8]) -> Result<Vec<BinarySymbol>, String> {
+    Ok(raw_symbols(data)?
+        .into_iter()
+        .map(|symbol| BinarySymbol {
+            name: symbol.name,
+            size: symbol.size,
+        })
+        .collect())
+}
+
+/// Reads the named symbols of an object file, in the same order as
+/// `symbols()`, each with the result of parsing it as a v0 symbol.
+pub fn read_symbols<'a, S: Into<Source<'a>>>(
+    source: S,
+) -> Result<impl Iterator<Item = (RawSymbol, Result<Symbol, String>)>, String> {
+    let symbols = match source.into() {
+        Source::Path(path) => {
+            let data =
+                fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+            raw_symbols(&data)?
+        }
+        Source::Bytes(data) => raw_symbols(data)?,
+    };
+
+    Ok(symbols.into_iter().map(|symbol| {
+        let parsed = Symbol::parse(&symbol.name);
+        (symbol, parsed)
+    }))
+}
+
+fn raw_symbols(data: &[u8]) -> Result<Vec<RawSymbol>, String> {
+    let file = object::File::parse(data).map_err(|e| e.to_string())?;
+    let macho = file.format() == BinaryFormat::MachO;
+
+    let mut seen = HashSet::new();
+    let mut symbols = Vec::new();
+
+    for symbol in file.symbols().chain(file.dynamic_symbols()) {
+        let name = match symbol.name() {
+            Ok(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+        let name = match name.strip_prefix('_') {
+            Some(name) if macho => name,
+            _ => name,
+        };
+
+        if seen.insert(name) {
+            let section = symbol
+                .section_index()
+                .and_then(|index| file.section_by_index(index).ok())
+                .and_then(|section| section.name().ok().map(str::to_string));
+
+            symbols.push(RawSymbol {
+                name: name.to_string(),
+                section,
+                address: symbol.address(),
+                size: Some(symbol.size()).filter(|&size| size != 0),
+            });
+        }
+    }
+
+    Ok(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_symbols, symbols};
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn read_own_symbols() {
+        let data = fs::read(env::current_exe().unwrap()).unwrap();
+        let symbols = symbols(&data).unwrap();
+        assert!(symbols
+            .iter()
+            .any(|symbol| symbol.name.contains("read_own_symbols")));
+
+        assert!(super::symbols(b"not an object file").is_err());
+
+        let exe = env::current_exe().unwrap();
+        let (raw, parsed) = read_symbols(exe.as_path())
+            .unwrap()
+            .find(|(symbol, _)| symbol.name.contains("read_own_symbols"))
+            .unwrap();
+        assert!(raw.address != 0);
+        assert!(raw.section.unwrap().contains("text"));
+        assert!(parsed.is_ok() || raw.name.starts_with("_ZN"));
+        assert!(read_symbols(&b"not an object file"[..]).is_err());
+    }
+}
@@ -0,0 +1,755 @@
+//! An AST whose identifiers borrow from the mangled input.
+//!
+//! This mirrors the owned AST in `ast` but stores every identifier as a
+//! `&'a str` slice of the symbol it was parsed from, so parsing allocates only
+//! for the tree structure itself and for decoding punycode identifiers. Nodes
+//! can be converted to and from their owned counterparts with `From`.
+
+use ast::{self, BasicType, Binder, DecimalNumber, Disambiguator, Lifetime, Namespace, Ptr};
+use grammar::{self, Builder};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Symbol<'a> {
+    pub version: Option<DecimalNumber>,
+    pub path: Path<'a>,
+    pub instantiating_crate: Option<Path<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Ident<'a> {
+    pub dis: Disambiguator,
+    pub u_ident: UIdent<'a>,
+}
+
+/// An identifier as it appears in the mangled input, along with its decoded
+/// form if it is a punycode identifier.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct UIdent<'a> {
+    raw: &'a str,
+    decoded: Option<Box<str>>,
+}
+
+impl<'a> UIdent<'a> {
+    pub fn new(ident: &'a str) -> UIdent<'a> {
+        UIdent {
+            raw: ident,
+            decoded: None,
+        }
+    }
+
+    /// The identifier exactly as it appears in the input, i.e. still encoded
+    /// if it is a punycode identifier.
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    pub fn is_punycode(&self) -> bool {
+        self.decoded.is_some()
+    }
+
+    /// The decoded identifier.
+    pub fn to_str(&self) -> &str {
+        match self.decoded {
+            Some(ref decoded) => decoded,
+            None => self.raw,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Path<'a> {
+    CrateRoot {
+        id: Ident<'a>,
+    },
+    InherentImpl {
+        impl_path: ImplPath<'a>,
+        self_type: Type<'a>,
+    },
+    TraitImpl {
+        impl_path: ImplPath<'a>,
+        self_type: Type<'a>,
+        trait_name: Box<Path<'a>>,
+    },
+    TraitDef {
+        self_type: Type<'a>,
+        trait_name: Box<Path<'a>>,
+    },
+    Nested {
+        ns: Namespace,
+        inner: Box<Path<'a>>,
+        ident: Ident<'a>,
+    },
+    Generic {
+        inner: Box<Path<'a>>,
+        args: Vec<GenericArg<'a>>,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ImplPath<'a> {
+    pub dis: Option<Disambiguator>,
+    pub path: Box<Path<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum GenericArg<'a> {
+    Lifetime(Lifetime),
+    Type(Type<'a>),
+    Const(Const<'a>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Type<'a> {
+    BasicType(BasicType),
+    Array(Box<Type<'a>>, Box<Const<'a>>),
+    Slice(Box<Type<'a>>),
+    Named(Box<Path<'a>>),
+    Tuple(Vec<Type<'a>>),
+    Ref(Option<Lifetime>, Box<Type<'a>>),
+    RefMut(Option<Lifetime>, Box<Type<'a>>),
+    RawPtrConst(Box<Type<'a>>),
+    RawPtrMut(Box<Type<'a>>),
+    Fn(Box<FnSig<'a>>),
+    DynTrait(Box<DynBounds<'a>>, Lifetime),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct FnSig<'a> {
+    pub binder: Binder,
+    pub is_unsafe: bool,
+    pub abi: Option<Abi<'a>>,
+    pub param_types: Vec<Type<'a>>,
+    pub return_type: Type<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Abi<'a> {
+    C,
+    Named(UIdent<'a>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynBounds<'a> {
+    pub binder: Binder,
+    pub traits: Vec<DynTrait<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynTrait<'a> {
+    pub path: Path<'a>,
+    pub assoc_type_bindings: Vec<DynTraitAssocBinding<'a>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynTraitAssocBinding<'a> {
+    pub ident: UIdent<'a>,
+    pub ty: Type<'a>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Const<'a> {
+    Value(Type<'a>, u64),
+    Placeholder(Type<'a>),
+}
+
+/// Parses a mangled symbol into a tree that borrows its identifiers from
+/// `input`.
+pub fn parse(input: &[u8]) -> Result<Symbol<'_>, String> {
+    grammar::parse(input, BorrowedBuilder)
+}
+
+struct BorrowedBuilder;
+
+impl<'a> Builder<'a> for BorrowedBuilder {
+    type Symbol = Symbol<'a>;
+    type Path = Path<'a>;
+    type ImplPath = ImplPath<'a>;
+    type Ident = Ident<'a>;
+    type UIdent = UIdent<'a>;
+    type GenericArg = GenericArg<'a>;
+    type Type = Type<'a>;
+    type FnSig = FnSig<'a>;
+    type DynBounds = DynBounds<'a>;
+    type DynTrait = DynTrait<'a>;
+    type Const = Const<'a>;
+
+    fn symbol(
+        &mut self,
+        version: Option<DecimalNumber>,
+        path: Path<'a>,
+        instantiating_crate: Option<Path<'a>>,
+    ) -> Symbol<'a> {
+        Symbol {
+            version,
+            path,
+            instantiating_crate,
+        }
+    }
+
+    fn crate_root(&mut self, id: Ident<'a>) -> Path<'a> {
+        Path::CrateRoot { id }
+    }
+
+    fn inherent_impl(&mut self, impl_path: ImplPath<'a>, self_type: Type<'a>) -> Path<'a> {
+        Path::InherentImpl {
+            impl_path,
+            self_type,
+        }
+    }
+
+    fn trait_impl(
+        &mut self,
+        impl_path: ImplPath<'a>,
+        self_type: Type<'a>,
+        trait_name: Path<'a>,
+    ) -> Path<'a> {
+        Path::TraitImpl {
+            impl_path,
+            self_type,
+            trait_name: Box::new(trait_name),
+        }
+    }
+
+    fn trait_def(&mut self, self_type: Type<'a>, trait_name: Path<'a>) -> Path<'a> {
+        Path::TraitDef {
+            self_type,
+            trait_name: Box::new(trait_name),
+        }
+    }
+
+    fn nested(&mut self, ns: Namespace, inner: Path<'a>, ident: Ident<'a>) -> Path<'a> {
+        Path::Nested {
+            ns,
+            inner: Box::new(inner),
+            ident,
+        }
+    }
+
+    fn generic(&mut self, inner: Path<'a>, args: Vec<GenericArg<'a>>) -> Path<'a> {
+        Path::Generic {
+            inner: Box::new(inner),
+            args,
+        }
+    }
+
+    fn impl_path(&mut self, dis: Option<Disambiguator>, path: Path<'a>) -> ImplPath<'a> {
+        ImplPath {
+            dis,
+            path: Box::new(path),
+        }
+    }
+
+    fn ident(&mut self, dis: Disambiguator, u_ident: UIdent<'a>) -> Ident<'a> {
+        Ident { dis, u_ident }
+    }
+
+    fn uident(&mut self, raw: &'a str, decoded: Option<String>) -> UIdent<'a> {
+        UIdent {
+            raw,
+            decoded: decoded.map(String::into_boxed_str),
+        }
+    }
+
+    fn lifetime_arg(&mut self, lifetime: Lifetime) -> GenericArg<'a> {
+        GenericArg::Lifetime(lifetime)
+    }
+
+    fn type_arg(&mut self, ty: Type<'a>) -> GenericArg<'a> {
+        GenericArg::Type(ty)
+    }
+
+    fn const_arg(&mut self, k: Const<'a>) -> GenericArg<'a> {
+        GenericArg::Const(k)
+    }
+
+    fn basic_type(&mut self, bt: BasicType) -> Type<'a> {
+        Type::BasicType(bt)
+    }
+
+    fn named(&mut self, path: Path<'a>) -> Type<'a> {
+        Type::Named(Box::new(path))
+    }
+
+    fn array(&mut self, inner: Type<'a>, len: Const<'a>) -> Type<'a> {
+        Type::Array(Box::new(inner), Box::new(len))
+    }
+
+    fn slice(&mut self, inner: Type<'a>) -> Type<'a> {
+        Type::Slice(Box::new(inner))
+    }
+
+    fn tuple(&mut self, components: Vec<Type<'a>>) -> Type<'a> {
+        Type::Tuple(components)
+    }
+
+    fn reference(&mut self, mutable: bool, lt: Option<Lifetime>, inner: Type<'a>) -> Type<'a> {
+        if mutable {
+            Type::RefMut(lt, Box::new(inner))
+        } else {
+            Type::Ref(lt, Box::new(inner))
+        }
+    }
+
+    fn raw_ptr(&mut self, mutable: bool, inner: Type<'a>) -> Type<'a> {
+        if mutable {
+            Type::RawPtrMut(Box::new(inner))
+        } else {
+            Type::RawPtrConst(Box::new(inner))
+        }
+    }
+
+    fn fn_type(&mut self, fn_sig: FnSig<'a>) -> Type<'a> {
+        Type::Fn(Box::new(fn_sig))
+    }
+
+    fn dyn_trait_type(&mut self, bounds: DynBounds<'a>, lt: Lifetime) -> Type<'a> {
+        Type::DynTrait(Box::new(bounds), lt)
+    }
+
+    fn fn_sig(
+        &mut self,
+        binder: Binder,
+        is_unsafe: bool,
+        abi: Option<grammar::Abi<UIdent<'a>>>,
+        param_types: Vec<Type<'a>>,
+        return_type: Type<'a>,
+    ) -> FnSig<'a> {
+        FnSig {
+            binder,
+            is_unsafe,
+            abi: abi.map(|abi| match abi {
+                grammar::Abi::C => Abi::C,
+                grammar::Abi::Named(name) => Abi::Named(name),
+            }),
+            param_types,
+            return_type,
+        }
+    }
+
+    fn dyn_bounds(&mut self, binder: Binder, traits: Vec<DynTrait<'a>>) -> DynBounds<'a> {
+        DynBounds { binder, traits }
+    }
+
+    fn dyn_trait(
+        &mut self,
+        path: Path<'a>,
+        assoc_type_bindings: Vec<(UIdent<'a>, Type<'a>)>,
+    ) -> DynTrait<'a> {
+        DynTrait {
+            path,
+            assoc_type_bindings: assoc_type_bindings
+                .into_iter()
+                .map(|(ident, ty)| DynTraitAssocBinding { ident, ty })
+                .collect(),
+        }
+    }
+
+    fn const_value(&mut self, ty: Type<'a>, value: u64) -> Const<'a> {
+        Const::Value(ty, value)
+    }
+
+    fn const_placeholder(&mut self, ty: Type<'a>) -> Const<'a> {
+        Const::Placeholder(ty)
+    }
+}
+
+impl<'a, 'b> From<&'b Symbol<'a>> for ast::Symbol {
+    fn from(symbol: &'b Symbol<'a>) -> ast::Symbol {
+        ast::Symbol {
+            version: symbol.version,
+            path: (&symbol.path).into(),
+            instantiating_crate: symbol.instantiating_crate.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Symbol> for Symbol<'a> {
+    fn from(symbol: &'a ast::Symbol) -> Symbol<'a> {
+        Symbol {
+            version: symbol.version,
+            path: (&symbol.path).into(),
+            instantiating_crate: symbol.instantiating_crate.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Ident<'a>> for ast::Ident {
+    fn from(ident: &'b Ident<'a>) -> ast::Ident {
+        ast::Ident {
+            dis: ident.dis,
+            u_ident: (&ident.u_ident).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Ident> for Ident<'a> {
+    fn from(ident: &'a ast::Ident) -> Ident<'a> {
+        Ident {
+            dis: ident.dis,
+            u_ident: (&ident.u_ident).into(),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b UIdent<'a>> for ast::UIdent {
+    fn from(ident: &'b UIdent<'a>) -> ast::UIdent {
+        ast::UIdent(ident.to_str().into())
+    }
+}
+
+impl<'a> From<&'a ast::UIdent> for UIdent<'a> {
+    fn from(ident: &'a ast::UIdent) -> UIdent<'a> {
+        UIdent::new(&ident.0)
+    }
+}
+
+impl<'a, 'b> From<&'b Path<'a>> for ast::Path {
+    fn from(path: &'b Path<'a>) -> ast::Path {
+        match *path {
+            Path::CrateRoot { ref id } => ast::Path::CrateRoot { id: id.into() },
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => ast::Path::InherentImpl {
+                impl_path: impl_path.into(),
+                self_type: self_type.into(),
+            },
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => ast::Path::TraitImpl {
+                impl_path: impl_path.into(),
+                self_type: self_type.into(),
+                trait_name: Ptr::new((&**trait_name).into()),
+            },
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => ast::Path::TraitDef {
+                self_type: self_type.into(),
+                trait_name: Ptr::new((&**trait_name).into()),
+            },
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => ast::Path::Nested {
+                ns: ns.clone(),
+                inner: Ptr::new((&**inner).into()),
+                ident: ident.into(),
+            },
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => ast::Path::Generic {
+                inner: Ptr::new((&**inner).into()),
+                args: args.iter().map(Into::into).collect(),
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Path> for Path<'a> {
+    fn from(path: &'a ast::Path) -> Path<'a> {
+        match *path {
+            ast::Path::CrateRoot { ref id } => Path::CrateRoot { id: id.into() },
+            ast::Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => Path::InherentImpl {
+                impl_path: impl_path.into(),
+                self_type: self_type.into(),
+            },
+            ast::Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => Path::TraitImpl {
+                impl_path: impl_path.into(),
+                self_type: self_type.into(),
+                trait_name: Box::new((&**trait_name).into()),
+            },
+            ast::Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => Path::TraitDef {
+                self_type: self_type.into(),
+                trait_name: Box::new((&**trait_name).into()),
+            },
+            ast::Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => Path::Nested {
+                ns: ns.clone(),
+                inner: Box::new((&**inner).into()),
+                ident: ident.into(),
+            },
+            ast::Path::Generic {
+                ref inner,
+                ref args,
+            } => Path::Generic {
+                inner: Box::new((&**inner).into()),
+                args: args.iter().map(Into::into).collect(),
+            },
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b ImplPath<'a>> for ast::ImplPath {
+    fn from(impl_path: &'b ImplPath<'a>) -> ast::ImplPath {
+        ast::ImplPath {
+            dis: impl_path.dis,
+            path: Ptr::new((&*impl_path.path).into()),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::ImplPath> for ImplPath<'a> {
+    fn from(impl_path: &'a ast::ImplPath) -> ImplPath<'a> {
+        ImplPath {
+            dis: impl_path.dis,
+            path: Box::new((&*impl_path.path).into()),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b GenericArg<'a>> for ast::GenericArg {
+    fn from(arg: &'b GenericArg<'a>) -> ast::GenericArg {
+        match *arg {
+            GenericArg::Lifetime(ref lt) => ast::GenericArg::Lifetime(lt.clone()),
+            GenericArg::Type(ref ty) => ast::GenericArg::Type(ty.into()),
+            GenericArg::Const(ref k) => ast::GenericArg::Const(k.into()),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::GenericArg> for GenericArg<'a> {
+    fn from(arg: &'a ast::GenericArg) -> GenericArg<'a> {
+        match *arg {
+            ast::GenericArg::Lifetime(ref lt) => GenericArg::Lifetime(lt.clone()),
+            ast::GenericArg::Type(ref ty) => GenericArg::Type(ty.into()),
+            ast::GenericArg::Const(ref k) => GenericArg::Const(k.into()),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Type<'a>> for ast::Type {
+    fn from(ty: &'b Type<'a>) -> ast::Type {
+        fn ptr<'a, 'b>(ty: &'b Type<'a>) -> Ptr<ast::Type> {
+            Ptr::new(ty.into())
+        }
+
+        match *ty {
+            Type::BasicType(bt) => ast::Type::BasicType(bt),
+            Type::Array(ref inner, ref len) => {
+                ast::Type::Array(ptr(inner), Ptr::new((&**len).into()))
+            }
+            Type::Slice(ref inner) => ast::Type::Slice(ptr(inner)),
+            Type::Named(ref path) => ast::Type::Named(Ptr::new((&**path).into())),
+            Type::Tuple(ref components) => {
+                ast::Type::Tuple(components.iter().map(Into::into).collect())
+            }
+            Type::Ref(ref lt, ref inner) => ast::Type::Ref(lt.clone(), ptr(inner)),
+            Type::RefMut(ref lt, ref inner) => ast::Type::RefMut(lt.clone(), ptr(inner)),
+            Type::RawPtrConst(ref inner) => ast::Type::RawPtrConst(ptr(inner)),
+            Type::RawPtrMut(ref inner) => ast::Type::RawPtrMut(ptr(inner)),
+            Type::Fn(ref fn_sig) => ast::Type::Fn(Ptr::new((&**fn_sig).into())),
+            Type::DynTrait(ref bounds, ref lt) => {
+                ast::Type::DynTrait(Ptr::new((&**bounds).into()), lt.clone())
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Type> for Type<'a> {
+    fn from(ty: &'a ast::Type) -> Type<'a> {
+        fn boxed(ty: &ast::Type) -> Box<Type<'_>> {
+            Box::new(ty.into())
+        }
+
+        match *ty {
+            ast::Type::BasicType(bt) => Type::BasicType(bt),
+            ast::Type::Array(ref inner, ref len) => {
+                Type::Array(boxed(inner), Box::new((&**len).into()))
+            }
+            ast::Type::Slice(ref inner) => Type::Slice(boxed(inner)),
+            ast::Type::Named(ref path) => Type::Named(Box::new((&**path).into())),
+            ast::Type::Tuple(ref components) => {
+                Type::Tuple(components.iter().map(Into::into).collect())
+            }
+            ast::Type::Ref(ref lt, ref inner) => Type::Ref(lt.clone(), boxed(inner)),
+            ast::Type::RefMut(ref lt, ref inner) => Type::RefMut(lt.clone(), boxed(inner)),
+            ast::Type::RawPtrConst(ref inner) => Type::RawPtrConst(boxed(inner)),
+            ast::Type::RawPtrMut(ref inner) => Type::RawPtrMut(boxed(inner)),
+            ast::Type::Fn(ref fn_sig) => Type::Fn(Box::new((&**fn_sig).into())),
+            ast::Type::DynTrait(ref bounds, ref lt) => {
+                Type::DynTrait(Box::new((&**bounds).into()), lt.clone())
+            }
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b FnSig<'a>> for ast::FnSig {
+    fn from(fn_sig: &'b FnSig<'a>) -> ast::FnSig {
+        ast::FnSig {
+            binder: fn_sig.binder.clone(),
+            is_unsafe: fn_sig.is_unsafe,
+            abi: fn_sig.abi.as_ref().map(|abi| match *abi {
+                Abi::C => ast::Abi::C,
+                Abi::Named(ref ident) => ast::Abi::Named(ident.into()),
+            }),
+            param_types: fn_sig.param_types.iter().map(Into::into).collect(),
+            return_type: (&fn_sig.return_type).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::FnSig> for FnSig<'a> {
+    fn from(fn_sig: &'a ast::FnSig) -> FnSig<'a> {
+        FnSig {
+            binder: fn_sig.binder.clone(),
+            is_unsafe: fn_sig.is_unsafe,
+            abi: fn_sig.abi.as_ref().map(|abi| match *abi {
+                ast::Abi::C => Abi::C,
+                ast::Abi::Named(ref ident) => Abi::Named(ident.into()),
+            }),
+            param_types: fn_sig.param_types.iter().map(Into::into).collect(),
+            return_type: (&fn_sig.return_type).into(),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b DynBounds<'a>> for ast::DynBounds {
+    fn from(bounds: &'b DynBounds<'a>) -> ast::DynBounds {
+        ast::DynBounds {
+            binder: bounds.binder.clone(),
+            traits: bounds
+                .traits
+                .iter()
+                .map(|tr| ast::DynTrait {
+                    path: (&tr.path).into(),
+                    assoc_type_bindings: tr
+                        .assoc_type_bindings
+                        .iter()
+                        .map(|binding| ast::DynTraitAssocBinding {
+                            ident: (&binding.ident).into(),
+                            ty: (&binding.ty).into(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::DynBounds> for DynBounds<'a> {
+    fn from(bounds: &'a ast::DynBounds) -> DynBounds<'a> {
+        DynBounds {
+            binder: bounds.binder.clone(),
+            traits: bounds
+                .traits
+                .iter()
+                .map(|tr| DynTrait {
+                    path: (&tr.path).into(),
+                    assoc_type_bindings: tr
+                        .assoc_type_bindings
+                        .iter()
+                        .map(|binding| DynTraitAssocBinding {
+                            ident: (&binding.ident).into(),
+                            ty: (&binding.ty).into(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a, 'b> From<&'b Const<'a>> for ast::Const {
+    fn from(k: &'b Const<'a>) -> ast::Const {
+        match *k {
+            Const::Value(ref ty, value) => ast::Const::Value(ty.into(), value),
+            Const::Placeholder(ref ty) => ast::Const::Placeholder(ty.into()),
+        }
+    }
+}
+
+impl<'a> From<&'a ast::Const> for Const<'a> {
+    fn from(k: &'a ast::Const) -> Const<'a> {
+        match *k {
+            ast::Const::Value(ref ty, value) => Const::Value(ty.into(), value),
+            ast::Const::Placeholder(ref ty) => Const::Placeholder(ty.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use ast::{self, Symbol};
+
+    #[test]
+    fn borrowed_matches_owned() {
+        for &mangled in &[
+            "_RNxXC3stdNyNyB2_3foo3FooB9_3bar",
+            "_RINxC7mycrate3bazFG_mEcE",
+            "_RINxC7mycrate3bazAsj309_E",
+            "_RNxMs1_C3fooFG_UKCEu4quux",
+            "_RINxC7mycrate3barmEC16some_other_crate",
+        ] {
+            let owned = Symbol::parse(mangled).unwrap();
+            let borrowed = parse(mangled.as_bytes()).unwrap();
+
+            assert_eq!(owned, Symbol::from(&borrowed));
+            assert_eq!(borrowed, super::Symbol::from(&owned));
+        }
+    }
+
+    #[test]
+    fn reject_overflowing_disambiguator() {
+        assert!(parse(b"_RCslYGhA16ahye_3foo")
+            .unwrap_err()
+            .contains("64 bits"));
+    }
+
+    #[test]
+    fn borrowed_idents_point_into_input() {
+        let mangled = "_RNxC7mycrate4quux";
+        let borrowed = parse(mangled.as_bytes()).unwrap();
+
+        if let super::Path::Nested { ref ident, .. } = borrowed.path {
+            assert_eq!("quux", ident.u_ident.raw());
+            assert_eq!(mangled[14..].as_ptr(), ident.u_ident.raw().as_ptr());
+        } else {
+            panic!("expected a nested path");
+        }
+    }
+
+    #[test]
+    fn punycode_idents_are_decoded() {
+        let symbol = Symbol {
+            version: None,
+            path: ast::Path::CrateRoot {
+                id: ast::Ident {
+                    dis: ast::Disambiguator(0),
+                    u_ident: ast::UIdent("gödel".into()),
+                },
+            },
+            instantiating_crate: None,
+        };
+        let mangled = symbol.mangle();
+        let borrowed = parse(mangled.as_bytes()).unwrap();
+
+        if let super::Path::CrateRoot { ref id } = borrowed.path {
+            assert!(id.u_ident.is_punycode());
+            assert_eq!("gödel", id.u_ident.to_str());
+            assert_eq!(&mangled[5..], id.u_ident.raw());
+        } else {
+            panic!("expected a crate root");
+        }
+    }
+}
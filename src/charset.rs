@@ -1,4 +1,6 @@
 use punycode;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 pub fn decode_punycode_ident(ident_bytes: &[u8]) -> Result<String, String> {
     if ident_bytes.iter().any(|b| !b.is_ascii()) {
@@ -21,11 +23,38 @@ pub fn decode_punycode_ident(ident_bytes: &[u8]) -> Result<String, String> {
 
     match punycode::decode_to_string(&ident_str) {
         Some(s) => Ok(s),
+        None => Err(format!(
+            "Could not decode punycode-encoded ident '{}'.",
+            ident_str
+        )),
+    }
+}
+
+pub fn encode_punycode_ident(ident: &str) -> Result<String, String> {
+    let encoded = match punycode::encode_str(ident) {
+        Some(s) => s,
         None => {
-            return Err(format!(
-                "Could not decode punycode-encoded ident '{}'.",
-                ident_str
-            ));
+            return Err(format!("Could not punycode-encode ident '{}'.", ident));
+        }
+    };
+
+    let mut ident_bytes = encoded.into_bytes();
+
+    if let Some(index) = ident_bytes.iter().rposition(|&c| c == b'-') {
+        ident_bytes[index] = b'_';
+        remap_punycode_charset_09_to_AJ(&mut ident_bytes[index..]);
+    } else {
+        remap_punycode_charset_09_to_AJ(&mut ident_bytes[..]);
+    }
+
+    Ok(String::from_utf8(ident_bytes).unwrap())
+}
+
+#[allow(non_snake_case)]
+fn remap_punycode_charset_09_to_AJ(punycode_suffix: &mut [u8]) {
+    for c in punycode_suffix {
+        if c.is_ascii_digit() {
+            *c = (*c - b'0') + b'A';
         }
     }
 }
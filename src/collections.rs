@@ -0,0 +1,43 @@
+//! The maps and sets of the mangler and the AST queries. With the `std`
+//! feature these are hash maps; without it, B-tree maps, which is why their
+//! keys implement both `Hash` and `Ord`.
+
+#[cfg(feature = "std")]
+pub use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
+
+#[cfg(not(feature = "std"))]
+pub use std::collections::{BTreeMap as Map, BTreeSet as Set};
+#[cfg(not(feature = "std"))]
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// The hasher for hashing whole subtrees into a `u64` without `std`. No
+/// random seed is available, which is fine because hash collisions are only
+/// slower, never wrong.
+#[cfg(not(feature = "std"))]
+pub type RandomState = BuildHasherDefault<FnvHasher>;
+
+/// The 64-bit FNV-1a hash.
+#[cfg(not(feature = "std"))]
+pub struct FnvHasher(u64);
+
+#[cfg(not(feature = "std"))]
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
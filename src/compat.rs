@@ -0,0 +1,208 @@
+//! An API compatible with the `rustc-demangle` crate, so that its users can
+//! switch to this crate by changing `use rustc_demangle::demangle;` to
+//! `use std_mangle_rs::compat::demangle;`.
+//!
+//! As with `rustc-demangle`, `{}` formats a symbol with its hash or
+//! disambiguators and `{:#}` without them, and neither prints the
+//! instantiating crate of v0 symbols. Symbols may have the extra `_` of
+//! macOS, and LLVM's `.llvm.<hash>` suffix is removed. Other suffixes like
+//! `.cold` are printed after the demangled symbol as they are.
+
+use ast::Symbol;
+use legacy;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// A symbol that is formatted in its demangled form, or as it is if it is
+/// not a Rust symbol.
+#[derive(Clone, Debug)]
+pub struct Demangle<'a> {
+    original: &'a str,
+    style: Option<Style<'a>>,
+    suffix: &'a str,
+}
+
+#[derive(Clone, Debug)]
+enum Style<'a> {
+    V0(Box<Symbol>),
+    Legacy {
+        demangled: String,
+        hash: Option<&'a str>,
+    },
+}
+
+/// The error of `try_demangle()` for strings that are not Rust symbols.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TryDemangleError {
+    _priv: (),
+}
+
+/// Demangles `s`, which is formatted as it is if it is not a Rust symbol.
+pub fn demangle<'a>(s: &'a str) -> Demangle<'a> {
+    try_demangle(s).unwrap_or(Demangle {
+        original: s,
+        style: None,
+        suffix: "",
+    })
+}
+
+/// Demangles `s`, failing if it is not a Rust symbol.
+pub fn try_demangle<'a>(s: &'a str) -> Result<Demangle<'a>, TryDemangleError> {
+    let error = TryDemangleError { _priv: () };
+
+    let mut symbol = s;
+    if let Some(i) = symbol.find(".llvm.") {
+        if symbol[i + 6..]
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit() || b == b'@')
+        {
+            symbol = &symbol[..i];
+        }
+    }
+    // The extra `_` of macOS symbols, or the `_` that is missing on some
+    // platforms.
+    let symbol = if symbol.starts_with("__") {
+        &symbol[1..]
+    } else {
+        symbol
+    };
+    let prefixed = if symbol.starts_with('_') {
+        symbol.to_string()
+    } else {
+        format!("_{}", symbol)
+    };
+    let offset = prefixed.len() - symbol.len();
+
+    let (style, len) = if prefixed.starts_with("_R") {
+        // v0 symbols cannot contain `.`, so anything after one is a suffix.
+        let len = symbol.find('.').unwrap_or(symbol.len());
+        let parsed = Symbol::parse(&prefixed[..offset + len]).map_err(|_| error.clone())?;
+        (Style::V0(Box::new(parsed)), len)
+    } else {
+        let (demangled, len) = legacy::demangle_prefix(&prefixed).map_err(|_| error.clone())?;
+        let len = len - offset;
+        let hash = legacy_hash(&symbol[..len]).filter(|hash| !demangled.ends_with(hash));
+        (Style::Legacy { demangled, hash }, len)
+    };
+
+    let suffix = &symbol[len..];
+    if !suffix.is_empty() && !suffix.starts_with('.') {
+        return Err(error);
+    }
+
+    Ok(Demangle {
+        original: s,
+        style: Some(style),
+        suffix,
+    })
+}
+
+/// The `h` and 16 hex digits at the end of the legacy symbol `mangled`.
+fn legacy_hash(mangled: &str) -> Option<&str> {
+    let body = mangled.strip_suffix('E')?;
+    let hash = body.get(body.len().checked_sub(17)?..)?;
+    let is_hash = body[..body.len() - 17].ends_with("17")
+        && hash.starts_with('h')
+        && hash[1..].bytes().all(|b| b.is_ascii_hexdigit());
+    if is_hash {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+impl<'a> Demangle<'a> {
+    /// The string this was created from.
+    pub fn as_str(&self) -> &'a str {
+        self.original
+    }
+
+    /// The parsed symbol, if this is a v0 symbol.
+    pub(crate) fn v0_symbol(&self) -> Option<&Symbol> {
+        match self.style {
+            Some(Style::V0(ref symbol)) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    /// Whether this is certainly a Rust symbol. Legacy symbols without a hash
+    /// are valid C++ symbols as well.
+    pub(crate) fn is_rust(&self) -> bool {
+        match self.style {
+            Some(Style::V0(_)) => true,
+            Some(Style::Legacy { hash, .. }) => hash.is_some(),
+            None => false,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Demangle<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.style {
+            None => return f.write_str(self.original),
+            Some(Style::V0(ref symbol)) => {
+                let mut symbol = if f.alternate() {
+                    symbol.erase_disambiguators()
+                } else {
+                    (**symbol).clone()
+                };
+                symbol.instantiating_crate = None;
+                f.write_str(&symbol.demangle())?;
+            }
+            Some(Style::Legacy {
+                ref demangled,
+                hash,
+            }) => {
+                f.write_str(demangled)?;
+                if let (Some(hash), false) = (hash, f.alternate()) {
+                    write!(f, "::{}", hash)?;
+                }
+            }
+        }
+
+        f.write_str(self.suffix)
+    }
+}
+
+impl fmt::Display for TryDemangleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("symbol is not a valid Rust symbol")
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for TryDemangleError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, try_demangle};
+
+    #[test]
+    fn display_like_rustc_demangle() {
+        let v0 = demangle("_RNvCs0_7mycrate3fooC5other");
+        assert_eq!("mycrate[2]::foo", v0.to_string());
+        assert_eq!("mycrate::foo", format!("{:#}", v0));
+        assert_eq!("_RNvCs0_7mycrate3fooC5other", v0.as_str());
+
+        let legacy = demangle("__ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE.llvm.1234AB");
+        assert_eq!(
+            "std::io::stdio::_print::h8f2b8a6a4b1c9d0e",
+            legacy.to_string()
+        );
+        assert_eq!("std::io::stdio::_print", format!("{:#}", legacy));
+
+        assert_eq!(
+            "foo::bar.cold",
+            format!("{:#}", demangle("ZN3foo3barE.cold"))
+        );
+        assert_eq!(
+            "mycrate::foo.cold.1",
+            format!("{:#}", demangle("RNvC7mycrate3foo.cold.1"))
+        );
+
+        assert_eq!("main", demangle("main").to_string());
+        assert!(try_demangle("main").is_err());
+        assert!(try_demangle("_ZN3fooE!").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! A WebAssembly component exporting the `demangler` interface of
+//! `wit/std-mangle.wit`, for plugin systems and other component-model hosts.
+//! Build it with `cargo rustc --release --lib --crate-type cdylib --features
+//! component --target wasm32-wasip2`.
+
+use ast::Symbol;
+use ffi::{self, SmrOptions};
+use info::{self, SymbolInfo};
+use stable;
+
+wit_bindgen::generate!({
+    path: "wit",
+    world: "std-mangle",
+});
+
+use self::exports::std_mangle::demangler::demangler::{self as wit, Guest};
+
+struct Component;
+
+impl Guest for Component {
+    fn demangle(mangled: String) -> Result<String, String> {
+        let options = SmrOptions {
+            legacy: true,
+            ..SmrOptions::default()
+        };
+        ffi::demangle_with(&mangled, &options).ok_or_else(|| "not a Rust symbol".to_string())
+    }
+
+    fn parse(mangled: String) -> Result<String, String> {
+        Symbol::parse(&mangled).map(|symbol| symbol.to_json())
+    }
+
+    fn analyze(mangled: String) -> Result<wit::SymbolInfo, String> {
+        info::analyze(&mangled).map(|info| {
+            let SymbolInfo {
+                demangled,
+                crate_name,
+                instantiating_crate,
+                path,
+                skeleton,
+                generic_args,
+                namespace,
+                suffixes,
+                legacy,
+                ..
+            } = info;
+            wit::SymbolInfo {
+                demangled,
+                crate_name,
+                instantiating_crate,
+                path,
+                skeleton,
+                generic_args,
+                namespace: namespace.map(|namespace| match namespace {
+                    stable::Namespace::Type => wit::Namespace::Type,
+                    stable::Namespace::Value => wit::Namespace::Value,
+                    stable::Namespace::Closure => wit::Namespace::Closure,
+                    stable::Namespace::Other(tag) => wit::Namespace::Other(tag),
+                }),
+                suffixes,
+                legacy,
+            }
+        })
+    }
+}
+
+export!(Component);
+
+#[cfg(test)]
+mod tests {
+    use super::wit::{Guest, Namespace};
+    use super::Component;
+
+    #[test]
+    fn component_exports() {
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            Component::demangle("_RNxC7mycrate3foo".to_string())
+        );
+        assert!(Component::demangle("main".to_string()).is_err());
+        assert!(Component::parse("_RNxC7mycrate3foo".to_string())
+            .unwrap()
+            .contains("\"foo\""));
+
+        let info = Component::analyze("_RNxC7mycrate3foo.cold".to_string()).unwrap();
+        assert_eq!("mycrate", info.crate_name);
+        assert!(matches!(info.namespace, Some(Namespace::Value)));
+        assert_eq!(vec![".cold".to_string()], info.suffixes);
+    }
+}
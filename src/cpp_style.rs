@@ -0,0 +1,185 @@
+//! An API shaped like that of `cpp_demangle`, so that code that demangles
+//! C++ symbols with it can demangle Rust symbols with the same structure:
+//! resource limits go into `ParseOptions` when parsing, and formatting
+//! choices into `DemangleOptions` when demangling.
+//!
+//! Rust symbols do not encode parameter or return types. `no_params()`
+//! leaves out generic arguments instead, and `no_return_type()` only exists
+//! for parity.
+
+use ast;
+use limits::ResourceLimits;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// Limits for `Symbol::new_with_options()`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    limits: ResourceLimits,
+}
+
+impl ParseOptions {
+    /// The maximum nesting depth of paths and types. Panics if `limit` is
+    /// zero, like `cpp_demangle`.
+    pub fn recursion_limit(mut self, limit: u32) -> ParseOptions {
+        assert!(limit > 0, "Recursion limit must be > 0");
+        self.limits.max_depth = limit;
+        self
+    }
+
+    /// The maximum number of paths and types, counting every node again each
+    /// time a backref pulls it in.
+    pub fn node_limit(mut self, limit: usize) -> ParseOptions {
+        self.limits.max_nodes = limit;
+        self
+    }
+
+    /// The maximum length of the demangled symbol, in bytes.
+    pub fn output_limit(mut self, limit: usize) -> ParseOptions {
+        self.limits.max_output_bytes = limit;
+        self
+    }
+}
+
+/// How `Symbol::demangle()` formats a symbol.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DemangleOptions {
+    no_params: bool,
+    no_return_type: bool,
+    hide_disambiguators: bool,
+    recursion_limit: Option<u32>,
+}
+
+impl DemangleOptions {
+    pub fn new() -> DemangleOptions {
+        DemangleOptions::default()
+    }
+
+    /// Leaves out generic arguments.
+    pub fn no_params(mut self) -> DemangleOptions {
+        self.no_params = true;
+        self
+    }
+
+    /// Does nothing, since Rust symbols have no return types.
+    pub fn no_return_type(mut self) -> DemangleOptions {
+        self.no_return_type = true;
+        self
+    }
+
+    /// Leaves out disambiguators and the instantiating crate.
+    pub fn hide_disambiguators(mut self) -> DemangleOptions {
+        self.hide_disambiguators = true;
+        self
+    }
+
+    /// Fails demangling symbols nested more deeply than `limit`. Panics if
+    /// `limit` is zero.
+    pub fn recursion_limit(mut self, limit: u32) -> DemangleOptions {
+        assert!(limit > 0, "Recursion limit must be > 0");
+        self.recursion_limit = Some(limit);
+        self
+    }
+}
+
+/// A parsed v0 symbol together with the raw symbol it was parsed from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol<T> {
+    raw: T,
+    parsed: ast::Symbol,
+    max_output_bytes: usize,
+}
+
+impl<T: AsRef<[u8]>> Symbol<T> {
+    /// Parses `raw` with the default `ParseOptions`.
+    pub fn new(raw: T) -> Result<Symbol<T>, String> {
+        Symbol::new_with_options(raw, &ParseOptions::default())
+    }
+
+    pub fn new_with_options(raw: T, options: &ParseOptions) -> Result<Symbol<T>, String> {
+        let parsed = {
+            let mangled = ::std::str::from_utf8(raw.as_ref())
+                .map_err(|e| format!("symbol is not UTF-8: {}", e))?;
+            ast::Symbol::parse_with_limits(mangled, &options.limits)?
+        };
+
+        Ok(Symbol {
+            raw,
+            parsed,
+            max_output_bytes: options.limits.max_output_bytes,
+        })
+    }
+
+    /// Demangles this symbol as given by `options`. Fails if it is nested
+    /// more deeply than their recursion limit, or if the output would be
+    /// longer than the output limit it was parsed with.
+    pub fn demangle(&self, options: &DemangleOptions) -> Result<String, fmt::Error> {
+        let mut symbol = if options.hide_disambiguators {
+            let mut symbol = self.parsed.erase_disambiguators();
+            symbol.instantiating_crate = None;
+            symbol
+        } else {
+            self.parsed.clone()
+        };
+        if options.no_params {
+            symbol = symbol.erase_generic_args();
+        }
+
+        let limits = ResourceLimits {
+            max_depth: options
+                .recursion_limit
+                .unwrap_or(ResourceLimits::UNLIMITED.max_depth),
+            max_output_bytes: self.max_output_bytes,
+            ..ResourceLimits::UNLIMITED
+        };
+        symbol.demangle_with_limits(&limits).map_err(|_| fmt::Error)
+    }
+
+    /// The AST of this symbol.
+    pub fn ast(&self) -> &ast::Symbol {
+        &self.parsed
+    }
+}
+
+impl<T> AsRef<T> for Symbol<T> {
+    fn as_ref(&self) -> &T {
+        &self.raw
+    }
+}
+
+impl<T: AsRef<[u8]>> fmt::Display for Symbol<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.demangle(&DemangleOptions::default())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DemangleOptions, ParseOptions, Symbol};
+
+    #[test]
+    fn options_like_cpp_demangle() {
+        let symbol = Symbol::new(&b"_RINvCs0_7mycrate3foohEC5other"[..]).unwrap();
+        assert_eq!("mycrate[2]::foo<u8> @ other", symbol.to_string());
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            symbol.demangle(&DemangleOptions::new().no_params().hide_disambiguators())
+        );
+        assert!(symbol
+            .demangle(&DemangleOptions::new().recursion_limit(1))
+            .is_err());
+
+        let options = ParseOptions::default().output_limit(14);
+        let short = Symbol::new_with_options("_RINvC7mycrate3foohE", &options).unwrap();
+        assert!(short.demangle(&DemangleOptions::new()).is_err());
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            short.demangle(&DemangleOptions::new().no_params())
+        );
+
+        let options = ParseOptions::default().recursion_limit(1);
+        assert!(Symbol::new_with_options("_RINvC7mycrate3foohE", &options).is_err());
+        assert!(Symbol::new(&b"\xff"[..]).is_err());
+    }
+}
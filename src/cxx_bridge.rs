@@ -0,0 +1,117 @@
+//! A `cxx` bridge for C++ code, which gets `rust::String` and a
+//! `SymbolInfo` struct instead of the raw pointers of the C interface, and
+//! exceptions for invalid symbols.
+//!
+//! Build the library with `cargo rustc --release --lib --crate-type
+//! staticlib --features cxx` and include
+//! `target/cxxbridge/std-mangle-rs/src/cxx_bridge.rs.h` in the C++ code,
+//! with `target/cxxbridge` on the include path for `rust/cxx.h`:
+//!
+//! ```cpp
+//! #include "std-mangle-rs/src/cxx_bridge.rs.h"
+//!
+//! rust::String demangled = std_mangle::demangle("_RNvC7mycrate3foo");
+//! ```
+//!
+//! `demangle()` and `analyze()` throw `rust::Error`, which is declared in
+//! `rust/cxx.h`, for invalid symbols.
+
+use ffi::{self, SmrOptions};
+use info;
+use stable;
+
+#[cxx::bridge(namespace = "std_mangle")]
+mod bridge {
+    /// The namespace of the last segment of a path, `Unspecified` for crate
+    /// roots and impls.
+    #[derive(Debug)]
+    enum Namespace {
+        Unspecified,
+        Type,
+        Value,
+        Closure,
+        /// A namespace without a name of its own, given by `namespace_tag`.
+        Other,
+    }
+
+    /// What `analyze()` finds out about a symbol. The fields mean the same
+    /// as those of `info::SymbolInfo`, with `has_instantiating_crate` for
+    /// whether there is an instantiating crate.
+    #[derive(Debug)]
+    struct SymbolInfo {
+        demangled: String,
+        crate_name: String,
+        has_instantiating_crate: bool,
+        instantiating_crate: String,
+        path: String,
+        skeleton: String,
+        generic_args: Vec<String>,
+        namespace_kind: Namespace,
+        namespace_tag: String,
+        suffixes: Vec<String>,
+        legacy: bool,
+    }
+
+    extern "Rust" {
+        /// Demangles a v0 or legacy symbol.
+        fn demangle(mangled: &str) -> Result<String>;
+
+        /// Analyzes a v0 or legacy symbol, which may have suffixes.
+        fn analyze(mangled: &str) -> Result<SymbolInfo>;
+    }
+}
+
+fn demangle(mangled: &str) -> Result<String, String> {
+    let options = SmrOptions {
+        legacy: true,
+        ..SmrOptions::default()
+    };
+    ffi::demangle_with(mangled, &options).ok_or_else(|| "not a Rust symbol".to_string())
+}
+
+fn analyze(mangled: &str) -> Result<bridge::SymbolInfo, String> {
+    let info = info::analyze(mangled)?;
+    let (namespace_kind, namespace_tag) = match info.namespace {
+        None => (bridge::Namespace::Unspecified, String::new()),
+        Some(stable::Namespace::Type) => (bridge::Namespace::Type, String::new()),
+        Some(stable::Namespace::Value) => (bridge::Namespace::Value, String::new()),
+        Some(stable::Namespace::Closure) => (bridge::Namespace::Closure, String::new()),
+        Some(stable::Namespace::Other(tag)) => (bridge::Namespace::Other, tag.to_string()),
+    };
+
+    Ok(bridge::SymbolInfo {
+        demangled: info.demangled,
+        crate_name: info.crate_name,
+        has_instantiating_crate: info.instantiating_crate.is_some(),
+        instantiating_crate: info.instantiating_crate.unwrap_or_default(),
+        path: info.path,
+        skeleton: info.skeleton,
+        generic_args: info.generic_args,
+        namespace_kind,
+        namespace_tag,
+        suffixes: info.suffixes,
+        legacy: info.legacy,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bridge::Namespace;
+    use super::{analyze, demangle};
+
+    #[test]
+    fn bridge_functions() {
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            demangle("_RNxC7mycrate3foo")
+        );
+        assert!(demangle("main").is_err());
+
+        let info = analyze("_RINxC7mycrate3foohEC5other").unwrap();
+        assert_eq!("mycrate::foo", info.path);
+        assert!(info.has_instantiating_crate);
+        assert_eq!("other", info.instantiating_crate);
+        assert_eq!(vec!["u8".to_string()], info.generic_args);
+        assert_eq!(Namespace::Value, info.namespace_kind);
+    }
+}
@@ -0,0 +1,129 @@
+//! The path of a symbol as rustc's `DefPath` sees it, for compiler plugins
+//! and MIR tools that correlate symbols with compiler metadata.
+//!
+//! `Symbol::def_path()` drops everything that is not part of the item's
+//! definition: generic arguments, the instantiating crate, and the self
+//! types of impls, which rustc does not store in the def path either. A
+//! trait item reached through `<T as Trait>` has the def path of the trait.
+
+use ast::{self, Ident, Symbol};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// A crate and the segments of a path within it, like rustc's `DefPath`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DefPath {
+    pub krate: DefPathCrate,
+    pub data: Vec<DefPathSegment>,
+}
+
+/// The crate of a `DefPath`, with the disambiguator that rustc derives from
+/// its `StableCrateId`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DefPathCrate {
+    pub name: String,
+    pub disambiguator: u64,
+}
+
+/// A path segment, like rustc's `DisambiguatedDefPathData`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DefPathSegment {
+    pub data: DefPathData,
+    /// Distinguishes the segment from siblings with the same `data`, e.g. the
+    /// closures of a function or the impls of a module.
+    pub disambiguator: u64,
+}
+
+/// What a path segment names, like rustc's `DefPathData`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DefPathData {
+    /// A module, type, trait or other item in the type namespace.
+    TypeNs(String),
+    /// A function, static, const or other item in the value namespace.
+    ValueNs(String),
+    /// A closure, without a name unless the compiler gave it one.
+    Closure(String),
+    /// An inherent or trait impl.
+    Impl,
+    /// An item in a namespace that this crate does not know, with its tag.
+    Other(char, String),
+}
+
+impl Symbol {
+    /// Returns the def path of the item this symbol names.
+    pub fn def_path(&self) -> DefPath {
+        let mut data = Vec::new();
+        let krate = collect(&self.path, &mut data);
+        DefPath {
+            krate: DefPathCrate {
+                name: krate.u_ident.0.to_string(),
+                disambiguator: krate.dis.value(),
+            },
+            data,
+        }
+    }
+}
+
+/// Appends the segments of `path` to `data` and returns its crate.
+fn collect<'a>(path: &'a ast::Path, data: &mut Vec<DefPathSegment>) -> &'a Ident {
+    match *path {
+        ast::Path::CrateRoot { ref id } => id,
+        ast::Path::InherentImpl { ref impl_path, .. }
+        | ast::Path::TraitImpl { ref impl_path, .. } => {
+            let krate = collect(&impl_path.path, data);
+            data.push(DefPathSegment {
+                data: DefPathData::Impl,
+                disambiguator: impl_path.dis.map_or(0, |dis| dis.value()),
+            });
+            krate
+        }
+        ast::Path::TraitDef { ref trait_name, .. } => collect(trait_name, data),
+        ast::Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            let krate = collect(inner, data);
+            let name = ident.u_ident.0.to_string();
+            data.push(DefPathSegment {
+                data: match ns.0 {
+                    b'y' => DefPathData::TypeNs(name),
+                    b'x' => DefPathData::ValueNs(name),
+                    b'C' => DefPathData::Closure(name),
+                    tag => DefPathData::Other(tag as char, name),
+                },
+                disambiguator: ident.dis.value(),
+            });
+            krate
+        }
+        ast::Path::Generic { ref inner, .. } => collect(inner, data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DefPathData, DefPathSegment};
+    use ast::Symbol;
+
+    #[test]
+    fn def_path_of_impl_item() {
+        let symbol = Symbol::parse("_RINxMs_NyCs4_3std3fooNyCs4_3std3Bar3bazhEC5other").unwrap();
+        let def_path = symbol.def_path();
+
+        assert_eq!("std", def_path.krate.name);
+        assert_eq!(6, def_path.krate.disambiguator);
+
+        let segment = |data, disambiguator| DefPathSegment {
+            data,
+            disambiguator,
+        };
+        assert_eq!(
+            vec![
+                segment(DefPathData::TypeNs("foo".to_string()), 0),
+                segment(DefPathData::Impl, 1),
+                segment(DefPathData::ValueNs("baz".to_string()), 0),
+            ],
+            def_path.data
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use ast::*;
+use parse::MAX_DEPTH;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+impl Symbol {
+    /// Like `mangle()`, but fails instead of overflowing the stack if the
+    /// symbol is nested more deeply than `parse::MAX_DEPTH`. Symbols produced
+    /// by the parser never are; this is meant for trees built by hand.
+    pub fn try_mangle(&self) -> Result<String, String> {
+        self.check_depth(MAX_DEPTH)?;
+        Ok(self.mangle())
+    }
+
+    /// Like `demangle()`, with the same depth check as `try_mangle()`.
+    pub fn try_demangle(&self) -> Result<String, String> {
+        self.check_depth(MAX_DEPTH)?;
+        Ok(self.demangle())
+    }
+
+    /// Checks that paths and types are nested at most `max_depth` levels
+    /// deep, counted the same way the parser counts them. This walks the tree
+    /// with an explicit stack, so it is safe on arbitrarily deep trees.
+    pub fn check_depth(&self, max_depth: u32) -> Result<(), String> {
+        let mut stack = vec![(Node::Path(&self.path), 1)];
+
+        if let Some(ref instantiating_crate) = self.instantiating_crate {
+            stack.push((Node::Path(instantiating_crate), 1));
+        }
+
+        while let Some((node, depth)) = stack.pop() {
+            if depth > max_depth {
+                return Err(format!("symbol nested deeper than {} levels", max_depth));
+            }
+
+            let mut children = Children {
+                stack: &mut stack,
+                depth: depth + 1,
+            };
+
+            match node {
+                Node::Path(path) => children.path(path),
+                Node::Type(ty) => children.ty(ty),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Node<'a> {
+    Path(&'a Path),
+    Type(&'a Type),
+}
+
+struct Children<'s, 'a: 's> {
+    stack: &'s mut Vec<(Node<'a>, u32)>,
+    depth: u32,
+}
+
+impl<'s, 'a> Children<'s, 'a> {
+    fn push_path(&mut self, path: &'a Path) {
+        self.stack.push((Node::Path(path), self.depth));
+    }
+
+    fn push_type(&mut self, ty: &'a Type) {
+        self.stack.push((Node::Type(ty), self.depth));
+    }
+
+    fn push_const(&mut self, k: &'a Const) {
+        match *k {
+            Const::Value(ref ty, _) | Const::Placeholder(ref ty) => self.push_type(ty),
+        }
+    }
+
+    fn path(&mut self, path: &'a Path) {
+        match *path {
+            Path::CrateRoot { .. } => {}
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => {
+                self.push_path(&impl_path.path);
+                self.push_type(self_type);
+            }
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => {
+                self.push_path(&impl_path.path);
+                self.push_type(self_type);
+                self.push_path(trait_name);
+            }
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => {
+                self.push_type(self_type);
+                self.push_path(trait_name);
+            }
+            Path::Nested { ref inner, .. } => {
+                self.push_path(inner);
+            }
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => {
+                self.push_path(inner);
+                for arg in args {
+                    match *arg {
+                        GenericArg::Lifetime(_) => {}
+                        GenericArg::Type(ref ty) => self.push_type(ty),
+                        GenericArg::Const(ref k) => self.push_const(k),
+                    }
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self, ty: &'a Type) {
+        match *ty {
+            Type::BasicType(_) => {}
+            Type::Array(ref inner, ref len) => {
+                self.push_type(inner);
+                self.push_const(len);
+            }
+            Type::Slice(ref inner)
+            | Type::Ref(_, ref inner)
+            | Type::RefMut(_, ref inner)
+            | Type::RawPtrConst(ref inner)
+            | Type::RawPtrMut(ref inner) => {
+                self.push_type(inner);
+            }
+            Type::Named(ref path) => {
+                self.push_path(path);
+            }
+            Type::Tuple(ref components) => {
+                for ty in components {
+                    self.push_type(ty);
+                }
+            }
+            Type::Fn(ref fn_sig) => {
+                for ty in fn_sig.param_types.iter() {
+                    self.push_type(ty);
+                }
+                self.push_type(&fn_sig.return_type);
+            }
+            Type::DynTrait(ref bounds, _) => {
+                for tr in bounds.traits.iter() {
+                    self.push_path(&tr.path);
+                    for binding in tr.assoc_type_bindings.iter() {
+                        self.push_type(&binding.ty);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::*;
+    use parse::MAX_DEPTH;
+
+    #[test]
+    fn depth_matches_parser() {
+        let mut mangled = String::from("_RINxC3foo3bar");
+        for _ in 0..MAX_DEPTH - 2 {
+            mangled.push('S');
+        }
+        mangled.push_str("uE");
+
+        let symbol = Symbol::parse(&mangled).unwrap();
+        assert!(symbol.check_depth(MAX_DEPTH).is_ok());
+        assert!(symbol.check_depth(MAX_DEPTH - 1).is_err());
+        assert_eq!(mangled, symbol.try_mangle().unwrap());
+    }
+
+    #[test]
+    fn reject_deep_hand_built_tree() {
+        let mut ty = Type::UNIT;
+        for _ in 0..MAX_DEPTH {
+            ty = Type::Slice(Ptr::new(ty));
+        }
+
+        let symbol = Symbol {
+            version: None,
+            path: Path::Generic {
+                inner: Ptr::new(Path::CrateRoot {
+                    id: Ident {
+                        dis: Disambiguator(0),
+                        u_ident: UIdent("foo".into()),
+                    },
+                }),
+                args: vec![GenericArg::Type(ty)].into(),
+            },
+            instantiating_crate: None,
+        };
+
+        assert!(symbol.try_mangle().is_err());
+        assert!(symbol.try_demangle().is_err());
+    }
+}
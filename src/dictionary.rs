@@ -0,0 +1,344 @@
+//! A stable dump of the substitution dictionary that mangling a symbol
+//! builds, so that mangling bugs can be reported as a file and checked
+//! against another build of this crate.
+//!
+//! The text form starts with a `std-mangle dictionary <version>` line,
+//! followed by one line per field, entry and backref:
+//!
+//! ```text
+//! std-mangle dictionary 1
+//! mangled _RINxC7mycrate3fooNyC5other3BarBf_E
+//! demangled mycrate::foo<other::Bar,other::Bar>
+//! entry 0 path 2 35 - mycrate::foo<other::Bar,other::Bar>
+//! entry 1 path 3 18 0 mycrate::foo
+//! entry 2 path 5 14 1 mycrate
+//! entry 3 path 18 31 0 other::Bar
+//! entry 4 path 20 27 3 other
+//! backref 31 0 3
+//! ```
+//!
+//! An entry line has the index, the kind, the byte range of the entry within
+//! the mangled symbol, the index of the entry it is nested in or `-`, and
+//! the demangled entry, which is the rest of the line. A backref line has
+//! the byte offset of its `B`, the entry it is nested in or `-`, and the
+//! entry it points to. Offsets count from the start of the symbol, while the
+//! backrefs themselves count from the end of the `_R`. With the `json`
+//! feature, the dump can also be written as JSON, with the same fields.
+
+use ast::Symbol;
+pub use ast_mangle::EntryKind;
+use ast_mangle::{AstMangle, Compress};
+#[cfg(feature = "json")]
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The version of the format, which changes whenever a loader for an older
+/// version could not read the dump.
+pub const VERSION: u32 = 1;
+
+const HEADER: &str = "std-mangle dictionary";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct DictionaryDump {
+    pub version: u32,
+    /// The symbol with all possible backrefs, as this crate mangles it.
+    pub mangled: String,
+    pub demangled: String,
+    /// Every path, type, and const that a backref could point to, in the
+    /// order they start in `mangled`.
+    pub entries: Vec<Entry>,
+    pub backrefs: Vec<Backref>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct Entry {
+    pub kind: EntryKind,
+    /// Byte range of the entry within the mangled symbol.
+    pub start: usize,
+    pub end: usize,
+    /// The innermost entry this one is nested in, if any.
+    pub parent: Option<usize>,
+    pub demangled: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct Backref {
+    /// Byte offset of the `B` within the mangled symbol.
+    pub pos: usize,
+    /// The innermost entry the backref occurs in, if any.
+    pub from: Option<usize>,
+    /// The entry the backref points to.
+    pub to: usize,
+}
+
+impl Symbol {
+    /// Mangles this symbol and records its substitution dictionary.
+    pub fn dump_dictionary(&self) -> DictionaryDump {
+        let mut c = Compress::recording();
+        self.mangle_to_string(&mut c);
+        let (mangled, dictionary) = c.finish_recording();
+        let dictionary = dictionary.unwrap();
+
+        DictionaryDump {
+            version: VERSION,
+            mangled,
+            demangled: self.demangle(),
+            entries: dictionary
+                .entries
+                .into_iter()
+                .map(|entry| Entry {
+                    kind: entry.kind,
+                    start: entry.start,
+                    end: entry.end,
+                    parent: entry.parent,
+                    demangled: entry.demangled,
+                })
+                .collect(),
+            backrefs: dictionary
+                .backrefs
+                .into_iter()
+                .map(|backref| Backref {
+                    pos: backref.pos,
+                    from: backref.from,
+                    to: backref.to,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DictionaryDump {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{} {}", HEADER, self.version).unwrap();
+        writeln!(out, "mangled {}", self.mangled).unwrap();
+        writeln!(out, "demangled {}", self.demangled).unwrap();
+
+        let index = |index: Option<usize>| index.map_or("-".to_string(), |i| i.to_string());
+        for (i, entry) in self.entries.iter().enumerate() {
+            writeln!(
+                out,
+                "entry {} {} {} {} {} {}",
+                i,
+                kind_name(entry.kind),
+                entry.start,
+                entry.end,
+                index(entry.parent),
+                entry.demangled
+            )
+            .unwrap();
+        }
+        for backref in &self.backrefs {
+            writeln!(
+                out,
+                "backref {} {} {}",
+                backref.pos,
+                index(backref.from),
+                backref.to
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// Loads a dump written by `to_text()`.
+    pub fn from_text(text: &str) -> Result<DictionaryDump, String> {
+        let mut lines = text.lines().enumerate();
+
+        let version = match lines.next() {
+            Some((_, line)) if line.starts_with(HEADER) => line[HEADER.len()..]
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid version in {:?}", line))?,
+            _ => return Err("not a dictionary dump".to_string()),
+        };
+        if version != VERSION {
+            return Err(format!("unsupported dictionary dump version {}", version));
+        }
+
+        let mut dump = DictionaryDump {
+            version,
+            mangled: String::new(),
+            demangled: String::new(),
+            entries: Vec::new(),
+            backrefs: Vec::new(),
+        };
+
+        for (i, line) in lines {
+            let error = || format!("invalid line {}: {:?}", i + 1, line);
+            let (tag, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+            match tag {
+                "mangled" => dump.mangled = rest.to_string(),
+                "demangled" => dump.demangled = rest.to_string(),
+                "entry" => {
+                    let fields: Vec<_> = rest.splitn(6, ' ').collect();
+                    if fields.len() != 6 || fields[0] != dump.entries.len().to_string() {
+                        return Err(error());
+                    }
+                    dump.entries.push(Entry {
+                        kind: kind_from_name(fields[1]).ok_or_else(error)?,
+                        start: fields[2].parse().map_err(|_| error())?,
+                        end: fields[3].parse().map_err(|_| error())?,
+                        parent: parse_index(fields[4]).ok_or_else(error)?,
+                        demangled: fields[5].to_string(),
+                    });
+                }
+                "backref" => {
+                    let fields: Vec<_> = rest.split(' ').collect();
+                    if fields.len() != 3 {
+                        return Err(error());
+                    }
+                    dump.backrefs.push(Backref {
+                        pos: fields[0].parse().map_err(|_| error())?,
+                        from: parse_index(fields[1]).ok_or_else(error)?,
+                        to: fields[2].parse().map_err(|_| error())?,
+                    });
+                }
+                "" => {}
+                _ => return Err(error()),
+            }
+        }
+
+        Ok(dump)
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        ::serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Loads a dump written by `to_json()`.
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<DictionaryDump, String> {
+        let dump: DictionaryDump =
+            ::serde_json::from_str(json).map_err(|e| format!("invalid dictionary dump: {}", e))?;
+        if dump.version != VERSION {
+            return Err(format!(
+                "unsupported dictionary dump version {}",
+                dump.version
+            ));
+        }
+        Ok(dump)
+    }
+
+    /// Mangles the symbol of this dump again and returns how the result
+    /// differs from the dump, one line per difference. A dump from a build
+    /// with a mangling bug lists what this build does differently.
+    pub fn differences(&self) -> Result<Vec<String>, String> {
+        let symbol = Symbol::parse(&self.mangled)?;
+        let fresh = symbol.dump_dictionary();
+        let mut differences = Vec::new();
+
+        if fresh.mangled != self.mangled {
+            differences.push(format!("mangled: {} here", fresh.mangled));
+        }
+        if fresh.demangled != self.demangled {
+            differences.push(format!("demangled: {} here", fresh.demangled));
+        }
+
+        let len = self.entries.len().max(fresh.entries.len());
+        for i in 0..len {
+            match (self.entries.get(i), fresh.entries.get(i)) {
+                (Some(old), Some(new)) if old == new => {}
+                (_, Some(new)) => differences.push(format!(
+                    "entry {}: {} {}..{} {} here",
+                    i,
+                    kind_name(new.kind),
+                    new.start,
+                    new.end,
+                    new.demangled
+                )),
+                (_, None) => differences.push(format!("entry {}: missing here", i)),
+            }
+        }
+
+        let len = self.backrefs.len().max(fresh.backrefs.len());
+        for i in 0..len {
+            match (self.backrefs.get(i), fresh.backrefs.get(i)) {
+                (Some(old), Some(new)) if old == new => {}
+                (_, Some(new)) => differences.push(format!(
+                    "backref {}: at {} to entry {} here",
+                    i, new.pos, new.to
+                )),
+                (_, None) => differences.push(format!("backref {}: missing here", i)),
+            }
+        }
+
+        Ok(differences)
+    }
+}
+
+fn kind_name(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Path => "path",
+        EntryKind::Type => "type",
+        EntryKind::Const => "const",
+    }
+}
+
+fn kind_from_name(name: &str) -> Option<EntryKind> {
+    Some(match name {
+        "path" => EntryKind::Path,
+        "type" => EntryKind::Type,
+        "const" => EntryKind::Const,
+        _ => return None,
+    })
+}
+
+/// Parses an entry index or `-` for none.
+fn parse_index(text: &str) -> Option<Option<usize>> {
+    if text == "-" {
+        Some(None)
+    } else {
+        text.parse().ok().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{kind_name, DictionaryDump};
+    use ast::Symbol;
+
+    #[test]
+    fn dump_and_load() {
+        let symbol = Symbol::parse("_RINxC7mycrate3fooNyC5other3BarNyC5other3BarE").unwrap();
+        let dump = symbol.dump_dictionary();
+        assert_eq!(1, dump.backrefs.len());
+
+        let backref = &dump.backrefs[0];
+        let target = &dump.entries[backref.to];
+        assert_eq!("path", kind_name(target.kind));
+        assert_eq!("other::Bar", target.demangled);
+        assert_eq!("NyC5other3Bar", &dump.mangled[target.start..target.end]);
+
+        let text = dump.to_text();
+        assert!(text.starts_with("std-mangle dictionary 1\n"));
+        assert_eq!(Ok(&dump), DictionaryDump::from_text(&text).as_ref());
+        assert_eq!(Ok(vec![]), dump.differences());
+
+        let mut buggy = dump.clone();
+        buggy.entries[backref.to].end -= 1;
+        assert_eq!(1, buggy.differences().unwrap().len());
+
+        assert!(DictionaryDump::from_text("std-mangle dictionary 2\n").is_err());
+        assert!(DictionaryDump::from_text(&text.replace("backref", "backrf")).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        let symbol = Symbol::parse("_RINxC7mycrate3fooNyC5other3BarNyC5other3BarE").unwrap();
+        let dump = symbol.dump_dictionary();
+        assert_eq!(
+            Ok(&dump),
+            DictionaryDump::from_json(&dump.to_json()).as_ref()
+        );
+    }
+}
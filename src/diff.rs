@@ -0,0 +1,536 @@
+//! Structural comparison of two lists of symbols, e.g. the symbol tables of
+//! two builds of the same binary, and of two symbols component by
+//! component.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// The symbols that were added and removed between two lists, grouped by
+/// their skeleton, so that all changed instances of one generic item are
+/// reported together.
+#[derive(Clone, Default, Debug)]
+pub struct SymbolDiff {
+    /// The changed instances by their demangled skeleton.
+    pub skeletons: BTreeMap<String, SkeletonDiff>,
+    /// The number of symbols in either list that could not be parsed.
+    pub invalid: usize,
+}
+
+/// The demangled forms of the instances of one skeleton that were added and
+/// removed, each sorted.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct SkeletonDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl SymbolDiff {
+    /// Compares `old` and `new` by the structure of their symbols, so that
+    /// the same symbol with different backrefs counts as unchanged. With
+    /// `ignore_disambiguators`, symbols that only differ in their
+    /// disambiguators count as unchanged as well.
+    pub fn new<'a, I, J>(old: I, new: J, ignore_disambiguators: bool) -> SymbolDiff
+    where
+        I: IntoIterator<Item = &'a str>,
+        J: IntoIterator<Item = &'a str>,
+    {
+        let mut diff = SymbolDiff::default();
+        let old = diff.parse_all(old, ignore_disambiguators);
+        let new = diff.parse_all(new, ignore_disambiguators);
+
+        for symbol in new.difference(&old) {
+            diff.entry(symbol).added.push(symbol.demangle());
+        }
+        for symbol in old.difference(&new) {
+            diff.entry(symbol).removed.push(symbol.demangle());
+        }
+
+        for entry in diff.skeletons.values_mut() {
+            entry.added.sort();
+            entry.removed.sort();
+        }
+
+        diff
+    }
+
+    pub fn added(&self) -> usize {
+        self.skeletons.values().map(|entry| entry.added.len()).sum()
+    }
+
+    pub fn removed(&self) -> usize {
+        self.skeletons
+            .values()
+            .map(|entry| entry.removed.len())
+            .sum()
+    }
+
+    fn parse_all<'a, I>(&mut self, symbols: I, ignore_disambiguators: bool) -> HashSet<Symbol>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut parsed = HashSet::new();
+
+        for mangled in symbols {
+            match Symbol::parse(mangled) {
+                Ok(symbol) if ignore_disambiguators => {
+                    parsed.insert(symbol.erase_disambiguators());
+                }
+                Ok(symbol) => {
+                    parsed.insert(symbol);
+                }
+                Err(_) => self.invalid += 1,
+            }
+        }
+
+        parsed
+    }
+
+    fn entry(&mut self, symbol: &Symbol) -> &mut SkeletonDiff {
+        self.skeletons
+            .entry(symbol.skeleton().demangle())
+            .or_default()
+    }
+}
+
+/// Prints every changed skeleton with its added (`+`) and removed (`-`)
+/// instances, followed by the totals.
+impl fmt::Display for SymbolDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (skeleton, entry) in &self.skeletons {
+            writeln!(
+                f,
+                "{} (+{} -{})",
+                skeleton,
+                entry.added.len(),
+                entry.removed.len()
+            )?;
+            for added in &entry.added {
+                writeln!(f, "  + {}", added)?;
+            }
+            for removed in &entry.removed {
+                writeln!(f, "  - {}", removed)?;
+            }
+        }
+
+        writeln!(f, "{} added, {} removed", self.added(), self.removed())
+    }
+}
+
+/// The components in which two symbols differ, as found by `diff()`.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct StructuralDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl StructuralDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// One component in which two symbols differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// The way from the path of the symbol to the component. It is empty for
+    /// a difference in the last segment of the path.
+    pub location: Vec<Step>,
+    pub kind: DifferenceKind,
+    /// The demangled component in the first symbol.
+    pub old: String,
+    /// The demangled component in the second symbol.
+    pub new: String,
+}
+
+/// A step from a path or type to one of its components.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Step {
+    /// The instantiating crate of the symbol.
+    InstantiatingCrate,
+    /// The path a nested path is in.
+    Parent,
+    GenericArg(usize),
+    /// The path of the module that contains an impl.
+    ImplPath,
+    SelfType,
+    Trait,
+    /// The type that a reference or pointer points to, or the element type
+    /// of an array or slice.
+    Element,
+    ArrayLen,
+    TupleElement(usize),
+    Param(usize),
+    ReturnType,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DifferenceKind {
+    /// Different identifiers or crate names.
+    Name,
+    /// The same crate name with different disambiguators, i.e. different
+    /// crates or different builds of one crate.
+    CrateDisambiguator,
+    /// The same identifier with different disambiguators, or impls with
+    /// different disambiguators.
+    Disambiguator,
+    Namespace,
+    /// Different numbers of generic arguments, tuple elements or parameters.
+    Length,
+    /// Components of different kinds, like `u8` and `&str`, or that differ
+    /// in anything else.
+    Component,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Step::InstantiatingCrate => f.write_str("instantiating crate"),
+            Step::Parent => f.write_str("parent"),
+            Step::GenericArg(i) => write!(f, "generic arg {}", i),
+            Step::ImplPath => f.write_str("impl path"),
+            Step::SelfType => f.write_str("self type"),
+            Step::Trait => f.write_str("trait"),
+            Step::Element => f.write_str("element"),
+            Step::ArrayLen => f.write_str("array length"),
+            Step::TupleElement(i) => write!(f, "tuple element {}", i),
+            Step::Param(i) => write!(f, "param {}", i),
+            Step::ReturnType => f.write_str("return type"),
+        }
+    }
+}
+
+impl fmt::Display for DifferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DifferenceKind::Name => "name",
+            DifferenceKind::CrateDisambiguator => "crate disambiguator",
+            DifferenceKind::Disambiguator => "disambiguator",
+            DifferenceKind::Namespace => "namespace",
+            DifferenceKind::Length => "length",
+            DifferenceKind::Component => "component",
+        })
+    }
+}
+
+/// Prints one line per difference, like `parent > generic arg 1: u8 -> u32
+/// (component)`.
+impl fmt::Display for StructuralDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for difference in &self.differences {
+            if difference.location.is_empty() {
+                f.write_str("path")?;
+            }
+            for (i, step) in difference.location.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" > ")?;
+                }
+                write!(f, "{}", step)?;
+            }
+            writeln!(
+                f,
+                ": {} -> {} ({})",
+                difference.old, difference.new, difference.kind
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares `a` and `b` component by component and returns the innermost
+/// components in which they differ, so that two symbols that only differ in
+/// one generic argument deep inside give one difference instead of two long
+/// strings. Symbols that only differ in their backrefs have no differences.
+pub fn diff(a: &Symbol, b: &Symbol) -> StructuralDiff {
+    let mut differ = Differ {
+        location: Vec::new(),
+        differences: Vec::new(),
+    };
+
+    differ.path(&a.path, &b.path);
+    match (&a.instantiating_crate, &b.instantiating_crate) {
+        (Some(a), Some(b)) => differ.nested(Step::InstantiatingCrate, |differ| differ.path(a, b)),
+        (a, b) if a != b => differ.nested(Step::InstantiatingCrate, |differ| {
+            let demangle =
+                |path: &Option<Path>| path.as_ref().map_or(String::new(), Path::demangle);
+            differ.push(DifferenceKind::Component, demangle(a), demangle(b))
+        }),
+        _ => {}
+    }
+
+    StructuralDiff {
+        differences: differ.differences,
+    }
+}
+
+struct Differ {
+    location: Vec<Step>,
+    differences: Vec<Difference>,
+}
+
+impl Differ {
+    fn push(&mut self, kind: DifferenceKind, old: String, new: String) {
+        self.differences.push(Difference {
+            location: self.location.clone(),
+            kind,
+            old,
+            new,
+        });
+    }
+
+    fn component<T: AstDemangle>(&mut self, a: &T, b: &T) {
+        self.push(DifferenceKind::Component, a.demangle(), b.demangle());
+    }
+
+    fn nested<F: FnOnce(&mut Differ)>(&mut self, step: Step, f: F) {
+        self.location.push(step);
+        f(self);
+        self.location.pop();
+    }
+
+    fn path(&mut self, a: &Path, b: &Path) {
+        if a == b {
+            return;
+        }
+
+        match (a, b) {
+            (Path::CrateRoot { id: a }, Path::CrateRoot { id: b }) => {
+                self.ident(a, b, DifferenceKind::CrateDisambiguator)
+            }
+            (
+                Path::Nested {
+                    ns: a_ns,
+                    inner: a_inner,
+                    ident: a_ident,
+                },
+                Path::Nested {
+                    ns: b_ns,
+                    inner: b_inner,
+                    ident: b_ident,
+                },
+            ) => {
+                if a_ns != b_ns {
+                    self.push(
+                        DifferenceKind::Namespace,
+                        (a_ns.0 as char).to_string(),
+                        (b_ns.0 as char).to_string(),
+                    );
+                }
+                self.ident(a_ident, b_ident, DifferenceKind::Disambiguator);
+                self.nested(Step::Parent, |differ| differ.path(a_inner, b_inner));
+            }
+            (
+                Path::Generic {
+                    inner: a_inner,
+                    args: a_args,
+                },
+                Path::Generic {
+                    inner: b_inner,
+                    args: b_args,
+                },
+            ) => {
+                self.path(a_inner, b_inner);
+                if a_args.len() != b_args.len() {
+                    self.push(DifferenceKind::Length, a.demangle(), b.demangle());
+                    return;
+                }
+                for (i, (a, b)) in a_args.iter().zip(b_args.iter()).enumerate() {
+                    self.nested(Step::GenericArg(i), |differ| differ.generic_arg(a, b));
+                }
+            }
+            (
+                Path::InherentImpl {
+                    impl_path: a_impl,
+                    self_type: a_self,
+                },
+                Path::InherentImpl {
+                    impl_path: b_impl,
+                    self_type: b_self,
+                },
+            ) => {
+                self.impl_path(a_impl, b_impl);
+                self.nested(Step::SelfType, |differ| differ.ty(a_self, b_self));
+            }
+            (
+                Path::TraitImpl {
+                    impl_path: a_impl,
+                    self_type: a_self,
+                    trait_name: a_trait,
+                },
+                Path::TraitImpl {
+                    impl_path: b_impl,
+                    self_type: b_self,
+                    trait_name: b_trait,
+                },
+            ) => {
+                self.impl_path(a_impl, b_impl);
+                self.nested(Step::SelfType, |differ| differ.ty(a_self, b_self));
+                self.nested(Step::Trait, |differ| differ.path(a_trait, b_trait));
+            }
+            (
+                Path::TraitDef {
+                    self_type: a_self,
+                    trait_name: a_trait,
+                },
+                Path::TraitDef {
+                    self_type: b_self,
+                    trait_name: b_trait,
+                },
+            ) => {
+                self.nested(Step::SelfType, |differ| differ.ty(a_self, b_self));
+                self.nested(Step::Trait, |differ| differ.path(a_trait, b_trait));
+            }
+            _ => self.component(a, b),
+        }
+    }
+
+    fn ident(&mut self, a: &Ident, b: &Ident, dis_kind: DifferenceKind) {
+        if a.u_ident != b.u_ident {
+            self.push(
+                DifferenceKind::Name,
+                a.u_ident.demangle(),
+                b.u_ident.demangle(),
+            );
+        } else if a.dis != b.dis {
+            self.push(dis_kind, a.demangle(), b.demangle());
+        }
+    }
+
+    fn impl_path(&mut self, a: &ImplPath, b: &ImplPath) {
+        if a.dis != b.dis {
+            let value =
+                |dis: Option<Disambiguator>| dis.map_or(0, Disambiguator::value).to_string();
+            self.push(DifferenceKind::Disambiguator, value(a.dis), value(b.dis));
+        }
+        self.nested(Step::ImplPath, |differ| differ.path(&a.path, &b.path));
+    }
+
+    fn generic_arg(&mut self, a: &GenericArg, b: &GenericArg) {
+        match (a, b) {
+            (GenericArg::Type(a), GenericArg::Type(b)) => self.ty(a, b),
+            _ if a != b => self.component(a, b),
+            _ => {}
+        }
+    }
+
+    fn ty(&mut self, a: &Type, b: &Type) {
+        if a == b {
+            return;
+        }
+
+        match (a, b) {
+            (Type::Named(a), Type::Named(b)) => self.path(a, b),
+            (Type::Ref(a_lifetime, a), Type::Ref(b_lifetime, b))
+            | (Type::RefMut(a_lifetime, a), Type::RefMut(b_lifetime, b))
+                if a_lifetime == b_lifetime =>
+            {
+                self.nested(Step::Element, |differ| differ.ty(a, b))
+            }
+            (Type::RawPtrConst(a), Type::RawPtrConst(b))
+            | (Type::RawPtrMut(a), Type::RawPtrMut(b))
+            | (Type::Slice(a), Type::Slice(b)) => {
+                self.nested(Step::Element, |differ| differ.ty(a, b))
+            }
+            (Type::Array(a, a_len), Type::Array(b, b_len)) => {
+                self.nested(Step::Element, |differ| differ.ty(a, b));
+                if a_len != b_len {
+                    self.nested(Step::ArrayLen, |differ| {
+                        differ.component(&**a_len, &**b_len)
+                    });
+                }
+            }
+            (Type::Tuple(a), Type::Tuple(b)) if a.len() == b.len() => {
+                for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                    self.nested(Step::TupleElement(i), |differ| differ.ty(a, b));
+                }
+            }
+            (Type::Fn(a), Type::Fn(b))
+                if a.binder == b.binder
+                    && a.is_unsafe == b.is_unsafe
+                    && a.abi == b.abi
+                    && a.param_types.len() == b.param_types.len() =>
+            {
+                for (i, (a, b)) in a.param_types.iter().zip(&b.param_types).enumerate() {
+                    self.nested(Step::Param(i), |differ| differ.ty(a, b));
+                }
+                self.nested(Step::ReturnType, |differ| {
+                    differ.ty(&a.return_type, &b.return_type)
+                });
+            }
+            (Type::Tuple(_), Type::Tuple(_)) | (Type::Fn(_), Type::Fn(_)) => {
+                self.push(DifferenceKind::Length, a.demangle(), b.demangle())
+            }
+            _ => self.component(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Difference, DifferenceKind, SkeletonDiff, Step, SymbolDiff};
+    use ast::Symbol;
+
+    #[test]
+    fn diff_by_skeleton() {
+        let old = [
+            "_RINxC7mycrate3fooaE",
+            "_RINxC7mycrate3foohE",
+            "_RNxCs1_3std4quux",
+        ];
+        let new = [
+            "_RINxC7mycrate3foohE",
+            "_RINxC7mycrate3foomE",
+            "_RNxCs2_3std4quux",
+        ];
+
+        let diff = SymbolDiff::new(old.iter().cloned(), new.iter().cloned(), false);
+        assert_eq!((2, 2), (diff.added(), diff.removed()));
+        assert_eq!(
+            SkeletonDiff {
+                added: vec!["mycrate::foo<u32>".to_string()],
+                removed: vec!["mycrate::foo<i8>".to_string()],
+            },
+            diff.skeletons["mycrate::foo<_>"]
+        );
+
+        let diff = SymbolDiff::new(old.iter().cloned(), new.iter().cloned(), true);
+        assert_eq!((1, 1), (diff.added(), diff.removed()));
+        assert_eq!(
+            vec!["mycrate::foo<_>"],
+            diff.skeletons.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn diff_components() {
+        let parse = |mangled| Symbol::parse(mangled).unwrap();
+        let a = parse("_RINxNyCs1_3std3foo3barTRhaEEC5other");
+        let b = parse("_RINxNyCs2_3std3foo3barTRhmEEC5other");
+
+        let structural = diff(&a, &b);
+        assert_eq!(
+            vec![
+                Difference {
+                    location: vec![Step::Parent, Step::Parent],
+                    kind: DifferenceKind::CrateDisambiguator,
+                    old: "std[3]".to_string(),
+                    new: "std[4]".to_string(),
+                },
+                Difference {
+                    location: vec![Step::GenericArg(0), Step::TupleElement(1)],
+                    kind: DifferenceKind::Component,
+                    old: "i8".to_string(),
+                    new: "u32".to_string(),
+                },
+            ],
+            structural.differences
+        );
+        assert_eq!(
+            "parent > parent: std[3] -> std[4] (crate disambiguator)\n\
+             generic arg 0 > tuple element 1: i8 -> u32 (component)\n",
+            structural.to_string()
+        );
+
+        assert!(diff(&a, &parse(&a.mangle_uncompressed())).is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Graphviz export of a symbol's AST.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use ast_mangle::{AstMangle, Compress, EntryKind};
+use std::fmt::Write;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+impl Symbol {
+    /// Renders the AST of this symbol as a DOT graph. Every occurrence of a
+    /// node becomes a separate vertex, so the output is always a tree.
+    pub fn to_dot(&self) -> String {
+        let mut w = DotWriter {
+            out: String::new(),
+            next_id: 0,
+        };
+
+        w.out.push_str("digraph symbol {\n    node [shape=box];\n");
+
+        let root = w.node("Symbol");
+        let path = w.path(&self.path);
+        w.edge(root, path, "path");
+
+        if let Some(ref instantiating_crate) = self.instantiating_crate {
+            let krate = w.path(instantiating_crate);
+            w.edge(root, krate, "instantiating crate");
+        }
+
+        w.out.push_str("}\n");
+        w.out
+    }
+
+    /// Renders the structure of the compressed symbol as a DOT graph. Every
+    /// substitution dictionary entry becomes one vertex; solid edges show
+    /// which entry is nested in which, dashed edges show backrefs into the
+    /// dictionary.
+    pub fn to_substitution_dot(&self) -> String {
+        let mut c = Compress::recording();
+        self.mangle_to_string(&mut c);
+        let (mangled, dictionary) = c.finish_recording();
+        let dictionary = dictionary.unwrap();
+
+        let mut w = DotWriter {
+            out: String::new(),
+            next_id: 0,
+        };
+
+        w.out
+            .push_str("digraph substitutions {\n    node [shape=box];\n");
+
+        // Dictionary entry `i` is rendered as node `n{i + 1}`.
+        let root = w.node(&mangled);
+
+        for entry in dictionary.entries.iter() {
+            let kind = match entry.kind {
+                EntryKind::Path => "path",
+                EntryKind::Type => "type",
+                EntryKind::Const => "const",
+            };
+            let id = w.node(&format!(
+                "{} @{}\n{}\n{}",
+                kind,
+                entry.start - 2,
+                &mangled[entry.start..entry.end],
+                entry.demangled
+            ));
+            let parent = entry.parent.map_or(root, |parent| parent + 1);
+            w.edge(parent, id, "");
+        }
+
+        for backref in dictionary.backrefs.iter() {
+            let from = backref.from.map_or(root, |from| from + 1);
+            let end = backref.pos + mangled[backref.pos..].find('_').unwrap() + 1;
+            writeln!(
+                w.out,
+                "    n{} -> n{} [style=dashed, label=\"{}\"];",
+                from,
+                backref.to + 1,
+                &mangled[backref.pos..end]
+            )
+            .unwrap();
+        }
+
+        w.out.push_str("}\n");
+        w.out
+    }
+}
+
+struct DotWriter {
+    out: String,
+    next_id: usize,
+}
+
+impl DotWriter {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        writeln!(self.out, "    n{} [label=\"{}\"];", id, escape(label)).unwrap();
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            writeln!(self.out, "    n{} -> n{};", from, to).unwrap();
+        } else {
+            writeln!(
+                self.out,
+                "    n{} -> n{} [label=\"{}\"];",
+                from,
+                to,
+                escape(label)
+            )
+            .unwrap();
+        }
+    }
+
+    fn path(&mut self, path: &Path) -> usize {
+        match *path {
+            Path::CrateRoot { ref id } => self.node(&format!("CrateRoot\n{}", id.demangle())),
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => {
+                let node = self.node("InherentImpl");
+                self.impl_path(node, impl_path);
+                let self_type = self.ty(self_type);
+                self.edge(node, self_type, "self");
+                node
+            }
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => {
+                let node = self.node("TraitImpl");
+                self.impl_path(node, impl_path);
+                let self_type = self.ty(self_type);
+                self.edge(node, self_type, "self");
+                let trait_name = self.path(trait_name);
+                self.edge(node, trait_name, "trait");
+                node
+            }
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => {
+                let node = self.node("TraitDef");
+                let self_type = self.ty(self_type);
+                self.edge(node, self_type, "self");
+                let trait_name = self.path(trait_name);
+                self.edge(node, trait_name, "trait");
+                node
+            }
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => {
+                let node = self.node(&format!("Nested ({})\n{}", ns.0 as char, ident.demangle()));
+                let inner = self.path(inner);
+                self.edge(node, inner, "inner");
+                node
+            }
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => {
+                let node = self.node("Generic");
+                let inner = self.path(inner);
+                self.edge(node, inner, "inner");
+                for (i, arg) in args.iter().enumerate() {
+                    let arg = match *arg {
+                        GenericArg::Lifetime(ref lt) => self.lifetime(lt),
+                        GenericArg::Type(ref ty) => self.ty(ty),
+                        GenericArg::Const(ref k) => self.konst(k),
+                    };
+                    self.edge(node, arg, &format!("arg {}", i));
+                }
+                node
+            }
+        }
+    }
+
+    fn impl_path(&mut self, parent: usize, impl_path: &ImplPath) {
+        let path = self.path(&impl_path.path);
+        let label = match impl_path.dis {
+            Some(dis) => format!("impl [{}]", dis),
+            None => "impl".to_string(),
+        };
+        self.edge(parent, path, &label);
+    }
+
+    fn ty(&mut self, ty: &Type) -> usize {
+        match *ty {
+            Type::BasicType(bt) => self.node(&bt.demangle()),
+            Type::Array(ref inner, ref len) => {
+                let node = self.node("Array");
+                let inner = self.ty(inner);
+                self.edge(node, inner, "element");
+                let len = self.konst(len);
+                self.edge(node, len, "len");
+                node
+            }
+            Type::Slice(ref inner) => self.unary("Slice", inner),
+            Type::Named(ref path) => {
+                let node = self.node("Named");
+                let path = self.path(path);
+                self.edge(node, path, "");
+                node
+            }
+            Type::Tuple(ref components) => {
+                let node = self.node("Tuple");
+                for (i, component) in components.iter().enumerate() {
+                    let component = self.ty(component);
+                    self.edge(node, component, &i.to_string());
+                }
+                node
+            }
+            Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+                let label = if let Type::Ref(..) = *ty { "&" } else { "&mut" };
+                let node = self.unary(label, inner);
+                if let Some(ref lifetime) = *lifetime {
+                    let lifetime = self.lifetime(lifetime);
+                    self.edge(node, lifetime, "lifetime");
+                }
+                node
+            }
+            Type::RawPtrConst(ref inner) => self.unary("*const", inner),
+            Type::RawPtrMut(ref inner) => self.unary("*mut", inner),
+            Type::Fn(ref fn_sig) => {
+                let mut label = String::new();
+                if fn_sig.is_unsafe {
+                    label.push_str("unsafe ");
+                }
+                if let Some(ref abi) = fn_sig.abi {
+                    write!(label, "extern {} ", abi.demangle()).unwrap();
+                }
+                label.push_str("fn");
+                let node = self.node(&label);
+                for (i, param_type) in fn_sig.param_types.iter().enumerate() {
+                    let param_type = self.ty(param_type);
+                    self.edge(node, param_type, &format!("param {}", i));
+                }
+                let return_type = self.ty(&fn_sig.return_type);
+                self.edge(node, return_type, "return");
+                node
+            }
+            Type::DynTrait(ref bounds, ref lifetime) => {
+                let node = self.node("dyn");
+                for tr in bounds.traits.iter() {
+                    let path = self.path(&tr.path);
+                    self.edge(node, path, "trait");
+                    for binding in tr.assoc_type_bindings.iter() {
+                        let ty = self.ty(&binding.ty);
+                        self.edge(path, ty, &binding.ident.demangle());
+                    }
+                }
+                let lifetime = self.lifetime(lifetime);
+                self.edge(node, lifetime, "lifetime");
+                node
+            }
+        }
+    }
+
+    fn unary(&mut self, label: &str, inner: &Type) -> usize {
+        let node = self.node(label);
+        let inner = self.ty(inner);
+        self.edge(node, inner, "");
+        node
+    }
+
+    fn konst(&mut self, k: &Const) -> usize {
+        let (label, ty) = match *k {
+            Const::Value(ref ty, value) => (format!("Const\n{}", value), ty),
+            Const::Placeholder(ref ty) => ("Const\n_".to_string(), ty),
+        };
+        let node = self.node(&label);
+        let ty = self.ty(ty);
+        self.edge(node, ty, "type");
+        node
+    }
+
+    fn lifetime(&mut self, lifetime: &Lifetime) -> usize {
+        self.node(&format!("Lifetime\n{}", lifetime.debruijn_index))
+    }
+}
+
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
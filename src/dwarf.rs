@@ -0,0 +1,100 @@
+//! Demangling for symbolizers built on `gimli` and `addr2line`. Wrapping the
+//! `FrameIter` of `Context::find_frames()` in `DemangledFrames` gives every
+//! frame the demangled form of its `DW_AT_linkage_name`, including v0
+//! symbols, which `addr2line` cannot demangle without its default features.
+
+use addr2line::gimli;
+use addr2line::{Frame, FrameIter, FunctionName};
+use compat;
+
+/// A frame together with the demangled name of its function.
+pub struct DemangledFrame<'ctx, R: gimli::Reader> {
+    /// The frame as returned by `addr2line`.
+    pub frame: Frame<'ctx, R>,
+    /// The demangled name of the function of the frame, the name as it is if
+    /// it is not a Rust symbol, or `None` if the frame has no function name.
+    pub name: Option<String>,
+}
+
+/// An iterator over the frames of `addr2line` with demangled function names.
+pub struct DemangledFrames<'ctx, R: gimli::Reader> {
+    frames: FrameIter<'ctx, R>,
+}
+
+impl<'ctx, R: gimli::Reader> DemangledFrames<'ctx, R> {
+    pub fn new(frames: FrameIter<'ctx, R>) -> DemangledFrames<'ctx, R> {
+        DemangledFrames { frames }
+    }
+
+    /// The next frame, like `FrameIter::next()`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<DemangledFrame<'ctx, R>>, gimli::Error> {
+        let frame = match self.frames.next()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        let name = match frame.function {
+            Some(ref function) => Some(demangle_function_name(function)?),
+            None => None,
+        };
+        Ok(Some(DemangledFrame { frame, name }))
+    }
+}
+
+impl<'ctx, R: gimli::Reader> From<FrameIter<'ctx, R>> for DemangledFrames<'ctx, R> {
+    fn from(frames: FrameIter<'ctx, R>) -> DemangledFrames<'ctx, R> {
+        DemangledFrames::new(frames)
+    }
+}
+
+/// Demangles `function` like `rustc-demangle` does with `{:#}`, unless its
+/// compilation unit is known to be written in a language other than Rust.
+/// Names that are not Rust symbols are left to `FunctionName::demangle()`.
+pub fn demangle_function_name<R: gimli::Reader>(
+    function: &FunctionName<R>,
+) -> Result<String, gimli::Error> {
+    let raw_name = function.raw_name()?;
+    if function
+        .language
+        .is_none_or(|language| language == gimli::DW_LANG_Rust)
+    {
+        if let Ok(demangled) = compat::try_demangle(&raw_name) {
+            return Ok(format!("{:#}", demangled));
+        }
+    }
+
+    Ok(function.demangle()?.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::demangle_function_name;
+    use addr2line::gimli::{self, EndianSlice, LittleEndian};
+    use addr2line::FunctionName;
+
+    fn demangle(name: &str, language: Option<gimli::DwLang>) -> String {
+        let function = FunctionName {
+            name: EndianSlice::new(name.as_bytes(), LittleEndian),
+            language,
+        };
+        demangle_function_name(&function).unwrap()
+    }
+
+    #[test]
+    fn demangle_linkage_names() {
+        assert_eq!(
+            "mycrate::foo<u8>",
+            demangle("_RINvCs0_7mycrate3foohE", Some(gimli::DW_LANG_Rust))
+        );
+        assert_eq!(
+            "std::io::stdio::_print",
+            demangle("_ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE", None)
+        );
+        assert_eq!(
+            "_RNvC7mycrate3foo",
+            demangle("_RNvC7mycrate3foo", Some(gimli::DW_LANG_C_plus_plus))
+        );
+        assert_eq!("main", demangle("main", None));
+    }
+}
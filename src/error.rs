@@ -1,4 +1,38 @@
-use std::fmt::{Display, Write};
+use std::fmt::{self, Display, Write};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// Why a symbol could not be demangled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    position: usize,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn new(position: usize, message: String) -> Error {
+        Error { position, message }
+    }
+
+    /// The byte offset in the mangled symbol at which parsing failed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// What went wrong at `position()`, without the position.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at position {}: {}", self.position, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
 
 pub fn expected<T>(
     expected_chars: &str,
@@ -0,0 +1,591 @@
+//! A breakdown of a mangled symbol into its tokens, its AST, and its
+//! substitutions, for learning and debugging the mangling scheme.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use charset;
+use parse::{basic_type, Parser};
+use std::fmt::{self, Write};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::str;
+
+/// The explanation of one mangled symbol.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    pub mangled: String,
+    pub symbol: Symbol,
+    /// The tokens of `mangled`, in order.
+    pub tokens: Vec<Token>,
+    /// The backrefs in `mangled`, in order.
+    pub backrefs: Vec<Backref>,
+}
+
+/// A token of a mangled symbol, such as a tag, an identifier, or a number.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Token {
+    /// Byte range of the token within the mangled symbol.
+    pub start: usize,
+    pub end: usize,
+    pub description: String,
+}
+
+/// A backref and the substitution it points to.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Backref {
+    /// Byte offset of the `B` within the mangled symbol.
+    pub pos: usize,
+    /// Byte range of the substituted path, type, or const.
+    pub target_start: usize,
+    pub target_end: usize,
+    pub demangled: String,
+}
+
+/// Breaks `mangled` down into its tokens and backrefs.
+pub fn explain(mangled: &str) -> Result<Explanation, String> {
+    let symbol = Symbol::parse(mangled)?;
+
+    let mut lexer = Lexer {
+        p: Parser::new(mangled.as_bytes()),
+        tokens: Vec::new(),
+        backrefs: Vec::new(),
+    };
+    lexer.symbol()?;
+
+    Ok(Explanation {
+        mangled: mangled.to_string(),
+        symbol,
+        tokens: lexer.tokens,
+        backrefs: lexer.backrefs,
+    })
+}
+
+/// Walks the grammar like the parser does, but records the tokens instead of
+/// building the AST, and does not follow backrefs.
+struct Lexer<'input> {
+    p: Parser<'input>,
+    tokens: Vec<Token>,
+    backrefs: Vec<Backref>,
+}
+
+impl<'input> Lexer<'input> {
+    fn token(&mut self, start: usize, description: String) {
+        self.tokens.push(Token {
+            start,
+            end: self.p.pos(),
+            description,
+        });
+    }
+
+    /// Consumes a single-byte tag as a token.
+    fn tag(&mut self, description: &str) {
+        let start = self.p.pos();
+        self.p.try_eat(self.p.cur());
+        self.token(start, description.to_string());
+    }
+
+    fn symbol(&mut self) -> Result<(), String> {
+        self.p.parse_symbol_prefix()?;
+        self.token(0, "symbol prefix".to_string());
+
+        self.path()?;
+
+        if !self.p.at_end() {
+            self.path()?;
+        }
+
+        Ok(())
+    }
+
+    fn path(&mut self) -> Result<(), String> {
+        match self.p.cur() {
+            b'C' => {
+                self.tag("crate root");
+                self.ident()
+            }
+            b'M' => {
+                self.tag("inherent impl");
+                self.impl_path()?;
+                self.ty()
+            }
+            b'X' => {
+                self.tag("trait impl");
+                self.impl_path()?;
+                self.ty()?;
+                self.path()
+            }
+            b'Y' => {
+                self.tag("trait definition");
+                self.ty()?;
+                self.path()
+            }
+            b'N' => {
+                let start = self.p.pos();
+                self.p.try_eat(b'N');
+                let ns = self.p.parse_namespace()?;
+                self.token(
+                    start,
+                    format!("nested path in namespace '{}'", ns.0 as char),
+                );
+                self.path()?;
+                self.ident()
+            }
+            b'I' => {
+                self.tag("generic args");
+                self.path()?;
+                while self.p.cur() != b'E' {
+                    self.generic_arg()?;
+                }
+                self.tag("end of generic args");
+                Ok(())
+            }
+            b'B' => self.backref("path", Parser::parse_path),
+            c => Err(format!("unexpected path tag '{}'", c as char)),
+        }
+    }
+
+    fn impl_path(&mut self) -> Result<(), String> {
+        if self.p.cur() == b's' {
+            self.disambiguator()?;
+        }
+        self.path()
+    }
+
+    fn ident(&mut self) -> Result<(), String> {
+        if self.p.cur() == b's' {
+            self.disambiguator()?;
+        }
+        self.uident()
+    }
+
+    fn disambiguator(&mut self) -> Result<(), String> {
+        let start = self.p.pos();
+        let dis = self.p.parse_disambiguator()?;
+        self.token(start, format!("disambiguator {}", dis));
+        Ok(())
+    }
+
+    fn uident(&mut self) -> Result<(), String> {
+        let start = self.p.pos();
+        let (punycode, bytes) = self.p.parse_uident_bytes()?;
+
+        let description = if punycode {
+            format!(
+                "punycode identifier {}",
+                charset::decode_punycode_ident(bytes)?
+            )
+        } else {
+            format!(
+                "identifier {}",
+                str::from_utf8(bytes).map_err(|e| e.to_string())?
+            )
+        };
+        self.token(start, description);
+        Ok(())
+    }
+
+    fn generic_arg(&mut self) -> Result<(), String> {
+        match self.p.cur() {
+            b'L' => self.lifetime(),
+            b'K' => {
+                self.tag("const arg");
+                self.konst()
+            }
+            _ => self.ty(),
+        }
+    }
+
+    fn lifetime(&mut self) -> Result<(), String> {
+        let start = self.p.pos();
+        let lifetime = self.p.parse_lifetime()?;
+        self.token(
+            start,
+            format!("lifetime with De Bruijn index {}", lifetime.debruijn_index),
+        );
+        Ok(())
+    }
+
+    fn binder(&mut self) -> Result<(), String> {
+        let start = self.p.pos();
+        let binder = self.p.parse_binder()?;
+        self.token(start, format!("binder of {} lifetimes", binder.count));
+        Ok(())
+    }
+
+    fn ty(&mut self) -> Result<(), String> {
+        let tag = self.p.cur();
+
+        if let Some(bt) = basic_type(tag) {
+            self.tag(&format!("basic type {}", bt.demangle()));
+            return Ok(());
+        }
+
+        match tag {
+            b'A' => {
+                self.tag("array");
+                self.ty()?;
+                self.konst()
+            }
+            b'S' => {
+                self.tag("slice");
+                self.ty()
+            }
+            b'C' | b'M' | b'X' | b'Y' | b'N' | b'I' => self.path(),
+            b'T' => {
+                self.tag("tuple");
+                while self.p.cur() != b'E' {
+                    self.ty()?;
+                }
+                self.tag("end of tuple");
+                Ok(())
+            }
+            b'R' | b'Q' => {
+                self.tag(if tag == b'R' {
+                    "reference"
+                } else {
+                    "mutable reference"
+                });
+                if self.p.cur() == b'L' {
+                    self.lifetime()?;
+                }
+                self.ty()
+            }
+            b'P' => {
+                self.tag("const raw pointer");
+                self.ty()
+            }
+            b'O' => {
+                self.tag("mut raw pointer");
+                self.ty()
+            }
+            b'F' => {
+                self.tag("fn pointer");
+                self.fn_sig()
+            }
+            b'D' => {
+                self.tag("dyn trait object");
+                self.binder()?;
+                while self.p.cur() != b'E' {
+                    self.path()?;
+                    while self.p.cur() == b'p' {
+                        self.tag("associated type binding");
+                        self.uident()?;
+                        self.ty()?;
+                    }
+                }
+                self.tag("end of dyn traits");
+                self.lifetime()
+            }
+            b'B' => self.backref("type", Parser::parse_type),
+            c => Err(format!("unexpected type tag '{}'", c as char)),
+        }
+    }
+
+    fn fn_sig(&mut self) -> Result<(), String> {
+        self.binder()?;
+
+        if self.p.cur() == b'U' {
+            self.tag("unsafe");
+        }
+
+        if self.p.cur() == b'K' {
+            self.tag("abi");
+            if self.p.cur() == b'C' {
+                self.tag("abi \"C\"");
+            } else {
+                self.uident()?;
+            }
+        }
+
+        while self.p.cur() != b'E' {
+            self.ty()?;
+        }
+        self.tag("end of parameters");
+
+        self.ty()
+    }
+
+    fn konst(&mut self) -> Result<(), String> {
+        if self.p.cur() == b'B' {
+            return self.backref("const", Parser::parse_const);
+        }
+
+        self.ty()?;
+
+        if self.p.cur() == b'p' {
+            self.tag("const placeholder");
+            return Ok(());
+        }
+
+        let start = self.p.pos();
+        let value = self.p.parse_number(16)?;
+        self.p.eat(b'_', "<const-data>")?;
+        self.token(start, format!("const value {}", value));
+        Ok(())
+    }
+
+    /// Records the backref at the current position along with the node it
+    /// points to, which `parse` parses.
+    fn backref<T: AstDemangle>(
+        &mut self,
+        kind: &str,
+        parse: fn(&mut Parser<'input>) -> Result<T, String>,
+    ) -> Result<(), String> {
+        let pos = self.p.pos();
+        self.p.try_eat(b'B');
+
+        let mut target = self.p.parse_backref()?;
+        let target_start = target.pos();
+        let node = parse(&mut target)?;
+
+        self.token(pos, format!("{} backref to offset {}", kind, target_start));
+        self.backrefs.push(Backref {
+            pos,
+            target_start,
+            target_end: target.pos(),
+            demangled: node.demangle(),
+        });
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.mangled)?;
+        writeln!(f, "{}", self.symbol.demangle())?;
+
+        writeln!(f, "\ntokens:")?;
+        for token in &self.tokens {
+            writeln!(
+                f,
+                "{:>6}  {:<12}  {}",
+                token.start,
+                &self.mangled[token.start..token.end],
+                token.description
+            )?;
+        }
+
+        writeln!(f, "\nast ({}):", self.symbol.mangle_uncompressed())?;
+        let mut tree = String::new();
+        write_tree(&self.symbol, &mut tree);
+        f.write_str(&tree)?;
+
+        writeln!(f, "\nsubstitutions:")?;
+        if self.backrefs.is_empty() {
+            writeln!(f, "    none")?;
+        }
+        for backref in &self.backrefs {
+            writeln!(
+                f,
+                "{:>6}  {} -> {}..{} {}  {}",
+                backref.pos,
+                &self.mangled[backref.pos..self.backref_end(backref)],
+                backref.target_start,
+                backref.target_end,
+                &self.mangled[backref.target_start..backref.target_end],
+                backref.demangled
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Explanation {
+    fn backref_end(&self, backref: &Backref) -> usize {
+        backref.pos + self.mangled[backref.pos..].find('_').unwrap() + 1
+    }
+}
+
+/// Writes the fully expanded AST of `symbol` as an indented tree.
+fn write_tree(symbol: &Symbol, out: &mut String) {
+    let mut w = TreeWriter { out, depth: 0 };
+    w.path("path", &symbol.path);
+    if let Some(ref instantiating_crate) = symbol.instantiating_crate {
+        w.path("instantiating crate", instantiating_crate);
+    }
+}
+
+struct TreeWriter<'a> {
+    out: &'a mut String,
+    depth: usize,
+}
+
+impl<'a> TreeWriter<'a> {
+    fn line(&mut self, role: &str, label: &str) {
+        for _ in 0..self.depth + 1 {
+            self.out.push_str("  ");
+        }
+        if !role.is_empty() {
+            write!(self.out, "{}: ", role).unwrap();
+        }
+        writeln!(self.out, "{}", label).unwrap();
+    }
+
+    fn nested<F: FnOnce(&mut TreeWriter<'a>)>(&mut self, role: &str, label: &str, children: F) {
+        self.line(role, label);
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn impl_path(&mut self, impl_path: &ImplPath) {
+        let role = match impl_path.dis {
+            Some(dis) => format!("impl [{}]", dis),
+            None => "impl".to_string(),
+        };
+        self.path(&role, &impl_path.path);
+    }
+
+    fn path(&mut self, role: &str, path: &Path) {
+        match *path {
+            Path::CrateRoot { ref id } => self.line(role, &format!("CrateRoot {}", id.demangle())),
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => self.nested(role, "InherentImpl", |w| {
+                w.impl_path(impl_path);
+                w.ty("self", self_type);
+            }),
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => self.nested(role, "TraitImpl", |w| {
+                w.impl_path(impl_path);
+                w.ty("self", self_type);
+                w.path("trait", trait_name);
+            }),
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => self.nested(role, "TraitDef", |w| {
+                w.ty("self", self_type);
+                w.path("trait", trait_name);
+            }),
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => {
+                let label = format!("Nested ({}) {}", ns.0 as char, ident.demangle());
+                self.nested(role, &label, |w| w.path("inner", inner));
+            }
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => self.nested(role, "Generic", |w| {
+                w.path("inner", inner);
+                for (i, arg) in args.iter().enumerate() {
+                    let role = format!("arg {}", i);
+                    match *arg {
+                        GenericArg::Lifetime(ref lt) => w.lifetime(&role, lt),
+                        GenericArg::Type(ref ty) => w.ty(&role, ty),
+                        GenericArg::Const(ref k) => w.konst(&role, k),
+                    }
+                }
+            }),
+        }
+    }
+
+    fn ty(&mut self, role: &str, ty: &Type) {
+        match *ty {
+            Type::BasicType(bt) => self.line(role, &bt.demangle()),
+            Type::Array(ref inner, ref len) => self.nested(role, "Array", |w| {
+                w.ty("element", inner);
+                w.konst("len", len);
+            }),
+            Type::Slice(ref inner) => self.nested(role, "Slice", |w| w.ty("", inner)),
+            Type::Named(ref path) => self.path(role, path),
+            Type::Tuple(ref components) => self.nested(role, "Tuple", |w| {
+                for (i, component) in components.iter().enumerate() {
+                    w.ty(&i.to_string(), component);
+                }
+            }),
+            Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+                let label = if let Type::Ref(..) = *ty { "&" } else { "&mut" };
+                self.nested(role, label, |w| {
+                    if let Some(ref lifetime) = *lifetime {
+                        w.lifetime("lifetime", lifetime);
+                    }
+                    w.ty("", inner);
+                });
+            }
+            Type::RawPtrConst(ref inner) => self.nested(role, "*const", |w| w.ty("", inner)),
+            Type::RawPtrMut(ref inner) => self.nested(role, "*mut", |w| w.ty("", inner)),
+            Type::Fn(ref fn_sig) => {
+                let mut label = String::new();
+                if fn_sig.is_unsafe {
+                    label.push_str("unsafe ");
+                }
+                if let Some(ref abi) = fn_sig.abi {
+                    write!(label, "extern {} ", abi.demangle()).unwrap();
+                }
+                label.push_str("fn");
+                self.nested(role, &label, |w| {
+                    for (i, param_type) in fn_sig.param_types.iter().enumerate() {
+                        w.ty(&format!("param {}", i), param_type);
+                    }
+                    w.ty("return", &fn_sig.return_type);
+                });
+            }
+            Type::DynTrait(ref bounds, ref lifetime) => self.nested(role, "dyn", |w| {
+                for tr in bounds.traits.iter() {
+                    w.path("trait", &tr.path);
+                    for binding in tr.assoc_type_bindings.iter() {
+                        w.ty(&binding.ident.demangle(), &binding.ty);
+                    }
+                }
+                w.lifetime("lifetime", lifetime);
+            }),
+        }
+    }
+
+    fn konst(&mut self, role: &str, k: &Const) {
+        let (label, ty) = match *k {
+            Const::Value(ref ty, value) => (format!("Const {}", value), ty),
+            Const::Placeholder(ref ty) => ("Const _".to_string(), ty),
+        };
+        self.nested(role, &label, |w| w.ty("type", ty));
+    }
+
+    fn lifetime(&mut self, role: &str, lifetime: &Lifetime) {
+        self.line(role, &format!("Lifetime {}", lifetime.debruijn_index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, Backref};
+
+    #[test]
+    fn explain_backrefs() {
+        let explanation = explain("_RIC3fooSThhERB6_E").unwrap();
+
+        let tokens: Vec<_> = explanation
+            .tokens
+            .iter()
+            .map(|token| &explanation.mangled[token.start..token.end])
+            .collect();
+        assert_eq!(
+            vec!["_R", "I", "C", "3foo", "S", "T", "h", "h", "E", "R", "B6_", "E"],
+            tokens
+        );
+
+        assert_eq!(
+            vec![Backref {
+                pos: 14,
+                target_start: 9,
+                target_end: 13,
+                demangled: "(u8,u8)".to_string(),
+            }],
+            explanation.backrefs
+        );
+
+        let text = explanation.to_string();
+        assert!(text.contains("    14  B6_           type backref to offset 9\n"));
+        assert!(text.contains("ast (_RIC3fooSThhERThhEE):\n"));
+        assert!(text.contains("    14  B6_ -> 9..13 ThhE  (u8,u8)\n"));
+    }
+}
@@ -0,0 +1,370 @@
+//! A C interface, for profilers and other tools written in C or C++. The
+//! header `include/std_mangle.h` is generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/std_mangle.h`, and the
+//! shared and static libraries to link against are built from the `ffi`
+//! package with `cargo build --release -p std-mangle-ffi`. They are not
+//! crate types of this crate because they cannot be built without `std`,
+//! which would break `no_std` dependents.
+//!
+//! All strings are NUL-terminated UTF-8. Strings returned by the `smr_*`
+//! functions are owned by the caller and must be freed with `smr_free()`;
+//! `rustc_demangle()` writes into a buffer of the caller instead, and the
+//! protobuf messages of `smr_analyze_protobuf()` are freed with
+//! `smr_free_bytes()`.
+
+use ast::Symbol;
+use compat;
+use info::analyze;
+use legacy;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::c_char;
+use std::panic;
+use std::ptr;
+
+/// Options for `smr_demangle_opts()`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SmrOptions {
+    /// Leave out disambiguators other than the indices of closures, and the
+    /// instantiating crate, of v0 symbols.
+    pub strip_disambiguators: bool,
+    /// Replace the generic arguments of v0 symbols by `_`, which implies
+    /// `strip_disambiguators`.
+    pub strip_generics: bool,
+    /// Demangle legacy symbols as well as v0 symbols.
+    pub legacy: bool,
+}
+
+/// Demangles the v0 or legacy Rust symbol `mangled`. Returns NULL if
+/// `mangled` is NULL or not a valid symbol.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn smr_demangle(mangled: *const c_char) -> *mut c_char {
+    let options = SmrOptions {
+        legacy: true,
+        ..SmrOptions::default()
+    };
+    smr_demangle_opts(mangled, &options)
+}
+
+/// Demangles the Rust symbol `mangled` as given by `options`, or with all
+/// options off if `options` is NULL. Returns NULL if `mangled` is NULL or
+/// not a valid symbol.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string, and
+/// `options` must be NULL or point to an `SmrOptions`.
+#[no_mangle]
+pub unsafe extern "C" fn smr_demangle_opts(
+    mangled: *const c_char,
+    options: *const SmrOptions,
+) -> *mut c_char {
+    if mangled.is_null() {
+        return ptr::null_mut();
+    }
+    let mangled = match CStr::from_ptr(mangled).to_str() {
+        Ok(mangled) => mangled,
+        Err(_) => return ptr::null_mut(),
+    };
+    let options = options.as_ref().cloned().unwrap_or_default();
+
+    // Unwinding into C is undefined behavior.
+    let demangled = panic::catch_unwind(|| demangle_with(mangled, &options));
+    match demangled {
+        Ok(Some(demangled)) => CString::new(demangled)
+            .map(CString::into_raw)
+            .unwrap_or(ptr::null_mut()),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Frees a string returned by this library. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library that has not been
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn smr_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Analyzes the v0 or legacy symbol `mangled` into a
+/// `std_mangle.v1.SymbolInfo` protobuf message, whose length is stored in
+/// `*len`. Returns NULL if `mangled` is NULL or not a valid symbol. The
+/// message must be freed with `smr_free_bytes()`.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string, and `len`
+/// must point to a writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn smr_analyze_protobuf(mangled: *const c_char, len: *mut usize) -> *mut u8 {
+    *len = 0;
+    if mangled.is_null() {
+        return ptr::null_mut();
+    }
+    let mangled = match CStr::from_ptr(mangled).to_str() {
+        Ok(mangled) => mangled,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let message = panic::catch_unwind(|| analyze(mangled).map(|info| info.to_protobuf()));
+    match message {
+        Ok(Ok(message)) => {
+            *len = message.len();
+            Box::into_raw(message.into_boxed_slice()) as *mut u8
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Frees a message of `len` bytes returned by `smr_analyze_protobuf()`.
+/// Does nothing for NULL.
+///
+/// # Safety
+///
+/// `bytes` must be NULL or a message returned by this library that has not
+/// been freed yet, and `len` its length.
+#[no_mangle]
+pub unsafe extern "C" fn smr_free_bytes(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)));
+    }
+}
+
+/// The current version of the plugin interface.
+pub const SMR_PLUGIN_VERSION: u32 = 1;
+
+/// The functions of the library as a table, for profilers that load it as a
+/// plugin with `dlopen()` and look up `smr_plugin_v1` with `dlsym()`. Later
+/// versions only add fields at the end, so a plugin with a larger `size` or
+/// `version` can be used through this definition.
+///
+/// All functions accept v0 and legacy symbols and return NULL for anything
+/// else. Their results must be freed with this table's `free` function.
+#[repr(C)]
+pub struct SmrPluginV1 {
+    /// The size of this struct in bytes.
+    pub size: usize,
+    /// The version of the interface, `SMR_PLUGIN_VERSION`, which is 1 for
+    /// this definition.
+    pub version: u32,
+    /// The demangled symbol, like `smr_demangle()`.
+    pub demangle: unsafe extern "C" fn(*const c_char) -> *mut c_char,
+    /// The demangled symbol without hashes, disambiguators and the
+    /// instantiating crate.
+    pub demangle_short: unsafe extern "C" fn(*const c_char) -> *mut c_char,
+    /// The demangled symbol with generic arguments replaced by `_`, which
+    /// all instances of a generic function share.
+    pub skeleton: unsafe extern "C" fn(*const c_char) -> *mut c_char,
+    /// Frees a string returned by `demangle`, `demangle_short` or `skeleton`
+    /// of this table, which the caller owns until then, like `smr_free()`.
+    /// Does nothing for NULL. It must not be called on any other memory,
+    /// such as strings from `malloc()` or from another copy of the library.
+    pub free: unsafe extern "C" fn(*mut c_char),
+}
+
+/// The plugin table of this library.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static smr_plugin_v1: SmrPluginV1 = SmrPluginV1 {
+    size: mem::size_of::<SmrPluginV1>(),
+    version: SMR_PLUGIN_VERSION,
+    demangle: smr_demangle,
+    demangle_short: plugin_demangle_short,
+    skeleton: plugin_skeleton,
+    free: smr_free,
+};
+
+unsafe extern "C" fn plugin_demangle_short(mangled: *const c_char) -> *mut c_char {
+    let options = SmrOptions {
+        strip_disambiguators: true,
+        legacy: true,
+        ..SmrOptions::default()
+    };
+    smr_demangle_opts(mangled, &options)
+}
+
+unsafe extern "C" fn plugin_skeleton(mangled: *const c_char) -> *mut c_char {
+    let options = SmrOptions {
+        strip_generics: true,
+        legacy: true,
+        ..SmrOptions::default()
+    };
+    smr_demangle_opts(mangled, &options)
+}
+
+/// Demangles `mangled` into the buffer `out` of `len` bytes, with the
+/// signature of the `rustc_demangle` C shim that native toolchains look
+/// for. The output is that of `compat::demangle()` with `{}`. Returns `out`,
+/// or NULL if `mangled` is not a Rust symbol or its demangled form and the
+/// terminating NUL do not fit into `out`.
+///
+/// # Safety
+///
+/// `mangled` must be NULL or point to a NUL-terminated string, and `out`
+/// must be NULL or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rustc_demangle(
+    mangled: *const c_char,
+    out: *mut c_char,
+    len: usize,
+) -> *mut c_char {
+    if mangled.is_null() || out.is_null() {
+        return ptr::null_mut();
+    }
+    let mangled = match CStr::from_ptr(mangled).to_str() {
+        Ok(mangled) => mangled,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let demangled = panic::catch_unwind(|| {
+        compat::try_demangle(mangled)
+            .ok()
+            .map(|demangled| demangled.to_string())
+    });
+    match demangled {
+        Ok(Some(demangled)) if demangled.len() < len && !demangled.contains('\0') => {
+            ptr::copy_nonoverlapping(demangled.as_ptr(), out as *mut u8, demangled.len());
+            *out.add(demangled.len()) = 0;
+            out
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Demangles `mangled` as given by `options`, also for the WebAssembly
+/// bindings.
+pub(crate) fn demangle_with(mangled: &str, options: &SmrOptions) -> Option<String> {
+    let symbol = match Symbol::parse(mangled) {
+        Ok(symbol) => symbol,
+        Err(_) if options.legacy => return legacy::demangle(mangled).ok(),
+        Err(_) => return None,
+    };
+
+    Some(if options.strip_generics {
+        symbol.erase_disambiguators().skeleton().demangle()
+    } else if options.strip_disambiguators {
+        let mut symbol = symbol.erase_disambiguators();
+        symbol.instantiating_crate = None;
+        symbol.demangle()
+    } else {
+        symbol.demangle()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        rustc_demangle, smr_analyze_protobuf, smr_demangle, smr_demangle_opts, smr_free,
+        smr_free_bytes, smr_plugin_v1, SmrOptions,
+    };
+    use info::analyze;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+    use std::slice;
+
+    fn demangle(mangled: &str, options: Option<&SmrOptions>) -> Option<String> {
+        let mangled = CString::new(mangled).unwrap();
+        unsafe {
+            let demangled = match options {
+                Some(options) => smr_demangle_opts(mangled.as_ptr(), options),
+                None => smr_demangle(mangled.as_ptr()),
+            };
+            if demangled.is_null() {
+                return None;
+            }
+            let result = CStr::from_ptr(demangled).to_str().unwrap().to_string();
+            smr_free(demangled);
+            Some(result)
+        }
+    }
+
+    #[test]
+    fn demangle_through_ffi() {
+        assert_eq!(
+            Some("mycrate[2]::foo<u8>"),
+            demangle("_RINvCs0_7mycrate3foohE", None).as_deref()
+        );
+        assert_eq!(
+            Some("std::foo"),
+            demangle("_ZN3std3foo17h0123456789abcdefE", None).as_deref()
+        );
+
+        let options = SmrOptions {
+            strip_generics: true,
+            ..SmrOptions::default()
+        };
+        assert_eq!(
+            Some("mycrate::foo<_>"),
+            demangle("_RINvCs0_7mycrate3foohE", Some(&options)).as_deref()
+        );
+        assert_eq!(
+            None,
+            demangle("_ZN3std3foo17h0123456789abcdefE", Some(&options))
+        );
+
+        unsafe {
+            assert!(smr_demangle(ptr::null()).is_null());
+            smr_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn analyze_through_ffi() {
+        let mangled = CString::new("_RNxC7mycrate3foo").unwrap();
+        let mut len = 0;
+        unsafe {
+            let message = smr_analyze_protobuf(mangled.as_ptr(), &mut len);
+            assert_eq!(
+                &analyze("_RNxC7mycrate3foo").unwrap().to_protobuf()[..],
+                slice::from_raw_parts(message, len)
+            );
+            smr_free_bytes(message, len);
+
+            let main = CString::new("main").unwrap();
+            assert!(smr_analyze_protobuf(main.as_ptr(), &mut len).is_null());
+            assert_eq!(0, len);
+        }
+    }
+
+    #[test]
+    fn plugin_table() {
+        let mangled = CString::new("_RINvCs0_7mycrate3foohE").unwrap();
+        let call = |f: unsafe extern "C" fn(*const c_char) -> *mut c_char| unsafe {
+            let demangled = f(mangled.as_ptr());
+            let result = CStr::from_ptr(demangled).to_str().unwrap().to_string();
+            (smr_plugin_v1.free)(demangled);
+            result
+        };
+
+        assert_eq!(1, smr_plugin_v1.version);
+        assert_eq!("mycrate[2]::foo<u8>", call(smr_plugin_v1.demangle));
+        assert_eq!("mycrate::foo<u8>", call(smr_plugin_v1.demangle_short));
+        assert_eq!("mycrate::foo<_>", call(smr_plugin_v1.skeleton));
+    }
+
+    #[test]
+    fn demangle_into_buffer() {
+        let mangled = CString::new("_RNvC7mycrate3foo").unwrap();
+        let mut out = [0x7f as c_char; 13];
+        unsafe {
+            let demangled = rustc_demangle(mangled.as_ptr(), out.as_mut_ptr(), out.len());
+            assert_eq!(out.as_mut_ptr(), demangled);
+            assert_eq!("mycrate::foo", CStr::from_ptr(demangled).to_str().unwrap());
+
+            assert!(rustc_demangle(mangled.as_ptr(), out.as_mut_ptr(), 12).is_null());
+            let main = CString::new("main").unwrap();
+            assert!(rustc_demangle(main.as_ptr(), out.as_mut_ptr(), out.len()).is_null());
+        }
+    }
+}
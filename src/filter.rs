@@ -0,0 +1,470 @@
+//! Demangling of symbols embedded in arbitrary text, e.g. the output of a
+//! profiler or a stack trace.
+
+use ast::Symbol;
+use legacy;
+#[cfg(feature = "json")]
+use serde_json::{self, Value};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::{mem, str};
+
+/// Appends `text` to `out`, with every v0 and legacy symbol in it replaced by
+/// its demangled form. Anything that only looks like a symbol is copied
+/// unchanged.
+pub fn replace_symbols(text: &str, out: &mut String) {
+    replace_with(text, out, Symbol::demangle);
+}
+
+/// Like `replace_symbols()`, but replaces v0 symbols by their demangled
+/// skeleton, so that all instances of one generic item read the same.
+/// Legacy symbols do not encode their generic arguments and are demangled
+/// as usual.
+pub fn replace_skeletons(text: &str, out: &mut String) {
+    replace_with(text, out, |symbol| symbol.skeleton().demangle());
+}
+
+fn replace_with(text: &str, out: &mut String, demangle: fn(&Symbol) -> String) {
+    let mut rest = text;
+
+    while let Some(start) = find_candidate(rest) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let len = if rest.starts_with("_R") || rest.starts_with("__R") {
+            v0_symbol(rest, out, demangle)
+        } else {
+            legacy_symbol(rest, out)
+        };
+
+        // Not a symbol after all; skip its first character.
+        let len = len.unwrap_or_else(|| {
+            out.push_str(&rest[..1]);
+            1
+        });
+        rest = &rest[len..];
+    }
+
+    out.push_str(rest);
+}
+
+/// Appends a line of `nm` output to `out`, with the symbol in its last column
+/// demangled. Addresses, symbol types, and whitespace are kept as they are.
+pub fn replace_nm_line(line: &str, out: &mut String) {
+    let end = line.trim_end().len();
+    let start = line[..end]
+        .rfind(|c: char| c.is_ascii_whitespace())
+        .map_or(0, |i| i + 1);
+
+    out.push_str(&line[..start]);
+    replace_symbols(&line[start..end], out);
+    out.push_str(&line[end..]);
+}
+
+/// Appends a line of `objdump -d` output to `out`, with the symbols in its
+/// `<symbol+offset>` labels demangled. Everything outside of the labels,
+/// including the instruction bytes and operands, is kept as it is.
+pub fn replace_objdump_line(line: &str, out: &mut String) {
+    let mut rest = line;
+
+    while let Some(open) = rest.find('<') {
+        let close = match rest[open..].find('>') {
+            Some(close) => open + close,
+            None => break,
+        };
+
+        out.push_str(&rest[..=open]);
+        replace_symbols(&rest[open + 1..close], out);
+        rest = &rest[close..];
+    }
+
+    out.push_str(rest);
+}
+
+/// Appends a line of folded stacks, `frame;frame;frame count`, to `out`, with
+/// every frame demangled and the separators and the count kept as they are.
+///
+/// A `;` within a demangled frame, like that of the array type `[u8; 4]`,
+/// would split it into two frames, so it is replaced by a `,`.
+pub fn replace_folded_line(line: &str, out: &mut String) {
+    let split = line
+        .rfind(' ')
+        .filter(|&i| {
+            let count = line[i + 1..].trim_end();
+            !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit())
+        })
+        .unwrap_or(line.len());
+    let (stack, count) = line.split_at(split);
+
+    for (i, frame) in stack.split(';').enumerate() {
+        if i > 0 {
+            out.push(';');
+        }
+
+        let start = out.len();
+        replace_symbols(frame, out);
+
+        if out[start..].contains(';') {
+            let demangled = out.split_off(start).replace(';', ",");
+            out.push_str(&demangled);
+        }
+    }
+
+    out.push_str(count);
+}
+
+/// Appends a line of a Rust backtrace to `out`, with the symbol of a frame
+/// line like `  12: _RNv...` or `  12:     0x55d0c0ffee - _RNv...` demangled.
+/// Frame numbers, addresses, and `at <file>:<line>:<column>` lines are kept
+/// as they are; any other line, like the panic message, is demangled as
+/// text.
+pub fn replace_backtrace_line(line: &str, out: &mut String) {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("at ") {
+        out.push_str(line);
+        return;
+    }
+
+    let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 || !trimmed[digits..].starts_with(':') {
+        replace_symbols(line, out);
+        return;
+    }
+
+    // The frame number, and the address if there is one.
+    let mut prefix = line.len() - trimmed.len() + digits + 1;
+    let rest = line[prefix..].trim_start();
+    if rest.starts_with("0x") {
+        if let Some(dash) = rest.find(" - ") {
+            prefix = line.len() - rest.len() + dash + " - ".len();
+        }
+    }
+
+    out.push_str(&line[..prefix]);
+    replace_symbols(&line[prefix..], out);
+}
+
+/// Appends a line of JSON to `out`, with the symbols in the string values of
+/// `fields` demangled, or in all string values if `fields` is empty. A field
+/// is a key of the top-level object or a dotted path like `error.stack`, and
+/// all strings within an array or object field are demangled. Lines that are
+/// not JSON, or that contain no symbols, are copied unchanged.
+#[cfg(feature = "json")]
+pub fn replace_json_line(line: &str, fields: &[&str], out: &mut String) {
+    let mut value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(_) => {
+            out.push_str(line);
+            return;
+        }
+    };
+
+    let mut changed = false;
+    if fields.is_empty() {
+        changed = replace_json_strings(&mut value);
+    }
+    'fields: for field in fields {
+        let mut value = &mut value;
+        for key in field.split('.') {
+            value = match value.get_mut(key) {
+                Some(value) => value,
+                None => continue 'fields,
+            };
+        }
+        changed |= replace_json_strings(value);
+    }
+
+    if changed {
+        out.push_str(&serde_json::to_string(&value).unwrap());
+    } else {
+        out.push_str(line);
+    }
+}
+
+/// Demangles the symbols in all strings within `value` and returns whether
+/// there were any.
+#[cfg(feature = "json")]
+fn replace_json_strings(value: &mut Value) -> bool {
+    match *value {
+        Value::String(ref mut text) => {
+            let mut demangled = String::new();
+            replace_symbols(text, &mut demangled);
+            let changed = demangled != *text;
+            *text = demangled;
+            changed
+        }
+        Value::Array(ref mut values) => values.iter_mut().fold(false, |changed, value| {
+            replace_json_strings(value) | changed
+        }),
+        Value::Object(ref mut map) => map.values_mut().fold(false, |changed, value| {
+            replace_json_strings(value) | changed
+        }),
+        _ => false,
+    }
+}
+
+/// The longest run of symbol characters held back by `StreamingFilter` before
+/// it is copied as it is.
+const MAX_PENDING: usize = 1 << 16;
+
+/// Demangles the symbols in text that arrives in chunks of any size, like
+/// `replace_symbols()` but without waiting for whole lines.
+///
+/// A chunk may end in the middle of a symbol or of a UTF-8 sequence, so the
+/// trailing run of characters that could still continue a symbol is held
+/// back until the next chunk shows where it ends. Invalid UTF-8 is copied
+/// unchanged.
+#[derive(Clone, Default, Debug)]
+pub struct StreamingFilter {
+    pending: Vec<u8>,
+}
+
+impl StreamingFilter {
+    pub fn new() -> StreamingFilter {
+        StreamingFilter::default()
+    }
+
+    /// Appends everything up to the end of `input` that can no longer be
+    /// part of a symbol to `out`, with its symbols demangled.
+    pub fn push(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        self.pending.extend_from_slice(input);
+
+        let incomplete = incomplete_utf8_len(&self.pending);
+        let mut end = self.pending.len() - incomplete;
+        if incomplete == 0 {
+            while end > 0 && is_symbol_char(self.pending[end - 1]) {
+                end -= 1;
+            }
+            if self.pending.len() - end > MAX_PENDING {
+                end = self.pending.len();
+            }
+        }
+
+        let rest = self.pending.split_off(end);
+        let text = mem::replace(&mut self.pending, rest);
+        replace_bytes(&text, out);
+    }
+
+    /// Appends the text that is still held back to `out`, at the end of the
+    /// input.
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        let text = mem::take(&mut self.pending);
+        replace_bytes(&text, out);
+    }
+}
+
+/// The number of bytes at the end of `text` that start but do not complete a
+/// UTF-8 sequence.
+fn incomplete_utf8_len(text: &[u8]) -> usize {
+    for len in 1..4.min(text.len() + 1) {
+        let byte = text[text.len() - len];
+        if byte & 0xc0 == 0x80 {
+            continue;
+        }
+
+        let expected = match byte {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return 0,
+        };
+        return if len < expected { len } else { 0 };
+    }
+    0
+}
+
+/// Like `replace_symbols()`, for text that may not be valid UTF-8.
+fn replace_bytes(mut text: &[u8], out: &mut Vec<u8>) {
+    let mut demangled = String::new();
+
+    while !text.is_empty() {
+        let (valid, invalid) = match str::from_utf8(text) {
+            Ok(valid) => (valid, 0),
+            Err(e) => (
+                str::from_utf8(&text[..e.valid_up_to()]).unwrap(),
+                e.error_len().unwrap_or(text.len() - e.valid_up_to()),
+            ),
+        };
+
+        demangled.clear();
+        replace_symbols(valid, &mut demangled);
+        out.extend_from_slice(demangled.as_bytes());
+
+        let invalid = &text[valid.len()..valid.len() + invalid];
+        out.extend_from_slice(invalid);
+        text = &text[valid.len() + invalid.len()..];
+    }
+}
+
+fn is_symbol_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c == b'.'
+}
+
+/// The offset of the next `_R`, `_ZN`, or their macOS variants `__R` and
+/// `__ZN` that does not continue an identifier.
+fn find_candidate(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+
+    (0..bytes.len()).find(|&i| {
+        let rest = &bytes[i..];
+        let starts_symbol = rest.starts_with(b"_R")
+            || rest.starts_with(b"__R")
+            || rest.starts_with(b"_ZN")
+            || rest.starts_with(b"__ZN");
+        starts_symbol && (i == 0 || !is_symbol_char(bytes[i - 1]))
+    })
+}
+
+/// Demangles the v0 symbol at the start of `text` into `out` with `demangle`
+/// and returns its length, or `None` if there is none.
+fn v0_symbol(text: &str, out: &mut String, demangle: fn(&Symbol) -> String) -> Option<usize> {
+    // macOS prefixes every symbol with an extra `_`.
+    let prefix = if text.starts_with("__R") { 1 } else { 0 };
+    let text = &text[prefix..];
+
+    // v0 symbols only consist of alphanumerics and `_`; a `.` starts a
+    // suffix like `.llvm.1234`, which is kept as it is.
+    let len = text
+        .bytes()
+        .position(|c| !(c.is_ascii_alphanumeric() || c == b'_'))
+        .unwrap_or(text.len());
+
+    let symbol = Symbol::parse(&text[..len]).ok()?;
+    out.push_str(&demangle(&symbol));
+    Some(prefix + len)
+}
+
+fn legacy_symbol(text: &str, out: &mut String) -> Option<usize> {
+    let (demangled, len) = legacy::demangle_prefix(text).ok()?;
+    out.push_str(&demangled);
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        replace_backtrace_line, replace_folded_line, replace_nm_line, replace_objdump_line,
+        replace_skeletons, replace_symbols, StreamingFilter,
+    };
+
+    #[test]
+    fn replace_embedded_symbols() {
+        let text = "at _RNxC7mycrate4quux+0x10 (_ZN4core3fmt5write17h0123456789abcdefE.llvm.42)\n\
+                    _RXX and x_RNxC7mycrate4quux are left alone, _ZN3foo too";
+        let mut out = String::new();
+        replace_symbols(text, &mut out);
+
+        assert_eq!(
+            "at mycrate::quux+0x10 (core::fmt::write.llvm.42)\n\
+             _RXX and x_RNxC7mycrate4quux are left alone, _ZN3foo too",
+            out
+        );
+
+        out.clear();
+        replace_skeletons("<_RINxC7mycrate3bazAhj4_E>", &mut out);
+        assert_eq!("<mycrate::baz<_>>", out);
+    }
+
+    #[test]
+    fn replace_symbol_columns() {
+        let mut out = String::new();
+        for line in &[
+            "0000000000012340 T _RNxC7mycrate4quux",
+            "                 U __RNxC7mycrate4quux  ",
+        ] {
+            replace_nm_line(line, &mut out);
+            out.push('\n');
+        }
+        assert_eq!(
+            "0000000000012340 T mycrate::quux\n                 U mycrate::quux  \n",
+            out
+        );
+
+        out.clear();
+        replace_objdump_line(
+            "  12345:\te8 00 00 00 00 \tcall   12350 <_RNxC7mycrate4quux+0x10>  # _RNxC7mycrate3baz",
+            &mut out,
+        );
+        assert_eq!(
+            "  12345:\te8 00 00 00 00 \tcall   12350 <mycrate::quux+0x10>  # _RNxC7mycrate3baz",
+            out
+        );
+    }
+
+    #[test]
+    fn replace_folded_stacks() {
+        let mut out = String::new();
+        replace_folded_line(
+            "my prog;_RNxC7mycrate4quux;_RINxC7mycrate3bazAhj4_E;[unknown] 1234",
+            &mut out,
+        );
+        assert_eq!(
+            "my prog;mycrate::quux;mycrate::baz<[u8, 4]>;[unknown] 1234",
+            out
+        );
+    }
+
+    #[test]
+    fn stream_symbols_in_chunks() {
+        // `é`, which must not be split, and an invalid byte.
+        let bytes = b"at _RNxC7mycrate4quux, \xc3\xa9\xff _RNxC7mycrate3baz";
+
+        for chunk_len in 1..bytes.len() {
+            let mut filter = StreamingFilter::new();
+            let mut out = Vec::new();
+            for chunk in bytes.chunks(chunk_len) {
+                filter.push(chunk, &mut out);
+            }
+            filter.finish(&mut out);
+            assert_eq!(
+                &b"at mycrate::quux, \xc3\xa9\xff mycrate::baz"[..],
+                &out[..],
+                "chunk length {}",
+                chunk_len
+            );
+        }
+    }
+
+    #[test]
+    fn replace_backtrace_frames() {
+        let backtrace = [
+            "thread 'main' panicked at src/main.rs:2:5:",
+            "stack backtrace:",
+            "   0: _RNxC7mycrate4quux",
+            "             at ./src/_RNxC7mycrate4quux.rs:10:5",
+            "  11:     0x55d0c0ffee - _ZN4core3fmt5write17h0123456789abcdefE",
+            "  12:     0x55d0c0ffef - <unknown>",
+        ];
+        let expected = [
+            "thread 'main' panicked at src/main.rs:2:5:",
+            "stack backtrace:",
+            "   0: mycrate::quux",
+            "             at ./src/_RNxC7mycrate4quux.rs:10:5",
+            "  11:     0x55d0c0ffee - core::fmt::write",
+            "  12:     0x55d0c0ffef - <unknown>",
+        ];
+        for (line, expected) in backtrace.iter().zip(&expected) {
+            let mut out = String::new();
+            replace_backtrace_line(line, &mut out);
+            assert_eq!(*expected, out);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn replace_json_fields() {
+        let line = r#"{"msg":"in _RNxC7mycrate4quux","error":{"stack":["_RNxC7mycrate3baz"]},"id":"_RNxC7mycrate4quux"}"#;
+        let mut out = String::new();
+        super::replace_json_line(line, &["msg", "error.stack", "missing.field"], &mut out);
+        assert_eq!(
+            r#"{"msg":"in mycrate::quux","error":{"stack":["mycrate::baz"]},"id":"_RNxC7mycrate4quux"}"#,
+            out
+        );
+
+        for line in &["not json _RNxC7mycrate4quux", r#"{ "msg": "nothing" }"#] {
+            out.clear();
+            super::replace_json_line(line, &[], &mut out);
+            assert_eq!(*line, out);
+        }
+    }
+}
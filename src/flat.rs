@@ -0,0 +1,525 @@
+//! A compact AST whose nodes live in a single `Vec`.
+//!
+//! Children are referred to by `NodeId`, a `u32` index into that `Vec`, and
+//! lists of children are ranges of a second `Vec` of ids. Structurally
+//! identical subtrees are stored once, so a `FlatSymbol` is a DAG. The
+//! representation has no pointers and no per-node allocations, which makes
+//! it cheap to store, copy, and serialize in bulk.
+
+use ast::{
+    self, Abi, BasicType, Binder, DecimalNumber, Disambiguator, Ident, Lifetime, Namespace, Ptr,
+    UIdent,
+};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct NodeId(pub u32);
+
+/// A range of `FlatSymbol::lists`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ListId {
+    pub start: u32,
+    pub len: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Node {
+    // Paths
+    CrateRoot {
+        id: Ident,
+    },
+    InherentImpl {
+        impl_dis: Option<Disambiguator>,
+        impl_path: NodeId,
+        self_type: NodeId,
+    },
+    TraitImpl {
+        impl_dis: Option<Disambiguator>,
+        impl_path: NodeId,
+        self_type: NodeId,
+        trait_name: NodeId,
+    },
+    TraitDef {
+        self_type: NodeId,
+        trait_name: NodeId,
+    },
+    Nested {
+        ns: Namespace,
+        inner: NodeId,
+        ident: Ident,
+    },
+    /// The arguments are types, consts, and `Lifetime` nodes.
+    Generic {
+        inner: NodeId,
+        args: ListId,
+    },
+
+    /// A lifetime generic argument.
+    Lifetime(Lifetime),
+
+    // Types
+    BasicType(BasicType),
+    Array(NodeId, NodeId),
+    Slice(NodeId),
+    Named(NodeId),
+    Tuple(ListId),
+    Ref(Option<Lifetime>, NodeId),
+    RefMut(Option<Lifetime>, NodeId),
+    RawPtrConst(NodeId),
+    RawPtrMut(NodeId),
+    Fn {
+        binder: Binder,
+        is_unsafe: bool,
+        abi: Option<Abi>,
+        param_types: ListId,
+        return_type: NodeId,
+    },
+    /// The traits are `DynTraitBound` nodes.
+    DynTrait {
+        binder: Binder,
+        traits: ListId,
+        lifetime: Lifetime,
+    },
+
+    /// One trait of a `dyn` type. The bindings are `AssocBinding` nodes.
+    DynTraitBound {
+        path: NodeId,
+        assoc_type_bindings: ListId,
+    },
+    AssocBinding {
+        ident: UIdent,
+        ty: NodeId,
+    },
+
+    // Consts
+    Value(NodeId, u64),
+    Placeholder(NodeId),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FlatSymbol {
+    pub version: Option<DecimalNumber>,
+    pub path: NodeId,
+    pub instantiating_crate: Option<NodeId>,
+    pub nodes: Vec<Node>,
+    pub lists: Vec<NodeId>,
+}
+
+impl FlatSymbol {
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn list(&self, list: ListId) -> &[NodeId] {
+        &self.lists[list.start as usize..(list.start + list.len) as usize]
+    }
+}
+
+impl<'a> From<&'a ast::Symbol> for FlatSymbol {
+    fn from(symbol: &'a ast::Symbol) -> FlatSymbol {
+        let mut flatten = Flatten::default();
+        let path = flatten.path(&symbol.path);
+        let instantiating_crate = symbol
+            .instantiating_crate
+            .as_ref()
+            .map(|krate| flatten.path(krate));
+
+        FlatSymbol {
+            version: symbol.version,
+            path,
+            instantiating_crate,
+            nodes: flatten.nodes,
+            lists: flatten.lists,
+        }
+    }
+}
+
+impl<'a> From<&'a FlatSymbol> for ast::Symbol {
+    fn from(symbol: &'a FlatSymbol) -> ast::Symbol {
+        let mut unflatten = Unflatten {
+            symbol,
+            paths: HashMap::new(),
+            types: HashMap::new(),
+        };
+
+        ast::Symbol {
+            version: symbol.version,
+            path: unflatten.path(symbol.path),
+            instantiating_crate: symbol
+                .instantiating_crate
+                .map(|krate| unflatten.path(krate)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Flatten {
+    nodes: Vec<Node>,
+    lists: Vec<NodeId>,
+    ids: HashMap<Node, NodeId>,
+    list_ids: HashMap<Vec<NodeId>, ListId>,
+}
+
+impl Flatten {
+    fn add(&mut self, node: Node) -> NodeId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    // Lists are shared too, so that nodes with identical lists compare equal.
+    fn list(&mut self, ids: Vec<NodeId>) -> ListId {
+        if let Some(&list) = self.list_ids.get(&ids) {
+            return list;
+        }
+
+        let list = ListId {
+            start: self.lists.len() as u32,
+            len: ids.len() as u32,
+        };
+        self.lists.extend_from_slice(&ids);
+        self.list_ids.insert(ids, list);
+        list
+    }
+
+    fn path(&mut self, path: &ast::Path) -> NodeId {
+        let node = match *path {
+            ast::Path::CrateRoot { ref id } => Node::CrateRoot { id: id.clone() },
+            ast::Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => Node::InherentImpl {
+                impl_dis: impl_path.dis,
+                impl_path: self.path(&impl_path.path),
+                self_type: self.ty(self_type),
+            },
+            ast::Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => Node::TraitImpl {
+                impl_dis: impl_path.dis,
+                impl_path: self.path(&impl_path.path),
+                self_type: self.ty(self_type),
+                trait_name: self.path(trait_name),
+            },
+            ast::Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => Node::TraitDef {
+                self_type: self.ty(self_type),
+                trait_name: self.path(trait_name),
+            },
+            ast::Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => Node::Nested {
+                ns: ns.clone(),
+                inner: self.path(inner),
+                ident: ident.clone(),
+            },
+            ast::Path::Generic {
+                ref inner,
+                ref args,
+            } => {
+                let inner = self.path(inner);
+                let args = args.iter().map(|arg| self.generic_arg(arg)).collect();
+                Node::Generic {
+                    inner,
+                    args: self.list(args),
+                }
+            }
+        };
+
+        self.add(node)
+    }
+
+    fn generic_arg(&mut self, arg: &ast::GenericArg) -> NodeId {
+        match *arg {
+            ast::GenericArg::Lifetime(ref lt) => self.add(Node::Lifetime(lt.clone())),
+            ast::GenericArg::Type(ref ty) => self.ty(ty),
+            ast::GenericArg::Const(ref k) => self.konst(k),
+        }
+    }
+
+    fn ty(&mut self, ty: &ast::Type) -> NodeId {
+        let node = match *ty {
+            ast::Type::BasicType(bt) => Node::BasicType(bt),
+            ast::Type::Array(ref inner, ref len) => Node::Array(self.ty(inner), self.konst(len)),
+            ast::Type::Slice(ref inner) => Node::Slice(self.ty(inner)),
+            ast::Type::Named(ref path) => Node::Named(self.path(path)),
+            ast::Type::Tuple(ref components) => {
+                let components = components.iter().map(|ty| self.ty(ty)).collect();
+                Node::Tuple(self.list(components))
+            }
+            ast::Type::Ref(ref lt, ref inner) => Node::Ref(lt.clone(), self.ty(inner)),
+            ast::Type::RefMut(ref lt, ref inner) => Node::RefMut(lt.clone(), self.ty(inner)),
+            ast::Type::RawPtrConst(ref inner) => Node::RawPtrConst(self.ty(inner)),
+            ast::Type::RawPtrMut(ref inner) => Node::RawPtrMut(self.ty(inner)),
+            ast::Type::Fn(ref fn_sig) => {
+                let param_types = fn_sig.param_types.iter().map(|ty| self.ty(ty)).collect();
+                let param_types = self.list(param_types);
+                Node::Fn {
+                    binder: fn_sig.binder.clone(),
+                    is_unsafe: fn_sig.is_unsafe,
+                    abi: fn_sig.abi.clone(),
+                    param_types,
+                    return_type: self.ty(&fn_sig.return_type),
+                }
+            }
+            ast::Type::DynTrait(ref bounds, ref lt) => {
+                let traits = bounds.traits.iter().map(|tr| self.dyn_trait(tr)).collect();
+                Node::DynTrait {
+                    binder: bounds.binder.clone(),
+                    traits: self.list(traits),
+                    lifetime: lt.clone(),
+                }
+            }
+        };
+
+        self.add(node)
+    }
+
+    fn dyn_trait(&mut self, tr: &ast::DynTrait) -> NodeId {
+        let path = self.path(&tr.path);
+        let bindings = tr
+            .assoc_type_bindings
+            .iter()
+            .map(|binding| {
+                let ty = self.ty(&binding.ty);
+                self.add(Node::AssocBinding {
+                    ident: binding.ident.clone(),
+                    ty,
+                })
+            })
+            .collect();
+
+        let node = Node::DynTraitBound {
+            path,
+            assoc_type_bindings: self.list(bindings),
+        };
+        self.add(node)
+    }
+
+    fn konst(&mut self, k: &ast::Const) -> NodeId {
+        let node = match *k {
+            ast::Const::Value(ref ty, value) => Node::Value(self.ty(ty), value),
+            ast::Const::Placeholder(ref ty) => Node::Placeholder(self.ty(ty)),
+        };
+
+        self.add(node)
+    }
+}
+
+/// Rebuilds the pointer-based AST, sharing a `Ptr` between all references to
+/// the same node.
+struct Unflatten<'f> {
+    symbol: &'f FlatSymbol,
+    paths: HashMap<NodeId, Ptr<ast::Path>>,
+    types: HashMap<NodeId, Ptr<ast::Type>>,
+}
+
+impl<'f> Unflatten<'f> {
+    fn shared_path(&mut self, id: NodeId) -> Ptr<ast::Path> {
+        if let Some(path) = self.paths.get(&id) {
+            return path.clone();
+        }
+
+        let path = Ptr::new(self.path(id));
+        self.paths.insert(id, path.clone());
+        path
+    }
+
+    fn shared_type(&mut self, id: NodeId) -> Ptr<ast::Type> {
+        if let Some(ty) = self.types.get(&id) {
+            return ty.clone();
+        }
+
+        let ty = Ptr::new(self.ty(id));
+        self.types.insert(id, ty.clone());
+        ty
+    }
+
+    fn impl_path(&mut self, dis: Option<Disambiguator>, path: NodeId) -> ast::ImplPath {
+        ast::ImplPath {
+            dis,
+            path: self.shared_path(path),
+        }
+    }
+
+    fn path(&mut self, id: NodeId) -> ast::Path {
+        let symbol = self.symbol;
+
+        match *symbol.node(id) {
+            Node::CrateRoot { ref id } => ast::Path::CrateRoot { id: id.clone() },
+            Node::InherentImpl {
+                impl_dis,
+                impl_path,
+                self_type,
+            } => ast::Path::InherentImpl {
+                impl_path: self.impl_path(impl_dis, impl_path),
+                self_type: self.ty(self_type),
+            },
+            Node::TraitImpl {
+                impl_dis,
+                impl_path,
+                self_type,
+                trait_name,
+            } => ast::Path::TraitImpl {
+                impl_path: self.impl_path(impl_dis, impl_path),
+                self_type: self.ty(self_type),
+                trait_name: self.shared_path(trait_name),
+            },
+            Node::TraitDef {
+                self_type,
+                trait_name,
+            } => ast::Path::TraitDef {
+                self_type: self.ty(self_type),
+                trait_name: self.shared_path(trait_name),
+            },
+            Node::Nested {
+                ref ns,
+                inner,
+                ref ident,
+            } => ast::Path::Nested {
+                ns: ns.clone(),
+                inner: self.shared_path(inner),
+                ident: ident.clone(),
+            },
+            Node::Generic { inner, args } => ast::Path::Generic {
+                inner: self.shared_path(inner),
+                args: symbol
+                    .list(args)
+                    .iter()
+                    .map(|&arg| self.generic_arg(arg))
+                    .collect(),
+            },
+            ref node => panic!("expected a path node, found {:?}", node),
+        }
+    }
+
+    fn generic_arg(&mut self, id: NodeId) -> ast::GenericArg {
+        match *self.symbol.node(id) {
+            Node::Lifetime(ref lt) => ast::GenericArg::Lifetime(lt.clone()),
+            Node::Value(..) | Node::Placeholder(_) => ast::GenericArg::Const(self.konst(id)),
+            _ => ast::GenericArg::Type(self.ty(id)),
+        }
+    }
+
+    fn ty(&mut self, id: NodeId) -> ast::Type {
+        let symbol = self.symbol;
+
+        match *symbol.node(id) {
+            Node::BasicType(bt) => ast::Type::BasicType(bt),
+            Node::Array(inner, len) => {
+                ast::Type::Array(self.shared_type(inner), Ptr::new(self.konst(len)))
+            }
+            Node::Slice(inner) => ast::Type::Slice(self.shared_type(inner)),
+            Node::Named(path) => ast::Type::Named(self.shared_path(path)),
+            Node::Tuple(components) => ast::Type::Tuple(
+                symbol
+                    .list(components)
+                    .iter()
+                    .map(|&ty| self.ty(ty))
+                    .collect(),
+            ),
+            Node::Ref(ref lt, inner) => ast::Type::Ref(lt.clone(), self.shared_type(inner)),
+            Node::RefMut(ref lt, inner) => ast::Type::RefMut(lt.clone(), self.shared_type(inner)),
+            Node::RawPtrConst(inner) => ast::Type::RawPtrConst(self.shared_type(inner)),
+            Node::RawPtrMut(inner) => ast::Type::RawPtrMut(self.shared_type(inner)),
+            Node::Fn {
+                ref binder,
+                is_unsafe,
+                ref abi,
+                param_types,
+                return_type,
+            } => ast::Type::Fn(Ptr::new(ast::FnSig {
+                binder: binder.clone(),
+                is_unsafe,
+                abi: abi.clone(),
+                param_types: symbol
+                    .list(param_types)
+                    .iter()
+                    .map(|&ty| self.ty(ty))
+                    .collect(),
+                return_type: self.ty(return_type),
+            })),
+            Node::DynTrait {
+                ref binder,
+                traits,
+                ref lifetime,
+            } => ast::Type::DynTrait(
+                Ptr::new(ast::DynBounds {
+                    binder: binder.clone(),
+                    traits: symbol
+                        .list(traits)
+                        .iter()
+                        .map(|&tr| self.dyn_trait(tr))
+                        .collect(),
+                }),
+                lifetime.clone(),
+            ),
+            ref node => panic!("expected a type node, found {:?}", node),
+        }
+    }
+
+    fn dyn_trait(&mut self, id: NodeId) -> ast::DynTrait {
+        let symbol = self.symbol;
+
+        match *symbol.node(id) {
+            Node::DynTraitBound {
+                path,
+                assoc_type_bindings,
+            } => ast::DynTrait {
+                path: self.path(path),
+                assoc_type_bindings: symbol
+                    .list(assoc_type_bindings)
+                    .iter()
+                    .map(|&binding| match *symbol.node(binding) {
+                        Node::AssocBinding { ref ident, ty } => ast::DynTraitAssocBinding {
+                            ident: ident.clone(),
+                            ty: self.ty(ty),
+                        },
+                        ref node => panic!("expected an associated type binding, found {:?}", node),
+                    })
+                    .collect(),
+            },
+            ref node => panic!("expected a dyn trait node, found {:?}", node),
+        }
+    }
+
+    fn konst(&mut self, id: NodeId) -> ast::Const {
+        match *self.symbol.node(id) {
+            Node::Value(ty, value) => ast::Const::Value(self.ty(ty), value),
+            Node::Placeholder(ty) => ast::Const::Placeholder(self.ty(ty)),
+            ref node => panic!("expected a const node, found {:?}", node),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlatSymbol;
+    use ast::Symbol;
+
+    #[test]
+    fn round_trip_shares_identical_subtrees() {
+        // mycrate::foo<[std::Bar], [std::Bar], (u8, u8), (u8, u8)>, without
+        // backrefs for the repeated arguments.
+        let mangled = "_RINxC7mycrate3fooSNtC3std3BarSNtC3std3BarThhEThhEE";
+        let symbol = Symbol::parse(mangled).unwrap();
+
+        let flat = FlatSymbol::from(&symbol);
+        // The two crate roots, `mycrate::foo`, `std::Bar`, the named type
+        // `std::Bar`, `[std::Bar]`, `u8`, `(u8, u8)`, and the generic path.
+        assert_eq!(9, flat.nodes.len());
+        assert_eq!(2 + 4, flat.lists.len());
+        assert_eq!(symbol, Symbol::from(&flat));
+    }
+}
@@ -0,0 +1,216 @@
+//! Rewriting of the AST.
+//!
+//! A `Folder` builds a new tree from an existing one. By default every node is
+//! copied unchanged; implementors override the `fold_*` methods for the nodes
+//! they want to replace and call the corresponding `walk_*` function to keep
+//! the default behavior for the children.
+
+use ast::*;
+
+pub trait Folder {
+    fn fold_symbol(&mut self, symbol: &Symbol) -> Symbol {
+        walk_symbol(self, symbol)
+    }
+
+    fn fold_path(&mut self, path: &Path) -> Path {
+        walk_path(self, path)
+    }
+
+    fn fold_impl_path(&mut self, impl_path: &ImplPath) -> ImplPath {
+        walk_impl_path(self, impl_path)
+    }
+
+    fn fold_ident(&mut self, ident: &Ident) -> Ident {
+        ident.clone()
+    }
+
+    fn fold_generic_arg(&mut self, arg: &GenericArg) -> GenericArg {
+        walk_generic_arg(self, arg)
+    }
+
+    fn fold_lifetime(&mut self, lifetime: &Lifetime) -> Lifetime {
+        lifetime.clone()
+    }
+
+    fn fold_type(&mut self, ty: &Type) -> Type {
+        walk_type(self, ty)
+    }
+
+    fn fold_fn_sig(&mut self, fn_sig: &FnSig) -> FnSig {
+        walk_fn_sig(self, fn_sig)
+    }
+
+    fn fold_dyn_bounds(&mut self, bounds: &DynBounds) -> DynBounds {
+        walk_dyn_bounds(self, bounds)
+    }
+
+    fn fold_dyn_trait(&mut self, dyn_trait: &DynTrait) -> DynTrait {
+        walk_dyn_trait(self, dyn_trait)
+    }
+
+    fn fold_dyn_trait_assoc_binding(
+        &mut self,
+        binding: &DynTraitAssocBinding,
+    ) -> DynTraitAssocBinding {
+        walk_dyn_trait_assoc_binding(self, binding)
+    }
+
+    fn fold_const(&mut self, k: &Const) -> Const {
+        walk_const(self, k)
+    }
+}
+
+pub fn walk_symbol<F: Folder + ?Sized>(f: &mut F, symbol: &Symbol) -> Symbol {
+    Symbol {
+        version: symbol.version,
+        path: f.fold_path(&symbol.path),
+        instantiating_crate: symbol
+            .instantiating_crate
+            .as_ref()
+            .map(|krate| f.fold_path(krate)),
+    }
+}
+
+pub fn walk_path<F: Folder + ?Sized>(f: &mut F, path: &Path) -> Path {
+    match *path {
+        Path::CrateRoot { ref id } => Path::CrateRoot {
+            id: f.fold_ident(id),
+        },
+        Path::InherentImpl {
+            ref impl_path,
+            ref self_type,
+        } => Path::InherentImpl {
+            impl_path: f.fold_impl_path(impl_path),
+            self_type: f.fold_type(self_type),
+        },
+        Path::TraitImpl {
+            ref impl_path,
+            ref self_type,
+            ref trait_name,
+        } => Path::TraitImpl {
+            impl_path: f.fold_impl_path(impl_path),
+            self_type: f.fold_type(self_type),
+            trait_name: Ptr::new(f.fold_path(trait_name)),
+        },
+        Path::TraitDef {
+            ref self_type,
+            ref trait_name,
+        } => Path::TraitDef {
+            self_type: f.fold_type(self_type),
+            trait_name: Ptr::new(f.fold_path(trait_name)),
+        },
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => Path::Nested {
+            ns: ns.clone(),
+            inner: Ptr::new(f.fold_path(inner)),
+            ident: f.fold_ident(ident),
+        },
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => Path::Generic {
+            inner: Ptr::new(f.fold_path(inner)),
+            args: args.iter().map(|arg| f.fold_generic_arg(arg)).collect(),
+        },
+    }
+}
+
+pub fn walk_impl_path<F: Folder + ?Sized>(f: &mut F, impl_path: &ImplPath) -> ImplPath {
+    ImplPath {
+        dis: impl_path.dis,
+        path: Ptr::new(f.fold_path(&impl_path.path)),
+    }
+}
+
+pub fn walk_generic_arg<F: Folder + ?Sized>(f: &mut F, arg: &GenericArg) -> GenericArg {
+    match *arg {
+        GenericArg::Lifetime(ref lt) => GenericArg::Lifetime(f.fold_lifetime(lt)),
+        GenericArg::Type(ref ty) => GenericArg::Type(f.fold_type(ty)),
+        GenericArg::Const(ref k) => GenericArg::Const(f.fold_const(k)),
+    }
+}
+
+pub fn walk_type<F: Folder + ?Sized>(f: &mut F, ty: &Type) -> Type {
+    match *ty {
+        Type::BasicType(bt) => Type::BasicType(bt),
+        Type::Array(ref inner, ref len) => {
+            Type::Array(Ptr::new(f.fold_type(inner)), Ptr::new(f.fold_const(len)))
+        }
+        Type::Slice(ref inner) => Type::Slice(Ptr::new(f.fold_type(inner))),
+        Type::Named(ref path) => Type::Named(Ptr::new(f.fold_path(path))),
+        Type::Tuple(ref components) => {
+            Type::Tuple(components.iter().map(|ty| f.fold_type(ty)).collect())
+        }
+        Type::Ref(ref lifetime, ref inner) => Type::Ref(
+            lifetime.as_ref().map(|lt| f.fold_lifetime(lt)),
+            Ptr::new(f.fold_type(inner)),
+        ),
+        Type::RefMut(ref lifetime, ref inner) => Type::RefMut(
+            lifetime.as_ref().map(|lt| f.fold_lifetime(lt)),
+            Ptr::new(f.fold_type(inner)),
+        ),
+        Type::RawPtrConst(ref inner) => Type::RawPtrConst(Ptr::new(f.fold_type(inner))),
+        Type::RawPtrMut(ref inner) => Type::RawPtrMut(Ptr::new(f.fold_type(inner))),
+        Type::Fn(ref fn_sig) => Type::Fn(Ptr::new(f.fold_fn_sig(fn_sig))),
+        Type::DynTrait(ref bounds, ref lifetime) => Type::DynTrait(
+            Ptr::new(f.fold_dyn_bounds(bounds)),
+            f.fold_lifetime(lifetime),
+        ),
+    }
+}
+
+pub fn walk_fn_sig<F: Folder + ?Sized>(f: &mut F, fn_sig: &FnSig) -> FnSig {
+    FnSig {
+        binder: fn_sig.binder.clone(),
+        is_unsafe: fn_sig.is_unsafe,
+        abi: fn_sig.abi.clone(),
+        param_types: fn_sig
+            .param_types
+            .iter()
+            .map(|ty| f.fold_type(ty))
+            .collect(),
+        return_type: f.fold_type(&fn_sig.return_type),
+    }
+}
+
+pub fn walk_dyn_bounds<F: Folder + ?Sized>(f: &mut F, bounds: &DynBounds) -> DynBounds {
+    DynBounds {
+        binder: bounds.binder.clone(),
+        traits: bounds
+            .traits
+            .iter()
+            .map(|tr| f.fold_dyn_trait(tr))
+            .collect(),
+    }
+}
+
+pub fn walk_dyn_trait<F: Folder + ?Sized>(f: &mut F, dyn_trait: &DynTrait) -> DynTrait {
+    DynTrait {
+        path: f.fold_path(&dyn_trait.path),
+        assoc_type_bindings: dyn_trait
+            .assoc_type_bindings
+            .iter()
+            .map(|binding| f.fold_dyn_trait_assoc_binding(binding))
+            .collect(),
+    }
+}
+
+pub fn walk_dyn_trait_assoc_binding<F: Folder + ?Sized>(
+    f: &mut F,
+    binding: &DynTraitAssocBinding,
+) -> DynTraitAssocBinding {
+    DynTraitAssocBinding {
+        ident: binding.ident.clone(),
+        ty: f.fold_type(&binding.ty),
+    }
+}
+
+pub fn walk_const<F: Folder + ?Sized>(f: &mut F, k: &Const) -> Const {
+    match *k {
+        Const::Value(ref ty, value) => Const::Value(f.fold_type(ty), value),
+        Const::Placeholder(ref ty) => Const::Placeholder(f.fold_type(ty)),
+    }
+}
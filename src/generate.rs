@@ -0,0 +1,561 @@
+//! Random generation of valid symbols from the grammar, e.g. for fuzzing
+//! or property testing tools that consume Rust symbols:
+//!
+//! ```
+//! use std_mangle_rs::generate::{GeneratorConfig, SymbolGenerator};
+//!
+//! let config = GeneratorConfig {
+//!     max_depth: 3,
+//!     unicode: false,
+//!     ..GeneratorConfig::default()
+//! };
+//! for mangled in SymbolGenerator::new(config).with_seed(42).mangled().take(100) {
+//!     assert!(mangled.starts_with("_R"));
+//! }
+//! ```
+//!
+//! By default, the symbols exercise every production of the grammar: all
+//! path and type forms, disambiguators, punycode identifiers, binders, ABIs,
+//! and subtrees that occur more than once and so are mangled as backrefs.
+//! Names are realistic enough to read but the combinations are not, e.g.
+//! closures inside trait definitions of function pointer types.
+
+use ast::*;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+const WORDS: [&str; 24] = [
+    "std",
+    "core",
+    "alloc",
+    "mycrate",
+    "foo",
+    "bar",
+    "baz",
+    "quux",
+    "Vec",
+    "String",
+    "HashMap",
+    "Option",
+    "Iterator",
+    "Fn",
+    "Drop",
+    "new",
+    "map",
+    "iter",
+    "next",
+    "drop",
+    "call_once",
+    "from_str",
+    "with_capacity",
+    "_private",
+];
+
+const UNICODE_WORDS: [&str; 6] = ["größe", "café", "λ", "日本", "ÿö", "über_alles"];
+
+const ABIS: [&str; 4] = ["system", "stdcall", "efiapi", "sysv64"];
+
+const BASIC_TYPES: [BasicType; 20] = [
+    BasicType::Bool,
+    BasicType::Char,
+    BasicType::Str,
+    BasicType::Unit,
+    BasicType::I8,
+    BasicType::I16,
+    BasicType::I32,
+    BasicType::I64,
+    BasicType::I128,
+    BasicType::Isize,
+    BasicType::U8,
+    BasicType::U16,
+    BasicType::U32,
+    BasicType::U64,
+    BasicType::U128,
+    BasicType::Usize,
+    BasicType::F32,
+    BasicType::F64,
+    BasicType::Never,
+    BasicType::Placeholder,
+];
+
+/// The shape of the symbols that a `SymbolGenerator` produces. Turning a
+/// production off never makes the generator produce symbols it would not
+/// produce otherwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    /// The maximum nesting depth of paths, types, and consts.
+    pub max_depth: usize,
+    /// The maximum number of generic arguments of a path, of components of a
+    /// tuple, and of parameters of a function type. At least 1.
+    pub max_width: u64,
+    /// Whether to repeat paths and types within a symbol, which the mangled
+    /// form encodes as backrefs.
+    pub backrefs: bool,
+    /// Whether to give crates, identifiers, and impls disambiguators.
+    pub disambiguators: bool,
+    /// Whether to use non-ASCII identifiers, which are punycode-encoded.
+    pub unicode: bool,
+    /// Whether to generate inherent impls, trait impls, and trait
+    /// definitions.
+    pub impls: bool,
+    /// Whether to generate function pointer and `dyn Trait` types.
+    pub fn_types: bool,
+    /// Whether to generate const generic arguments and array types.
+    pub consts: bool,
+    /// Whether to generate lifetimes and binders.
+    pub lifetimes: bool,
+    /// Whether to give some symbols an instantiating crate.
+    pub instantiating_crate: bool,
+}
+
+impl Default for GeneratorConfig {
+    /// Symbols nested at most 6 levels deep that use every production.
+    fn default() -> GeneratorConfig {
+        GeneratorConfig {
+            max_depth: 6,
+            max_width: 3,
+            backrefs: true,
+            disambiguators: true,
+            unicode: true,
+            impls: true,
+            fn_types: true,
+            consts: true,
+            lifetimes: true,
+            instantiating_crate: true,
+        }
+    }
+}
+
+/// An endless, reproducible stream of random symbols. The same seed always
+/// produces the same symbols.
+#[derive(Clone, Debug)]
+pub struct SymbolGenerator {
+    state: u64,
+    config: GeneratorConfig,
+    /// The paths and types generated for the current symbol, which later
+    /// parts of it may repeat.
+    paths: Vec<Ptr<Path>>,
+    types: Vec<Type>,
+}
+
+impl SymbolGenerator {
+    /// Creates a generator for symbols of the shape given by `config`, with
+    /// the seed 0.
+    pub fn new(config: GeneratorConfig) -> SymbolGenerator {
+        SymbolGenerator {
+            state: 0,
+            config,
+            paths: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> SymbolGenerator {
+        self.state = seed;
+        self
+    }
+
+    pub fn symbol(&mut self) -> Symbol {
+        self.paths.clear();
+        self.types.clear();
+
+        let depth = self.config.max_depth;
+        let path = self.path(depth);
+        let instantiating_crate = if self.config.instantiating_crate && self.chance(30) {
+            Some(self.crate_root())
+        } else {
+            None
+        };
+
+        Symbol {
+            version: None,
+            path,
+            instantiating_crate,
+        }
+    }
+
+    fn path(&mut self, depth: usize) -> Path {
+        if depth == 0 {
+            return self.crate_root();
+        }
+
+        match self.below(if self.config.impls { 10 } else { 7 }) {
+            0 => self.crate_root(),
+            1..=4 => Path::Nested {
+                ns: Namespace(*self.pick(b"vtCS")),
+                inner: self.shared_path(depth - 1),
+                ident: self.ident(),
+            },
+            5 | 6 => Path::Generic {
+                inner: self.shared_path(depth - 1),
+                args: self.generic_args(depth - 1),
+            },
+            7 => Path::InherentImpl {
+                impl_path: self.impl_path(depth - 1),
+                self_type: self.ty(depth - 1),
+            },
+            8 => Path::TraitImpl {
+                impl_path: self.impl_path(depth - 1),
+                self_type: self.ty(depth - 1),
+                trait_name: Ptr::new(self.trait_path(depth - 1)),
+            },
+            _ => Path::TraitDef {
+                self_type: self.ty(depth - 1),
+                trait_name: Ptr::new(self.trait_path(depth - 1)),
+            },
+        }
+    }
+
+    /// A path generated before for this symbol, or a new one.
+    fn shared_path(&mut self, depth: usize) -> Ptr<Path> {
+        if self.config.backrefs && !self.paths.is_empty() && self.chance(15) {
+            let index = self.below(self.paths.len() as u64) as usize;
+            return self.paths[index].clone();
+        }
+
+        let path = Ptr::new(self.path(depth));
+        self.paths.push(path.clone());
+        path
+    }
+
+    fn crate_root(&mut self) -> Path {
+        let dis = if self.config.disambiguators && self.chance(70) {
+            Disambiguator(1 + self.below(u64::MAX - 1))
+        } else {
+            Disambiguator(0)
+        };
+
+        Path::CrateRoot {
+            id: Ident {
+                dis,
+                u_ident: self.uident(),
+            },
+        }
+    }
+
+    fn impl_path(&mut self, depth: usize) -> ImplPath {
+        ImplPath {
+            dis: if self.config.disambiguators && self.chance(50) {
+                Some(Disambiguator(1 + self.below(1000)))
+            } else {
+                None
+            },
+            path: self.shared_path(depth),
+        }
+    }
+
+    fn trait_path(&mut self, depth: usize) -> Path {
+        let path = Path::Nested {
+            ns: Namespace(b't'),
+            inner: Ptr::new(if depth == 0 {
+                self.crate_root()
+            } else {
+                self.path(depth - 1)
+            }),
+            ident: self.ident(),
+        };
+
+        if depth > 0 && self.chance(30) {
+            Path::Generic {
+                inner: Ptr::new(path),
+                args: self.generic_args(depth - 1),
+            }
+        } else {
+            path
+        }
+    }
+
+    fn generic_args(&mut self, depth: usize) -> GenericArgs {
+        let len = 1 + self.below(self.config.max_width.max(1));
+        (0..len)
+            .map(|_| match self.below(10) {
+                0 if self.config.lifetimes => GenericArg::Lifetime(self.lifetime()),
+                1 if self.config.consts => GenericArg::Const(self.constant(depth)),
+                _ => GenericArg::Type(self.ty(depth)),
+            })
+            .collect()
+    }
+
+    fn ty(&mut self, depth: usize) -> Type {
+        if self.config.backrefs && !self.types.is_empty() && self.chance(15) {
+            let index = self.below(self.types.len() as u64) as usize;
+            return self.types[index].clone();
+        }
+
+        let ty = self.new_type(depth);
+        self.types.push(ty.clone());
+        ty
+    }
+
+    fn new_type(&mut self, depth: usize) -> Type {
+        if depth == 0 || self.chance(30) {
+            return Type::BasicType(*self.pick(&BASIC_TYPES));
+        }
+
+        let depth = depth - 1;
+        match self.below(11) {
+            0 if self.config.consts => {
+                Type::Array(Ptr::new(self.ty(depth)), Ptr::new(self.constant(depth)))
+            }
+            1 => Type::Slice(Ptr::new(self.ty(depth))),
+            4 => {
+                let len = self.below(self.config.max_width + 1);
+                Type::Tuple((0..len).map(|_| self.ty(depth)).collect())
+            }
+            5 => Type::Ref(self.opt_lifetime(), Ptr::new(self.ty(depth))),
+            6 => Type::RefMut(self.opt_lifetime(), Ptr::new(self.ty(depth))),
+            7 => Type::RawPtrConst(Ptr::new(self.ty(depth))),
+            8 => Type::RawPtrMut(Ptr::new(self.ty(depth))),
+            9 if self.config.fn_types => Type::Fn(Ptr::new(self.fn_sig(depth))),
+            10 if self.config.fn_types => {
+                Type::DynTrait(Ptr::new(self.dyn_bounds(depth)), self.lifetime())
+            }
+            _ => Type::Named(self.shared_path(depth)),
+        }
+    }
+
+    fn fn_sig(&mut self, depth: usize) -> FnSig {
+        let abi = match self.below(4) {
+            0 => Some(Abi::C),
+            1 => Some(Abi::Named(UIdent((*self.pick(&ABIS)).into()))),
+            _ => None,
+        };
+
+        let len = self.below(self.config.max_width + 1);
+        let mut param_types: TypeList = (0..len).map(|_| self.ty(depth)).collect();
+        if abi.is_some() && self.chance(20) {
+            param_types.push(Type::BasicType(BasicType::Ellipsis));
+        }
+
+        FnSig {
+            binder: self.binder(),
+            is_unsafe: self.chance(30),
+            abi,
+            param_types,
+            return_type: self.ty(depth),
+        }
+    }
+
+    fn dyn_bounds(&mut self, depth: usize) -> DynBounds {
+        let len = 1 + self.below(2);
+        let traits = (0..len)
+            .map(|_| {
+                let bindings = if self.chance(30) {
+                    vec![DynTraitAssocBinding {
+                        ident: UIdent((*self.pick(&["Item", "Output", "Target"])).into()),
+                        ty: self.ty(depth),
+                    }]
+                } else {
+                    Vec::new()
+                };
+
+                DynTrait {
+                    path: self.trait_path(depth),
+                    assoc_type_bindings: bindings,
+                }
+            })
+            .collect();
+
+        DynBounds {
+            binder: self.binder(),
+            traits,
+        }
+    }
+
+    fn constant(&mut self, depth: usize) -> Const {
+        let (ty, max) = match self.below(4) {
+            0 => (BasicType::Bool, 2),
+            1 => (BasicType::U8, 1 << 8),
+            2 => (BasicType::Usize, u64::MAX),
+            _ => (BasicType::I32, 1 << 31),
+        };
+
+        if depth > 0 && self.chance(10) {
+            Const::Placeholder(Type::BasicType(ty))
+        } else {
+            Const::Value(Type::BasicType(ty), self.below(max))
+        }
+    }
+
+    fn binder(&mut self) -> Binder {
+        Binder {
+            count: LifetimeCount(if self.config.lifetimes {
+                self.below(3)
+            } else {
+                0
+            }),
+        }
+    }
+
+    fn lifetime(&mut self) -> Lifetime {
+        Lifetime {
+            debruijn_index: BinderIndex(self.below(3)),
+        }
+    }
+
+    fn opt_lifetime(&mut self) -> Option<Lifetime> {
+        if self.config.lifetimes && self.chance(50) {
+            Some(self.lifetime())
+        } else {
+            None
+        }
+    }
+
+    fn ident(&mut self) -> Ident {
+        Ident {
+            dis: if self.config.disambiguators && self.chance(20) {
+                Disambiguator(1 + self.below(1000))
+            } else {
+                Disambiguator(0)
+            },
+            u_ident: self.uident(),
+        }
+    }
+
+    fn uident(&mut self) -> UIdent {
+        let word = if self.config.unicode && self.chance(10) {
+            *self.pick(&UNICODE_WORDS)
+        } else {
+            *self.pick(&WORDS)
+        };
+        UIdent(word.into())
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+
+    /// A random number in `0..n`.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// The SplitMix64 generator, which is fast and needs no dependency.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// The mangled symbols of a `SymbolGenerator`, with all possible backrefs.
+#[derive(Clone, Debug)]
+pub struct Mangled(SymbolGenerator);
+
+impl SymbolGenerator {
+    /// Turns this into a stream of mangled symbols instead of ASTs.
+    pub fn mangled(self) -> Mangled {
+        Mangled(self)
+    }
+}
+
+impl Iterator for Mangled {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        Some(self.0.symbol().mangle())
+    }
+}
+
+impl Iterator for SymbolGenerator {
+    type Item = Symbol;
+
+    fn next(&mut self) -> Option<Symbol> {
+        Some(self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeneratorConfig, SymbolGenerator};
+    use ast::*;
+    use visit::{self, Visitor};
+
+    /// Panics on everything that `restricted_symbols` turns off.
+    struct Restricted;
+
+    impl<'ast> Visitor<'ast> for Restricted {
+        fn visit_path(&mut self, path: &'ast Path) {
+            match *path {
+                Path::CrateRoot { .. } | Path::Nested { .. } => {}
+                Path::Generic { ref args, .. } => assert_eq!(1, args.len()),
+                _ => panic!("{:?}", path),
+            }
+            visit::walk_path(self, path);
+        }
+
+        fn visit_ident(&mut self, ident: &'ast Ident) {
+            assert_eq!(Disambiguator(0), ident.dis);
+            assert!(ident.u_ident.0.is_ascii());
+        }
+
+        fn visit_generic_arg(&mut self, arg: &'ast GenericArg) {
+            assert!(matches!(*arg, GenericArg::Type(_)), "{:?}", arg);
+            visit::walk_generic_arg(self, arg);
+        }
+
+        fn visit_type(&mut self, ty: &'ast Type) {
+            match *ty {
+                Type::Array(..) | Type::Fn(_) | Type::DynTrait(..) | Type::Ref(Some(_), _) => {
+                    panic!("{:?}", ty)
+                }
+                Type::Tuple(ref components) => assert!(components.len() <= 1),
+                _ => {}
+            }
+            visit::walk_type(self, ty);
+        }
+    }
+
+    #[test]
+    fn generated_symbols_round_trip() {
+        let generator = SymbolGenerator::new(GeneratorConfig::default()).with_seed(42);
+        for symbol in generator.take(2000) {
+            let mangled = symbol.mangle();
+            assert_eq!(Ok(&symbol), Symbol::parse(&mangled).as_ref(), "{}", mangled);
+            assert_eq!(
+                Ok(&symbol),
+                Symbol::parse(&symbol.mangle_uncompressed()).as_ref()
+            );
+        }
+
+        let config = GeneratorConfig {
+            max_depth: 4,
+            ..GeneratorConfig::default()
+        };
+        let first: Vec<_> = SymbolGenerator::new(config).with_seed(7).take(10).collect();
+        let second: Vec<_> = SymbolGenerator::new(config).with_seed(7).take(10).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn restricted_symbols() {
+        let config = GeneratorConfig {
+            max_width: 1,
+            backrefs: false,
+            disambiguators: false,
+            unicode: false,
+            impls: false,
+            fn_types: false,
+            consts: false,
+            lifetimes: false,
+            instantiating_crate: false,
+            ..GeneratorConfig::default()
+        };
+
+        for mangled in SymbolGenerator::new(config)
+            .with_seed(3)
+            .mangled()
+            .take(500)
+        {
+            let symbol = Symbol::parse(&mangled).unwrap();
+            assert_eq!(None, symbol.instantiating_crate);
+            Restricted.visit_symbol(&symbol);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+//! The grammar of v0 symbols, for the trees that are not `ast`.
+//!
+//! `borrowed` and `arena` store their nodes differently but parse the same
+//! way, so they only implement `Builder` and leave the parsing to `Grammar`.
+//! Backrefs are parsed again every time they occur.
+
+use ast::{BasicType, Binder, DecimalNumber, Disambiguator, Lifetime, Namespace};
+use charset;
+use parse::{self, Parser};
+use std::mem;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::str;
+
+/// Makes the nodes of a tree from their parsed parts.
+pub(crate) trait Builder<'i> {
+    type Symbol;
+    type Path;
+    type ImplPath;
+    type Ident;
+    type UIdent;
+    type GenericArg;
+    type Type;
+    type FnSig;
+    type DynBounds;
+    type DynTrait;
+    type Const;
+
+    fn symbol(
+        &mut self,
+        version: Option<DecimalNumber>,
+        path: Self::Path,
+        instantiating_crate: Option<Self::Path>,
+    ) -> Self::Symbol;
+
+    fn crate_root(&mut self, id: Self::Ident) -> Self::Path;
+    fn inherent_impl(&mut self, impl_path: Self::ImplPath, self_type: Self::Type) -> Self::Path;
+    fn trait_impl(
+        &mut self,
+        impl_path: Self::ImplPath,
+        self_type: Self::Type,
+        trait_name: Self::Path,
+    ) -> Self::Path;
+    fn trait_def(&mut self, self_type: Self::Type, trait_name: Self::Path) -> Self::Path;
+    fn nested(&mut self, ns: Namespace, inner: Self::Path, ident: Self::Ident) -> Self::Path;
+    fn generic(&mut self, inner: Self::Path, args: Vec<Self::GenericArg>) -> Self::Path;
+    fn impl_path(&mut self, dis: Option<Disambiguator>, path: Self::Path) -> Self::ImplPath;
+
+    fn ident(&mut self, dis: Disambiguator, u_ident: Self::UIdent) -> Self::Ident;
+    /// `raw` is the identifier as it appears in the input, and `decoded` its
+    /// decoded form if it is punycode.
+    fn uident(&mut self, raw: &'i str, decoded: Option<String>) -> Self::UIdent;
+
+    fn lifetime_arg(&mut self, lifetime: Lifetime) -> Self::GenericArg;
+    fn type_arg(&mut self, ty: Self::Type) -> Self::GenericArg;
+    fn const_arg(&mut self, k: Self::Const) -> Self::GenericArg;
+
+    fn basic_type(&mut self, bt: BasicType) -> Self::Type;
+    fn named(&mut self, path: Self::Path) -> Self::Type;
+    fn array(&mut self, inner: Self::Type, len: Self::Const) -> Self::Type;
+    fn slice(&mut self, inner: Self::Type) -> Self::Type;
+    fn tuple(&mut self, components: Vec<Self::Type>) -> Self::Type;
+    fn reference(&mut self, mutable: bool, lt: Option<Lifetime>, inner: Self::Type) -> Self::Type;
+    fn raw_ptr(&mut self, mutable: bool, inner: Self::Type) -> Self::Type;
+    fn fn_type(&mut self, fn_sig: Self::FnSig) -> Self::Type;
+    fn dyn_trait_type(&mut self, bounds: Self::DynBounds, lt: Lifetime) -> Self::Type;
+
+    fn fn_sig(
+        &mut self,
+        binder: Binder,
+        is_unsafe: bool,
+        abi: Option<Abi<Self::UIdent>>,
+        param_types: Vec<Self::Type>,
+        return_type: Self::Type,
+    ) -> Self::FnSig;
+    fn dyn_bounds(&mut self, binder: Binder, traits: Vec<Self::DynTrait>) -> Self::DynBounds;
+    fn dyn_trait(
+        &mut self,
+        path: Self::Path,
+        assoc_type_bindings: Vec<(Self::UIdent, Self::Type)>,
+    ) -> Self::DynTrait;
+
+    fn const_value(&mut self, ty: Self::Type, value: u64) -> Self::Const;
+    fn const_placeholder(&mut self, ty: Self::Type) -> Self::Const;
+}
+
+/// The ABI of a function type, with the identifier type of a `Builder`.
+pub(crate) enum Abi<U> {
+    C,
+    Named(U),
+}
+
+/// Parses a mangled symbol into the tree that `builder` makes.
+pub(crate) fn parse<'i, B: Builder<'i>>(input: &'i [u8], builder: B) -> Result<B::Symbol, String> {
+    let mut grammar = Grammar {
+        p: Parser::new(input),
+        b: builder,
+    };
+
+    grammar
+        .symbol()
+        .map_err(|e| format!("at position {}: {}", grammar.p.pos(), e))
+}
+
+struct Grammar<'i, B> {
+    p: Parser<'i>,
+    b: B,
+}
+
+impl<'i, B: Builder<'i>> Grammar<'i, B> {
+    /// Parses a backref and its target with `parse`.
+    fn backref<T>(&mut self, parse: fn(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        self.p.eat(b'B', "<backref>")?;
+        let target = self.p.parse_backref()?;
+        let outer = mem::replace(&mut self.p, target);
+        let result = parse(self);
+        let target = mem::replace(&mut self.p, outer);
+        self.p.finish_backref(&target);
+        result
+    }
+
+    /// Parses items with `f` until `end`, and consumes `end`.
+    fn list<T>(
+        &mut self,
+        end: u8,
+        noun: &str,
+        f: fn(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        while self.p.cur() != end {
+            items.push(f(self)?);
+        }
+        self.p.eat(end, noun)?;
+        Ok(items)
+    }
+
+    fn symbol(&mut self) -> Result<B::Symbol, String> {
+        let version = self.p.parse_symbol_prefix()?;
+        let path = self.path()?;
+        let instantiating_crate = if !self.p.at_end() {
+            Some(self.path()?)
+        } else {
+            None
+        };
+
+        Ok(self.b.symbol(version, path, instantiating_crate))
+    }
+
+    fn path(&mut self) -> Result<B::Path, String> {
+        self.p.enter()?;
+        let path = self.path_inner()?;
+        self.p.exit();
+        Ok(path)
+    }
+
+    fn path_inner(&mut self) -> Result<B::Path, String> {
+        let tag = self.p.cur();
+
+        if tag == b'B' {
+            return self.backref(Self::path);
+        }
+
+        if tag != b'C' && tag != b'M' && tag != b'X' && tag != b'Y' && tag != b'N' && tag != b'I' {
+            return ::error::expected("CMXYNIB", tag, "parsing", "<path>");
+        }
+
+        self.p.eat(tag, "<path>")?;
+
+        Ok(match tag {
+            b'C' => {
+                let id = self.ident()?;
+                self.b.crate_root(id)
+            }
+            b'M' => {
+                let impl_path = self.impl_path()?;
+                let self_type = self.ty()?;
+                self.b.inherent_impl(impl_path, self_type)
+            }
+            b'X' => {
+                let impl_path = self.impl_path()?;
+                let self_type = self.ty()?;
+                let trait_name = self.path()?;
+                self.b.trait_impl(impl_path, self_type, trait_name)
+            }
+            b'Y' => {
+                let self_type = self.ty()?;
+                let trait_name = self.path()?;
+                self.b.trait_def(self_type, trait_name)
+            }
+            b'N' => {
+                let ns = self.p.parse_namespace()?;
+                let inner = self.path()?;
+                let ident = self.ident()?;
+                self.b.nested(ns, inner, ident)
+            }
+            _ => {
+                let inner = self.path()?;
+                let args = self.list(b'E', "<path>", Self::generic_arg)?;
+                self.b.generic(inner, args)
+            }
+        })
+    }
+
+    fn impl_path(&mut self) -> Result<B::ImplPath, String> {
+        let dis = if self.p.cur() == b's' {
+            Some(self.p.parse_disambiguator()?)
+        } else {
+            None
+        };
+        let path = self.path()?;
+
+        Ok(self.b.impl_path(dis, path))
+    }
+
+    fn ident(&mut self) -> Result<B::Ident, String> {
+        let dis = self.p.parse_opt_disambiguator()?;
+        let u_ident = self.uident()?;
+
+        Ok(self.b.ident(dis, u_ident))
+    }
+
+    fn uident(&mut self) -> Result<B::UIdent, String> {
+        let (punycode, bytes) = self.p.parse_uident_bytes()?;
+
+        let decoded = if punycode {
+            Some(charset::decode_punycode_ident(bytes)?)
+        } else {
+            None
+        };
+        let raw = str::from_utf8(bytes).map_err(|e| format!("{:?}", e))?;
+
+        Ok(self.b.uident(raw, decoded))
+    }
+
+    fn generic_arg(&mut self) -> Result<B::GenericArg, String> {
+        Ok(match self.p.cur() {
+            b'L' => {
+                let lifetime = self.p.parse_lifetime()?;
+                self.b.lifetime_arg(lifetime)
+            }
+            b'K' => {
+                self.p.eat(b'K', "<generic-arg>")?;
+                let k = self.konst()?;
+                self.b.const_arg(k)
+            }
+            _ => {
+                let ty = self.ty()?;
+                self.b.type_arg(ty)
+            }
+        })
+    }
+
+    fn konst(&mut self) -> Result<B::Const, String> {
+        if self.p.cur() == b'B' {
+            return self.backref(Self::konst);
+        }
+
+        let ty = self.ty()?;
+
+        if self.p.try_eat(b'p') {
+            Ok(self.b.const_placeholder(ty))
+        } else {
+            let value = self.p.parse_number(16)?;
+            self.p.eat(b'_', "<const-data>")?;
+            Ok(self.b.const_value(ty, value))
+        }
+    }
+
+    fn opt_lifetime(&mut self) -> Result<Option<Lifetime>, String> {
+        if self.p.cur() == b'L' {
+            Ok(Some(self.p.parse_lifetime()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn ty(&mut self) -> Result<B::Type, String> {
+        self.p.enter()?;
+        let ty = self.ty_inner()?;
+        self.p.exit();
+        Ok(ty)
+    }
+
+    fn ty_inner(&mut self) -> Result<B::Type, String> {
+        let tag = self.p.cur();
+
+        if let Some(bt) = parse::basic_type(tag) {
+            self.p.eat(tag, "<type>")?;
+            return Ok(self.b.basic_type(bt));
+        }
+
+        match tag {
+            b'C' | b'M' | b'X' | b'Y' | b'N' | b'I' => {
+                let path = self.path()?;
+                return Ok(self.b.named(path));
+            }
+            b'B' => {
+                return self.backref(Self::ty);
+            }
+            b'A' | b'S' | b'T' | b'R' | b'Q' | b'P' | b'O' | b'F' | b'D' => {
+                self.p.eat(tag, "<type>")?;
+            }
+            c => {
+                return parse::unexpected_type_tag(c);
+            }
+        }
+
+        Ok(match tag {
+            b'A' => {
+                let inner = self.ty()?;
+                let len = self.konst()?;
+                self.b.array(inner, len)
+            }
+            b'S' => {
+                let inner = self.ty()?;
+                self.b.slice(inner)
+            }
+            b'T' => {
+                let components = self.list(b'E', "<type>", Self::ty)?;
+                self.b.tuple(components)
+            }
+            b'R' | b'Q' => {
+                let lt = self.opt_lifetime()?;
+                let inner = self.ty()?;
+                self.b.reference(tag == b'Q', lt, inner)
+            }
+            b'P' | b'O' => {
+                let inner = self.ty()?;
+                self.b.raw_ptr(tag == b'O', inner)
+            }
+            b'F' => {
+                let fn_sig = self.fn_sig()?;
+                self.b.fn_type(fn_sig)
+            }
+            _ => {
+                let bounds = self.dyn_bounds()?;
+                let lt = self.p.parse_lifetime()?;
+                self.b.dyn_trait_type(bounds, lt)
+            }
+        })
+    }
+
+    fn fn_sig(&mut self) -> Result<B::FnSig, String> {
+        let binder = self.p.parse_binder()?;
+        let is_unsafe = self.p.try_eat(b'U');
+        let abi = if self.p.try_eat(b'K') {
+            if self.p.try_eat(b'C') {
+                Some(Abi::C)
+            } else {
+                Some(Abi::Named(self.uident()?))
+            }
+        } else {
+            None
+        };
+        let param_types = self.list(b'E', "<fn-sig>", Self::ty)?;
+        let return_type = self.ty()?;
+
+        Ok(self
+            .b
+            .fn_sig(binder, is_unsafe, abi, param_types, return_type))
+    }
+
+    fn dyn_bounds(&mut self) -> Result<B::DynBounds, String> {
+        let binder = self.p.parse_binder()?;
+        let traits = self.list(b'E', "<dyn-trait>", Self::dyn_trait)?;
+
+        Ok(self.b.dyn_bounds(binder, traits))
+    }
+
+    fn dyn_trait(&mut self) -> Result<B::DynTrait, String> {
+        let path = self.path()?;
+        let mut assoc_type_bindings = Vec::new();
+        while self.p.try_eat(b'p') {
+            let ident = self.uident()?;
+            let ty = self.ty()?;
+            assoc_type_bindings.push((ident, ty));
+        }
+
+        Ok(self.b.dyn_trait(path, assoc_type_bindings))
+    }
+}
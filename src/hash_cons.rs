@@ -0,0 +1,233 @@
+//! Sharing of structurally identical subtrees.
+//!
+//! The parser allocates every node it parses, so a type that occurs twice in a
+//! symbol ends up in two separate allocations unless the second occurrence
+//! was a backref. A `HashCons` table rebuilds trees so that all identical
+//! subtrees behind a `Ptr` point to the same allocation, within one symbol
+//! and across all symbols passed through the same table. Since comparing two
+//! `Ptr`s first compares their addresses, this also makes equality checks on
+//! shared subtrees cheap.
+
+use ast::*;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+#[derive(Default, Debug)]
+pub struct HashCons {
+    paths: HashSet<Ptr<Path>>,
+    types: HashSet<Ptr<Type>>,
+    consts: HashSet<Ptr<Const>>,
+    fn_sigs: HashSet<Ptr<FnSig>>,
+    dyn_bounds: HashSet<Ptr<DynBounds>>,
+}
+
+impl HashCons {
+    pub fn new() -> HashCons {
+        HashCons::default()
+    }
+
+    /// Parses a mangled symbol name, sharing its subtrees with all symbols
+    /// seen before.
+    pub fn parse(&mut self, mangled: &str) -> Result<Symbol, String> {
+        Ok(self.share_symbol(&Symbol::parse(mangled)?))
+    }
+
+    /// Returns a copy of `symbol` in which all subtrees are shared.
+    pub fn share_symbol(&mut self, symbol: &Symbol) -> Symbol {
+        Symbol {
+            version: symbol.version,
+            path: self.path(&symbol.path),
+            instantiating_crate: symbol
+                .instantiating_crate
+                .as_ref()
+                .map(|krate| self.path(krate)),
+        }
+    }
+
+    /// The number of distinct shared subtrees.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+            + self.types.len()
+            + self.consts.len()
+            + self.fn_sigs.len()
+            + self.dyn_bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shared_path(&mut self, path: &Path) -> Ptr<Path> {
+        let path = self.path(path);
+        share(&mut self.paths, path)
+    }
+
+    fn shared_type(&mut self, ty: &Type) -> Ptr<Type> {
+        let ty = self.ty(ty);
+        share(&mut self.types, ty)
+    }
+
+    fn shared_const(&mut self, k: &Const) -> Ptr<Const> {
+        let k = self.konst(k);
+        share(&mut self.consts, k)
+    }
+
+    fn impl_path(&mut self, impl_path: &ImplPath) -> ImplPath {
+        ImplPath {
+            dis: impl_path.dis,
+            path: self.shared_path(&impl_path.path),
+        }
+    }
+
+    fn path(&mut self, path: &Path) -> Path {
+        match *path {
+            Path::CrateRoot { ref id } => Path::CrateRoot { id: id.clone() },
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => Path::InherentImpl {
+                impl_path: self.impl_path(impl_path),
+                self_type: self.ty(self_type),
+            },
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => Path::TraitImpl {
+                impl_path: self.impl_path(impl_path),
+                self_type: self.ty(self_type),
+                trait_name: self.shared_path(trait_name),
+            },
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => Path::TraitDef {
+                self_type: self.ty(self_type),
+                trait_name: self.shared_path(trait_name),
+            },
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => Path::Nested {
+                ns: ns.clone(),
+                inner: self.shared_path(inner),
+                ident: ident.clone(),
+            },
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => Path::Generic {
+                inner: self.shared_path(inner),
+                args: args.iter().map(|arg| self.generic_arg(arg)).collect(),
+            },
+        }
+    }
+
+    fn generic_arg(&mut self, arg: &GenericArg) -> GenericArg {
+        match *arg {
+            GenericArg::Lifetime(ref lt) => GenericArg::Lifetime(lt.clone()),
+            GenericArg::Type(ref ty) => GenericArg::Type(self.ty(ty)),
+            GenericArg::Const(ref k) => GenericArg::Const(self.konst(k)),
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) -> Type {
+        match *ty {
+            Type::BasicType(bt) => Type::BasicType(bt),
+            Type::Array(ref inner, ref len) => {
+                Type::Array(self.shared_type(inner), self.shared_const(len))
+            }
+            Type::Slice(ref inner) => Type::Slice(self.shared_type(inner)),
+            Type::Named(ref path) => Type::Named(self.shared_path(path)),
+            Type::Tuple(ref components) => {
+                Type::Tuple(components.iter().map(|ty| self.ty(ty)).collect())
+            }
+            Type::Ref(ref lt, ref inner) => Type::Ref(lt.clone(), self.shared_type(inner)),
+            Type::RefMut(ref lt, ref inner) => Type::RefMut(lt.clone(), self.shared_type(inner)),
+            Type::RawPtrConst(ref inner) => Type::RawPtrConst(self.shared_type(inner)),
+            Type::RawPtrMut(ref inner) => Type::RawPtrMut(self.shared_type(inner)),
+            Type::Fn(ref fn_sig) => {
+                let fn_sig = FnSig {
+                    binder: fn_sig.binder.clone(),
+                    is_unsafe: fn_sig.is_unsafe,
+                    abi: fn_sig.abi.clone(),
+                    param_types: fn_sig.param_types.iter().map(|ty| self.ty(ty)).collect(),
+                    return_type: self.ty(&fn_sig.return_type),
+                };
+                Type::Fn(share(&mut self.fn_sigs, fn_sig))
+            }
+            Type::DynTrait(ref bounds, ref lt) => {
+                let bounds = DynBounds {
+                    binder: bounds.binder.clone(),
+                    traits: bounds
+                        .traits
+                        .iter()
+                        .map(|tr| DynTrait {
+                            path: self.path(&tr.path),
+                            assoc_type_bindings: tr
+                                .assoc_type_bindings
+                                .iter()
+                                .map(|binding| DynTraitAssocBinding {
+                                    ident: binding.ident.clone(),
+                                    ty: self.ty(&binding.ty),
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                };
+                Type::DynTrait(share(&mut self.dyn_bounds, bounds), lt.clone())
+            }
+        }
+    }
+
+    fn konst(&mut self, k: &Const) -> Const {
+        match *k {
+            Const::Value(ref ty, value) => Const::Value(self.ty(ty), value),
+            Const::Placeholder(ref ty) => Const::Placeholder(self.ty(ty)),
+        }
+    }
+}
+
+fn share<T: Eq + Hash>(table: &mut HashSet<Ptr<T>>, node: T) -> Ptr<T> {
+    if let Some(shared) = table.get(&node) {
+        return shared.clone();
+    }
+
+    let shared = Ptr::new(node);
+    table.insert(shared.clone());
+    shared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashCons;
+    use ast::*;
+
+    fn slice_element(arg: &GenericArg) -> &Ptr<Type> {
+        match *arg {
+            GenericArg::Type(Type::Slice(ref element)) => element,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn identical_subtrees_are_shared() {
+        // mycrate::foo<[std::Bar], [std::Bar]>, without a backref for the
+        // second occurrence of `std::Bar`.
+        let mangled = "_RINxC7mycrate3fooSNtC3std3BarSNtC3std3BarE";
+        let mut hash_cons = HashCons::new();
+        let symbol = hash_cons.parse(mangled).unwrap();
+
+        assert_eq!(Symbol::parse(mangled).unwrap(), symbol);
+
+        if let Path::Generic { ref args, .. } = symbol.path {
+            assert!(Ptr::ptr_eq(
+                slice_element(&args[0]),
+                slice_element(&args[1])
+            ));
+        } else {
+            panic!("expected a generic path");
+        }
+    }
+}
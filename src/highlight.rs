@@ -0,0 +1,208 @@
+//! ANSI highlighting of demangled symbols for terminals.
+//!
+//! Highlighting works on the demangled text rather than on the AST, so that
+//! it applies equally to v0 and legacy symbols and to demangled symbols that
+//! are embedded in other text.
+
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The SGR parameters, like `"1;36"`, used for each kind of token. An empty
+/// string leaves the token unstyled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Path segments before a `::`, like `std` and `vec` in `std::vec::Vec`.
+    pub path: &'static str,
+    /// The last segment of a path, i.e. the name of the item.
+    pub name: &'static str,
+    pub keyword: &'static str,
+    /// Basic types and lifetimes.
+    pub primitive: &'static str,
+    /// Const generic values and strings.
+    pub literal: &'static str,
+    /// Disambiguators like `[a1b2]`, `{closure#0}`-style namespaces, and the
+    /// instantiating crate.
+    pub detail: &'static str,
+    pub punctuation: &'static str,
+}
+
+impl Theme {
+    /// For terminals with a dark background.
+    pub const DARK: Theme = Theme {
+        path: "36",
+        name: "1;97",
+        keyword: "35",
+        primitive: "33",
+        literal: "32",
+        detail: "2",
+        punctuation: "90",
+    };
+
+    /// For terminals with a light background.
+    pub const LIGHT: Theme = Theme {
+        path: "34",
+        name: "1;30",
+        keyword: "35",
+        primitive: "31",
+        literal: "32",
+        detail: "2",
+        punctuation: "90",
+    };
+
+    /// Only bold and dim text, for terminals with few colors.
+    pub const MONO: Theme = Theme {
+        path: "",
+        name: "1",
+        keyword: "",
+        primitive: "",
+        literal: "",
+        detail: "2",
+        punctuation: "",
+    };
+
+    pub const NAMES: [&'static str; 3] = ["dark", "light", "mono"];
+
+    pub fn named(name: &str) -> Option<&'static Theme> {
+        match name {
+            "dark" => Some(&Theme::DARK),
+            "light" => Some(&Theme::LIGHT),
+            "mono" => Some(&Theme::MONO),
+            _ => None,
+        }
+    }
+}
+
+const KEYWORDS: [&str; 9] = [
+    "as", "const", "dyn", "extern", "fn", "for", "impl", "mut", "unsafe",
+];
+
+const PRIMITIVES: [&str; 17] = [
+    "bool", "char", "str", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64",
+    "u128", "usize", "f32", "f64",
+];
+
+/// Appends the demangled symbol `demangled` to `out`, with ANSI escape
+/// sequences around its tokens as given by `theme`.
+pub fn highlight(demangled: &str, theme: &Theme, out: &mut String) {
+    let (symbol, instantiating_crate) = match demangled.find(" @ ") {
+        Some(at) => demangled.split_at(at),
+        None => (demangled, ""),
+    };
+
+    let mut rest = symbol;
+    let mut after_word = false;
+    while let Some(c) = rest.chars().next() {
+        let (len, style) = if is_word_char(c) {
+            let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let style = if c.is_ascii_digit() {
+                theme.literal
+            } else if rest[len + disambiguator_len(&rest[len..])..].starts_with("::") {
+                theme.path
+            } else if KEYWORDS.contains(&word) {
+                theme.keyword
+            } else if PRIMITIVES.contains(&word) {
+                theme.primitive
+            } else {
+                theme.name
+            };
+            (len, style)
+        } else {
+            match c {
+                // A disambiguator directly follows the identifier it belongs
+                // to, unlike the brackets of arrays and slices.
+                '[' if after_word && disambiguator_len(rest) > 0 => {
+                    (disambiguator_len(rest), theme.detail)
+                }
+                '{' => (rest.find('}').map_or(1, |end| end + 1), theme.detail),
+                '"' => (
+                    rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+                    theme.literal,
+                ),
+                '\'' => (
+                    1 + rest[1..]
+                        .find(|c| !is_word_char(c))
+                        .unwrap_or(rest.len() - 1),
+                    theme.primitive,
+                ),
+                ':' if rest.starts_with("::") => (2, theme.punctuation),
+                '!' => (1, theme.primitive),
+                c if c.is_whitespace() => (c.len_utf8(), ""),
+                c => (c.len_utf8(), theme.punctuation),
+            }
+        };
+
+        styled(&rest[..len], style, out);
+        after_word = is_word_char(c);
+        rest = &rest[len..];
+    }
+
+    styled(instantiating_crate, theme.detail, out);
+}
+
+/// The length of the disambiguator like `[a1b2]` at the start of `text`, or
+/// 0 if there is none.
+fn disambiguator_len(text: &str) -> usize {
+    if !text.starts_with('[') {
+        return 0;
+    }
+
+    match text.find(']') {
+        Some(end) if end > 1 && text[1..end].chars().all(|c| c.is_ascii_alphanumeric()) => end + 1,
+        _ => 0,
+    }
+}
+
+fn styled(text: &str, style: &str, out: &mut String) {
+    if style.is_empty() || text.is_empty() {
+        out.push_str(text);
+        return;
+    }
+
+    out.push_str("\x1b[");
+    out.push_str(style);
+    out.push('m');
+    out.push_str(text);
+    out.push_str("\x1b[0m");
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight, Theme};
+
+    #[test]
+    fn highlight_tokens() {
+        let theme = Theme {
+            path: "p",
+            name: "n",
+            keyword: "k",
+            primitive: "t",
+            literal: "l",
+            detail: "d",
+            punctuation: ".",
+        };
+        let mut out = String::new();
+        highlight(
+            "<std[1a]::Vec<[u8; 4]> as dyn core::Foo<&[u8]>>::bar::{closure#0} @ mycrate",
+            &theme,
+            &mut out,
+        );
+
+        // Makes the output readable by replacing `\x1b[Xm...\x1b[0m` with
+        // `X(...)`.
+        let mut out = out.replace("\x1b[0m", ")");
+        for style in &["p", "n", "k", "t", "l", "d", "."] {
+            out = out.replace(&format!("\x1b[{}m", style), &format!("{}(", style));
+        }
+        assert_eq!(
+            ".(<)p(std)d([1a]).(::)n(Vec).(<).([)t(u8).(;) l(4).(]).(>) k(as) k(dyn) \
+             p(core).(::)n(Foo).(<).(&).([)t(u8).(]).(>).(>).(::)p(bar).(::)\
+             d({closure#0})d( @ mycrate)",
+            out
+        );
+    }
+}
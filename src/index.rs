@@ -0,0 +1,207 @@
+//! An in-memory index of symbols by their demangled path, for symbol servers
+//! and other tools that look up symbols by name.
+//!
+//! The index is a trie of path components without generic arguments and
+//! disambiguators, so all instances of a generic item end up in one node.
+//! Impls are components like `<alloc::vec::Vec as core::ops::Drop>`, written
+//! without generic arguments, and closures are `{closure}`.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use pattern::split_top_level;
+use std::collections::BTreeMap;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[derive(Clone, Default, Debug)]
+pub struct SymbolIndex {
+    root: Node,
+    /// The indexed symbols, mangled.
+    symbols: Vec<String>,
+}
+
+#[derive(Clone, Default, Debug)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    /// Indices into `SymbolIndex::symbols` of the symbols with this path.
+    symbols: Vec<usize>,
+}
+
+impl SymbolIndex {
+    pub fn new() -> SymbolIndex {
+        SymbolIndex::default()
+    }
+
+    /// Adds the v0 symbol `mangled`, and returns whether it could be parsed.
+    /// Adding a symbol again has no effect.
+    pub fn insert(&mut self, mangled: &str) -> bool {
+        let symbol = match Symbol::parse(mangled) {
+            Ok(symbol) => symbol.erase_disambiguators().erase_generic_args(),
+            Err(_) => return false,
+        };
+
+        let mut components = Vec::new();
+        collect_components(&symbol.path, &mut components);
+
+        let mut node = &mut self.root;
+        for component in components {
+            node = node.children.entry(component).or_default();
+        }
+
+        let symbols = &self.symbols;
+        if !node.symbols.iter().any(|&i| symbols[i] == mangled) {
+            node.symbols.push(self.symbols.len());
+            self.symbols.push(mangled.to_string());
+        }
+        true
+    }
+
+    /// The number of symbols in the index.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// The symbols whose path is `path`, like `alloc::vec::Vec::push`, in the
+    /// order they were added.
+    pub fn get(&self, path: &str) -> Vec<&str> {
+        self.node(&split_path(path))
+            .map_or_else(Vec::new, |node| self.symbols_of(node))
+    }
+
+    /// The symbols whose path is `prefix` or starts with it, like all those
+    /// under `tokio::runtime::`, ordered by path.
+    pub fn under(&self, prefix: &str) -> Vec<&str> {
+        let mut symbols = Vec::new();
+        if let Some(node) = self.node(&split_path(prefix)) {
+            self.collect_under(node, &mut symbols);
+        }
+        symbols
+    }
+
+    /// The symbols under the longest prefix of `path` that is in the index,
+    /// along with that prefix, e.g. those under `tokio::runtime` for
+    /// `tokio::runtime::Handle::misspelled`. `None` if not even the crate
+    /// of `path` is in the index.
+    pub fn nearest(&self, path: &str) -> Option<(String, Vec<&str>)> {
+        let components = split_path(path);
+        let mut node = &self.root;
+        let mut matched = 0;
+
+        for component in &components {
+            match node.children.get(*component) {
+                Some(child) => node = child,
+                None => break,
+            }
+            matched += 1;
+        }
+
+        if matched == 0 {
+            return None;
+        }
+
+        let mut symbols = Vec::new();
+        self.collect_under(node, &mut symbols);
+        Some((components[..matched].join("::"), symbols))
+    }
+
+    fn node(&self, components: &[&str]) -> Option<&Node> {
+        components
+            .iter()
+            .try_fold(&self.root, |node, component| node.children.get(*component))
+    }
+
+    fn symbols_of(&self, node: &Node) -> Vec<&str> {
+        node.symbols.iter().map(|&i| &self.symbols[i][..]).collect()
+    }
+
+    fn collect_under<'a>(&'a self, node: &Node, out: &mut Vec<&'a str>) {
+        out.extend(self.symbols_of(node));
+        for child in node.children.values() {
+            self.collect_under(child, out);
+        }
+    }
+}
+
+impl<'a> Extend<&'a str> for SymbolIndex {
+    /// Adds the symbols, skipping those that do not parse.
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, symbols: I) {
+        for mangled in symbols {
+            self.insert(mangled);
+        }
+    }
+}
+
+/// Splits a demangled path into its components, ignoring a trailing `::`.
+fn split_path(path: &str) -> Vec<&str> {
+    let path = path.strip_suffix("::").unwrap_or(path);
+    if path.is_empty() {
+        return Vec::new();
+    }
+    split_top_level(path, "::")
+}
+
+/// Appends the components of `path`, which has no generic arguments or
+/// disambiguators, to `out`.
+fn collect_components(path: &Path, out: &mut Vec<String>) {
+    match *path {
+        Path::CrateRoot { ref id } => out.push(id.u_ident.0.to_string()),
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            collect_components(inner, out);
+            if *ns == Namespace(b'C') {
+                out.push("{closure}".to_string());
+            } else if !ident.u_ident.0.is_empty() {
+                out.push(ident.u_ident.0.to_string());
+            }
+        }
+        Path::Generic { ref inner, .. } => collect_components(inner, out),
+        Path::InherentImpl { .. } | Path::TraitImpl { .. } | Path::TraitDef { .. } => {
+            out.push(path.demangle());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolIndex;
+
+    #[test]
+    fn look_up_by_path() {
+        let mut index = SymbolIndex::new();
+        let symbols = [
+            "_RNxNyNyCs4_5tokio7runtime6Handle7current",
+            "_RINxNyNyCs4_5tokio7runtime6Handle5spawnhE",
+            "_RINxNyNyCs4_5tokio7runtime6Handle5spawnmE",
+            "_RNxNyNyC5tokio4sync5Mutex4lock",
+            "_RNxNyC5alloc3vec4push",
+        ];
+        index.extend(symbols.iter().cloned());
+        assert!(!index.insert("_RXX"));
+        index.insert(symbols[0]);
+        assert_eq!(5, index.len());
+
+        assert_eq!(
+            vec![symbols[1], symbols[2]],
+            index.get("tokio::runtime::Handle::spawn")
+        );
+        assert_eq!(
+            vec![symbols[0], symbols[1], symbols[2]],
+            index.under("tokio::runtime::")
+        );
+        assert_eq!(4, index.under("tokio").len());
+        assert!(index.under("tokio::rt").is_empty());
+
+        assert_eq!(
+            Some(("tokio::sync".to_string(), vec![symbols[3]])),
+            index.nearest("tokio::sync::RwLock::read")
+        );
+        assert_eq!(None, index.nearest("std::sync::Mutex"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Everything that size analysis tools show about a symbol, in one struct.
+//!
+//! `SymbolInfo` is a contract like the types of `stable`: it only holds
+//! strings and `stable` enums, so tools like `cargo-bloat` do not depend on
+//! the AST, and new fields can be added without breaking them.
+
+use ast::{self, GenericArg};
+use ast_demangle::AstDemangle;
+use legacy;
+use stable::Namespace;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// What `analyze()` finds out about a symbol.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SymbolInfo {
+    /// The demangled symbol, without any suffix.
+    pub demangled: String,
+    /// The crate that defines the item.
+    pub crate_name: String,
+    /// The crate that instantiated a generic item, if the symbol records one.
+    pub instantiating_crate: Option<String>,
+    /// The path of the item without generic arguments and disambiguators,
+    /// e.g. `alloc::vec::Vec<T>::push` becomes `alloc::vec::Vec::push`.
+    pub path: String,
+    /// The demangled symbol with every generic argument replaced by `_`,
+    /// which all instances of a generic item share.
+    pub skeleton: String,
+    /// The demangled generic arguments of the item itself, e.g. `u8` and
+    /// `bool` for `mycrate::foo<u8, bool>`.
+    pub generic_args: Vec<String>,
+    /// The namespace of the last path segment, or `None` if the item is a
+    /// crate root or an impl.
+    pub namespace: Option<Namespace>,
+    /// The suffixes that compilers add after the symbol, e.g. `.llvm.1234`
+    /// and `.cold.1`, each with its leading `.`.
+    pub suffixes: Vec<String>,
+    /// Whether this is a legacy symbol, for which `generic_args` is always
+    /// empty and `skeleton` the same as `path`.
+    pub legacy: bool,
+}
+
+/// Analyzes the v0 or legacy symbol `mangled`, which may have suffixes.
+pub fn analyze(mangled: &str) -> Result<SymbolInfo, String> {
+    // Neither v0 nor legacy symbols contain `.`.
+    let (symbol, suffix) = match mangled.find('.') {
+        Some(i) => mangled.split_at(i),
+        None => (mangled, ""),
+    };
+    let suffixes = split_suffixes(suffix);
+
+    let parsed = match ast::Symbol::parse(symbol) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let demangled = legacy::demangle(symbol).map_err(|_| e)?;
+            return Ok(SymbolInfo {
+                crate_name: demangled.split("::").next().unwrap_or("").to_string(),
+                instantiating_crate: None,
+                path: demangled.clone(),
+                skeleton: demangled.clone(),
+                generic_args: Vec::new(),
+                namespace: None,
+                suffixes,
+                legacy: true,
+                demangled,
+            });
+        }
+    };
+
+    let plain = {
+        let mut plain = parsed.erase_disambiguators();
+        plain.instantiating_crate = None;
+        plain
+    };
+
+    let (generic_args, namespace) = match plain.path {
+        ast::Path::Generic {
+            ref inner,
+            ref args,
+        } => (args.iter().map(demangle_arg).collect(), namespace(inner)),
+        ref path => (Vec::new(), namespace(path)),
+    };
+
+    Ok(SymbolInfo {
+        demangled: parsed.demangle(),
+        crate_name: parsed.defining_crate().u_ident.0.to_string(),
+        instantiating_crate: parsed
+            .instantiating_crate_ident()
+            .map(|ident| ident.u_ident.0.to_string()),
+        path: plain.erase_generic_args().demangle(),
+        skeleton: plain.skeleton().demangle(),
+        generic_args,
+        namespace,
+        suffixes,
+        legacy: false,
+    })
+}
+
+fn namespace(path: &ast::Path) -> Option<Namespace> {
+    match *path {
+        ast::Path::Nested { ref ns, .. } => Some(Namespace::of(ns)),
+        _ => None,
+    }
+}
+
+fn demangle_arg(arg: &GenericArg) -> String {
+    let mut out = String::new();
+    arg.demangle_to_string(&mut out);
+    out
+}
+
+/// Splits `.llvm.1234.cold.1` into `.llvm.1234` and `.cold.1`: a suffix is
+/// a name followed by any numbers, except that all of an LLVM suffix is its
+/// hash.
+fn split_suffixes(suffix: &str) -> Vec<String> {
+    let mut suffixes: Vec<String> = Vec::new();
+    for part in suffix.split('.').skip(1) {
+        let continues = match suffixes.last() {
+            Some(last) => last == ".llvm" || part.is_empty() || part.as_bytes()[0].is_ascii_digit(),
+            None => false,
+        };
+        match suffixes.last_mut() {
+            Some(last) if continues => {
+                last.push('.');
+                last.push_str(part);
+            }
+            _ => suffixes.push(format!(".{}", part)),
+        }
+    }
+    suffixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::analyze;
+    use stable::Namespace;
+
+    #[test]
+    fn analyze_symbols() {
+        let info = analyze(
+            "_RINxNyCs0_4core3ptr13drop_in_placeTNyCs1_5alloc6StringhEEC7mycrate.llvm.1234.cold.1",
+        )
+        .unwrap();
+        assert_eq!("core", info.crate_name);
+        assert_eq!(Some("mycrate".to_string()), info.instantiating_crate);
+        assert_eq!("core::ptr::drop_in_place", info.path);
+        assert_eq!("core::ptr::drop_in_place<_>", info.skeleton);
+        assert_eq!(vec!["(alloc::String,u8)".to_string()], info.generic_args);
+        assert_eq!(Some(Namespace::Value), info.namespace);
+        assert_eq!(vec![".llvm.1234", ".cold.1"], info.suffixes);
+        assert!(!info.legacy);
+
+        let info = analyze("_ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE.cold").unwrap();
+        assert_eq!("std", info.crate_name);
+        assert_eq!("std::io::stdio::_print", info.path);
+        assert_eq!(vec![".cold"], info.suffixes);
+        assert!(info.legacy);
+
+        assert!(analyze("main").is_err());
+    }
+}
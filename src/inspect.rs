@@ -0,0 +1,123 @@
+//! Queries that collect information from anywhere within a symbol.
+
+use ast::*;
+use collections::Set;
+use std::collections::BTreeSet;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use visit::{self, Visitor};
+
+impl Symbol {
+    /// Returns every crate referenced by this symbol, including crates that
+    /// only appear inside generic arguments, impl self types, trait paths,
+    /// and the instantiating crate.
+    pub fn referenced_crates(&self) -> BTreeSet<(&str, Disambiguator)> {
+        let mut collector = CrateCollector {
+            crates: BTreeSet::new(),
+        };
+        collector.visit_symbol(self);
+        collector.crates
+    }
+
+    /// Returns the distinct named (i.e. non-basic, path-based) types that
+    /// appear anywhere in this symbol, in order of first appearance.
+    /// Structurally equal types are only returned once.
+    pub fn mentioned_types(&self) -> Vec<&Type> {
+        let mut collector = TypeCollector {
+            seen: Set::new(),
+            types: Vec::new(),
+        };
+        collector.visit_symbol(self);
+        collector.types
+    }
+
+    /// Returns the crate the item named by this symbol is defined in. For
+    /// impl items this is the crate of the impl, not of its self type.
+    pub fn defining_crate(&self) -> &Ident {
+        path_crate(&self.path)
+    }
+
+    /// Returns the crate that instantiated the item named by this symbol, if
+    /// the symbol records one.
+    pub fn instantiating_crate_ident(&self) -> Option<&Ident> {
+        self.instantiating_crate.as_ref().map(path_crate)
+    }
+}
+
+/// Returns the crate the item named by `path` is defined in.
+fn path_crate(mut path: &Path) -> &Ident {
+    loop {
+        path = match *path {
+            Path::CrateRoot { ref id } => return id,
+            Path::InherentImpl { ref impl_path, .. } | Path::TraitImpl { ref impl_path, .. } => {
+                &impl_path.path
+            }
+            Path::TraitDef { ref trait_name, .. } => trait_name,
+            Path::Nested { ref inner, .. } | Path::Generic { ref inner, .. } => inner,
+        };
+    }
+}
+
+struct CrateCollector<'ast> {
+    crates: BTreeSet<(&'ast str, Disambiguator)>,
+}
+
+impl<'ast> Visitor<'ast> for CrateCollector<'ast> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Path::CrateRoot { ref id } = *path {
+            self.crates.insert((&id.u_ident.0[..], id.dis));
+        }
+
+        visit::walk_path(self, path);
+    }
+}
+
+struct TypeCollector<'ast> {
+    seen: Set<&'ast Type>,
+    types: Vec<&'ast Type>,
+}
+
+impl<'ast> Visitor<'ast> for TypeCollector<'ast> {
+    fn visit_type(&mut self, ty: &'ast Type) {
+        if let Type::Named(_) = *ty {
+            if self.seen.insert(ty) {
+                self.types.push(ty);
+            }
+        }
+
+        visit::walk_type(self, ty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::*;
+    use ast_demangle::AstDemangle;
+
+    #[test]
+    fn crates_and_types_inside_generic_args() {
+        // <std::vec::Vec<i16>>::retain<xxx::predicate>
+        let symbol =
+            Symbol::parse("_RINxMs89_NyC3std3vecINyNyC3std3vec3VecsE6retainNxC3xxx9predicateE")
+                .unwrap();
+
+        let crates: Vec<_> = symbol.referenced_crates().into_iter().collect();
+        assert_eq!(
+            vec![("std", Disambiguator(0)), ("xxx", Disambiguator(0))],
+            crates
+        );
+
+        let types: Vec<_> = symbol
+            .mentioned_types()
+            .into_iter()
+            .map(|ty| ty.demangle())
+            .collect();
+        assert_eq!(vec!["std::vec::Vec<i16>", "xxx::predicate"], types);
+        assert_eq!("std", &*symbol.defining_crate().u_ident.0);
+        assert_eq!(None, symbol.instantiating_crate_ident());
+
+        let symbol = Symbol::parse("_RINvC3std4swaphEC7mycrate").unwrap();
+        let instantiating_crate = symbol.instantiating_crate_ident().unwrap();
+        assert_eq!("mycrate", &*instantiating_crate.u_ident.0);
+    }
+}
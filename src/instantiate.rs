@@ -0,0 +1,128 @@
+//! Filling in the placeholders of a skeleton, to predict the symbol that a
+//! monomorphization of a generic item gets without compiling it.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use fold::{self, Folder};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// Replaces the type and const placeholders of `skeleton`, like the ones
+/// `Symbol::skeleton()` leaves, with `args` in the order they occur in the
+/// mangled symbol. Erased lifetimes are left alone and take no argument. A
+/// const argument must have the type of the placeholder it replaces.
+///
+/// The symbol the monomorphization gets is the `mangle()` of the result,
+/// after setting its instantiating crate if the instance is not in the crate
+/// of the item.
+pub fn instantiate(skeleton: &Symbol, args: &[GenericArg]) -> Result<Symbol, String> {
+    let mut instantiator = Instantiator {
+        args: args.iter(),
+        error: None,
+    };
+    let symbol = instantiator.fold_symbol(skeleton);
+
+    if let Some(error) = instantiator.error {
+        return Err(error);
+    }
+    let unused = instantiator.args.len();
+    if unused > 0 {
+        return Err(format!(
+            "{} generic arguments given, but {} has {} placeholders",
+            args.len(),
+            skeleton.demangle(),
+            args.len() - unused
+        ));
+    }
+    Ok(symbol)
+}
+
+struct Instantiator<'a> {
+    args: ::std::slice::Iter<'a, GenericArg>,
+    /// The first error, after which the placeholders are left as they are.
+    error: Option<String>,
+}
+
+impl<'a> Instantiator<'a> {
+    fn next_arg(&mut self) -> Option<&'a GenericArg> {
+        if self.error.is_some() {
+            return None;
+        }
+        let arg = self.args.next();
+        if arg.is_none() {
+            self.error = Some("not enough generic arguments for the placeholders".to_string());
+        }
+        arg
+    }
+
+    fn mismatch(&mut self, expected: &str, arg: &GenericArg) {
+        self.error = Some(format!(
+            "expected {} for the placeholder, found `{}`",
+            expected,
+            arg.demangle()
+        ));
+    }
+}
+
+impl<'a> Folder for Instantiator<'a> {
+    fn fold_type(&mut self, ty: &Type) -> Type {
+        match *ty {
+            Type::BasicType(BasicType::Placeholder) => match self.next_arg() {
+                Some(GenericArg::Type(ty)) => ty.clone(),
+                Some(arg) => {
+                    self.mismatch("a type", arg);
+                    ty.clone()
+                }
+                None => ty.clone(),
+            },
+            _ => fold::walk_type(self, ty),
+        }
+    }
+
+    fn fold_const(&mut self, k: &Const) -> Const {
+        match *k {
+            Const::Placeholder(ref ty) => match self.next_arg() {
+                Some(GenericArg::Const(arg @ Const::Value(arg_ty, _))) if arg_ty == ty => {
+                    arg.clone()
+                }
+                Some(arg) => {
+                    self.mismatch(&format!("a const of type `{}`", ty.demangle()), arg);
+                    k.clone()
+                }
+                None => k.clone(),
+            },
+            _ => fold::walk_const(self, k),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::instantiate;
+    use ast::*;
+
+    #[test]
+    fn instantiate_skeleton() {
+        let skeleton = Symbol::parse("_RINxC3std4swaphEC1a").unwrap().skeleton();
+        let u32 = GenericArg::Type(Type::basic(BasicType::U32));
+        let four = GenericArg::Const(Const::Value(Type::basic(BasicType::Usize), 4));
+        let args = [u32.clone(), four, u32];
+        assert_eq!(
+            "_RINxC3std4swapmE",
+            instantiate(&skeleton, &args[..1]).unwrap().mangle()
+        );
+
+        // mycrate::zeroed<u8, 3>
+        let skeleton = Symbol::parse("_RINxC7mycrate6zeroedhKj3_E")
+            .unwrap()
+            .skeleton();
+        assert_eq!(
+            "_RINxC7mycrate6zeroedmKj4_E",
+            instantiate(&skeleton, &args[..2]).unwrap().mangle()
+        );
+
+        assert!(instantiate(&skeleton, &args[..1]).is_err());
+        assert!(instantiate(&skeleton, &args[1..]).is_err());
+        assert!(instantiate(&skeleton, &args).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 
 pub struct RadixFmt {
     radix: u8,
@@ -36,13 +38,23 @@ impl fmt::Display for RadixFmt {
     }
 }
 
+/// The value of every byte as a base-62 digit, or `NOT_A_DIGIT`.
+pub(crate) static DIGIT_VALUES: [u8; 256] = digit_values();
+
+const NOT_A_DIGIT: u8 = 0xff;
+
+const fn digit_values() -> [u8; 256] {
+    let mut values = [NOT_A_DIGIT; 256];
+    let mut i = 0;
+    while i < DIGITS.len() {
+        values[DIGITS[i] as usize] = i as u8;
+        i += 1;
+    }
+    values
+}
+
 pub fn ascii_digit_to_value(ascii_char: u8, radix: u8) -> Option<u64> {
-    let value = match ascii_char {
-        b'0'..=b'9' => ascii_char - b'0',
-        b'a'..=b'z' => (ascii_char - b'a') + 10,
-        b'A'..=b'Z' => (ascii_char - b'A') + 36,
-        _ => return None,
-    };
+    let value = DIGIT_VALUES[ascii_char as usize];
 
     if value < radix {
         Some(value as u64)
@@ -55,7 +67,6 @@ pub fn ascii_digit_to_value(ascii_char: u8, radix: u8) -> Option<u64> {
 mod tests {
     use super::*;
     use std::str;
-    use std::u64;
 
     #[test]
     fn ascii_digit_to_value_cross_check() {
@@ -0,0 +1,131 @@
+//! Sharing of identifiers between symbols.
+//!
+//! The same crate and module names occur in thousands of symbols of a single
+//! binary. An `Interner` keeps one copy of every identifier it has seen and
+//! makes all symbols parsed through it point to that copy.
+
+use ast::*;
+use fold::{self, Folder};
+use parse::{self, Parser};
+use std::collections::HashSet;
+
+#[derive(Default, Debug)]
+pub struct Interner {
+    idents: HashSet<Ptr<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Returns the shared copy of `ident`, adding it if it has not been seen
+    /// before.
+    pub fn intern(&mut self, ident: &str) -> Ptr<str> {
+        if let Some(ident) = self.idents.get(ident) {
+            return ident.clone();
+        }
+
+        let ident: Ptr<str> = ident.into();
+        self.idents.insert(ident.clone());
+        ident
+    }
+
+    /// The number of distinct identifiers interned so far.
+    pub fn len(&self) -> usize {
+        self.idents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idents.is_empty()
+    }
+
+    /// Parses a mangled symbol name with all identifiers interned as they
+    /// are parsed, so identifiers seen before are not allocated again.
+    pub fn parse(&mut self, mangled: &str) -> Result<Symbol, String> {
+        parse::parse_with(Parser::new(mangled.as_bytes()).interning(self))
+    }
+
+    /// Returns a copy of `symbol` with all identifiers interned.
+    pub fn intern_symbol(&mut self, symbol: &Symbol) -> Symbol {
+        InternFolder(self).fold_symbol(symbol)
+    }
+
+    fn intern_uident(&mut self, ident: &UIdent) -> UIdent {
+        UIdent(self.intern(&ident.0))
+    }
+}
+
+struct InternFolder<'i>(&'i mut Interner);
+
+impl<'i> Folder for InternFolder<'i> {
+    fn fold_ident(&mut self, ident: &Ident) -> Ident {
+        Ident {
+            dis: ident.dis,
+            u_ident: self.0.intern_uident(&ident.u_ident),
+        }
+    }
+
+    fn fold_fn_sig(&mut self, fn_sig: &FnSig) -> FnSig {
+        let mut folded = fold::walk_fn_sig(self, fn_sig);
+        if let Some(Abi::Named(ref mut name)) = folded.abi {
+            *name = self.0.intern_uident(name);
+        }
+        folded
+    }
+
+    fn fold_dyn_trait_assoc_binding(
+        &mut self,
+        binding: &DynTraitAssocBinding,
+    ) -> DynTraitAssocBinding {
+        DynTraitAssocBinding {
+            ident: self.0.intern_uident(&binding.ident),
+            ty: self.fold_type(&binding.ty),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+    use ast::{GenericArg, Path, Ptr, Symbol, Type};
+
+    #[test]
+    fn identifiers_are_shared_between_symbols() {
+        let mut interner = Interner::new();
+        let a = interner.parse("_RNxC7mycrate4quux").unwrap();
+        let b = interner.parse("_RNxC7mycrate3baz").unwrap();
+
+        let crate_name = |symbol: &::ast::Symbol| match symbol.path {
+            Path::Nested { ref inner, .. } => match **inner {
+                Path::CrateRoot { ref id } => id.u_ident.0.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        assert!(Ptr::ptr_eq(&crate_name(&a), &crate_name(&b)));
+        assert_eq!(3, interner.len());
+    }
+
+    #[test]
+    fn backrefs_stay_shared() {
+        // a::f::<b::Foo<u8>, b::Foo<u8>, b::Foo<u8>>, with the last two
+        // arguments backrefs to the first, which resolve to the same node.
+        let mangled = Symbol::parse("_RINvC1a1fINtC1b3FoohEINtC1b3FoohEINtC1b3FoohEE")
+            .unwrap()
+            .mangle();
+        let symbol = Interner::new().parse(&mangled).unwrap();
+
+        let args = match symbol.path {
+            Path::Generic { ref args, .. } => args,
+            _ => unreachable!(),
+        };
+        match (&args[1], &args[2]) {
+            (&GenericArg::Type(Type::Named(ref a)), &GenericArg::Type(Type::Named(ref b))) => {
+                assert!(Ptr::ptr_eq(a, b))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
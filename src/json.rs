@@ -0,0 +1,35 @@
+//! Conversion between `ast::Symbol` and JSON.
+//!
+//! The JSON form mirrors the AST one to one: structs become objects, enum
+//! variants objects with a single key naming the variant, and newtypes their
+//! contents. Namespaces are given by the ASCII code of their tag.
+
+use ast::Symbol;
+use serde_json;
+
+impl Symbol {
+    /// Serializes the AST of this symbol to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Deserializes the AST of a symbol from JSON, as produced by `to_json()`.
+    pub fn from_json(json: &str) -> Result<Symbol, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid symbol AST: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Symbol;
+
+    #[test]
+    fn json_round_trip() {
+        let symbol = Symbol::parse("_RINvNtC3std3mem8align_ofjEC3foo").unwrap();
+        let json = symbol.to_json();
+
+        assert!(json.contains(r#""CrateRoot":{"id":{"dis":0,"u_ident":"std"}}"#));
+        assert_eq!(Ok(symbol), Symbol::from_json(&json));
+        assert!(Symbol::from_json(r#"{"version":null}"#).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+//! Demangling of symbols in the legacy Rust mangling scheme.
+//!
+//! Legacy symbols are Itanium-style nested names, `_ZN` followed by
+//! length-prefixed path components and an `E`. The last component usually is
+//! a hash of the form `h` + 16 hex digits, which is left out of the demangled
+//! form. Characters that are not valid in C++ identifiers are escaped as
+//! `$..$` sequences and `::` within components as `..`.
+
+use ast::{Disambiguator, Ident, Namespace, Path, Ptr, Symbol, UIdent};
+use ast_demangle::AstDemangle;
+use std::fmt::Write;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use std::str;
+
+/// Demangles a legacy symbol such as `_ZN4core3fmt5write17h0123456789abcdefE`
+/// into `core::fmt::write`.
+pub fn demangle(mangled: &str) -> Result<String, String> {
+    let (demangled, len) = demangle_prefix(mangled)?;
+
+    if len != mangled.len() {
+        return Err(format!("unexpected input after symbol at position {}", len));
+    }
+
+    Ok(demangled)
+}
+
+/// Demangles the legacy symbol at the start of `mangled` and returns it
+/// together with the length of the mangled symbol.
+pub(crate) fn demangle_prefix(mangled: &str) -> Result<(String, usize), String> {
+    let (mut components, len) = split_prefix(mangled)?;
+
+    if components.len() > 1 && is_hash(components[components.len() - 1]) {
+        components.pop();
+    }
+
+    let mut out = String::new();
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            out.push_str("::");
+        }
+        unescape(component, &mut out)?;
+    }
+
+    Ok((out, len))
+}
+
+/// Converts a legacy symbol into a v0 one, so that `.mangle()` gives the
+/// `_R` symbol for it.
+///
+/// This is best-effort, since legacy symbols lack most of what v0 encodes:
+/// the first component becomes the crate, the last one a value and the
+/// others types, with the hash as the disambiguator of the last one. Impls
+/// like `<Foo as Bar>` stay single components, with their `::`s.
+pub fn to_v0(mangled: &str) -> Result<Symbol, String> {
+    let (mut components, len) = split_prefix(mangled)?;
+
+    if len != mangled.len() {
+        return Err(format!("unexpected input after symbol at position {}", len));
+    }
+
+    let mut hash = 0;
+    if components.len() > 1 && is_hash(components[components.len() - 1]) {
+        hash = u64::from_str_radix(&components.pop().unwrap()[1..], 16).unwrap();
+    }
+
+    let ident = |component: &str, dis| -> Result<Ident, String> {
+        let mut name = String::new();
+        unescape(component, &mut name)?;
+        Ok(Ident {
+            dis: Disambiguator(dis),
+            u_ident: UIdent(name.into()),
+        })
+    };
+
+    let last = components.len() - 1;
+    let mut path = Path::CrateRoot {
+        id: ident(components[0], if last == 0 { hash } else { 0 })?,
+    };
+    for (i, component) in components.iter().enumerate().skip(1) {
+        let (ns, dis) = if i == last { (b'x', hash) } else { (b'y', 0) };
+        path = Path::Nested {
+            ns: Namespace(ns),
+            inner: Ptr::new(path),
+            ident: ident(component, dis)?,
+        };
+    }
+
+    Ok(Symbol {
+        version: None,
+        path,
+        instantiating_crate: None,
+    })
+}
+
+/// Converts a v0 symbol into a legacy one, for tools that only know `_ZN`
+/// symbols.
+///
+/// Generic arguments become part of the component they belong to, as in
+/// `drop_in_place$LT$u8$GT$`, impls become `<Foo as Bar>` components, and
+/// disambiguators and the instantiating crate are left out. The hash is
+/// `hash`, or else one derived from the v0 symbol, so that symbols that only
+/// differ in what was left out still get different names.
+pub fn from_v0(symbol: &Symbol, hash: Option<u64>) -> String {
+    let hash = hash.unwrap_or_else(|| {
+        symbol
+            .mangle()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    });
+
+    let mut components = Vec::new();
+    collect_components(&symbol.erase_disambiguators().path, &mut components);
+
+    let mut out = "_ZN".to_string();
+    for component in components {
+        let escaped = escape(&component);
+        write!(out, "{}{}", escaped.len(), escaped).unwrap();
+    }
+    write!(out, "17h{:016x}E", hash).unwrap();
+    out
+}
+
+/// Appends the unescaped legacy components of `path` to `out`.
+fn collect_components(path: &Path, out: &mut Vec<String>) {
+    match *path {
+        Path::CrateRoot { ref id } => out.push(id.u_ident.0.to_string()),
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            collect_components(inner, out);
+            if *ns == Namespace(b'C') {
+                out.push("{{closure}}".to_string());
+            } else if !ident.u_ident.0.is_empty() {
+                out.push(ident.u_ident.0.to_string());
+            }
+        }
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => {
+            collect_components(inner, out);
+            let last = out.last_mut().unwrap();
+            last.push('<');
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    last.push(',');
+                }
+                arg.demangle_to_string(last);
+            }
+            last.push('>');
+        }
+        Path::InherentImpl { .. } | Path::TraitImpl { .. } | Path::TraitDef { .. } => {
+            out.push(path.demangle());
+        }
+    }
+}
+
+/// Escapes `component` the way rustc's legacy mangling does, which `unescape`
+/// reverses.
+fn escape(component: &str) -> String {
+    let mut out = String::new();
+    let mut rest = component;
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("::") {
+            out.push_str("..");
+            rest = &rest[2..];
+            continue;
+        }
+
+        match c {
+            '@' => out.push_str("$SP$"),
+            '*' => out.push_str("$BP$"),
+            '&' => out.push_str("$RF$"),
+            '<' => out.push_str("$LT$"),
+            '>' => out.push_str("$GT$"),
+            '(' => out.push_str("$LP$"),
+            ')' => out.push_str("$RP$"),
+            ',' => out.push_str("$C$"),
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' => out.push(c),
+            _ => write!(out, "$u{:x}$", c as u32).unwrap(),
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if out.starts_with('$') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Splits the legacy symbol at the start of `mangled` into its raw path
+/// components and returns them together with the length of the mangled
+/// symbol.
+fn split_prefix(mangled: &str) -> Result<(Vec<&str>, usize), String> {
+    let rest = strip_prefix(mangled).ok_or_else(|| "Not a legacy Rust symbol".to_string())?;
+
+    let mut components = Vec::new();
+    let mut rest = rest.as_bytes();
+
+    while rest.first() != Some(&b'E') {
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return Err("expected the length of a path component".to_string());
+        }
+
+        let len: usize = str::from_utf8(&rest[..digits])
+            .unwrap()
+            .parse()
+            .map_err(|_| "path component length does not fit into usize".to_string())?;
+        rest = &rest[digits..];
+
+        if len > rest.len() {
+            return Err("path component extends beyond end of input".to_string());
+        }
+
+        let component = str::from_utf8(&rest[..len])
+            .map_err(|_| "path component length splits a character".to_string())?;
+        components.push(component);
+        rest = &rest[len..];
+    }
+
+    // The `E`
+    let len = mangled.len() - rest.len() + 1;
+
+    if components.is_empty() {
+        return Err("legacy symbol without path components".to_string());
+    }
+
+    Ok((components, len))
+}
+
+/// Strips `_ZN`, along with the extra underscore of macOS symbols.
+fn strip_prefix(mangled: &str) -> Option<&str> {
+    let mangled = if mangled.starts_with("__ZN") {
+        &mangled[1..]
+    } else {
+        mangled
+    };
+
+    mangled.strip_prefix("_ZN")
+}
+
+fn is_hash(component: &str) -> bool {
+    component.len() == 17
+        && component.starts_with('h')
+        && component[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn unescape(component: &str, out: &mut String) -> Result<(), String> {
+    let mut rest = component;
+
+    // A leading `_` only keeps a component that starts with an escape from
+    // starting with `$`.
+    if rest.starts_with("_$") {
+        rest = &rest[1..];
+    }
+
+    while !rest.is_empty() {
+        if rest.starts_with('$') {
+            let end = rest[1..]
+                .find('$')
+                .ok_or_else(|| format!("unterminated escape in {:?}", component))?;
+            let escape = &rest[1..=end];
+
+            out.push(match escape {
+                "SP" => '@',
+                "BP" => '*',
+                "RF" => '&',
+                "LT" => '<',
+                "GT" => '>',
+                "LP" => '(',
+                "RP" => ')',
+                "C" => ',',
+                _ if escape.starts_with('u') => u32::from_str_radix(&escape[1..], 16)
+                    .ok()
+                    .and_then(::std::char::from_u32)
+                    .ok_or_else(|| format!("invalid escape ${}$", escape))?,
+                _ => return Err(format!("invalid escape ${}$", escape)),
+            });
+
+            rest = &rest[end + 2..];
+        } else if rest.starts_with("..") {
+            out.push_str("::");
+            rest = &rest[2..];
+        } else {
+            let end = rest
+                .find(['$', '.'])
+                .map_or(rest.len(), |end| if end == 0 { 1 } else { end });
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, from_v0, to_v0};
+    use ast::{Path, Symbol};
+
+    #[test]
+    fn demangle_legacy_symbols() {
+        for &(mangled, demangled) in &[
+            ("_ZN4core3fmt5write17h0123456789abcdefE", "core::fmt::write"),
+            ("__ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE", "std::io::stdio::_print"),
+            ("_ZN3foo3barE", "foo::bar"),
+            (
+                "_ZN60_$LT$alloc..string..String$u20$as$u20$core..fmt..Display$GT$3fmt17h5f4c2d1e0a9b8c7dE",
+                "<alloc::string::String as core::fmt::Display>::fmt",
+            ),
+            ("_ZN4core3ptr13drop.in.place17h0000000000000000E", "core::ptr::drop.in.place"),
+        ] {
+            assert_eq!(Ok(demangled.to_string()), demangle(mangled));
+        }
+
+        for &mangled in &[
+            "_ZN",
+            "_ZNE",
+            "_ZN3fooE3",
+            "_ZN9fooE",
+            "_ZN4$XX$E",
+            "_ZN3$LTE",
+            "_RNvC3foo3bar",
+        ] {
+            assert!(demangle(mangled).is_err(), "{}", mangled);
+        }
+    }
+
+    #[test]
+    fn convert_to_v0() {
+        let symbol = to_v0("_ZN4core3fmt5write17h0123456789abcdefE").unwrap();
+        let mangled = symbol.mangle();
+        assert!(mangled.starts_with("_R"), "{}", mangled);
+        assert_eq!(symbol, Symbol::parse(&mangled).unwrap());
+        assert_eq!("core::fmt::write[81985529216486895]", symbol.demangle());
+        match symbol.path {
+            Path::Nested { ref ident, .. } => assert_eq!(0x0123_4567_89ab_cdef, ident.dis.0),
+            ref path => panic!("{:?}", path),
+        }
+
+        let symbol = to_v0("_ZN12panic_unwind3imp5panicE").unwrap();
+        assert_eq!("_RNxNyC12panic_unwind3imp5panic", symbol.mangle());
+
+        assert!(to_v0("_ZN3fooE3").is_err());
+    }
+
+    #[test]
+    fn convert_from_v0() {
+        let symbol = Symbol::parse("_RINxNyC4core3ptr13drop_in_placeNyCs4_5alloc6StringE").unwrap();
+        let mangled = from_v0(&symbol, Some(0x0123_4567_89ab_cdef));
+        assert_eq!(
+            "_ZN4core3ptr34drop_in_place$LT$alloc..String$GT$17h0123456789abcdefE",
+            mangled
+        );
+        assert_eq!(
+            Ok("core::ptr::drop_in_place<alloc::String>".to_string()),
+            demangle(&mangled)
+        );
+
+        let symbol = Symbol::parse("_RNCNxMs_C1aNyC3foo3Bar3new0").unwrap();
+        assert_eq!(
+            Ok("<foo::Bar>::new::{{closure}}".to_string()),
+            demangle(&from_v0(&symbol, None))
+        );
+
+        let other = Symbol::parse("_RNCNxMs0_C1aNyC3foo3Bar3new0").unwrap();
+        assert_ne!(from_v0(&symbol, None), from_v0(&other, None));
+    }
+}
@@ -1,4 +1,61 @@
-extern crate unic_idna_punycode as punycode;
+//! Parsing, demangling and mangling of Rust symbols.
+//!
+//! Without the default `std` feature, the crate only needs `core` and
+//! `alloc`, e.g. for panic handlers of kernels and embedded programs that
+//! demangle their own symbols. Parsing, demangling, mangling, the legacy
+//! demangler, and everything that works on one symbol at a time remain
+//! available. Batch processing, caches and interners, statistics, diffs, and
+//! the C interface need `std`, as do all other optional features.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+extern crate smallvec;
+
+#[cfg(feature = "addr2line")]
+extern crate addr2line;
+#[cfg(feature = "backtrace")]
+extern crate backtrace;
+#[cfg(feature = "arena")]
+extern crate bumpalo;
+#[cfg(feature = "cxx")]
+extern crate cxx;
+
+#[cfg(feature = "wasm")]
+extern crate js_sys;
+#[cfg(feature = "node")]
+extern crate napi;
+#[cfg(feature = "node")]
+#[macro_use]
+extern crate napi_derive;
+#[cfg(feature = "object")]
+extern crate object;
+#[cfg(feature = "syn")]
+extern crate proc_macro2;
+// The code generated by `pyo3` refers to `::core`.
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "syn")]
+extern crate syn;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "component")]
+extern crate wit_bindgen;
+
+// `rc` makes the AST `!Send`, which features for parallel use must not be
+// built with.
+#[cfg(all(feature = "rc", feature = "rayon"))]
+compile_error!("the `rc` feature makes symbols `!Send` and cannot be combined with `rayon`");
 
 #[cfg(test)]
 #[macro_use]
@@ -6,17 +63,122 @@ extern crate quickcheck;
 #[cfg(test)]
 extern crate rand;
 
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "arena")]
+pub mod arena;
 pub mod ast;
 pub mod ast_demangle;
+mod ast_mangle;
+#[cfg(feature = "backtrace")]
+pub mod backtraces;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "object")]
+pub mod binary;
+pub mod borrowed;
 pub mod parse;
+mod punycode;
+pub mod visit;
 
 mod charset;
+mod collections;
+pub mod compat;
+#[cfg(feature = "component")]
+mod component;
+pub mod cpp_style;
+#[cfg(feature = "cxx")]
+mod cxx_bridge;
+#[cfg(feature = "def-path")]
+pub mod def_path;
+mod depth;
+pub mod dictionary;
+#[cfg(feature = "std")]
+pub mod diff;
+pub mod dot;
+#[cfg(feature = "addr2line")]
+pub mod dwarf;
 mod error;
+pub mod explain;
+#[cfg(feature = "std")]
+pub mod ffi;
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod flat;
+pub mod fold;
+pub mod generate;
+mod grammar;
+#[cfg(feature = "std")]
+pub mod hash_cons;
+pub mod highlight;
+pub mod index;
+pub mod info;
+mod inspect;
+pub mod instantiate;
 pub mod int_radix;
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "json")]
+mod json;
+pub mod legacy;
+pub mod limits;
+pub mod linkage;
+#[cfg(feature = "std")]
+mod lru;
+pub mod metrics;
+#[cfg(feature = "node")]
+mod node;
+pub mod normalize;
+pub mod pattern;
+#[cfg(feature = "profile")]
+pub mod profile;
+pub mod protobuf;
+#[cfg(feature = "python")]
+mod python;
+pub mod rewrite;
+pub mod scheme;
+#[cfg(feature = "std")]
+pub mod shared;
+mod skeleton;
+pub mod stable;
+#[cfg(feature = "std")]
+pub mod stats;
+pub mod symbolic;
+#[cfg(feature = "syn")]
+mod syn_interop;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wire;
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+mod std {
+    //! The parts of `std` that are in `core` and `alloc`, so that modules can
+    //! use the same paths with and without the `std` feature.
+
+    pub use alloc::{borrow, boxed, collections, fmt, rc, string, sync, vec};
+    pub use core::*;
+
+    pub mod prelude {
+        pub mod v1 {
+            pub use alloc::borrow::ToOwned;
+            pub use alloc::boxed::Box;
+            pub use alloc::string::{String, ToString};
+            pub use alloc::vec::Vec;
+        }
+    }
+}
 
 #[cfg(test)]
 mod generated_tests;
 
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+pub use ast_mangle::Compress;
+pub use error::Error;
+
 /// Construct the AST for a mangled symbol name.
 pub fn mangled_symbol_to_ast(mangled_symbol: &str) -> Result<ast::Symbol, String> {
     parse::parse(mangled_symbol.as_bytes())
@@ -26,3 +188,52 @@ pub fn mangled_symbol_to_ast(mangled_symbol: &str) -> Result<ast::Symbol, String
 pub fn ast_to_demangled_symbol(symbol_ast: &ast::Symbol) -> String {
     ast_demangle::AstDemangle::demangle(symbol_ast)
 }
+
+/// Mangles a symbol name's AST, with all possible backrefs. `Compress` also
+/// mangles without them. Fails if an identifier cannot be punycode-encoded.
+pub fn mangle(symbol_ast: &ast::Symbol) -> Result<String, String> {
+    Compress::new().mangle(symbol_ast)
+}
+
+/// Demangles a v0 symbol name, which starts with `_R`. Backrefs are
+/// resolved while parsing, so this is all it takes to go from a symbol to
+/// its human-readable form.
+pub fn demangle(mangled_symbol: &str) -> Result<String, Error> {
+    parse::parse_at(parse::Parser::new(mangled_symbol.as_bytes()))
+        .map(|symbol_ast| ast_to_demangled_symbol(&symbol_ast))
+        .map_err(|(position, message)| Error::new(position, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, mangle, mangled_symbol_to_ast, Compress};
+
+    #[test]
+    fn demangle_symbol() {
+        assert_eq!(
+            Ok("mycrate::foo<other::Bar,other::Bar>".to_string()),
+            demangle("_RINxC7mycrate3fooNyC5other3BarBf_E")
+        );
+        let error = demangle("_RNvC7mycrate").unwrap_err();
+        let _: &dyn std::error::Error = &error;
+        assert_eq!(13, error.position());
+        assert_eq!(
+            format!("at position 13: {}", error.message()),
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn mangle_symbol() {
+        let symbol =
+            mangled_symbol_to_ast("_RINxC7mycrate3fooNyC5other3BarNyC5other3BarE").unwrap();
+        assert_eq!(
+            Ok("_RINxC7mycrate3fooNyC5other3BarBf_E".to_string()),
+            mangle(&symbol)
+        );
+        assert_eq!(
+            Ok("_RINxC7mycrate3fooNyC5other3BarNyC5other3BarE".to_string()),
+            Compress::uncompressed().mangle(&symbol)
+        );
+    }
+}
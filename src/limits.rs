@@ -0,0 +1,212 @@
+//! Budgets that bound the work done on a single symbol.
+//!
+//! Backrefs let a short symbol describe a tree that is exponentially larger
+//! than the symbol itself. The parsers refuse to expand a symbol beyond
+//! `ResourceLimits`, counting every path and type and every byte of every
+//! identifier again each time a backref pulls it in. `Symbol::parse()`
+//! applies `ResourceLimits::default()`, so the demangled form of any symbol
+//! it returns is at most `max_output_bytes` plus a few bytes for each of at
+//! most `max_nodes` nodes long. `Symbol::demangle_with_limits()` stops
+//! writing at exactly `max_output_bytes`, which also bounds symbols built by
+//! hand or parsed with `ResourceLimits::UNLIMITED`.
+//!
+//! Long symbols are fine as long as they stay within these limits. Every
+//! path and type takes up at least one byte of the symbol, so the default
+//! budget of 2^20 of them admits any symbol of up to 1 MiB that does not use
+//! backrefs, and typical symbols of several MiB. Parsing, demangling, and
+//! mangling are linear in the size of the symbol, apart from the
+//! substitution lookups of the mangler, which hash every path and type with
+//! its whole subtree and are therefore proportional to size times nesting
+//! depth. Nesting is bounded by `parse::MAX_DEPTH` since all three recurse,
+//! which keeps them within a stack of `parse::STACK_SIZE`.
+
+use ast::Symbol;
+use ast_demangle;
+use parse::{self, Parser, MAX_DEPTH};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ResourceLimits {
+    /// The maximum nesting depth of paths and types.
+    pub max_depth: u32,
+    /// The maximum number of paths and types in a symbol, counting every
+    /// node again each time a backref pulls it in.
+    pub max_nodes: usize,
+    /// The maximum length of a demangled symbol, in bytes. The parsers
+    /// apply it to the total length of the identifiers, counting every
+    /// identifier again each time a backref pulls it in.
+    pub max_output_bytes: usize,
+}
+
+impl ResourceLimits {
+    /// No limits beyond what fits into memory and the stack. Only meant for
+    /// trusted input.
+    pub const UNLIMITED: ResourceLimits = ResourceLimits {
+        max_depth: MAX_DEPTH,
+        max_nodes: usize::MAX,
+        max_output_bytes: usize::MAX,
+    };
+}
+
+impl Default for ResourceLimits {
+    /// The limits applied by `Symbol::parse()`, which are far beyond what
+    /// compilers produce.
+    fn default() -> ResourceLimits {
+        ResourceLimits {
+            max_depth: MAX_DEPTH,
+            max_nodes: 1 << 20,
+            max_output_bytes: 1 << 24,
+        }
+    }
+}
+
+impl Symbol {
+    /// Parses a mangled symbol name, failing if it is nested more deeply or
+    /// expands to more nodes than `limits` allow. `max_depth` is capped at
+    /// `parse::MAX_DEPTH`.
+    pub fn parse_with_limits(mangled: &str, limits: &ResourceLimits) -> Result<Symbol, String> {
+        parse::parse_with(Parser::with_limits(mangled.as_bytes(), *limits))
+    }
+
+    /// Like `demangle()`, but fails if the output would be longer than
+    /// `limits.max_output_bytes`, or if the symbol is nested more deeply
+    /// than `limits.max_depth`. Stops writing as soon as the output reaches
+    /// the limit, so a symbol built by hand or parsed with
+    /// `ResourceLimits::UNLIMITED` cannot make it allocate more than that.
+    pub fn demangle_with_limits(&self, limits: &ResourceLimits) -> Result<String, String> {
+        self.check_depth(limits.max_depth)?;
+
+        ast_demangle::demangle_bounded(self, limits.max_output_bytes).ok_or_else(|| {
+            format!(
+                "demangled symbol longer than {} bytes",
+                limits.max_output_bytes
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResourceLimits;
+    use ast::Symbol;
+    use borrowed;
+    use int_radix;
+
+    fn backref(pos: usize) -> String {
+        if pos == 0 {
+            "B_".to_string()
+        } else {
+            format!("B{}_", int_radix::radix(62, (pos - 1) as u64))
+        }
+    }
+
+    /// `foo::bar<(u, u), (T0, T0), (T1, T1), ..>`, where every `Tn` is a
+    /// backref to the previous argument, so the tree doubles in size with
+    /// every argument while the symbol only grows by a few bytes.
+    fn amplifying_symbol(levels: usize) -> String {
+        let mut mangled = String::from("_RINvC3foo3bar");
+        let mut prev = mangled.len() - 2;
+        mangled.push_str("TuuE");
+
+        for _ in 0..levels {
+            let pos = mangled.len() - 2;
+            let arg = backref(prev);
+            mangled.push_str(&format!("T{}{}E", arg, arg));
+            prev = pos;
+        }
+
+        mangled.push('E');
+        mangled
+    }
+
+    /// `foo::bar<(T0, T0), (T1, T1), ..>` like `amplifying_symbol()`, but
+    /// starting with a type named by a 60000-byte identifier, so that the
+    /// output grows by megabytes with every level while the number of nodes
+    /// stays small.
+    fn amplifying_ident_symbol(levels: usize) -> String {
+        let mut mangled = String::from("_RINvC3foo3bar");
+        let mut prev = mangled.len() - 2;
+        mangled.push_str(&format!("NvC1a60000{}", "x".repeat(60000)));
+
+        for _ in 0..levels {
+            let pos = mangled.len() - 2;
+            let arg = backref(prev);
+            mangled.push_str(&format!("T{}{}E", arg, arg));
+            prev = pos;
+        }
+
+        mangled.push('E');
+        mangled
+    }
+
+    #[test]
+    fn megabyte_symbol_end_to_end() {
+        // foo::bar<&[core::iter::T0],&[core::iter::T1],..>
+        let mut mangled = String::from("_RINvC3foo3bar");
+        let mut i = 0;
+        while mangled.len() < 1 << 20 {
+            let name = format!("T{}", i);
+            mangled.push_str(&format!("RSNvNtC4core4iter{}{}", name.len(), name));
+            i += 1;
+        }
+        mangled.push('E');
+
+        let symbol = Symbol::parse(&mangled).unwrap();
+        let demangled = symbol
+            .demangle_with_limits(&ResourceLimits::default())
+            .unwrap();
+        assert!(demangled.starts_with("foo::bar<&[core::iter::T0],&[core::iter::T1],"));
+        assert!(demangled.ends_with(&format!("&[core::iter::T{}]>", i - 1)));
+
+        // The mangler emits backrefs for the repeated `core::iter`.
+        let remangled = symbol.mangle();
+        assert!(remangled.len() < mangled.len());
+        assert_eq!(symbol, Symbol::parse(&remangled).unwrap());
+    }
+
+    #[test]
+    fn reject_backref_amplification() {
+        let limits = ResourceLimits {
+            max_nodes: 1000,
+            max_output_bytes: 1000,
+            ..ResourceLimits::default()
+        };
+
+        let small = amplifying_symbol(4);
+        let symbol = Symbol::parse_with_limits(&small, &limits).unwrap();
+        assert!(symbol.demangle_with_limits(&limits).is_ok());
+
+        let large = amplifying_symbol(12);
+        assert!(Symbol::parse_with_limits(&large, &limits)
+            .unwrap_err()
+            .contains("more than 1000 nodes"));
+
+        let symbol = Symbol::parse(&large).unwrap();
+        assert!(symbol.demangle_with_limits(&limits).is_err());
+
+        let huge = amplifying_symbol(40);
+        assert!(Symbol::parse(&huge).is_err());
+        assert!(borrowed::parse(huge.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reject_identifier_amplification() {
+        let small = amplifying_ident_symbol(2);
+        let symbol = Symbol::parse(&small).unwrap();
+        assert!(symbol.demangle().len() > 4 * 60000);
+
+        // Demangles to almost 1 GB.
+        let large = amplifying_ident_symbol(14);
+        assert!(Symbol::parse(&large)
+            .unwrap_err()
+            .contains("longer than 16777216 bytes"));
+        assert!(borrowed::parse(large.as_bytes()).is_err());
+
+        let symbol = Symbol::parse_with_limits(&large, &ResourceLimits::UNLIMITED).unwrap();
+        assert!(symbol
+            .demangle_with_limits(&ResourceLimits::default())
+            .unwrap_err()
+            .contains("longer than 16777216 bytes"));
+    }
+}
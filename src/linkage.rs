@@ -0,0 +1,156 @@
+//! Reconciling the `DW_AT_linkage_name` and `DW_AT_name` attributes of DWARF
+//! subprograms, for debug-info validators that audit compiler output.
+//!
+//! The `DW_AT_name` of a Rust function is the last segment of the path its
+//! linkage name demangles to, without generic arguments, e.g. `foo` for
+//! `mycrate::foo<u8>` and `{closure#0}` for its first closure.
+
+use ast::*;
+use compat;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The result of `reconcile()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// The name to show for the subprogram: the demangled linkage name if
+    /// there is one that demangles, otherwise whichever attribute there is.
+    pub name: String,
+    pub correspondence: Correspondence,
+}
+
+/// How the two attributes relate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Correspondence {
+    /// The `DW_AT_name` is the last segment of the demangled linkage name.
+    Match,
+    /// The demangled linkage name ends in `expected` instead of the
+    /// `DW_AT_name`.
+    Mismatch { expected: String },
+    /// There is no `DW_AT_name` to compare the linkage name with.
+    LinkageNameOnly,
+    /// There is no `DW_AT_linkage_name`.
+    NameOnly,
+    /// The linkage name is not a Rust symbol, or its last segment is an impl,
+    /// so the attributes cannot be compared.
+    Unverifiable,
+}
+
+impl Correspondence {
+    /// Whether nothing is known to be wrong.
+    pub fn is_consistent(&self) -> bool {
+        !matches!(*self, Correspondence::Mismatch { .. })
+    }
+}
+
+/// Reconciles the linkage name and the name of a subprogram, or returns
+/// `None` if it has neither.
+pub fn reconcile(linkage_name: Option<&str>, name: Option<&str>) -> Option<Reconciliation> {
+    let linkage_name = match linkage_name {
+        Some(linkage_name) => linkage_name,
+        None => {
+            return name.map(|name| Reconciliation {
+                name: name.to_string(),
+                correspondence: Correspondence::NameOnly,
+            })
+        }
+    };
+
+    let demangled = match compat::try_demangle(linkage_name) {
+        Ok(demangled) => demangled,
+        Err(_) => {
+            return Some(Reconciliation {
+                name: name.unwrap_or(linkage_name).to_string(),
+                correspondence: if name.is_some() {
+                    Correspondence::Unverifiable
+                } else {
+                    Correspondence::LinkageNameOnly
+                },
+            })
+        }
+    };
+
+    let expected = match demangled.v0_symbol() {
+        Some(symbol) => last_segment(&symbol.path),
+        // Legacy symbols end in the function name, or `{{closure}}`.
+        None => format!("{:#}", demangled)
+            .rsplit("::")
+            .next()
+            .map(str::to_string),
+    };
+
+    let correspondence = match (name, expected) {
+        (None, _) => Correspondence::LinkageNameOnly,
+        (Some(_), None) => Correspondence::Unverifiable,
+        (Some(name), Some(expected)) => {
+            // Older compilers leave out the index of closures.
+            if name == expected || (name == "{closure}" && expected.starts_with("{closure#")) {
+                Correspondence::Match
+            } else {
+                Correspondence::Mismatch { expected }
+            }
+        }
+    };
+
+    Some(Reconciliation {
+        name: format!("{:#}", demangled),
+        correspondence,
+    })
+}
+
+/// The `DW_AT_name` rustc gives to the item at `path`, if it has one.
+fn last_segment(path: &Path) -> Option<String> {
+    match *path {
+        Path::CrateRoot { ref id } => Some(id.u_ident.0.to_string()),
+        Path::Nested {
+            ref ns, ref ident, ..
+        } => {
+            if *ns == Namespace(b'C') {
+                Some(format!("{{closure#{}}}", ident.dis))
+            } else if ident.u_ident.0.is_empty() {
+                None
+            } else {
+                Some(ident.u_ident.0.to_string())
+            }
+        }
+        Path::Generic { ref inner, .. } => last_segment(inner),
+        Path::InherentImpl { .. } | Path::TraitImpl { .. } | Path::TraitDef { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconcile, Correspondence};
+
+    #[test]
+    fn reconcile_name_pairs() {
+        let generic = reconcile(Some("_RINvCs0_7mycrate3foohE"), Some("foo")).unwrap();
+        assert_eq!("mycrate::foo<u8>", generic.name);
+        assert_eq!(Correspondence::Match, generic.correspondence);
+
+        let closure = reconcile(Some("_RNCNvC7mycrate3foo0"), Some("{closure#0}")).unwrap();
+        assert_eq!(Correspondence::Match, closure.correspondence);
+
+        let legacy = reconcile(
+            Some("_ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE"),
+            Some("print"),
+        )
+        .unwrap();
+        assert_eq!("std::io::stdio::_print", legacy.name);
+        assert_eq!(
+            Correspondence::Mismatch {
+                expected: "_print".to_string()
+            },
+            legacy.correspondence
+        );
+        assert!(!legacy.correspondence.is_consistent());
+
+        let c = reconcile(Some("memcpy"), Some("memcpy")).unwrap();
+        assert_eq!(Correspondence::Unverifiable, c.correspondence);
+        assert_eq!(
+            Correspondence::NameOnly,
+            reconcile(None, Some("main")).unwrap().correspondence
+        );
+        assert_eq!(None, reconcile(None, None));
+    }
+}
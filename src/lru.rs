@@ -0,0 +1,131 @@
+//! A small least-recently-used cache.
+
+use std::collections::HashMap;
+
+const NIL: usize = usize::MAX;
+
+/// A map from strings to values holding at most `capacity` entries. Inserting
+/// into a full cache evicts the entry that was used least recently.
+#[derive(Debug)]
+pub struct LruCache<V> {
+    map: HashMap<String, usize>,
+    entries: Vec<Entry<V>>,
+    /// The most recently used entry.
+    head: usize,
+    /// The least recently used entry.
+    tail: usize,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    key: String,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+impl<V> LruCache<V> {
+    pub fn new(capacity: usize) -> LruCache<V> {
+        assert!(capacity > 0);
+
+        LruCache {
+            map: HashMap::new(),
+            entries: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<&V> {
+        let index = *self.map.get(key)?;
+        self.unlink(index);
+        self.push_front(index);
+        Some(&self.entries[index].value)
+    }
+
+    pub fn insert(&mut self, key: &str, value: V) -> &V {
+        if let Some(&index) = self.map.get(key) {
+            self.entries[index].value = value;
+            self.unlink(index);
+            self.push_front(index);
+            return &self.entries[index].value;
+        }
+
+        let index = if self.entries.len() < self.capacity {
+            self.entries.push(Entry {
+                key: key.to_string(),
+                value,
+                prev: NIL,
+                next: NIL,
+            });
+            self.entries.len() - 1
+        } else {
+            let index = self.tail;
+            self.unlink(index);
+            let entry = &mut self.entries[index];
+            self.map.remove(&entry.key);
+            entry.key.clear();
+            entry.key.push_str(key);
+            entry.value = value;
+            index
+        };
+
+        self.map.insert(key.to_string(), index);
+        self.push_front(index);
+        &self.entries[index].value
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = (self.entries[index].prev, self.entries[index].next);
+
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.entries[prev].next = next;
+        }
+
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.entries[next].prev = prev;
+        }
+    }
+
+    fn push_front(&mut self, index: usize) {
+        self.entries[index].prev = NIL;
+        self.entries[index].next = self.head;
+
+        if self.head == NIL {
+            self.tail = index;
+        } else {
+            self.entries[self.head].prev = index;
+        }
+
+        self.head = index;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruCache;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(Some(&1), cache.get("a"));
+
+        cache.insert("c", 3);
+        assert_eq!(None, cache.get("b"));
+        assert_eq!(Some(&1), cache.get("a"));
+        assert_eq!(Some(&3), cache.get("c"));
+        assert_eq!(2, cache.len());
+    }
+}
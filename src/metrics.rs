@@ -0,0 +1,190 @@
+use ast::*;
+use collections::Set;
+use std::cmp::Reverse;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+use visit::{self, Visitor};
+
+/// Size and shape statistics of a symbol's AST.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Metrics {
+    /// The number of paths, types, consts, and lifetimes in the tree, counting
+    /// shared subtrees once per occurrence.
+    pub node_count: usize,
+    /// The length of the longest chain of nested paths, types, and consts.
+    pub max_depth: usize,
+    /// The total number of generic arguments over all generic paths.
+    pub generic_arg_count: usize,
+    /// The number of structurally distinct types.
+    pub distinct_type_count: usize,
+}
+
+/// How much each nesting level adds to `Metrics::complexity_score()`.
+pub const DEPTH_WEIGHT: u64 = 4;
+/// How much each distinct type adds to `Metrics::complexity_score()`.
+pub const DISTINCT_TYPE_WEIGHT: u64 = 2;
+/// How much each generic argument adds to `Metrics::complexity_score()`.
+pub const GENERIC_ARG_WEIGHT: u64 = 1;
+
+impl Metrics {
+    /// A single number for how hard a symbol is to read, for budgets on
+    /// symbol complexity:
+    ///
+    /// ```text
+    /// DEPTH_WEIGHT * max_depth
+    ///     + DISTINCT_TYPE_WEIGHT * distinct_type_count
+    ///     + GENERIC_ARG_WEIGHT * generic_arg_count
+    /// ```
+    ///
+    /// Nesting weighs the most, since generics nested without bound make
+    /// symbols grow the fastest. Shared subtrees count once for the distinct
+    /// types but once per occurrence for the generic arguments, so a symbol
+    /// that repeats one type many times still scores high.
+    pub fn complexity_score(&self) -> u64 {
+        DEPTH_WEIGHT * self.max_depth as u64
+            + DISTINCT_TYPE_WEIGHT * self.distinct_type_count as u64
+            + GENERIC_ARG_WEIGHT * self.generic_arg_count as u64
+    }
+}
+
+impl Symbol {
+    /// Shorthand for `self.metrics().complexity_score()`.
+    pub fn complexity_score(&self) -> u64 {
+        self.metrics().complexity_score()
+    }
+
+    /// Measures the size and shape of this symbol's AST, with backrefs
+    /// expanded: its node count, nesting depth, generic arguments and
+    /// distinct types. `Metrics::complexity_score()` combines them into one
+    /// number for budgets.
+    pub fn metrics(&self) -> Metrics {
+        let mut collector = MetricsCollector {
+            metrics: Metrics::default(),
+            depth: 0,
+            types: Set::new(),
+        };
+
+        collector.visit_symbol(self);
+        collector.metrics.distinct_type_count = collector.types.len();
+        collector.metrics
+    }
+}
+
+/// Scores the v0 symbols that parse, most complex first. Symbols with the
+/// same score keep their order.
+pub fn rank_by_complexity<'a, I>(symbols: I) -> Vec<(&'a str, u64)>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut ranked: Vec<_> = symbols
+        .into_iter()
+        .filter_map(|mangled| {
+            Symbol::parse(mangled)
+                .ok()
+                .map(|symbol| (mangled, symbol.complexity_score()))
+        })
+        .collect();
+    ranked.sort_by_key(|&(_, score)| Reverse(score));
+    ranked
+}
+
+/// The symbols that score more than `budget`, most complex first.
+pub fn over_budget<'a, I>(symbols: I, budget: u64) -> Vec<(&'a str, u64)>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut ranked = rank_by_complexity(symbols);
+    ranked.retain(|&(_, score)| score > budget);
+    ranked
+}
+
+struct MetricsCollector<'ast> {
+    metrics: Metrics,
+    depth: usize,
+    types: Set<&'ast Type>,
+}
+
+impl<'ast> MetricsCollector<'ast> {
+    fn enter(&mut self) {
+        self.metrics.node_count += 1;
+        self.depth += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+    }
+
+    fn exit(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+impl<'ast> Visitor<'ast> for MetricsCollector<'ast> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Path::Generic { ref args, .. } = *path {
+            self.metrics.generic_arg_count += args.len();
+        }
+
+        self.enter();
+        visit::walk_path(self, path);
+        self.exit();
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        self.types.insert(ty);
+
+        self.enter();
+        visit::walk_type(self, ty);
+        self.exit();
+    }
+
+    fn visit_const(&mut self, k: &'ast Const) {
+        self.enter();
+        visit::walk_const(self, k);
+        self.exit();
+    }
+
+    fn visit_lifetime(&mut self, _lifetime: &'ast Lifetime) {
+        self.enter();
+        self.exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{over_budget, rank_by_complexity, Metrics};
+    use ast::Symbol;
+
+    #[test]
+    fn metrics_of_compressed_symbol() {
+        // std::foo<(std::Bar,std::Bar),(std::Bar,std::Bar)>
+        let symbol = Symbol::parse("_RINxC3std3fooTNyB2_3BarBc_EBb_E").unwrap();
+
+        assert_eq!(
+            Metrics {
+                // Generic, Nested, CrateRoot, 2 * (Tuple, 2 * (Named, Nested, CrateRoot))
+                node_count: 17,
+                max_depth: 5,
+                generic_arg_count: 2,
+                distinct_type_count: 2,
+            },
+            symbol.metrics()
+        );
+    }
+
+    #[test]
+    fn rank_symbols_by_complexity() {
+        let simple = "_RNvC7mycrate3foo";
+        let generic = "_RINxC3std3fooTNyB2_3BarBc_EBb_E";
+        // std::foo<std::foo<std::foo<u8>>>
+        let nested = "_RINxC3std3fooINxB2_3fooINxB2_3foohEEE";
+
+        // 4 * 5 + 2 * 2 + 2
+        assert_eq!(26, Symbol::parse(generic).unwrap().complexity_score());
+        assert_eq!(
+            vec![nested, generic, simple],
+            rank_by_complexity(vec![simple, "_RXX", generic, nested])
+                .into_iter()
+                .map(|(mangled, _)| mangled)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![(generic, 26)], over_budget(vec![simple, generic], 10));
+    }
+}
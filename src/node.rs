@@ -0,0 +1,83 @@
+//! Node.js bindings built on `napi-rs`, so that crash dashboards and other
+//! JavaScript tooling can demangle symbols without starting a process per
+//! batch. Build the addon with `cargo rustc --release --lib --crate-type
+//! cdylib --features node` and rename the library to `std_mangle.node`, or
+//! build it with `napi build`.
+//!
+//! The addon exports these functions:
+//!
+//! - `demangle(mangled)` returns the demangled string, and throws for
+//!   invalid symbols.
+//! - `demangleAll(symbols)` demangles an array of strings into an array with
+//!   `null` for every string that is not a valid symbol.
+//! - `demangleAllAsync(symbols)` does the same on the libuv thread pool and
+//!   returns a `Promise`, so large symbol tables do not block the event loop.
+
+use ast::Symbol;
+use batch::Demangler;
+use napi::bindgen_prelude::AsyncTask;
+use napi::{Env, Error, Result, Task};
+
+/// Demangles the v0 symbol `mangled`.
+#[napi]
+pub fn demangle(mangled: String) -> Result<String> {
+    Symbol::parse(&mangled)
+        .map(|symbol| symbol.demangle())
+        .map_err(Error::from_reason)
+}
+
+/// Demangles `symbols`, with `null` for the invalid ones.
+#[napi]
+pub fn demangle_all(symbols: Vec<String>) -> Vec<Option<String>> {
+    let mut demangler = Demangler::new();
+    symbols
+        .iter()
+        .map(|mangled| demangler.demangle(mangled).ok().map(str::to_string))
+        .collect()
+}
+
+/// Demangles `symbols` on the libuv thread pool.
+// Only napi's registration calls this, and it is left out of test builds.
+#[cfg_attr(test, allow(dead_code))]
+#[napi]
+pub fn demangle_all_async(symbols: Vec<String>) -> AsyncTask<DemangleAll> {
+    AsyncTask::new(DemangleAll { symbols })
+}
+
+/// The work of `demangleAllAsync()`.
+pub struct DemangleAll {
+    symbols: Vec<String>,
+}
+
+impl Task for DemangleAll {
+    type Output = Vec<Option<String>>;
+    type JsValue = Vec<Option<String>>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        Ok(demangle_all(::std::mem::take(&mut self.symbols)))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, demangle_all, DemangleAll};
+    use napi::Task;
+
+    #[test]
+    fn demangle_for_node() {
+        assert_eq!(
+            "mycrate::foo",
+            demangle("_RNvC7mycrate3foo".to_string()).unwrap()
+        );
+        assert!(demangle("main".to_string()).is_err());
+
+        let symbols = vec!["_RNvC7mycrate3foo".to_string(), "main".to_string()];
+        let expected = vec![Some("mycrate::foo".to_string()), None];
+        assert_eq!(expected, demangle_all(symbols.clone()));
+        assert_eq!(expected, DemangleAll { symbols }.compute().unwrap());
+    }
+}
@@ -0,0 +1,68 @@
+//! Symbols without the parts that change from one compiler session to the
+//! next, for caches and for matching the symbols of two builds.
+//!
+//! Crate disambiguators are hashes of the crate's metadata and compiler
+//! flags, and impl disambiguators count the impls of a module, so both
+//! change whenever an unrelated part of the build does. The instantiating
+//! crate depends on which crate happened to need an instance first. The
+//! disambiguators of other identifiers, e.g. the indices of closures, are
+//! kept, since they distinguish different items.
+
+use ast::*;
+use fold::{self, Folder};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+impl Symbol {
+    /// Returns a copy of this symbol without crate and impl disambiguators
+    /// and without the instantiating crate.
+    pub fn normalize(&self) -> Symbol {
+        let mut normalized = Normalizer.fold_symbol(self);
+        normalized.instantiating_crate = None;
+        normalized
+    }
+
+    /// Returns the mangled form of `normalize()`, which is the same for the
+    /// symbols of one item in any build.
+    pub fn normalized_mangle(&self) -> String {
+        self.normalize().mangle()
+    }
+}
+
+struct Normalizer;
+
+impl Folder for Normalizer {
+    fn fold_path(&mut self, path: &Path) -> Path {
+        match *path {
+            Path::CrateRoot { ref id } => Path::CrateRoot {
+                id: Ident {
+                    dis: Disambiguator(0),
+                    u_ident: id.u_ident.clone(),
+                },
+            },
+            _ => fold::walk_path(self, path),
+        }
+    }
+
+    fn fold_impl_path(&mut self, impl_path: &ImplPath) -> ImplPath {
+        ImplPath {
+            dis: None,
+            ..fold::walk_impl_path(self, impl_path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Symbol;
+
+    #[test]
+    fn normalize_across_sessions() {
+        let a = Symbol::parse("_RNCNvMs1_NyCs4_3std3vecNyCs4_3std3Vec3news_0C5other").unwrap();
+        let b = Symbol::parse("_RNCNvMs5_NyCs9_3std3vecNyCs9_3std3Vec3news_0").unwrap();
+
+        assert_eq!(a.normalize(), b.normalize());
+        assert_eq!(a.normalized_mangle(), b.normalized_mangle());
+        assert_eq!("<std::Vec>::new::{closure}[1]", a.normalize().demangle());
+    }
+}
@@ -1,43 +1,209 @@
 use ast::*;
 use charset;
 use error::{self, expected};
-use int_radix::ascii_digit_to_value;
+use int_radix::DIGIT_VALUES;
+#[cfg(feature = "std")]
+use intern::Interner;
+use limits::ResourceLimits;
+use std::cmp;
+use std::convert::TryFrom;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
 use std::str;
-use std::sync::Arc;
 
 pub const EOT: u8 = 5; // ASCII "end of transmission"
 
+/// The maximum nesting depth of paths and types, counting the nodes pulled in
+/// through backrefs. Deeper symbols are rejected instead of overflowing the
+/// stack.
+///
+/// In an unoptimized build, parsing, demangling and mangling take up to about
+/// 6.4 KiB of stack per level, for dyn traits with generic arguments that
+/// are again dyn traits, so this leaves a fifth of `STACK_SIZE` to spare.
+pub const MAX_DEPTH: u32 = 256;
+
+/// The stack size that `MAX_DEPTH` is measured against, which is also the
+/// default for spawned threads. Threads that handle symbols need at least
+/// this much.
+pub const STACK_SIZE: usize = 2 << 20;
+
 pub fn parse(input: &[u8]) -> Result<Symbol, String> {
-    let mut parser = Parser { input, pos: 0 };
+    parse_with(Parser::new(input))
+}
+
+pub(crate) fn parse_with(parser: Parser) -> Result<Symbol, String> {
+    parse_at(parser).map_err(|(pos, e)| format!("at position {}: {}", pos, e))
+}
 
-    parser
-        .parse_symbol()
-        .map_err(|e| format!("at position {}: {}", parser.pos, e))
+/// Like `parse_with()`, but returns the position of an error separately
+/// from its message.
+pub(crate) fn parse_at(mut parser: Parser) -> Result<Symbol, (usize, String)> {
+    parser.parse_symbol().map_err(|e| (parser.pos, e))
 }
 
 pub struct Parser<'input> {
     input: &'input [u8],
     pos: usize,
+    depth: u32,
+    /// The deepest `depth` reached so far.
+    deepest: u32,
+    /// The number of paths and types parsed so far, including those parsed
+    /// by the parsers for backref targets.
+    nodes: usize,
+    /// The number of identifier bytes parsed so far, counted like `nodes`.
+    ident_bytes: usize,
+    limits: ResourceLimits,
+    memo: Memo,
+    /// Where the identifiers of the symbol are interned, if anywhere.
+    #[cfg(feature = "std")]
+    interner: Option<&'input mut Interner>,
+}
+
+/// The nodes that backrefs have been resolved to, by the position of their
+/// target, together with how many levels deep the node is nested, how many
+/// nodes it consists of, and how many identifier bytes it contains.
+/// Resolving a backref a second time only clones the top of the node, since
+/// all of its children are behind a `Ptr`.
+#[derive(Default)]
+struct Memo {
+    paths: Table<Path>,
+    types: Table<Type>,
+    consts: Table<Const>,
+    /// The number of `B` bytes after the first memoized backref, counted
+    /// once for all three tables.
+    backrefs: Option<usize>,
+}
+
+type Memoized<T> = (T, u32, usize, usize);
+
+/// The memoized nodes of one kind. Backref targets are positions in the
+/// input, so they index a vector instead of being hashed.
+struct Table<T> {
+    /// For each position, one more than the index into `nodes` of the node
+    /// there, or 0 if there is none.
+    at: Vec<usize>,
+    nodes: Vec<Memoized<T>>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Table<T> {
+        Table {
+            at: Vec::new(),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+impl<T> Table<T> {
+    fn get(&self, pos: usize) -> Option<&Memoized<T>> {
+        match self.at.get(pos) {
+            Some(&index) if index > 0 => Some(&self.nodes[index - 1]),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, pos: usize, memoized: Memoized<T>) {
+        if self.at.len() <= pos {
+            self.at.resize(pos + 1, 0);
+        }
+        self.nodes.push(memoized);
+        self.at[pos] = self.nodes.len();
+    }
 }
 
 impl<'input> Parser<'input> {
-    fn parse_symbol(&mut self) -> Result<Symbol, String> {
-        if &self.input[0..2] != b"_R" {
-            return Err("Not a Rust symbol".to_string());
+    pub(crate) fn new(input: &'input [u8]) -> Parser<'input> {
+        Parser::with_limits(input, ResourceLimits::default())
+    }
+
+    pub(crate) fn with_limits(input: &'input [u8], limits: ResourceLimits) -> Parser<'input> {
+        Parser {
+            input,
+            pos: 0,
+            depth: 0,
+            deepest: 0,
+            nodes: 0,
+            ident_bytes: 0,
+            limits: ResourceLimits {
+                max_depth: cmp::min(limits.max_depth, MAX_DEPTH),
+                ..limits
+            },
+            memo: Memo::default(),
+            #[cfg(feature = "std")]
+            interner: None,
         }
+    }
 
-        self.pos += 2;
+    /// Makes the parser intern all identifiers in `interner`.
+    #[cfg(feature = "std")]
+    pub(crate) fn interning(self, interner: &'input mut Interner) -> Parser<'input> {
+        Parser {
+            interner: Some(interner),
+            ..self
+        }
+    }
 
-        let version = if self.cur().is_ascii_digit() {
-            let encoding_version = self.parse_number(10)? + 1;
-            return error::version_mismatch(encoding_version, 0);
-        } else {
-            None
-        };
+    /// Must be called before descending into a path or type, and `exit()`
+    /// after it has been parsed successfully.
+    pub(crate) fn enter(&mut self) -> Result<(), String> {
+        if self.depth >= self.limits.max_depth {
+            return self.too_deep();
+        }
+
+        self.count_nodes(1)?;
+        self.depth += 1;
+        self.deepest = cmp::max(self.deepest, self.depth);
+
+        Ok(())
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn count_nodes(&mut self, nodes: usize) -> Result<(), String> {
+        self.nodes = self.nodes.saturating_add(nodes);
+        if self.nodes > self.limits.max_nodes {
+            return Err(format!(
+                "symbol expands to more than {} nodes",
+                self.limits.max_nodes
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Counts identifier bytes towards `max_output_bytes`, since every one of
+    /// them is part of the demangled symbol.
+    fn count_ident_bytes(&mut self, bytes: usize) -> Result<(), String> {
+        self.ident_bytes = self.ident_bytes.saturating_add(bytes);
+        if self.ident_bytes > self.limits.max_output_bytes {
+            return Err(format!(
+                "demangled symbol longer than {} bytes",
+                self.limits.max_output_bytes
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn too_deep<T>(&self) -> Result<T, String> {
+        Err(format!(
+            "symbol nested deeper than {} levels",
+            self.limits.max_depth
+        ))
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn parse_symbol(&mut self) -> Result<Symbol, String> {
+        let version = self.parse_symbol_prefix()?;
 
         let path = self.parse_path()?;
 
-        let instantiating_crate = if self.cur() != EOT {
+        let instantiating_crate = if !self.at_end() {
             Some(self.parse_path()?)
         } else {
             None
@@ -50,14 +216,33 @@ impl<'input> Parser<'input> {
         })
     }
 
-    fn parse_const(&mut self) -> Result<Const, String> {
-        if self.cur() == b'B' {
-            let mut parser = self.parse_backref()?;
-            parser.parse_const()
+    /// Parses the `_R` prefix and the optional encoding version.
+    pub(crate) fn parse_symbol_prefix(&mut self) -> Result<Option<DecimalNumber>, String> {
+        if !self.input.starts_with(b"_R") {
+            return Err("Not a Rust symbol".to_string());
+        }
+
+        self.pos += 2;
+
+        if self.cur().is_ascii_digit() {
+            let encoding_version = self.parse_number(10)? + 1;
+            return error::version_mismatch(encoding_version, 0);
+        }
+
+        Ok(None)
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.cur() == EOT
+    }
+
+    pub(crate) fn parse_const(&mut self) -> Result<Const, String> {
+        if self.try_eat(b'B') {
+            self.resolve_backref(|memo| &mut memo.consts, Parser::parse_const)
         } else {
             let ty = self.parse_type()?;
 
-            if self.cur() == b'p' {
+            if self.try_eat(b'p') {
                 Ok(Const::Placeholder(ty))
             } else {
                 let value = self.parse_number(16)?;
@@ -70,23 +255,26 @@ impl<'input> Parser<'input> {
     fn parse_generic_arg(&mut self) -> Result<GenericArg, String> {
         Ok(match self.cur() {
             b'L' => GenericArg::Lifetime(self.parse_lifetime()?),
-            b'K' => GenericArg::Const(self.parse_const()?),
+            b'K' => {
+                self.pos += 1;
+                GenericArg::Const(self.parse_const()?)
+            }
             _ => GenericArg::Type(self.parse_type()?),
         })
     }
 
-    fn parse_lifetime(&mut self) -> Result<Lifetime, String> {
+    pub(crate) fn parse_lifetime(&mut self) -> Result<Lifetime, String> {
         self.eat(b'L', "<lifetime>")?;
         Ok(Lifetime {
-            debruijn_index: self.parse_base62_number()?,
+            debruijn_index: BinderIndex(self.parse_base62_number()?.0),
         })
     }
 
-    fn parse_binder(&mut self) -> Result<Binder, String> {
+    pub(crate) fn parse_binder(&mut self) -> Result<Binder, String> {
         self.eat(b'G', "<binder>")?;
 
         Ok(Binder {
-            count: self.parse_base62_number()?,
+            count: LifetimeCount(self.parse_base62_number()?.0),
         })
     }
 
@@ -108,7 +296,7 @@ impl<'input> Parser<'input> {
             None
         };
 
-        let mut param_types = Vec::new();
+        let mut param_types = TypeList::new();
 
         while self.cur() != b'E' {
             param_types.push(self.parse_type()?);
@@ -160,40 +348,40 @@ impl<'input> Parser<'input> {
         })
     }
 
-    fn parse_type(&mut self) -> Result<Type, String> {
+    pub(crate) fn parse_type(&mut self) -> Result<Type, String> {
+        self.enter()?;
+        let ty = self.parse_type_inner()?;
+        self.exit();
+        Ok(ty)
+    }
+
+    // Allocating these nodes outside of `parse_type_inner()` keeps their
+    // inline argument lists out of its stack frame, which matters for deeply
+    // nested types in unoptimized builds.
+    fn parse_path_arc(&mut self) -> Result<Ptr<Path>, String> {
+        Ok(Ptr::new(self.parse_path()?))
+    }
+
+    fn parse_fn_sig_arc(&mut self) -> Result<Ptr<FnSig>, String> {
+        Ok(Ptr::new(self.parse_fn_sig()?))
+    }
+
+    fn parse_type_inner(&mut self) -> Result<Type, String> {
         let tag = self.cur();
         self.pos += 1;
 
+        if let Some(bt) = basic_type(tag) {
+            return Ok(Type::BasicType(bt));
+        }
+
         Ok(match tag {
-            b'a' => Type::BasicType(BasicType::I8),
-            b'b' => Type::BasicType(BasicType::Bool),
-            b'c' => Type::BasicType(BasicType::Char),
-            b'd' => Type::BasicType(BasicType::F64),
-            b'e' => Type::BasicType(BasicType::Str),
-            b'f' => Type::BasicType(BasicType::F32),
-            b'h' => Type::BasicType(BasicType::U8),
-            b'i' => Type::BasicType(BasicType::Isize),
-            b'j' => Type::BasicType(BasicType::Usize),
-            b'l' => Type::BasicType(BasicType::I32),
-            b'm' => Type::BasicType(BasicType::U32),
-            b'n' => Type::BasicType(BasicType::I128),
-            b'o' => Type::BasicType(BasicType::U128),
-            b'p' => Type::BasicType(BasicType::Placeholder),
-            b's' => Type::BasicType(BasicType::I16),
-            b't' => Type::BasicType(BasicType::U16),
-            b'u' => Type::BasicType(BasicType::Unit),
-            b'v' => Type::BasicType(BasicType::Ellipsis),
-            b'x' => Type::BasicType(BasicType::I64),
-            b'y' => Type::BasicType(BasicType::U64),
-            b'z' => Type::BasicType(BasicType::Never),
-
-            b'A' => Type::Array(Arc::new(self.parse_type()?), Arc::new(self.parse_const()?)),
-
-            b'S' => Type::Slice(Arc::new(self.parse_type()?)),
+            b'A' => Type::Array(Ptr::new(self.parse_type()?), Ptr::new(self.parse_const()?)),
+
+            b'S' => Type::Slice(Ptr::new(self.parse_type()?)),
 
             b'C' | b'M' | b'X' | b'Y' | b'N' | b'I' => {
                 self.pos -= 1;
-                Type::Named(Arc::new(self.parse_path()?))
+                Type::Named(self.parse_path_arc()?)
             }
 
             b'T' => {
@@ -214,7 +402,7 @@ impl<'input> Parser<'input> {
                     None
                 };
 
-                Type::Ref(lifetime, Arc::new(self.parse_type()?))
+                Type::Ref(lifetime, Ptr::new(self.parse_type()?))
             }
 
             b'Q' => {
@@ -224,27 +412,21 @@ impl<'input> Parser<'input> {
                     None
                 };
 
-                Type::RefMut(lifetime, Arc::new(self.parse_type()?))
+                Type::RefMut(lifetime, Ptr::new(self.parse_type()?))
             }
 
-            b'P' => Type::RawPtrConst(Arc::new(self.parse_type()?)),
+            b'P' => Type::RawPtrConst(Ptr::new(self.parse_type()?)),
 
-            b'O' => Type::RawPtrMut(Arc::new(self.parse_type()?)),
+            b'O' => Type::RawPtrMut(Ptr::new(self.parse_type()?)),
 
-            b'F' => Type::Fn(Arc::new(self.parse_fn_sig()?)),
+            b'F' => Type::Fn(self.parse_fn_sig_arc()?),
 
-            b'D' => Type::DynTrait(Arc::new(self.parse_dyn_bounds()?), self.parse_lifetime()?),
+            b'D' => Type::DynTrait(Ptr::new(self.parse_dyn_bounds()?), self.parse_lifetime()?),
 
-            b'B' => {
-                let mut parser = self.parse_backref()?;
-                parser.parse_type()?
-            }
+            b'B' => self.resolve_backref(|memo| &mut memo.types, Parser::parse_type)?,
 
             c => {
-                return Err(format!(
-                    "Expected start of <type>, found {} instead.",
-                    c as char
-                ));
+                return unexpected_type_tag(c);
             }
         })
     }
@@ -258,11 +440,18 @@ impl<'input> Parser<'input> {
 
         Ok(ImplPath {
             dis,
-            path: Arc::new(self.parse_path()?),
+            path: Ptr::new(self.parse_path()?),
         })
     }
 
-    fn parse_path(&mut self) -> Result<Path, String> {
+    pub(crate) fn parse_path(&mut self) -> Result<Path, String> {
+        self.enter()?;
+        let path = self.parse_path_inner()?;
+        self.exit();
+        Ok(path)
+    }
+
+    fn parse_path_inner(&mut self) -> Result<Path, String> {
         let tag = self.cur();
         self.pos += 1;
 
@@ -277,21 +466,21 @@ impl<'input> Parser<'input> {
             b'X' => Path::TraitImpl {
                 impl_path: self.parse_impl_path()?,
                 self_type: self.parse_type()?,
-                trait_name: Arc::new(self.parse_path()?),
+                trait_name: Ptr::new(self.parse_path()?),
             },
             b'Y' => Path::TraitDef {
                 self_type: self.parse_type()?,
-                trait_name: Arc::new(self.parse_path()?),
+                trait_name: Ptr::new(self.parse_path()?),
             },
             b'N' => Path::Nested {
                 ns: self.parse_namespace()?,
-                inner: Arc::new(self.parse_path()?),
+                inner: Ptr::new(self.parse_path()?),
                 ident: self.parse_ident()?,
             },
             b'I' => {
                 let inner = self.parse_path()?;
 
-                let mut args = Vec::new();
+                let mut args = GenericArgs::new();
                 while self.cur() != b'E' {
                     args.push(self.parse_generic_arg()?);
                 }
@@ -299,21 +488,18 @@ impl<'input> Parser<'input> {
                 self.eat(b'E', "<path>")?;
 
                 Path::Generic {
-                    inner: Arc::new(inner),
+                    inner: Ptr::new(inner),
                     args,
                 }
             }
-            b'B' => {
-                let mut parser = self.parse_backref()?;
-                parser.parse_path()?
-            }
+            b'B' => self.resolve_backref(|memo| &mut memo.paths, Parser::parse_path)?,
             other => {
                 return expected("CMXYNIB", other, "parsing", "<path>");
             }
         })
     }
 
-    fn parse_namespace(&mut self) -> Result<Namespace, String> {
+    pub(crate) fn parse_namespace(&mut self) -> Result<Namespace, String> {
         let c = self.cur();
 
         match c {
@@ -327,56 +513,86 @@ impl<'input> Parser<'input> {
     }
 
     fn parse_ident(&mut self) -> Result<Ident, String> {
-        let dis = if self.cur() == b's' {
-            self.parse_disambiguator()?
-        } else {
-            Base62Number(0)
-        };
-
         Ok(Ident {
-            dis,
+            dis: self.parse_opt_disambiguator()?,
             u_ident: self.parse_uident()?,
         })
     }
 
-    fn parse_disambiguator(&mut self) -> Result<Base62Number, String> {
+    /// Parses a disambiguator if there is one, defaulting to zero otherwise.
+    pub(crate) fn parse_opt_disambiguator(&mut self) -> Result<Disambiguator, String> {
+        if self.cur() == b's' {
+            self.parse_disambiguator()
+        } else {
+            Ok(Disambiguator(0))
+        }
+    }
+
+    pub(crate) fn parse_disambiguator(&mut self) -> Result<Disambiguator, String> {
         self.eat(b's', "<disambiguator>")?;
 
-        Ok(Base62Number(self.parse_base62_number()?.0 + 1))
+        let Base62Number(n) = self.parse_base62_number()?;
+        Ok(Disambiguator(
+            n.checked_add(1)
+                .ok_or("disambiguator does not fit into 64 bits")?,
+        ))
     }
 
     fn parse_uident(&mut self) -> Result<UIdent, String> {
-        let punycode = self.try_eat(b'u');
-        let DecimalNumber(num_bytes) = self.parse_decimal_number()?;
-        let start = self.pos;
-        let end = start + num_bytes as usize;
+        let (punycode, bytes) = self.parse_uident_bytes()?;
 
-        if end > self.input.len() {
-            return Err("identifier extend beyond end of input".to_string());
+        if punycode {
+            let ident = charset::decode_punycode_ident(bytes)?;
+            return Ok(UIdent(self.share(&ident)));
         }
 
-        self.pos = end;
+        let ident = str::from_utf8(bytes).map_err(|e| format!("{:?}", e))?;
+        Ok(UIdent(self.share(ident)))
+    }
 
-        let bytes = &self.input[start..end];
+    /// Returns the interned copy of `ident` if the parser interns
+    /// identifiers, or a new one otherwise.
+    fn share(&mut self, ident: &str) -> Ptr<str> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref mut interner) = self.interner {
+                return interner.intern(ident);
+            }
+        }
+        ident.into()
+    }
 
-        let ident = if punycode {
-            charset::decode_punycode_ident(bytes)?
-        } else {
-            String::from_utf8(bytes.to_owned()).map_err(|e| format!("{:?}", e))?
+    /// Parses the raw bytes of an identifier, returning whether they are
+    /// punycode-encoded along with the bytes themselves.
+    pub(crate) fn parse_uident_bytes(&mut self) -> Result<(bool, &'input [u8]), String> {
+        let punycode = self.try_eat(b'u');
+        let DecimalNumber(num_bytes) = self.parse_decimal_number()?;
+        let start = self.pos;
+        let end = match usize::try_from(num_bytes)
+            .ok()
+            .and_then(|num_bytes| start.checked_add(num_bytes))
+        {
+            Some(end) if end <= self.input.len() => end,
+            _ => return Err("identifier extend beyond end of input".to_string()),
         };
+        self.count_ident_bytes(end - start)?;
 
-        Ok(UIdent(ident))
+        self.pos = end;
+
+        Ok((punycode, &self.input[start..end]))
     }
 
-    fn parse_decimal_number(&mut self) -> Result<DecimalNumber, String> {
+    pub(crate) fn parse_decimal_number(&mut self) -> Result<DecimalNumber, String> {
         Ok(DecimalNumber(self.parse_number(10)?))
     }
 
-    fn parse_base62_number(&mut self) -> Result<Base62Number, String> {
+    pub(crate) fn parse_base62_number(&mut self) -> Result<Base62Number, String> {
         let n = if self.cur() == b'_' {
             0
         } else {
-            self.parse_number(62)? + 1
+            self.parse_number(62)?
+                .checked_add(1)
+                .ok_or("base-62 number does not fit into 64 bits")?
         };
 
         self.eat(b'_', "<base-62-number>")?;
@@ -384,7 +600,7 @@ impl<'input> Parser<'input> {
         Ok(Base62Number(n))
     }
 
-    fn cur(&self) -> u8 {
+    pub(crate) fn cur(&self) -> u8 {
         if self.pos < self.input.len() {
             self.input[self.pos]
         } else {
@@ -392,7 +608,7 @@ impl<'input> Parser<'input> {
         }
     }
 
-    fn eat(&mut self, c: u8, noun: &str) -> Result<(), String> {
+    pub(crate) fn eat(&mut self, c: u8, noun: &str) -> Result<(), String> {
         if self.cur() != c {
             return expected(str::from_utf8(&[c]).unwrap(), self.cur(), "parsing", noun);
         }
@@ -402,7 +618,7 @@ impl<'input> Parser<'input> {
         Ok(())
     }
 
-    fn try_eat(&mut self, c: u8) -> bool {
+    pub(crate) fn try_eat(&mut self, c: u8) -> bool {
         if self.cur() == c {
             self.pos += 1;
             true
@@ -411,8 +627,16 @@ impl<'input> Parser<'input> {
         }
     }
 
-    fn parse_number(&mut self, radix: u8) -> Result<u64, String> {
-        if ascii_digit_to_value(self.cur(), radix).is_none() {
+    pub(crate) fn parse_number(&mut self, radix: u8) -> Result<u64, String> {
+        // Scan the whole run of digits at once instead of going through
+        // `cur()` for every byte.
+        let rest = self.input.get(self.pos..).unwrap_or(&[]);
+        let len = rest
+            .iter()
+            .position(|&b| DIGIT_VALUES[b as usize] >= radix)
+            .unwrap_or(rest.len());
+
+        if len == 0 {
             return Err(format!(
                 "expected base-{} digit, found {:?}",
                 radix,
@@ -420,25 +644,274 @@ impl<'input> Parser<'input> {
             ));
         }
 
-        let mut value = 0;
+        let mut value: u64 = 0;
 
-        while let Some(digit) = ascii_digit_to_value(self.cur(), radix) {
-            value = value * (radix as u64) + digit;
-            self.pos += 1;
+        for &b in &rest[..len] {
+            value = value
+                .checked_mul(radix as u64)
+                .and_then(|value| value.checked_add(DIGIT_VALUES[b as usize] as u64))
+                .ok_or_else(|| format!("base-{} number does not fit into 64 bits", radix))?;
         }
 
+        self.pos += len;
+
         Ok(value)
     }
 
-    fn parse_backref(&mut self) -> Result<Parser<'input>, String> {
+    /// Parses the number of a backref whose `B` has already been consumed and
+    /// returns a parser positioned at its target.
+    pub(crate) fn parse_backref(&mut self) -> Result<Parser<'input>, String> {
+        let backref_pos = self.pos - 1;
         let Base62Number(pos) = self.parse_base62_number()?;
 
         // Account for the `_R` prefix
-        let pos = pos + 2;
+        let pos = pos.saturating_add(2);
+
+        // Only allowing backrefs to earlier positions guarantees that
+        // resolving them terminates.
+        if pos >= backref_pos as u64 {
+            return Err(format!(
+                "backref at position {} does not point to an earlier position",
+                backref_pos
+            ));
+        }
 
         Ok(Parser {
             input: self.input,
             pos: pos as usize,
+            depth: self.depth,
+            deepest: self.depth,
+            nodes: self.nodes,
+            ident_bytes: self.ident_bytes,
+            limits: self.limits,
+            memo: Memo::default(),
+            #[cfg(feature = "std")]
+            interner: None,
         })
     }
+
+    /// Must be called with the parser returned by `parse_backref()` once its
+    /// target has been parsed, so the nodes parsed there count towards the
+    /// limits of this parser.
+    pub(crate) fn finish_backref(&mut self, target: &Parser) {
+        self.nodes = target.nodes;
+        self.ident_bytes = target.ident_bytes;
+    }
+
+    /// Parses a backref whose `B` has already been consumed and returns the
+    /// node it points to, parsing the target only the first time.
+    fn resolve_backref<T: Clone>(
+        &mut self,
+        table: fn(&mut Memo) -> &mut Table<T>,
+        parse: fn(&mut Parser<'input>) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let target = self.parse_backref()?.pos;
+
+        if let Some(&(ref node, height, size, ident_bytes)) = table(&mut self.memo).get(target) {
+            let node = node.clone();
+
+            if self.depth + height > self.limits.max_depth {
+                return self.too_deep();
+            }
+            self.count_nodes(size)?;
+            self.count_ident_bytes(ident_bytes)?;
+
+            self.deepest = cmp::max(self.deepest, self.depth + height);
+            return Ok(node);
+        }
+
+        let (pos, deepest, nodes, ident_bytes) =
+            (self.pos, self.deepest, self.nodes, self.ident_bytes);
+        self.pos = target;
+        self.deepest = self.depth;
+
+        let result = parse(self);
+
+        let height = self.deepest - self.depth;
+        let size = self.nodes - nodes;
+        let ident_bytes = self.ident_bytes - ident_bytes;
+        self.pos = pos;
+        self.deepest = cmp::max(deepest, self.deepest);
+
+        let node = result?;
+        if table(&mut self.memo).nodes.is_empty() {
+            // Every later `B` may be another backref to memoize. Reserving
+            // for all of them up front keeps the table from reallocating
+            // while parsing large symbols. Each of them takes at least one
+            // node, so there cannot be more than the node budget has room
+            // for.
+            let input = &self.input[self.pos..];
+            let backrefs = *self
+                .memo
+                .backrefs
+                .get_or_insert_with(|| input.iter().filter(|&&b| b == b'B').count());
+            let room = self.limits.max_nodes.saturating_sub(self.nodes);
+            table(&mut self.memo)
+                .nodes
+                .reserve(1 + cmp::min(backrefs, room));
+        }
+        table(&mut self.memo).insert(target, (node.clone(), height, size, ident_bytes));
+        Ok(node)
+    }
+}
+
+pub(crate) fn basic_type(tag: u8) -> Option<BasicType> {
+    Some(match tag {
+        b'a' => BasicType::I8,
+        b'b' => BasicType::Bool,
+        b'c' => BasicType::Char,
+        b'd' => BasicType::F64,
+        b'e' => BasicType::Str,
+        b'f' => BasicType::F32,
+        b'h' => BasicType::U8,
+        b'i' => BasicType::Isize,
+        b'j' => BasicType::Usize,
+        b'l' => BasicType::I32,
+        b'm' => BasicType::U32,
+        b'n' => BasicType::I128,
+        b'o' => BasicType::U128,
+        b'p' => BasicType::Placeholder,
+        b's' => BasicType::I16,
+        b't' => BasicType::U16,
+        b'u' => BasicType::Unit,
+        b'v' => BasicType::Ellipsis,
+        b'x' => BasicType::I64,
+        b'y' => BasicType::U64,
+        b'z' => BasicType::Never,
+        _ => return None,
+    })
+}
+
+pub(crate) fn unexpected_type_tag<T>(tag: u8) -> Result<T, String> {
+    Err(format!(
+        "Expected start of <type>, found {} instead.",
+        tag as char
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Parser, MAX_DEPTH, STACK_SIZE};
+    use ast::Symbol;
+    use borrowed;
+    use limits::ResourceLimits;
+    use std::thread;
+
+    #[test]
+    fn reject_cyclic_backrefs() {
+        assert!(parse(b"_RB_").is_err());
+        assert!(parse(b"_RNvB0_3foo").is_err());
+    }
+
+    #[test]
+    fn reject_overflowing_numbers() {
+        assert!(parse(b"_RNvC99999999999999999999foo3bar")
+            .unwrap_err()
+            .contains("64 bits"));
+        // u64::MAX, which is one less than the encoded index.
+        assert!(parse(b"_RNvCslYGhA16ahyf_3foo3bar")
+            .unwrap_err()
+            .contains("64 bits"));
+        // A disambiguator of u64::MAX, which is one more than the encoded
+        // number.
+        assert!(parse(b"_RCslYGhA16ahye_3foo")
+            .unwrap_err()
+            .contains("64 bits"));
+    }
+
+    #[test]
+    fn reject_overlong_identifiers() {
+        // u64::MAX bytes, which would overflow the end position.
+        assert!(parse(b"_RC18446744073709551615foo")
+            .unwrap_err()
+            .contains("beyond end of input"));
+        assert!(parse(b"_RC4foo").is_err());
+    }
+
+    #[test]
+    fn reject_deep_nesting() {
+        let mut mangled = String::from("_RINxC3foo3bar");
+        for _ in 0..MAX_DEPTH {
+            mangled.push('S');
+        }
+        mangled.push_str("uE");
+
+        assert!(parse(mangled.as_bytes())
+            .unwrap_err()
+            .contains("nested deeper"));
+    }
+
+    #[test]
+    fn memoized_backrefs_count_towards_depth() {
+        // foo::bar<[..[u]..], <backref>, [..[<backref>]..]>, where both
+        // backrefs point to the first argument and the second one is
+        // resolved from the memo.
+        let nested = |n: usize, inner: &str| "S".repeat(n) + inner;
+        let symbol = |n: usize| format!("_RINxC3foo3bar{}Bb_{}E", nested(n, "u"), nested(n, "Bb_"));
+
+        let half = MAX_DEPTH as usize / 2;
+        assert!(parse(symbol(half - 2).as_bytes()).is_ok());
+        assert!(parse(symbol(half).as_bytes())
+            .unwrap_err()
+            .contains("nested deeper"));
+    }
+
+    /// Parses, demangles and mangles `prefix` repeated as often as the depth
+    /// limit allows, followed by `suffix` repeated as often, on a thread with
+    /// a stack of `STACK_SIZE`.
+    fn assert_max_depth_fits_stack(prefix: &'static str, suffix: &'static str) {
+        let nested = move |levels: usize| {
+            format!(
+                "_RINxC3foo3bar{}u{}E",
+                prefix.repeat(levels),
+                suffix.repeat(levels)
+            )
+        };
+
+        let result = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let levels = (1..MAX_DEPTH as usize)
+                    .take_while(|&levels| parse(nested(levels).as_bytes()).is_ok())
+                    .last()
+                    .unwrap();
+                assert!(parse(nested(levels + 1).as_bytes())
+                    .unwrap_err()
+                    .contains("nested deeper"));
+
+                let mangled = nested(levels);
+                let symbol = Symbol::parse(&mangled).unwrap();
+                assert!(borrowed::parse(mangled.as_bytes()).is_ok());
+                assert!(symbol.demangle().starts_with("foo::bar<"));
+                assert!(symbol.mangle().len() <= mangled.len());
+            })
+            .unwrap()
+            .join();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn max_depth_fits_stack() {
+        assert_max_depth_fits_stack("FG_E", "");
+        assert_max_depth_fits_stack("S", "");
+        assert_max_depth_fits_stack("INvC1a1b", "E");
+        assert_max_depth_fits_stack("DG_INvC1a1b", "EEL_");
+        assert_max_depth_fits_stack("DG_NvC1a1bp1c", "EL_");
+        assert_max_depth_fits_stack("NvMC1aS", "3foo");
+    }
+
+    #[test]
+    fn reserve_memo_within_node_budget() {
+        // foo::bar<u, <backref>> followed by a megabyte of `B`s in an
+        // identifier, which must not make the memo reserve room for a
+        // million backrefs under a budget of 1000 nodes.
+        let mangled = format!("_RINxC3foo3baruBb_EC1048576{}", "B".repeat(1 << 20));
+        let limits = ResourceLimits {
+            max_nodes: 1000,
+            ..ResourceLimits::default()
+        };
+        let mut parser = Parser::with_limits(mangled.as_bytes(), limits);
+        parser.parse_symbol().unwrap();
+        assert!(parser.memo.types.nodes.capacity() < 2000);
+    }
 }
@@ -0,0 +1,287 @@
+//! Patterns like `core::ptr::drop_in_place<*>` that are matched against the
+//! AST of symbols, so that filters neither demangle every symbol nor have to
+//! escape the regex metacharacters in Rust paths.
+//!
+//! A pattern is a path of `::`-separated segments, each of which is a name,
+//! `*` for any one segment, or `**` for any number of segments. Impls are
+//! written as they are demangled, like `<foo::Bar as core::fmt::Debug>`, and
+//! closures as `{closure}`. A segment may be followed by generic arguments in
+//! `<>`, each of which is `*` for any one argument, `**` for any number of
+//! them, or a pattern for a named type or the demangled form of any other
+//! argument, like `u8` or `&str`. A segment without `<>` matches with any
+//! generic arguments. Disambiguators and the instantiating crate are ignored.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use fold::Folder;
+use skeleton::DisambiguatorEraser;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// A compiled pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    /// `**`
+    Any,
+    /// A name, or `*` for `None`, with the patterns for the generic arguments
+    /// if they were given.
+    One {
+        name: Option<String>,
+        args: Option<Vec<Arg>>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Arg {
+    /// `**`
+    Any,
+    /// `*`
+    One,
+    /// A named type, or the demangled form of another argument.
+    Path(Pattern, String),
+    /// The demangled form of an argument that is not a path.
+    Text(String),
+}
+
+/// A path segment of a symbol.
+struct SymbolSegment<'a> {
+    name: Name<'a>,
+    args: Option<&'a [GenericArg]>,
+}
+
+enum Name<'a> {
+    Ident(&'a str),
+    Closure,
+    Impl(&'a Path),
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Pattern, String> {
+        let segments = split_top_level(pattern, "::")
+            .into_iter()
+            .map(parse_segment)
+            .collect::<Result<_, _>>()?;
+        Ok(Pattern { segments })
+    }
+
+    /// Whether the path of `symbol` matches.
+    pub fn matches(&self, symbol: &Symbol) -> bool {
+        self.matches_path(&symbol.path)
+    }
+
+    pub fn matches_path(&self, path: &Path) -> bool {
+        let mut segments = Vec::new();
+        flatten(path, &mut segments);
+        matches_segments(&self.segments, &segments)
+    }
+}
+
+fn parse_segment(segment: &str) -> Result<Segment, String> {
+    if segment == "**" {
+        return Ok(Segment::Any);
+    }
+    if segment.starts_with('<') {
+        return Ok(Segment::One {
+            name: Some(segment.to_string()),
+            args: None,
+        });
+    }
+
+    let (name, args) = match segment.find('<') {
+        Some(start) if segment.ends_with('>') => {
+            let args = split_top_level(&segment[start + 1..segment.len() - 1], ",")
+                .into_iter()
+                .map(|arg| parse_arg(arg.trim()))
+                .collect::<Result<_, _>>()?;
+            (&segment[..start], Some(args))
+        }
+        Some(_) => return Err(format!("unterminated generic arguments in {:?}", segment)),
+        None => (segment, None),
+    };
+
+    if name.is_empty() || name.contains(['<', '>', ',']) {
+        return Err(format!("invalid path segment {:?}", segment));
+    }
+
+    Ok(Segment::One {
+        name: if name == "*" {
+            None
+        } else {
+            Some(name.to_string())
+        },
+        args,
+    })
+}
+
+fn parse_arg(arg: &str) -> Result<Arg, String> {
+    Ok(match arg {
+        "" => return Err("empty generic argument".to_string()),
+        "**" => Arg::Any,
+        "*" => Arg::One,
+        _ => match Pattern::parse(arg) {
+            Ok(pattern) => Arg::Path(pattern, arg.to_string()),
+            Err(_) => Arg::Text(arg.to_string()),
+        },
+    })
+}
+
+/// Splits `text` at each `separator` that is not within brackets.
+pub(crate) fn split_top_level<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        match text.as_bytes()[i] {
+            b'<' | b'(' | b'[' => depth += 1,
+            b'>' | b')' | b']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && text[i..].starts_with(separator) => {
+                parts.push(&text[start..i]);
+                i += separator.len();
+                start = i;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Appends the segments of `path` to `out`.
+fn flatten<'a>(path: &'a Path, out: &mut Vec<SymbolSegment<'a>>) {
+    let name = match *path {
+        Path::CrateRoot { ref id } => Name::Ident(&id.u_ident.0),
+        Path::InherentImpl { .. } | Path::TraitImpl { .. } | Path::TraitDef { .. } => {
+            Name::Impl(path)
+        }
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            flatten(inner, out);
+            if *ns == Namespace(b'C') {
+                Name::Closure
+            } else if ident.u_ident.0.is_empty() {
+                return;
+            } else {
+                Name::Ident(&ident.u_ident.0)
+            }
+        }
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => {
+            flatten(inner, out);
+            if let Some(last) = out.last_mut() {
+                last.args = Some(args);
+            }
+            return;
+        }
+    };
+
+    out.push(SymbolSegment { name, args: None });
+}
+
+fn matches_segments(patterns: &[Segment], segments: &[SymbolSegment]) -> bool {
+    match patterns.split_first() {
+        None => segments.is_empty(),
+        Some((&Segment::Any, rest)) => {
+            (0..=segments.len()).any(|skip| matches_segments(rest, &segments[skip..]))
+        }
+        Some((Segment::One { name, args }, rest)) => match segments.split_first() {
+            Some((segment, segments)) => {
+                name.as_ref()
+                    .is_none_or(|name| matches_name(name, &segment.name))
+                    && args
+                        .as_ref()
+                        .is_none_or(|args| matches_args(args, segment.args.unwrap_or(&[])))
+                    && matches_segments(rest, segments)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_name(pattern: &str, name: &Name) -> bool {
+    match *name {
+        Name::Ident(ident) => pattern == ident,
+        Name::Closure => pattern == "{closure}",
+        Name::Impl(path) => pattern == DisambiguatorEraser.fold_path(path).demangle(),
+    }
+}
+
+fn matches_args(patterns: &[Arg], args: &[GenericArg]) -> bool {
+    match patterns.split_first() {
+        None => args.is_empty(),
+        Some((&Arg::Any, rest)) => (0..=args.len()).any(|skip| matches_args(rest, &args[skip..])),
+        Some((pattern, rest)) => match args.split_first() {
+            Some((arg, args)) => matches_arg(pattern, arg) && matches_args(rest, args),
+            None => false,
+        },
+    }
+}
+
+fn matches_arg(pattern: &Arg, arg: &GenericArg) -> bool {
+    match (pattern, arg) {
+        (Arg::Path(pattern, _), GenericArg::Type(Type::Named(path))) => pattern.matches_path(path),
+        (Arg::Path(_, text), _) | (Arg::Text(text), _) => {
+            *text == DisambiguatorEraser.fold_generic_arg(arg).demangle()
+        }
+        (Arg::Any, _) | (Arg::One, _) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+    use ast::Symbol;
+
+    #[test]
+    fn match_patterns() {
+        // core[6]::ptr::drop_in_place<alloc::vec::Vec<u8>>
+        let symbol =
+            Symbol::parse("_RINxNyCs4_4core3ptr13drop_in_placeINyNyC5alloc3vec3VechEEC1a").unwrap();
+        // <foo::Bar as core::fmt::Debug>::fmt::{closure}
+        let closure = Symbol::parse("_RNCNxXs_C3fooNyC3foo3BarNyNyC4core3fmt5Debug3fmt0").unwrap();
+
+        for &(pattern, matches_symbol, matches_closure) in &[
+            ("core::ptr::drop_in_place", true, false),
+            ("core::ptr::drop_in_place<*>", true, false),
+            ("core::ptr::drop_in_place<alloc::**>", true, false),
+            ("core::ptr::drop_in_place<alloc::vec::Vec<u8>>", true, false),
+            (
+                "core::ptr::drop_in_place<alloc::vec::Vec<u16>>",
+                false,
+                false,
+            ),
+            ("core::ptr::drop_in_place<*, *>", false, false),
+            ("core::*", false, false),
+            ("core::**", true, false),
+            ("**::drop_in_place<**>", true, false),
+            (
+                "<foo::Bar as core::fmt::Debug>::fmt::{closure}",
+                false,
+                true,
+            ),
+            ("*::fmt::*", false, true),
+            ("**", true, true),
+        ] {
+            let pattern = Pattern::parse(pattern).unwrap();
+            assert_eq!(matches_symbol, pattern.matches(&symbol), "{:?}", pattern);
+            assert_eq!(matches_closure, pattern.matches(&closure), "{:?}", pattern);
+        }
+
+        assert!(Pattern::parse("core::").is_err());
+        assert!(Pattern::parse("core::ptr<u8").is_err());
+    }
+}
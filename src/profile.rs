@@ -0,0 +1,150 @@
+//! Per-phase statistics for the demangling pipeline.
+//!
+//! `PipelineStats` times every phase it runs. Allocations are counted too if
+//! the program uses `CountingAllocator` as its global allocator:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator;
+//! ```
+//!
+//! The allocation counters are process-wide, so allocations made by other
+//! threads while a phase runs are attributed to that phase.
+//!
+//! This crate resolves backrefs while parsing, so their cost is part of the
+//! parse phase rather than a separate decompression phase.
+
+use ast::Symbol;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The system allocator, counting every allocation it makes.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// The number of allocations made through `CountingAllocator` so far, and
+/// their total size in bytes.
+pub fn allocation_counts() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+fn count_allocation(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PhaseStats {
+    pub calls: u64,
+    pub time: Duration,
+    /// Zero unless `CountingAllocator` is the global allocator.
+    pub allocations: u64,
+    pub allocated_bytes: u64,
+}
+
+impl PhaseStats {
+    fn measure<T, F: FnOnce() -> T>(&mut self, f: F) -> T {
+        let (allocations, allocated_bytes) = allocation_counts();
+        let start = Instant::now();
+
+        let result = f();
+
+        self.time += start.elapsed();
+        self.calls += 1;
+        let (end_allocations, end_allocated_bytes) = allocation_counts();
+        self.allocations += end_allocations - allocations;
+        self.allocated_bytes += end_allocated_bytes - allocated_bytes;
+
+        result
+    }
+}
+
+/// Statistics accumulated over all symbols demangled through it.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PipelineStats {
+    pub parse: PhaseStats,
+    pub demangle: PhaseStats,
+}
+
+impl PipelineStats {
+    pub fn new() -> PipelineStats {
+        PipelineStats::default()
+    }
+
+    /// Demangles `mangled`, adding the cost of each phase to the stats.
+    pub fn demangle(&mut self, mangled: &str) -> Result<String, String> {
+        let symbol = self.parse.measure(|| Symbol::parse(mangled))?;
+        Ok(self.demangle.measure(|| symbol.demangle()))
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>8} {:>12} {:>12} {:>14}",
+            "phase", "calls", "time", "allocations", "bytes"
+        )?;
+
+        for &(name, ref phase) in &[("parse", self.parse), ("demangle", self.demangle)] {
+            writeln!(
+                f,
+                "{:<10} {:>8} {:>12.2?} {:>12} {:>14}",
+                name, phase.calls, phase.time, phase.allocations, phase.allocated_bytes
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CountingAllocator, PipelineStats};
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn phases_are_counted() {
+        let mut stats = PipelineStats::new();
+
+        assert_eq!(
+            "mycrate::quux",
+            stats.demangle("_RNxC7mycrate4quux").unwrap()
+        );
+        assert!(stats.demangle("_RXX").is_err());
+
+        assert_eq!((2, 1), (stats.parse.calls, stats.demangle.calls));
+        assert!(stats.parse.allocations > 0);
+        assert!(stats.demangle.allocated_bytes >= "mycrate::quux".len() as u64);
+    }
+}
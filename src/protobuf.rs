@@ -0,0 +1,117 @@
+//! The protobuf encoding of `SymbolInfo`, for symbol pipelines in other
+//! languages. `SCHEMA` is the schema to generate their decoders from, which
+//! is also in `schema/symbol_info.proto`.
+//!
+//! Streams of messages, as written by `std-mangle analyze`, are delimited
+//! the usual way, with each message preceded by its length as a varint, so
+//! Java's `parseDelimitedFrom()` and Go's `protodelim` read them directly.
+
+use info::SymbolInfo;
+use stable::Namespace;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The schema of the messages.
+pub const SCHEMA: &str = include_str!("../schema/symbol_info.proto");
+
+const VARINT: u8 = 0;
+const LEN: u8 = 2;
+
+impl SymbolInfo {
+    /// Encodes this as a `std_mangle.v1.SymbolInfo` message.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        string(&mut out, 1, &self.demangled);
+        string(&mut out, 2, &self.crate_name);
+        if let Some(ref krate) = self.instantiating_crate {
+            // `optional` fields are written even if they are empty.
+            bytes(&mut out, 3, krate.as_bytes());
+        }
+        string(&mut out, 4, &self.path);
+        string(&mut out, 5, &self.skeleton);
+        for arg in &self.generic_args {
+            bytes(&mut out, 6, arg.as_bytes());
+        }
+
+        let (namespace, tag) = match self.namespace {
+            None => (0, None),
+            Some(Namespace::Type) => (1, None),
+            Some(Namespace::Value) => (2, None),
+            Some(Namespace::Closure) => (3, None),
+            Some(Namespace::Other(tag)) => (4, Some(tag)),
+        };
+        if namespace != 0 {
+            key(&mut out, 7, VARINT);
+            varint(&mut out, namespace);
+        }
+        if let Some(tag) = tag {
+            string(&mut out, 8, tag.encode_utf8(&mut [0; 4]));
+        }
+
+        for suffix in &self.suffixes {
+            bytes(&mut out, 9, suffix.as_bytes());
+        }
+        if self.legacy {
+            key(&mut out, 10, VARINT);
+            varint(&mut out, 1);
+        }
+
+        out
+    }
+
+    /// Appends this message to `out`, preceded by its length.
+    pub fn write_protobuf_delimited(&self, out: &mut Vec<u8>) {
+        let message = self.to_protobuf();
+        varint(out, message.len() as u64);
+        out.extend_from_slice(&message);
+    }
+}
+
+fn key(out: &mut Vec<u8>, field: u32, wire_type: u8) {
+    varint(out, u64::from(field) << 3 | u64::from(wire_type));
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Writes a singular string field, which proto3 leaves out if it is empty.
+fn string(out: &mut Vec<u8>, field: u32, value: &str) {
+    if !value.is_empty() {
+        bytes(out, field, value.as_bytes());
+    }
+}
+
+fn bytes(out: &mut Vec<u8>, field: u32, value: &[u8]) {
+    key(out, field, LEN);
+    varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use info::analyze;
+
+    #[test]
+    fn encode_symbol_info() {
+        let info = analyze("_RNxC7mycrate3foo.cold").unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\x0a\x0cmycrate::foo");
+        expected.extend_from_slice(b"\x12\x07mycrate");
+        expected.extend_from_slice(b"\x22\x0cmycrate::foo");
+        expected.extend_from_slice(b"\x2a\x0cmycrate::foo");
+        expected.extend_from_slice(b"\x38\x02");
+        expected.extend_from_slice(b"\x4a\x05.cold");
+        assert_eq!(expected, info.to_protobuf());
+
+        let mut delimited = Vec::new();
+        info.write_protobuf_delimited(&mut delimited);
+        assert_eq!(expected.len(), delimited[0] as usize);
+        assert_eq!(expected, &delimited[1..]);
+    }
+}
@@ -0,0 +1,176 @@
+//! Punycode ([RFC 3492](https://tools.ietf.org/html/rfc3492)), which encodes
+//! Unicode identifiers with ASCII letters, digits and a `-` delimiter.
+//!
+//! The symbol-specific changes to this encoding, `_` instead of `-` and `A`
+//! to `J` instead of digits, are done by `charset`.
+
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 0x80;
+const DELIMITER: char = '-';
+
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = delta / if first_time { DAMP } else { 2 };
+    delta += delta / num_points;
+
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+
+    k + ((BASE - T_MIN + 1) * delta) / (delta + SKEW)
+}
+
+/// The threshold for the digit at position `k` of a variable-length integer.
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        T_MIN
+    } else if k >= bias + T_MAX {
+        T_MAX
+    } else {
+        k - bias
+    }
+}
+
+/// Decodes `input`, returning `None` if it is malformed or overflows.
+pub fn decode_to_string(input: &str) -> Option<String> {
+    // The basic code points come before the last delimiter, if any.
+    let (mut output, input): (Vec<char>, &str) = match input.rfind(DELIMITER) {
+        Some(position) if position > 0 => {
+            (input[..position].chars().collect(), &input[position + 1..])
+        }
+        _ => (Vec::new(), input),
+    };
+
+    let mut code_point = INITIAL_N;
+    let mut bias = INITIAL_BIAS;
+    let mut i: u32 = 0;
+    let mut bytes = input.bytes().peekable();
+
+    while bytes.peek().is_some() {
+        let previous_i = i;
+        let mut weight: u32 = 1;
+        let mut k = BASE;
+
+        loop {
+            let digit = match bytes.next()? {
+                byte @ b'a'..=b'z' => byte - b'a',
+                byte @ b'A'..=b'Z' => byte - b'A',
+                byte @ b'0'..=b'9' => byte - b'0' + 26,
+                _ => return None,
+            } as u32;
+
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            weight = weight.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - previous_i, len, previous_i == 0);
+        code_point = code_point.checked_add(i / len)?;
+        i %= len;
+
+        output.insert(i as usize, char::from_u32(code_point)?);
+        i += 1;
+    }
+
+    Some(output.into_iter().collect())
+}
+
+/// Encodes `input`, returning `None` if it overflows.
+pub fn encode_str(input: &str) -> Option<String> {
+    let mut output: String = input.chars().filter(char::is_ascii).collect();
+    let basic_len = output.len() as u32;
+    if basic_len > 0 {
+        output.push(DELIMITER);
+    }
+
+    let mut code_point = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut processed = basic_len;
+    let len = input.chars().count() as u32;
+
+    while processed < len {
+        // All code points below `code_point` are encoded already.
+        let next = input
+            .chars()
+            .map(|c| c as u32)
+            .filter(|&c| c >= code_point)
+            .min()?;
+        delta = delta.checked_add((next - code_point).checked_mul(processed + 1)?)?;
+        code_point = next;
+
+        for c in input.chars().map(|c| c as u32) {
+            if c < code_point {
+                delta = delta.checked_add(1)?;
+            }
+            if c != code_point {
+                continue;
+            }
+
+            let mut q = delta;
+            let mut k = BASE;
+            loop {
+                let t = threshold(k, bias);
+                if q < t {
+                    break;
+                }
+                output.push(digit(t + (q - t) % (BASE - t)));
+                q = (q - t) / (BASE - t);
+                k += BASE;
+            }
+            output.push(digit(q));
+
+            bias = adapt(delta, processed + 1, processed == basic_len);
+            delta = 0;
+            processed += 1;
+        }
+
+        delta += 1;
+        code_point += 1;
+    }
+
+    Some(output)
+}
+
+fn digit(value: u32) -> char {
+    match value {
+        0..=25 => (b'a' + value as u8) as char,
+        _ => (b'0' + (value - 26) as u8) as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_to_string, encode_str};
+
+    #[test]
+    fn rfc_examples() {
+        for &(decoded, encoded) in &[
+            ("bücher", "bcher-kva"),
+            ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+            ("Pročprostěnemluvíčesky", "Proprostnemluvesky-uyb24dma41a"),
+            ("abc", "abc-"),
+            ("", ""),
+        ] {
+            assert_eq!(Some(encoded.to_string()), encode_str(decoded));
+            assert_eq!(Some(decoded.to_string()), decode_to_string(encoded));
+        }
+
+        assert_eq!(None, decode_to_string("a-b!"));
+        assert_eq!(None, decode_to_string("99999999999"));
+    }
+}
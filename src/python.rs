@@ -0,0 +1,92 @@
+//! Python bindings, so that scripts analyzing symbol dumps can use this
+//! crate directly. Build the extension module with
+//! `cargo rustc --release --lib --crate-type cdylib --features
+//! python,pyo3/extension-module` and rename the library to `std_mangle.so`
+//! (`std_mangle.pyd` on Windows), or build a wheel with `maturin`.
+//!
+//! The module has these functions:
+//!
+//! - `demangle(mangled)` returns the demangled string.
+//! - `parse(mangled)` returns the AST as a `dict`, in the JSON form of
+//!   `Symbol::to_json()`.
+//! - `demangle_all(symbols)` demangles a list of strings into a list with
+//!   `None` for every string that is not a valid symbol. It does not hold
+//!   the GIL while demangling.
+//!
+//! `demangle()` and `parse()` raise `ValueError` for invalid symbols.
+
+use ast::Symbol;
+use batch::Demangler;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Demangles the v0 symbol `mangled`.
+#[pyfunction]
+fn demangle(mangled: &str) -> PyResult<String> {
+    Symbol::parse(mangled)
+        .map(|symbol| symbol.demangle())
+        .map_err(PyValueError::new_err)
+}
+
+/// Parses the v0 symbol `mangled` into a `dict`.
+#[pyfunction]
+fn parse<'py>(py: Python<'py>, mangled: &str) -> PyResult<Bound<'py, PyAny>> {
+    let symbol = Symbol::parse(mangled).map_err(PyValueError::new_err)?;
+    py.import("json")?
+        .call_method1("loads", (symbol.to_json(),))
+}
+
+/// Demangles `symbols` without holding the GIL.
+#[pyfunction]
+fn demangle_all(py: Python, symbols: Vec<String>) -> Vec<Option<String>> {
+    py.allow_threads(|| {
+        let mut demangler = Demangler::new();
+        symbols
+            .iter()
+            .map(|mangled| demangler.demangle(mangled).ok().map(str::to_string))
+            .collect()
+    })
+}
+
+#[pymodule]
+fn std_mangle(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(self::demangle, module)?)?;
+    module.add_function(wrap_pyfunction!(self::parse, module)?)?;
+    module.add_function(wrap_pyfunction!(self::demangle_all, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, demangle_all, parse};
+    use pyo3::prelude::*;
+
+    #[test]
+    fn call_from_python() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            assert_eq!("mycrate::foo", demangle("_RNvC7mycrate3foo").unwrap());
+            assert!(demangle("main")
+                .unwrap_err()
+                .is_instance_of::<pyo3::exceptions::PyValueError>(py));
+
+            let ast = parse(py, "_RNvC7mycrate3foo").unwrap();
+            let name: String = ast
+                .get_item("path")
+                .and_then(|path| path.get_item("Nested"))
+                .and_then(|nested| nested.get_item("ident"))
+                .and_then(|ident| ident.get_item("u_ident"))
+                .and_then(|ident| ident.extract())
+                .unwrap();
+            assert_eq!("foo", name);
+
+            assert_eq!(
+                vec![Some("mycrate::foo".to_string()), None],
+                demangle_all(
+                    py,
+                    vec!["_RNvC7mycrate3foo".to_string(), "main".to_string()]
+                )
+            );
+        });
+    }
+}
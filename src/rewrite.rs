@@ -0,0 +1,180 @@
+//! Rewriting of the crates and paths in symbols, e.g. for retargeting
+//! prebuilt object files from a vendored copy of a crate to the original.
+
+use ast::*;
+use fold::{self, Folder};
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// What `rewrite()` changes. All rules are matched against the original
+/// symbol, so renaming crate `a` to `b` and `b` to `a` swaps them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RewriteRules {
+    crate_names: Vec<(String, String)>,
+    crate_disambiguators: Vec<(String, u64)>,
+    moves: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl RewriteRules {
+    pub fn new() -> RewriteRules {
+        RewriteRules::default()
+    }
+
+    /// Renames the crate `from` to `to`.
+    pub fn rename_crate(mut self, from: &str, to: &str) -> RewriteRules {
+        self.crate_names.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Gives the crate `krate` the disambiguator `dis`, e.g. the one of the
+    /// build that the symbols should link against.
+    pub fn crate_disambiguator(mut self, krate: &str, dis: u64) -> RewriteRules {
+        self.crate_disambiguators.push((krate.to_string(), dis));
+        self
+    }
+
+    /// Moves the items in the module `from` to the module `to`, both given
+    /// as `::`-separated paths that start with a crate, like `foo::bar`.
+    /// Modules that `to` adds are in the type namespace, and the crate of
+    /// `to` keeps the old crate's disambiguator if it has the same name.
+    pub fn rehome(mut self, from: &str, to: &str) -> RewriteRules {
+        let segments = |path: &str| path.split("::").map(str::to_string).collect();
+        self.moves.push((segments(from), segments(to)));
+        self
+    }
+}
+
+/// Applies `rules` to every path in `symbol`, including generic arguments
+/// and the instantiating crate, and returns the result, mangled with all
+/// possible backrefs.
+pub fn rewrite(symbol: &Symbol, rules: &RewriteRules) -> String {
+    Rewriter { rules }.fold_symbol(symbol).mangle()
+}
+
+struct Rewriter<'a> {
+    rules: &'a RewriteRules,
+}
+
+impl<'a> Rewriter<'a> {
+    fn crate_root(&self, id: &Ident) -> Path {
+        let name = &*id.u_ident.0;
+        let renamed = self
+            .rules
+            .crate_names
+            .iter()
+            .find(|(from, _)| from == name)
+            .map_or(name, |(_, to)| &to[..]);
+        let dis = self
+            .rules
+            .crate_disambiguators
+            .iter()
+            .find(|(krate, _)| krate == name)
+            .map_or(id.dis, |&(_, dis)| Disambiguator(dis));
+
+        Path::CrateRoot {
+            id: Ident {
+                dis,
+                u_ident: UIdent(renamed.into()),
+            },
+        }
+    }
+
+    /// Returns `path` moved by the first matching move, if any.
+    fn moved(&self, path: &Path) -> Option<Path> {
+        let mut segments = Vec::new();
+        let mut root = path;
+        let krate = loop {
+            match *root {
+                Path::CrateRoot { ref id } => break id,
+                Path::Nested {
+                    ref inner,
+                    ref ident,
+                    ..
+                } => {
+                    segments.push(&*ident.u_ident.0);
+                    root = inner;
+                }
+                _ => return None,
+            }
+        };
+        segments.push(&*krate.u_ident.0);
+        segments.reverse();
+
+        let (_, to) = self.rules.moves.iter().find(|(from, _)| {
+            from.len() == segments.len() && from.iter().zip(&segments).all(|(a, b)| a == b)
+        })?;
+
+        let dis = if to[0] == segments[0] {
+            krate.dis
+        } else {
+            Disambiguator(0)
+        };
+        let mut moved = Path::CrateRoot {
+            id: Ident {
+                dis,
+                u_ident: UIdent(to[0].as_str().into()),
+            },
+        };
+        for (i, name) in to.iter().enumerate().skip(1) {
+            // The last segment takes the place of the matched one.
+            let (ns, dis) = match *path {
+                Path::Nested {
+                    ref ns, ref ident, ..
+                } if i == to.len() - 1 => (ns.clone(), ident.dis),
+                _ => (Namespace(b'y'), Disambiguator(0)),
+            };
+            moved = Path::Nested {
+                ns,
+                inner: Ptr::new(moved),
+                ident: Ident {
+                    dis,
+                    u_ident: UIdent(name.as_str().into()),
+                },
+            };
+        }
+        Some(moved)
+    }
+}
+
+impl<'a> Folder for Rewriter<'a> {
+    fn fold_path(&mut self, path: &Path) -> Path {
+        if let Some(moved) = self.moved(path) {
+            return moved;
+        }
+        match *path {
+            Path::CrateRoot { ref id } => self.crate_root(id),
+            _ => fold::walk_path(self, path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite, RewriteRules};
+    use ast::Symbol;
+
+    #[test]
+    fn rewrite_vendored_crate() {
+        let symbol = Symbol::parse(concat!(
+            "_RINxNyCs4_14vendored_serde2de5parse",
+            "NyNyCs4_14vendored_serde2de5ValueEC14vendored_serde"
+        ))
+        .unwrap();
+
+        let rules = RewriteRules::new()
+            .rename_crate("vendored_serde", "serde")
+            .crate_disambiguator("vendored_serde", 0);
+        let rewritten = Symbol::parse(&rewrite(&symbol, &rules)).unwrap();
+        assert_eq!(
+            "serde::de::parse<serde::de::Value> @ serde",
+            rewritten.demangle()
+        );
+
+        let rules = RewriteRules::new().rehome("vendored_serde::de", "serde::private::de");
+        let rewritten = Symbol::parse(&rewrite(&symbol, &rules)).unwrap();
+        assert_eq!(
+            "serde::private::de::parse<serde::private::de::Value> @ vendored_serde",
+            rewritten.demangle()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Mangling schemes behind one interface, so that tools handle every scheme
+//! in a binary without knowing which ones exist.
+//!
+//! The v0 and legacy schemes are built in. Others, e.g. the variant of a
+//! vendor toolchain, are added by implementing `ManglingScheme` and
+//! registering the implementation with a `SchemeRegistry`.
+
+use ast::Symbol;
+use legacy;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// A mangling scheme. The AST of a symbol is the v0 one, which is the only
+/// one able to express everything that other schemes encode.
+pub trait ManglingScheme: Send + Sync {
+    /// A short name like `v0`, for messages and statistics.
+    fn name(&self) -> &'static str;
+
+    /// Whether `mangled` looks like a symbol of this scheme, e.g. because of
+    /// its prefix. This should be cheap, and it need not mean that `parse()`
+    /// succeeds.
+    fn detect(&self, mangled: &str) -> bool;
+
+    fn parse(&self, mangled: &str) -> Result<Symbol, String>;
+
+    fn demangle(&self, mangled: &str) -> Result<String, String> {
+        self.parse(mangled).map(|symbol| symbol.demangle())
+    }
+
+    fn mangle(&self, symbol: &Symbol) -> String;
+}
+
+/// The v0 scheme, with symbols starting with `_R`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct V0;
+
+impl ManglingScheme for V0 {
+    fn name(&self) -> &'static str {
+        "v0"
+    }
+
+    fn detect(&self, mangled: &str) -> bool {
+        mangled.starts_with("_R")
+    }
+
+    fn parse(&self, mangled: &str) -> Result<Symbol, String> {
+        Symbol::parse(mangled)
+    }
+
+    fn mangle(&self, symbol: &Symbol) -> String {
+        symbol.mangle()
+    }
+}
+
+/// The legacy scheme, with symbols starting with `_ZN`, or `__ZN` on macOS.
+/// Parsing and mangling convert with `legacy::to_v0()` and
+/// `legacy::from_v0()`, which lose information.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Legacy;
+
+impl ManglingScheme for Legacy {
+    fn name(&self) -> &'static str {
+        "legacy"
+    }
+
+    fn detect(&self, mangled: &str) -> bool {
+        mangled.starts_with("_ZN") || mangled.starts_with("__ZN")
+    }
+
+    fn parse(&self, mangled: &str) -> Result<Symbol, String> {
+        legacy::to_v0(mangled)
+    }
+
+    fn demangle(&self, mangled: &str) -> Result<String, String> {
+        legacy::demangle(mangled)
+    }
+
+    fn mangle(&self, symbol: &Symbol) -> String {
+        legacy::from_v0(symbol, None)
+    }
+}
+
+/// The built-in schemes, in the order in which they are tried.
+pub static BUILTIN: [&dyn ManglingScheme; 2] = [&V0, &Legacy];
+
+/// Returns the built-in scheme that `mangled` belongs to, if any.
+pub fn detect(mangled: &str) -> Option<&'static dyn ManglingScheme> {
+    BUILTIN
+        .iter()
+        .cloned()
+        .find(|scheme| scheme.detect(mangled))
+}
+
+/// Demangles `mangled` with the built-in scheme it belongs to.
+pub fn demangle(mangled: &str) -> Result<String, String> {
+    detect(mangled)
+        .ok_or_else(|| "not a symbol of any known mangling scheme".to_string())?
+        .demangle(mangled)
+}
+
+/// A list of schemes to pick from by `detect()`.
+pub struct SchemeRegistry {
+    schemes: Vec<Box<dyn ManglingScheme>>,
+}
+
+impl SchemeRegistry {
+    /// A registry with only the built-in schemes.
+    pub fn new() -> SchemeRegistry {
+        SchemeRegistry {
+            schemes: vec![Box::new(V0), Box::new(Legacy)],
+        }
+    }
+
+    /// A registry without any schemes.
+    pub fn empty() -> SchemeRegistry {
+        SchemeRegistry {
+            schemes: Vec::new(),
+        }
+    }
+
+    /// Adds `scheme`, which takes precedence over the schemes registered
+    /// before, so that a variant of a scheme can claim some of its symbols.
+    pub fn register<S: ManglingScheme + 'static>(&mut self, scheme: S) -> &mut SchemeRegistry {
+        self.schemes.insert(0, Box::new(scheme));
+        self
+    }
+
+    /// The schemes in the order in which they are tried.
+    pub fn schemes(&self) -> impl Iterator<Item = &dyn ManglingScheme> {
+        self.schemes.iter().map(|scheme| &**scheme)
+    }
+
+    /// Returns the scheme that `mangled` belongs to, if any.
+    pub fn detect(&self, mangled: &str) -> Option<&dyn ManglingScheme> {
+        self.schemes().find(|scheme| scheme.detect(mangled))
+    }
+
+    pub fn parse(&self, mangled: &str) -> Result<Symbol, String> {
+        self.detect_or_err(mangled)?.parse(mangled)
+    }
+
+    pub fn demangle(&self, mangled: &str) -> Result<String, String> {
+        self.detect_or_err(mangled)?.demangle(mangled)
+    }
+
+    fn detect_or_err(&self, mangled: &str) -> Result<&dyn ManglingScheme, String> {
+        self.detect(mangled)
+            .ok_or_else(|| "not a symbol of any registered mangling scheme".to_string())
+    }
+}
+
+impl Default for SchemeRegistry {
+    fn default() -> SchemeRegistry {
+        SchemeRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, detect, ManglingScheme, SchemeRegistry, V0};
+    use ast::Symbol;
+
+    /// v0 symbols with a vendor prefix.
+    struct Prefixed;
+
+    impl ManglingScheme for Prefixed {
+        fn name(&self) -> &'static str {
+            "prefixed-v0"
+        }
+
+        fn detect(&self, mangled: &str) -> bool {
+            mangled.starts_with("_RV_")
+        }
+
+        fn parse(&self, mangled: &str) -> Result<Symbol, String> {
+            V0.parse(&format!("_R{}", &mangled[4..]))
+        }
+
+        fn mangle(&self, symbol: &Symbol) -> String {
+            format!("_RV_{}", &symbol.mangle()[2..])
+        }
+    }
+
+    #[test]
+    fn dispatch_to_schemes() {
+        assert_eq!(Some("v0"), detect("_RNxC7mycrate3foo").map(|s| s.name()));
+        assert_eq!(Some("legacy"), detect("_ZN3foo3barE").map(|s| s.name()));
+        assert!(detect("main").is_none());
+        assert_eq!(Ok("foo::bar".to_string()), demangle("_ZN3foo3barE"));
+        assert!(demangle("main").is_err());
+
+        let mut registry = SchemeRegistry::new();
+        registry.register(Prefixed);
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            registry.demangle("_RV_NxC7mycrate3foo")
+        );
+        assert_eq!(
+            Ok("mycrate::foo".to_string()),
+            registry.demangle("_RNxC7mycrate3foo")
+        );
+
+        let symbol = registry.parse("_ZN3foo3barE").unwrap();
+        let v0 = registry.detect("_RNxC7mycrate3foo").unwrap();
+        assert_eq!("_RNxC3foo3bar", v0.mangle(&symbol));
+
+        assert!(SchemeRegistry::empty()
+            .demangle("_RNxC7mycrate3foo")
+            .is_err());
+    }
+}
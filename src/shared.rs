@@ -0,0 +1,124 @@
+//! A demangler that can be shared between threads.
+
+use ast::Symbol;
+use batch::CacheStats;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+const SHARDS: usize = 16;
+
+type Demangled = Result<Arc<str>, String>;
+
+/// A caching demangler for many threads at once, e.g. stored in a `static`
+/// `OnceLock` and called from every thread of a profiler.
+///
+/// The cache is split into shards that each have their own lock, and
+/// lookups only take a read lock, so threads mostly demangle symbols they
+/// have seen before without waiting for each other. A shard that reaches
+/// its share of the capacity is cleared before it takes on a new symbol.
+#[derive(Debug)]
+pub struct SharedDemangler {
+    shards: Vec<RwLock<HashMap<String, Demangled>>>,
+    hasher: RandomState,
+    capacity_per_shard: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl SharedDemangler {
+    /// Creates a demangler that caches up to about `capacity` symbols.
+    pub fn new(capacity: usize) -> SharedDemangler {
+        assert!(capacity > 0);
+
+        SharedDemangler {
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+            capacity_per_shard: capacity.div_ceil(SHARDS),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn demangle(&self, mangled: &str) -> Result<Arc<str>, String> {
+        let shard = &self.shards[self.shard_index(mangled)];
+
+        if let Some(result) = shard.read().unwrap().get(mangled) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return result.clone();
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Demangle without holding the lock; if another thread demangles the
+        // same symbol at the same time, both get the same result.
+        let result: Demangled = Symbol::parse(mangled).map(|symbol| symbol.demangle().into());
+
+        let mut shard = shard.write().unwrap();
+        if shard.len() >= self.capacity_per_shard {
+            shard.clear();
+        }
+        shard.insert(mangled.to_string(), result.clone());
+
+        result
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self
+                .shards
+                .iter()
+                .map(|shard| shard.read().unwrap().len())
+                .sum(),
+        }
+    }
+
+    fn shard_index(&self, mangled: &str) -> usize {
+        self.hasher.hash_one(mangled) as usize % SHARDS
+    }
+}
+
+impl Default for SharedDemangler {
+    fn default() -> SharedDemangler {
+        SharedDemangler::new(4096)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedDemangler;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn demangle_from_many_threads() {
+        let demangler = Arc::new(SharedDemangler::new(64));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let demangler = demangler.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(
+                            "mycrate::quux",
+                            &*demangler.demangle("_RNxC7mycrate4quux").unwrap()
+                        );
+                        assert!(demangler.demangle("_RXX").is_err());
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let stats = demangler.cache_stats();
+        assert_eq!(800, stats.hits + stats.misses);
+        assert_eq!(2, stats.entries);
+    }
+}
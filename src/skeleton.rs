@@ -0,0 +1,153 @@
+use ast::*;
+use fold::{self, Folder};
+
+impl Symbol {
+    /// Returns the generic definition this symbol is an instance of: every
+    /// generic argument is replaced by a placeholder and the instantiating
+    /// crate is removed. All monomorphizations of one function share the same
+    /// skeleton.
+    pub fn skeleton(&self) -> Symbol {
+        let mut skeleton = SkeletonFolder.fold_symbol(self);
+        skeleton.instantiating_crate = None;
+        skeleton
+    }
+
+    /// Returns a copy of this symbol in which all identifiers and impl paths
+    /// have no disambiguator, so that symbols that only differ in their
+    /// disambiguators, e.g. between two builds, compare equal. Closures keep
+    /// theirs, which is their index and tells them apart from the other
+    /// closures of the same item.
+    pub fn erase_disambiguators(&self) -> Symbol {
+        DisambiguatorEraser.fold_symbol(self)
+    }
+
+    /// Returns a copy of this symbol without any generic arguments, for
+    /// showing only the name of an item.
+    pub(crate) fn erase_generic_args(&self) -> Symbol {
+        GenericArgsEraser.fold_symbol(self)
+    }
+}
+
+struct GenericArgsEraser;
+
+impl Folder for GenericArgsEraser {
+    fn fold_path(&mut self, path: &Path) -> Path {
+        match *path {
+            Path::Generic { ref inner, .. } => self.fold_path(inner),
+            _ => fold::walk_path(self, path),
+        }
+    }
+}
+
+pub(crate) struct DisambiguatorEraser;
+
+impl Folder for DisambiguatorEraser {
+    fn fold_path(&mut self, path: &Path) -> Path {
+        match *path {
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } if *ns == Namespace(b'C') => Path::Nested {
+                ns: ns.clone(),
+                inner: Ptr::new(self.fold_path(inner)),
+                ident: ident.clone(),
+            },
+            _ => fold::walk_path(self, path),
+        }
+    }
+
+    fn fold_impl_path(&mut self, impl_path: &ImplPath) -> ImplPath {
+        ImplPath {
+            dis: None,
+            ..fold::walk_impl_path(self, impl_path)
+        }
+    }
+
+    fn fold_ident(&mut self, ident: &Ident) -> Ident {
+        Ident {
+            dis: Disambiguator(0),
+            u_ident: ident.u_ident.clone(),
+        }
+    }
+}
+
+struct SkeletonFolder;
+
+impl Folder for SkeletonFolder {
+    fn fold_path(&mut self, path: &Path) -> Path {
+        match *path {
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => Path::Generic {
+                inner: Ptr::new(self.fold_path(inner)),
+                args: args.iter().map(placeholder).collect(),
+            },
+            _ => fold::walk_path(self, path),
+        }
+    }
+}
+
+fn placeholder(arg: &GenericArg) -> GenericArg {
+    match *arg {
+        GenericArg::Lifetime(_) => GenericArg::Lifetime(Lifetime::ERASED),
+        GenericArg::Type(_) => GenericArg::Type(Type::basic(BasicType::Placeholder)),
+        GenericArg::Const(Const::Value(ref ty, _))
+        | GenericArg::Const(Const::Placeholder(ref ty)) => {
+            GenericArg::Const(Const::Placeholder(ty.clone()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Symbol;
+
+    #[test]
+    fn skeleton_of_generic_method() {
+        // <std::vec::Vec<i16>>::retain<xxx::predicate>
+        let symbol =
+            Symbol::parse("_RINxMs89_NyC3std3vecINyNyC3std3vec3VecsE6retainNxC3xxx9predicateE")
+                .unwrap();
+
+        assert_eq!(
+            "<std::vec::Vec<_>>::retain<_>",
+            symbol.skeleton().demangle()
+        );
+    }
+
+    #[test]
+    fn skeleton_strips_instantiating_crate() {
+        let symbol = Symbol::parse("_RINxC7mycrate3barmEC16some_other_crate").unwrap();
+
+        assert_eq!("mycrate::bar<_>", symbol.skeleton().demangle());
+    }
+
+    #[test]
+    fn erase_disambiguators() {
+        let symbol = Symbol::parse("_RNvMs89_NyC3std3vecNtCs1_3std3Vec3new").unwrap();
+
+        assert_eq!(
+            Symbol::parse("_RNvMNyC3std3vecNtC3std3Vec3new").unwrap(),
+            symbol.erase_disambiguators()
+        );
+    }
+
+    #[test]
+    fn erase_disambiguators_keeps_closure_indices() {
+        // mycrate[1234]::main::{closure}[0] and mycrate[1234]::main::{closure}[1]
+        let first = Symbol::parse("_RNCNvCs1234_7mycrate4main0").unwrap();
+        let second = Symbol::parse("_RNCNvCs1234_7mycrate4mains_0").unwrap();
+
+        assert_eq!(
+            "mycrate::main::{closure}[0]",
+            first.erase_disambiguators().demangle()
+        );
+        assert_eq!(
+            "mycrate::main::{closure}[1]",
+            second.erase_disambiguators().demangle()
+        );
+        assert_ne!(first.erase_disambiguators(), second.erase_disambiguators());
+    }
+}
@@ -0,0 +1,460 @@
+//! A facade over the AST that does not expose its representation.
+//!
+//! The node types in `ast` mirror the grammar directly, so every new
+//! production changes a public enum. The types in this module are opaque
+//! views with getters instead: a new kind of node only adds a variant to one of
+//! the `#[non_exhaustive]` `*Kind` enums, and getters that do not apply to a
+//! node return `None`. Tools that only need to inspect symbols should prefer
+//! this module.
+
+use ast;
+use ast_demangle::AstDemangle;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// A parsed symbol.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Symbol(ast::Symbol);
+
+impl Symbol {
+    pub fn parse(mangled: &str) -> Result<Symbol, String> {
+        ast::Symbol::parse(mangled).map(Symbol)
+    }
+
+    pub fn path(&self) -> Path<'_> {
+        Path(&self.0.path)
+    }
+
+    pub fn instantiating_crate(&self) -> Option<Path<'_>> {
+        self.0.instantiating_crate.as_ref().map(Path)
+    }
+
+    pub fn mangle(&self) -> String {
+        self.0.mangle()
+    }
+
+    pub fn demangle(&self) -> String {
+        self.0.demangle()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub enum PathKind {
+    CrateRoot,
+    InherentImpl,
+    TraitImpl,
+    TraitDef,
+    Nested,
+    Generic,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub enum Namespace {
+    Type,
+    Value,
+    Closure,
+    Other(char),
+}
+
+impl Namespace {
+    pub(crate) fn of(ns: &ast::Namespace) -> Namespace {
+        match ns.0 {
+            b'y' => Namespace::Type,
+            b'x' => Namespace::Value,
+            b'C' => Namespace::Closure,
+            c => Namespace::Other(c as char),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Path<'a>(&'a ast::Path);
+
+impl<'a> Path<'a> {
+    pub fn kind(&self) -> PathKind {
+        match *self.0 {
+            ast::Path::CrateRoot { .. } => PathKind::CrateRoot,
+            ast::Path::InherentImpl { .. } => PathKind::InherentImpl,
+            ast::Path::TraitImpl { .. } => PathKind::TraitImpl,
+            ast::Path::TraitDef { .. } => PathKind::TraitDef,
+            ast::Path::Nested { .. } => PathKind::Nested,
+            ast::Path::Generic { .. } => PathKind::Generic,
+        }
+    }
+
+    /// The crate name of a crate root, or the last component of a nested path.
+    pub fn ident(&self) -> Option<Ident<'a>> {
+        match *self.0 {
+            ast::Path::CrateRoot { ref id } | ast::Path::Nested { ident: ref id, .. } => {
+                Some(Ident(id))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn namespace(&self) -> Option<Namespace> {
+        match *self.0 {
+            ast::Path::Nested { ref ns, .. } => Some(Namespace::of(ns)),
+            _ => None,
+        }
+    }
+
+    /// The path this one is nested in or instantiates.
+    pub fn inner(&self) -> Option<Path<'a>> {
+        match *self.0 {
+            ast::Path::Nested { ref inner, .. } | ast::Path::Generic { ref inner, .. } => {
+                Some(Path(inner))
+            }
+            _ => None,
+        }
+    }
+
+    /// The path of the item containing an impl.
+    pub fn impl_path(&self) -> Option<Path<'a>> {
+        match *self.0 {
+            ast::Path::InherentImpl { ref impl_path, .. }
+            | ast::Path::TraitImpl { ref impl_path, .. } => Some(Path(&impl_path.path)),
+            _ => None,
+        }
+    }
+
+    pub fn impl_disambiguator(&self) -> Option<u64> {
+        match *self.0 {
+            ast::Path::InherentImpl { ref impl_path, .. }
+            | ast::Path::TraitImpl { ref impl_path, .. } => impl_path.dis.map(|dis| dis.value()),
+            _ => None,
+        }
+    }
+
+    pub fn self_type(&self) -> Option<Type<'a>> {
+        match *self.0 {
+            ast::Path::InherentImpl { ref self_type, .. }
+            | ast::Path::TraitImpl { ref self_type, .. }
+            | ast::Path::TraitDef { ref self_type, .. } => Some(Type(self_type)),
+            _ => None,
+        }
+    }
+
+    pub fn trait_path(&self) -> Option<Path<'a>> {
+        match *self.0 {
+            ast::Path::TraitImpl { ref trait_name, .. }
+            | ast::Path::TraitDef { ref trait_name, .. } => Some(Path(trait_name)),
+            _ => None,
+        }
+    }
+
+    /// The generic arguments of a generic path; empty for any other path.
+    pub fn generic_args(&self) -> impl Iterator<Item = GenericArg<'a>> + 'a {
+        let args: &'a [ast::GenericArg] = match *self.0 {
+            ast::Path::Generic { ref args, .. } => args,
+            _ => &[],
+        };
+        args.iter().map(GenericArg)
+    }
+}
+
+impl<'a> fmt::Display for Path<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Ident<'a>(&'a ast::Ident);
+
+impl<'a> Ident<'a> {
+    pub fn name(&self) -> &'a str {
+        &self.0.u_ident.0
+    }
+
+    /// The disambiguator of this identifier, zero if it has none.
+    pub fn disambiguator(&self) -> u64 {
+        self.0.dis.value()
+    }
+}
+
+impl<'a> fmt::Display for Ident<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub enum GenericArgKind {
+    Lifetime,
+    Type,
+    Const,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct GenericArg<'a>(&'a ast::GenericArg);
+
+impl<'a> GenericArg<'a> {
+    pub fn kind(&self) -> GenericArgKind {
+        match *self.0 {
+            ast::GenericArg::Lifetime(_) => GenericArgKind::Lifetime,
+            ast::GenericArg::Type(_) => GenericArgKind::Type,
+            ast::GenericArg::Const(_) => GenericArgKind::Const,
+        }
+    }
+
+    pub fn as_lifetime(&self) -> Option<Lifetime<'a>> {
+        match *self.0 {
+            ast::GenericArg::Lifetime(ref lt) => Some(Lifetime(lt)),
+            _ => None,
+        }
+    }
+
+    pub fn as_type(&self) -> Option<Type<'a>> {
+        match *self.0 {
+            ast::GenericArg::Type(ref ty) => Some(Type(ty)),
+            _ => None,
+        }
+    }
+
+    pub fn as_const(&self) -> Option<Const<'a>> {
+        match *self.0 {
+            ast::GenericArg::Const(ref k) => Some(Const(k)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for GenericArg<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Lifetime<'a>(&'a ast::Lifetime);
+
+impl<'a> Lifetime<'a> {
+    /// The De Bruijn index of the binder this lifetime refers to, zero for
+    /// the erased lifetime.
+    pub fn debruijn_index(&self) -> u64 {
+        self.0.debruijn_index.value()
+    }
+}
+
+impl<'a> fmt::Display for Lifetime<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+#[non_exhaustive]
+pub enum TypeKind {
+    Basic,
+    Array,
+    Slice,
+    Named,
+    Tuple,
+    Ref,
+    RefMut,
+    RawPtrConst,
+    RawPtrMut,
+    Fn,
+    DynTrait,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Type<'a>(&'a ast::Type);
+
+impl<'a> Type<'a> {
+    pub fn kind(&self) -> TypeKind {
+        match *self.0 {
+            ast::Type::BasicType(_) => TypeKind::Basic,
+            ast::Type::Array(..) => TypeKind::Array,
+            ast::Type::Slice(_) => TypeKind::Slice,
+            ast::Type::Named(_) => TypeKind::Named,
+            ast::Type::Tuple(_) => TypeKind::Tuple,
+            ast::Type::Ref(..) => TypeKind::Ref,
+            ast::Type::RefMut(..) => TypeKind::RefMut,
+            ast::Type::RawPtrConst(_) => TypeKind::RawPtrConst,
+            ast::Type::RawPtrMut(_) => TypeKind::RawPtrMut,
+            ast::Type::Fn(_) => TypeKind::Fn,
+            ast::Type::DynTrait(..) => TypeKind::DynTrait,
+        }
+    }
+
+    /// The element type of arrays and slices, or the pointee of references
+    /// and raw pointers.
+    pub fn element(&self) -> Option<Type<'a>> {
+        match *self.0 {
+            ast::Type::Array(ref inner, _)
+            | ast::Type::Slice(ref inner)
+            | ast::Type::Ref(_, ref inner)
+            | ast::Type::RefMut(_, ref inner)
+            | ast::Type::RawPtrConst(ref inner)
+            | ast::Type::RawPtrMut(ref inner) => Some(Type(inner)),
+            _ => None,
+        }
+    }
+
+    pub fn array_len(&self) -> Option<Const<'a>> {
+        match *self.0 {
+            ast::Type::Array(_, ref len) => Some(Const(len)),
+            _ => None,
+        }
+    }
+
+    pub fn path(&self) -> Option<Path<'a>> {
+        match *self.0 {
+            ast::Type::Named(ref path) => Some(Path(path)),
+            _ => None,
+        }
+    }
+
+    /// The lifetime of a reference or a trait object, if it has one.
+    pub fn lifetime(&self) -> Option<Lifetime<'a>> {
+        match *self.0 {
+            ast::Type::Ref(Some(ref lt), _) | ast::Type::RefMut(Some(ref lt), _) => {
+                Some(Lifetime(lt))
+            }
+            ast::Type::DynTrait(_, ref lt) => Some(Lifetime(lt)),
+            _ => None,
+        }
+    }
+
+    /// The component types of a tuple; empty for any other type.
+    pub fn tuple_components(&self) -> impl Iterator<Item = Type<'a>> + 'a {
+        let components: &'a [ast::Type] = match *self.0 {
+            ast::Type::Tuple(ref components) => components,
+            _ => &[],
+        };
+        components.iter().map(Type)
+    }
+
+    pub fn fn_sig(&self) -> Option<FnSig<'a>> {
+        match *self.0 {
+            ast::Type::Fn(ref fn_sig) => Some(FnSig(fn_sig)),
+            _ => None,
+        }
+    }
+
+    /// The traits of a trait object; empty for any other type.
+    pub fn dyn_traits(&self) -> impl Iterator<Item = DynTrait<'a>> + 'a {
+        let traits: &'a [ast::DynTrait] = match *self.0 {
+            ast::Type::DynTrait(ref bounds, _) => &bounds.traits,
+            _ => &[],
+        };
+        traits.iter().map(DynTrait)
+    }
+}
+
+impl<'a> fmt::Display for Type<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct FnSig<'a>(&'a ast::FnSig);
+
+impl<'a> FnSig<'a> {
+    /// The number of lifetimes bound by the `for<...>` of this signature.
+    pub fn bound_lifetimes(&self) -> u64 {
+        self.0.binder.count.value()
+    }
+
+    pub fn is_unsafe(&self) -> bool {
+        self.0.is_unsafe
+    }
+
+    /// The ABI of an `extern` function, e.g. `"C"`.
+    pub fn abi(&self) -> Option<&'a str> {
+        self.0.abi.as_ref().map(|abi| match *abi {
+            ast::Abi::C => "C",
+            ast::Abi::Named(ref name) => &name.0[..],
+        })
+    }
+
+    pub fn param_types(&self) -> impl Iterator<Item = Type<'a>> + 'a {
+        self.0.param_types.iter().map(Type)
+    }
+
+    pub fn return_type(&self) -> Type<'a> {
+        Type(&self.0.return_type)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DynTrait<'a>(&'a ast::DynTrait);
+
+impl<'a> DynTrait<'a> {
+    pub fn path(&self) -> Path<'a> {
+        Path(&self.0.path)
+    }
+
+    /// The associated type bindings of this trait, e.g. `Item = u8`.
+    pub fn assoc_type_bindings(&self) -> impl Iterator<Item = (&'a str, Type<'a>)> + 'a {
+        self.0
+            .assoc_type_bindings
+            .iter()
+            .map(|binding| (&binding.ident.0[..], Type(&binding.ty)))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Const<'a>(&'a ast::Const);
+
+impl<'a> Const<'a> {
+    pub fn ty(&self) -> Type<'a> {
+        match *self.0 {
+            ast::Const::Value(ref ty, _) | ast::Const::Placeholder(ref ty) => Type(ty),
+        }
+    }
+
+    /// The value of the constant, `None` for a placeholder.
+    pub fn value(&self) -> Option<u64> {
+        match *self.0 {
+            ast::Const::Value(_, value) => Some(value),
+            ast::Const::Placeholder(_) => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Const<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.demangle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GenericArgKind, Namespace, PathKind, Symbol, TypeKind};
+
+    #[test]
+    fn inspect_through_facade() {
+        let symbol = Symbol::parse("_RINxC7mycrate3bazAsj309_E").unwrap();
+        let path = symbol.path();
+
+        assert_eq!(PathKind::Generic, path.kind());
+
+        let inner = path.inner().unwrap();
+        assert_eq!(Some(Namespace::Value), inner.namespace());
+        assert_eq!("baz", inner.ident().unwrap().name());
+        assert_eq!("mycrate", inner.inner().unwrap().to_string());
+
+        let args: Vec<_> = path.generic_args().collect();
+        assert_eq!(1, args.len());
+        assert_eq!(GenericArgKind::Type, args[0].kind());
+
+        let array = args[0].as_type().unwrap();
+        assert_eq!(TypeKind::Array, array.kind());
+        assert_eq!(Some(0x309), array.array_len().unwrap().value());
+        assert_eq!(TypeKind::Basic, array.element().unwrap().kind());
+    }
+}
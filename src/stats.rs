@@ -0,0 +1,364 @@
+//! Aggregate statistics over a list of symbols.
+
+use ast::*;
+use ast_demangle::AstDemangle;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use visit::{self, Visitor};
+
+/// Counts and lengths collected from a list of symbols, e.g. the symbol table
+/// of a binary.
+#[derive(Clone, Default, Debug)]
+pub struct SymbolStats {
+    /// The number of symbols that were added.
+    pub symbols: usize,
+    /// The number of those that could not be parsed.
+    pub invalid: usize,
+    pub mangled_len: usize,
+    pub demangled_len: usize,
+    crates: HashMap<String, usize>,
+    skeletons: HashMap<String, Skeleton>,
+    /// The longest symbols in order of decreasing length, at most `keep`.
+    longest: Vec<(usize, String)>,
+    keep: usize,
+}
+
+#[derive(Clone, Default, Debug)]
+struct Skeleton {
+    count: usize,
+    /// Whether any symbol with this skeleton has generic arguments.
+    generic: bool,
+}
+
+impl SymbolStats {
+    /// Creates an empty collector that keeps track of the `keep` longest
+    /// symbols.
+    pub fn new(keep: usize) -> SymbolStats {
+        SymbolStats {
+            keep,
+            ..SymbolStats::default()
+        }
+    }
+
+    pub fn add(&mut self, mangled: &str) {
+        self.symbols += 1;
+
+        let symbol = match Symbol::parse(mangled) {
+            Ok(symbol) => symbol,
+            Err(_) => {
+                self.invalid += 1;
+                return;
+            }
+        };
+
+        let demangled = symbol.demangle();
+        self.mangled_len += mangled.len();
+        self.demangled_len += demangled.len();
+
+        *self
+            .crates
+            .entry(symbol.defining_crate().u_ident.0.to_string())
+            .or_insert(0) += 1;
+
+        let skeleton = symbol.skeleton().demangle();
+        let generic = skeleton != demangled;
+        let entry = self.skeletons.entry(skeleton).or_default();
+        entry.count += 1;
+        entry.generic |= generic;
+
+        if self.longest.len() < self.keep
+            || self
+                .longest
+                .last()
+                .is_some_and(|&(len, _)| len < mangled.len())
+        {
+            let index = self
+                .longest
+                .iter()
+                .position(|&(len, _)| len < mangled.len())
+                .unwrap_or(self.longest.len());
+            self.longest.insert(index, (mangled.len(), demangled));
+            self.longest.truncate(self.keep);
+        }
+    }
+
+    /// The average length of the valid symbols, mangled and demangled.
+    pub fn average_len(&self) -> (f64, f64) {
+        let valid = (self.symbols - self.invalid).max(1) as f64;
+        (
+            self.mangled_len as f64 / valid,
+            self.demangled_len as f64 / valid,
+        )
+    }
+
+    /// The crates with the most symbols, with their symbol counts.
+    pub fn top_crates(&self, n: usize) -> Vec<(&str, usize)> {
+        top(
+            self.crates.iter().map(|(name, &count)| (&name[..], count)),
+            n,
+        )
+    }
+
+    /// The skeletons shared by the most symbols, with their symbol counts.
+    pub fn top_skeletons(&self, n: usize) -> Vec<(&str, usize)> {
+        top(
+            self.skeletons
+                .iter()
+                .map(|(skeleton, entry)| (&skeleton[..], entry.count)),
+            n,
+        )
+    }
+
+    /// The generic items with the most instantiations, by their skeleton.
+    pub fn most_instantiated(&self, n: usize) -> Vec<(&str, usize)> {
+        top(
+            self.skeletons
+                .iter()
+                .filter(|&(_, entry)| entry.generic)
+                .map(|(skeleton, entry)| (&skeleton[..], entry.count)),
+            n,
+        )
+    }
+
+    /// The longest symbols, demangled, with their mangled lengths.
+    pub fn longest(&self) -> &[(usize, String)] {
+        &self.longest
+    }
+}
+
+/// The `n` entries with the highest counts, ties broken by name.
+fn top<'a, I: Iterator<Item = (&'a str, usize)>>(entries: I, n: usize) -> Vec<(&'a str, usize)> {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    entries.truncate(n);
+    entries
+}
+
+/// Prints a report with the top ten entries of every list.
+impl fmt::Display for SymbolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (mangled, demangled) = self.average_len();
+
+        writeln!(f, "symbols: {} ({} invalid)", self.symbols, self.invalid)?;
+        writeln!(
+            f,
+            "average length: {:.1} mangled, {:.1} demangled",
+            mangled, demangled
+        )?;
+
+        let lists = [
+            ("symbols per crate", self.top_crates(10)),
+            ("symbols per skeleton", self.top_skeletons(10)),
+            ("most instantiated generics", self.most_instantiated(10)),
+        ];
+        for &(title, ref entries) in &lists {
+            writeln!(f, "\n{}:", title)?;
+            for &(name, count) in entries {
+                writeln!(f, "{:>8}  {}", count, name)?;
+            }
+        }
+
+        writeln!(f, "\nlongest symbols:")?;
+        for &(len, ref demangled) in &self.longest {
+            writeln!(f, "{:>8}  {}", len, demangled)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Distributions over a corpus of symbols, for judging how the mangling
+/// scheme and the standard library's generics play out in practice.
+#[derive(Clone, Default, Debug)]
+pub struct CorpusStats {
+    /// The number of symbols in the corpus.
+    pub symbols: usize,
+    /// The number of those that could not be parsed.
+    pub invalid: usize,
+    /// The total length of the valid symbols without backrefs.
+    pub uncompressed_len: usize,
+    /// The total length of the valid symbols with all possible backrefs.
+    pub compressed_len: usize,
+    identifiers: HashMap<String, usize>,
+    /// The number of generic paths by their number of arguments.
+    arg_counts: BTreeMap<usize, usize>,
+    /// The types used as generic arguments, without disambiguators.
+    arg_types: HashMap<String, usize>,
+}
+
+impl CorpusStats {
+    pub fn from_symbols<'a, I>(symbols: I) -> CorpusStats
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut stats = CorpusStats::default();
+        for mangled in symbols {
+            stats.add(mangled);
+        }
+        stats
+    }
+
+    pub fn add(&mut self, mangled: &str) {
+        self.symbols += 1;
+
+        let symbol = match Symbol::parse(mangled) {
+            Ok(symbol) => symbol.erase_disambiguators(),
+            Err(_) => {
+                self.invalid += 1;
+                return;
+            }
+        };
+
+        self.uncompressed_len += symbol.mangle_uncompressed().len();
+        self.compressed_len += symbol.mangle().len();
+        self.visit_symbol(&symbol);
+    }
+
+    /// The share of the uncompressed length that backrefs save.
+    pub fn substitution_savings(&self) -> f64 {
+        1.0 - self.compressed_len as f64 / self.uncompressed_len.max(1) as f64
+    }
+
+    /// The most frequent identifiers, including crate names, with their
+    /// number of occurrences.
+    pub fn top_identifiers(&self, n: usize) -> Vec<(&str, usize)> {
+        top(
+            self.identifiers
+                .iter()
+                .map(|(ident, &count)| (&ident[..], count)),
+            n,
+        )
+    }
+
+    /// How many generic paths have how many generic arguments, in order of
+    /// the number of arguments.
+    pub fn arg_count_histogram(&self) -> &BTreeMap<usize, usize> {
+        &self.arg_counts
+    }
+
+    /// The types used most often as generic arguments, at any depth, with
+    /// their number of uses.
+    pub fn top_arg_types(&self, n: usize) -> Vec<(&str, usize)> {
+        top(
+            self.arg_types.iter().map(|(ty, &count)| (&ty[..], count)),
+            n,
+        )
+    }
+}
+
+impl<'ast> Visitor<'ast> for CorpusStats {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Path::Generic { ref args, .. } = *path {
+            *self.arg_counts.entry(args.len()).or_insert(0) += 1;
+        }
+        visit::walk_path(self, path);
+    }
+
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        if !ident.u_ident.0.is_empty() {
+            *self
+                .identifiers
+                .entry(ident.u_ident.0.to_string())
+                .or_insert(0) += 1;
+        }
+    }
+
+    fn visit_generic_arg(&mut self, arg: &'ast GenericArg) {
+        if let GenericArg::Type(ref ty) = *arg {
+            *self.arg_types.entry(ty.demangle()).or_insert(0) += 1;
+        }
+        visit::walk_generic_arg(self, arg);
+    }
+}
+
+/// Prints a report with the top ten entries of every list.
+impl fmt::Display for CorpusStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "symbols: {} ({} invalid)", self.symbols, self.invalid)?;
+        writeln!(
+            f,
+            "backrefs: {} of {} bytes saved ({:.1}%)",
+            self.uncompressed_len - self.compressed_len,
+            self.uncompressed_len,
+            self.substitution_savings() * 100.0
+        )?;
+
+        writeln!(f, "\ngeneric paths per argument count:")?;
+        for (&args, &count) in &self.arg_counts {
+            writeln!(f, "{:>8}  {}", count, args)?;
+        }
+
+        let lists = [
+            ("most common identifiers", self.top_identifiers(10)),
+            ("most common argument types", self.top_arg_types(10)),
+        ];
+        for &(title, ref entries) in &lists {
+            writeln!(f, "\n{}:", title)?;
+            for &(name, count) in entries {
+                writeln!(f, "{:>8}  {}", count, name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CorpusStats, SymbolStats};
+
+    #[test]
+    fn aggregate_symbol_list() {
+        let mut stats = SymbolStats::new(2);
+        for &mangled in &[
+            "_RINxC7mycrate3fooaE",
+            "_RINxC7mycrate3foohE",
+            "_RINxC7mycrate3fooINtC3std3VecyEE",
+            "_RNxC3std4quux",
+            "_RXX",
+        ] {
+            stats.add(mangled);
+        }
+
+        assert_eq!((5, 1), (stats.symbols, stats.invalid));
+        assert_eq!(vec![("mycrate", 3), ("std", 1)], stats.top_crates(10));
+        assert_eq!(vec![("mycrate::foo<_>", 3)], stats.most_instantiated(10));
+        assert_eq!(
+            vec![("mycrate::foo<_>", 3), ("std::quux", 1)],
+            stats.top_skeletons(10)
+        );
+        assert_eq!(
+            vec![
+                (33, "mycrate::foo<std::Vec<u64>>".to_string()),
+                (20, "mycrate::foo<i8>".to_string())
+            ],
+            stats.longest()
+        );
+    }
+
+    #[test]
+    fn corpus_distributions() {
+        let stats = CorpusStats::from_symbols(vec![
+            "_RINxC7mycrate3fooINyC3std3VechEE",
+            "_RINxC7mycrate3barINyC3std3VechEINyC3std3VechEE",
+            "_RNxC3std4quux",
+            "_RXX",
+        ]);
+
+        assert_eq!((4, 1), (stats.symbols, stats.invalid));
+        assert_eq!(
+            vec![(1, 4), (2, 1)],
+            stats
+                .arg_count_histogram()
+                .iter()
+                .map(|(&args, &count)| (args, count))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![("std::Vec<u8>", 3), ("u8", 3)],
+            stats.top_arg_types(10)
+        );
+        assert_eq!(("std", 4), stats.top_identifiers(1)[0]);
+        assert!(stats.compressed_len < stats.uncompressed_len);
+    }
+}
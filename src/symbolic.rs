@@ -0,0 +1,157 @@
+//! Demangling with the conventions of the `symbolic-demangle` crate, so that
+//! crash-reporting backends built on `symbolic` can use this crate for Rust
+//! symbols. `Name` takes the place of `symbolic`'s `Name` with its
+//! `Demangle` trait, and `DemangleOptions` has the same constructors.
+//!
+//! Rust symbols do not encode parameter or return types, so `parameters`
+//! decides whether the generic arguments of v0 symbols are shown instead and
+//! `return_type` has no effect. Both forms leave out hashes and
+//! disambiguators, like `symbolic` does.
+
+use compat;
+use std::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The language of a symbol, as far as this crate can tell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Unknown,
+}
+
+impl Language {
+    /// The name `symbolic` uses for this language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Unknown => "unknown",
+        }
+    }
+}
+
+/// What demangled names include.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DemangleOptions {
+    return_type: bool,
+    parameters: bool,
+}
+
+impl DemangleOptions {
+    /// Only the name of the function, e.g. for grouping crash reports.
+    pub const fn name_only() -> DemangleOptions {
+        DemangleOptions {
+            return_type: false,
+            parameters: false,
+        }
+    }
+
+    /// Everything the symbol encodes, e.g. for displaying stack traces.
+    pub const fn complete() -> DemangleOptions {
+        DemangleOptions {
+            return_type: true,
+            parameters: true,
+        }
+    }
+
+    pub const fn return_type(self, return_type: bool) -> DemangleOptions {
+        DemangleOptions {
+            return_type,
+            ..self
+        }
+    }
+
+    pub const fn parameters(self, parameters: bool) -> DemangleOptions {
+        DemangleOptions { parameters, ..self }
+    }
+}
+
+/// A symbol name as it appears in a symbol table or in debug information.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Name<'a> {
+    string: &'a str,
+}
+
+impl<'a> Name<'a> {
+    pub fn new(string: &'a str) -> Name<'a> {
+        Name { string }
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.string
+    }
+
+    /// `Rust` for v0 symbols and for legacy symbols with a hash; legacy
+    /// symbols without one are C++ symbols as far as anyone can tell.
+    pub fn detect_language(&self) -> Language {
+        match compat::try_demangle(self.string) {
+            Ok(ref demangled) if demangled.is_rust() => Language::Rust,
+            _ => Language::Unknown,
+        }
+    }
+
+    /// Demangles this name, or returns `None` if it is not a Rust symbol.
+    pub fn demangle(&self, options: DemangleOptions) -> Option<String> {
+        let demangled = compat::try_demangle(self.string).ok()?;
+        if !demangled.is_rust() {
+            return None;
+        }
+
+        match demangled.v0_symbol() {
+            Some(symbol) if !options.parameters => {
+                let mut symbol = symbol.erase_disambiguators().erase_generic_args();
+                symbol.instantiating_crate = None;
+                Some(symbol.demangle())
+            }
+            _ => Some(format!("{:#}", demangled)),
+        }
+    }
+
+    /// Demangles this name, or returns it as it is if it is not a Rust
+    /// symbol.
+    pub fn try_demangle(&self, options: DemangleOptions) -> Cow<'a, str> {
+        match self.demangle(options) {
+            Some(demangled) => Cow::Owned(demangled),
+            None => Cow::Borrowed(self.string),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Name<'a> {
+    fn from(string: &'a str) -> Name<'a> {
+        Name::new(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DemangleOptions, Language, Name};
+
+    #[test]
+    fn demangle_like_symbolic() {
+        let v0 = Name::new("_RINvCs0_7mycrate3foohE");
+        assert_eq!(Language::Rust, v0.detect_language());
+        assert_eq!(
+            Some("mycrate::foo".to_string()),
+            v0.demangle(DemangleOptions::name_only())
+        );
+        assert_eq!(
+            Some("mycrate::foo<u8>".to_string()),
+            v0.demangle(DemangleOptions::complete())
+        );
+
+        let legacy = Name::new("_ZN3std2io5stdio6_print17h8f2b8a6a4b1c9d0eE");
+        assert_eq!(Language::Rust, legacy.detect_language());
+        assert_eq!(
+            "std::io::stdio::_print",
+            legacy.try_demangle(DemangleOptions::name_only())
+        );
+
+        let cpp = Name::new("_ZN3foo3barE");
+        assert_eq!(Language::Unknown, cpp.detect_language());
+        assert_eq!(
+            "_ZN3foo3barE",
+            cpp.try_demangle(DemangleOptions::complete())
+        );
+    }
+}
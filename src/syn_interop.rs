@@ -0,0 +1,654 @@
+//! Conversion between `ast::Type` and `syn::Type`.
+//!
+//! The mapping is best-effort in both directions: things that only exist at
+//! the symbol level (disambiguators, closures, De Bruijn indices) are dropped
+//! when going to `syn`, and things that only exist in source code (`impl
+//! Trait`, macros, inferred lifetimes names) are rejected when coming from it.
+
+use ast::*;
+use proc_macro2::Span;
+use syn;
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+
+/// The namespace that paths constructed from `syn` types are placed in.
+const TYPE_NAMESPACE: Namespace = Namespace(b'y');
+
+impl Type {
+    /// Converts this type into the equivalent source-level `syn::Type`.
+    pub fn to_syn(&self) -> Result<syn::Type, String> {
+        type_to_syn(self)
+    }
+
+    /// Constructs a type from a source-level `syn::Type`. All identifiers get
+    /// a zero disambiguator and all lifetimes are erased.
+    pub fn from_syn(ty: &syn::Type) -> Result<Type, String> {
+        type_from_syn(ty)
+    }
+}
+
+fn type_to_syn(ty: &Type) -> Result<syn::Type, String> {
+    Ok(match *ty {
+        Type::BasicType(bt) => basic_type_to_syn(bt)?,
+        Type::Array(ref inner, ref len) => syn::Type::Array(syn::TypeArray {
+            bracket_token: Default::default(),
+            elem: Box::new(type_to_syn(inner)?),
+            semi_token: Default::default(),
+            len: const_to_syn(len)?,
+        }),
+        Type::Slice(ref inner) => syn::Type::Slice(syn::TypeSlice {
+            bracket_token: Default::default(),
+            elem: Box::new(type_to_syn(inner)?),
+        }),
+        Type::Named(ref path) => {
+            let (qself, path) = path_to_syn(path)?;
+            if path.segments.is_empty() {
+                return Err("a bare impl path is not a source-level type".to_string());
+            }
+            syn::Type::Path(syn::TypePath { qself, path })
+        }
+        Type::Tuple(ref components) => {
+            let mut elems = components
+                .iter()
+                .map(type_to_syn)
+                .collect::<Result<Punctuated<_, _>, _>>()?;
+            if elems.len() == 1 {
+                elems.push_punct(Default::default());
+            }
+            syn::Type::Tuple(syn::TypeTuple {
+                paren_token: Default::default(),
+                elems,
+            })
+        }
+        Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+            let mutability = if let Type::RefMut(..) = *ty {
+                Some(Default::default())
+            } else {
+                None
+            };
+            syn::Type::Reference(syn::TypeReference {
+                and_token: Default::default(),
+                lifetime: lifetime.as_ref().map(|_| erased_lifetime()),
+                mutability,
+                elem: Box::new(type_to_syn(inner)?),
+            })
+        }
+        Type::RawPtrConst(ref inner) => syn::Type::Ptr(syn::TypePtr {
+            star_token: Default::default(),
+            const_token: Some(Default::default()),
+            mutability: None,
+            elem: Box::new(type_to_syn(inner)?),
+        }),
+        Type::RawPtrMut(ref inner) => syn::Type::Ptr(syn::TypePtr {
+            star_token: Default::default(),
+            const_token: None,
+            mutability: Some(Default::default()),
+            elem: Box::new(type_to_syn(inner)?),
+        }),
+        Type::Fn(ref fn_sig) => fn_sig_to_syn(fn_sig)?,
+        Type::DynTrait(ref bounds, _) => {
+            let bounds = bounds
+                .traits
+                .iter()
+                .map(|tr| dyn_trait_to_syn(tr).map(syn::TypeParamBound::Trait))
+                .collect::<Result<_, _>>()?;
+            syn::Type::TraitObject(syn::TypeTraitObject {
+                dyn_token: Some(Default::default()),
+                bounds,
+            })
+        }
+    })
+}
+
+fn basic_type_to_syn(bt: BasicType) -> Result<syn::Type, String> {
+    let name = match bt {
+        BasicType::Bool => "bool",
+        BasicType::Char => "char",
+        BasicType::Str => "str",
+        BasicType::I8 => "i8",
+        BasicType::I16 => "i16",
+        BasicType::I32 => "i32",
+        BasicType::I64 => "i64",
+        BasicType::I128 => "i128",
+        BasicType::Isize => "isize",
+        BasicType::U8 => "u8",
+        BasicType::U16 => "u16",
+        BasicType::U32 => "u32",
+        BasicType::U64 => "u64",
+        BasicType::U128 => "u128",
+        BasicType::Usize => "usize",
+        BasicType::F32 => "f32",
+        BasicType::F64 => "f64",
+        BasicType::Unit => {
+            return Ok(syn::Type::Tuple(syn::TypeTuple {
+                paren_token: Default::default(),
+                elems: Punctuated::new(),
+            }));
+        }
+        BasicType::Never => {
+            return Ok(syn::Type::Never(syn::TypeNever {
+                bang_token: Default::default(),
+            }));
+        }
+        BasicType::Placeholder => {
+            return Ok(syn::Type::Infer(syn::TypeInfer {
+                underscore_token: Default::default(),
+            }));
+        }
+        BasicType::Ellipsis => {
+            return Err("`...` is only valid as the last parameter of a fn type".to_string());
+        }
+    };
+
+    Ok(syn::Type::Path(syn::TypePath {
+        qself: None,
+        path: syn::Path::from(make_ident(name)?),
+    }))
+}
+
+fn fn_sig_to_syn(fn_sig: &FnSig) -> Result<syn::Type, String> {
+    let mut param_types = &fn_sig.param_types[..];
+
+    let variadic = match param_types.last() {
+        Some(&Type::BasicType(BasicType::Ellipsis)) => {
+            param_types = &param_types[..param_types.len() - 1];
+            Some(syn::BareVariadic {
+                attrs: Vec::new(),
+                name: None,
+                dots: Default::default(),
+                comma: None,
+            })
+        }
+        _ => None,
+    };
+
+    let mut inputs = param_types
+        .iter()
+        .map(|ty| {
+            Ok(syn::BareFnArg {
+                attrs: Vec::new(),
+                name: None,
+                ty: type_to_syn(ty)?,
+            })
+        })
+        .collect::<Result<Punctuated<_, _>, String>>()?;
+
+    if variadic.is_some() && !inputs.is_empty() {
+        inputs.push_punct(Default::default());
+    }
+
+    let abi = fn_sig.abi.as_ref().map(|abi| {
+        let name = match *abi {
+            Abi::C => "C",
+            Abi::Named(ref ident) => &ident.0[..],
+        };
+        syn::Abi {
+            extern_token: Default::default(),
+            name: Some(syn::LitStr::new(name, Span::call_site())),
+        }
+    });
+
+    let output = if fn_sig.return_type == Type::BasicType(BasicType::Unit) {
+        syn::ReturnType::Default
+    } else {
+        syn::ReturnType::Type(
+            Default::default(),
+            Box::new(type_to_syn(&fn_sig.return_type)?),
+        )
+    };
+
+    Ok(syn::Type::BareFn(syn::TypeBareFn {
+        lifetimes: None,
+        unsafety: if fn_sig.is_unsafe {
+            Some(Default::default())
+        } else {
+            None
+        },
+        abi,
+        fn_token: Default::default(),
+        paren_token: Default::default(),
+        inputs,
+        variadic,
+        output,
+    }))
+}
+
+fn dyn_trait_to_syn(dyn_trait: &DynTrait) -> Result<syn::TraitBound, String> {
+    let (qself, mut path) = path_to_syn(&dyn_trait.path)?;
+
+    if qself.is_some() || path.segments.is_empty() {
+        return Err("dyn trait bound is not a plain path".to_string());
+    }
+
+    if !dyn_trait.assoc_type_bindings.is_empty() {
+        let last = path.segments.last_mut().unwrap();
+        let args = angle_bracketed_args(&mut last.arguments);
+        for binding in dyn_trait.assoc_type_bindings.iter() {
+            args.push(syn::GenericArgument::AssocType(syn::AssocType {
+                ident: make_ident(&binding.ident.0)?,
+                generics: None,
+                eq_token: Default::default(),
+                ty: type_to_syn(&binding.ty)?,
+            }));
+        }
+    }
+
+    Ok(syn::TraitBound {
+        paren_token: None,
+        modifier: syn::TraitBoundModifier::None,
+        lifetimes: None,
+        path,
+    })
+}
+
+fn path_to_syn(path: &Path) -> Result<(Option<syn::QSelf>, syn::Path), String> {
+    Ok(match *path {
+        Path::CrateRoot { ref id } => (None, syn::Path::from(make_ident(&id.u_ident.0)?)),
+        Path::InherentImpl { ref self_type, .. } => (
+            Some(qself(self_type, 0, false)?),
+            syn::Path {
+                leading_colon: None,
+                segments: Punctuated::new(),
+            },
+        ),
+        Path::TraitImpl {
+            ref self_type,
+            ref trait_name,
+            ..
+        }
+        | Path::TraitDef {
+            ref self_type,
+            ref trait_name,
+        } => {
+            let (trait_qself, trait_path) = path_to_syn(trait_name)?;
+            if trait_qself.is_some() {
+                return Err("trait path must not be qualified".to_string());
+            }
+            (
+                Some(qself(self_type, trait_path.segments.len(), true)?),
+                trait_path,
+            )
+        }
+        Path::Nested {
+            ref ns,
+            ref inner,
+            ref ident,
+        } => {
+            if *ns == Namespace(b'C') {
+                return Err("closures have no source-level path".to_string());
+            }
+
+            let (qself, mut path) = path_to_syn(inner)?;
+            if !ident.u_ident.0.is_empty() {
+                path.segments
+                    .push(syn::PathSegment::from(make_ident(&ident.u_ident.0)?));
+            }
+            (qself, path)
+        }
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => {
+            let (qself, mut path) = path_to_syn(inner)?;
+            let last = match path.segments.last_mut() {
+                Some(last) => last,
+                None => return Err("generic arguments without a path segment".to_string()),
+            };
+            let syn_args = angle_bracketed_args(&mut last.arguments);
+            for arg in args {
+                syn_args.push(match *arg {
+                    GenericArg::Lifetime(_) => syn::GenericArgument::Lifetime(erased_lifetime()),
+                    GenericArg::Type(ref ty) => syn::GenericArgument::Type(type_to_syn(ty)?),
+                    GenericArg::Const(ref k) => syn::GenericArgument::Const(const_to_syn(k)?),
+                });
+            }
+            (qself, path)
+        }
+    })
+}
+
+fn qself(self_type: &Type, position: usize, with_as: bool) -> Result<syn::QSelf, String> {
+    Ok(syn::QSelf {
+        lt_token: Default::default(),
+        ty: Box::new(type_to_syn(self_type)?),
+        position,
+        as_token: if with_as {
+            Some(Default::default())
+        } else {
+            None
+        },
+        gt_token: Default::default(),
+    })
+}
+
+fn angle_bracketed_args(
+    arguments: &mut syn::PathArguments,
+) -> &mut Punctuated<syn::GenericArgument, syn::Token![,]> {
+    if let syn::PathArguments::None = *arguments {
+        *arguments = syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments {
+            colon2_token: None,
+            lt_token: Default::default(),
+            args: Punctuated::new(),
+            gt_token: Default::default(),
+        });
+    }
+
+    match *arguments {
+        syn::PathArguments::AngleBracketed(ref mut args) => &mut args.args,
+        _ => unreachable!(),
+    }
+}
+
+fn const_to_syn(k: &Const) -> Result<syn::Expr, String> {
+    match *k {
+        Const::Value(_, value) => Ok(syn::Expr::Lit(syn::ExprLit {
+            attrs: Vec::new(),
+            lit: syn::Lit::Int(syn::LitInt::new(&value.to_string(), Span::call_site())),
+        })),
+        Const::Placeholder(_) => Err("const placeholders have no source-level form".to_string()),
+    }
+}
+
+fn make_ident(name: &str) -> Result<syn::Ident, String> {
+    syn::parse_str::<syn::Ident>(name)
+        .or_else(|_| syn::parse_str::<syn::Ident>(&format!("r#{}", name)))
+        .map_err(|_| format!("'{}' is not a valid Rust identifier", name))
+}
+
+fn erased_lifetime() -> syn::Lifetime {
+    syn::Lifetime::new("'_", Span::call_site())
+}
+
+fn type_from_syn(ty: &syn::Type) -> Result<Type, String> {
+    Ok(match *ty {
+        syn::Type::Path(ref type_path) => {
+            if let Some(ref qself) = type_path.qself {
+                if qself.position == 0 {
+                    return Err("inherent associated paths cannot be represented".to_string());
+                }
+
+                let segments = &type_path.path.segments;
+                let trait_name = path_from_syn(None, segments.iter().take(qself.position))?;
+                let trait_def = Path::TraitDef {
+                    self_type: type_from_syn(&qself.ty)?,
+                    trait_name: Ptr::new(trait_name),
+                };
+                let path = path_from_syn(Some(trait_def), segments.iter().skip(qself.position))?;
+                return Ok(Type::Named(Ptr::new(path)));
+            }
+
+            if let Some(ident) = type_path.path.get_ident() {
+                if let Some(bt) = basic_type_from_name(&ident.unraw().to_string()) {
+                    return Ok(Type::BasicType(bt));
+                }
+            }
+
+            Type::Named(Ptr::new(path_from_syn(
+                None,
+                type_path.path.segments.iter(),
+            )?))
+        }
+        syn::Type::Array(ref array) => Type::Array(
+            Ptr::new(type_from_syn(&array.elem)?),
+            Ptr::new(const_from_syn(&array.len)?),
+        ),
+        syn::Type::Slice(ref slice) => Type::Slice(Ptr::new(type_from_syn(&slice.elem)?)),
+        syn::Type::Tuple(ref tuple) => {
+            if tuple.elems.is_empty() {
+                Type::BasicType(BasicType::Unit)
+            } else {
+                Type::Tuple(
+                    tuple
+                        .elems
+                        .iter()
+                        .map(type_from_syn)
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+        }
+        syn::Type::Reference(ref reference) => {
+            let lifetime = reference.lifetime.as_ref().map(|_| erased_ast_lifetime());
+            let inner = Ptr::new(type_from_syn(&reference.elem)?);
+            if reference.mutability.is_some() {
+                Type::RefMut(lifetime, inner)
+            } else {
+                Type::Ref(lifetime, inner)
+            }
+        }
+        syn::Type::Ptr(ref ptr) => {
+            let inner = Ptr::new(type_from_syn(&ptr.elem)?);
+            if ptr.mutability.is_some() {
+                Type::RawPtrMut(inner)
+            } else {
+                Type::RawPtrConst(inner)
+            }
+        }
+        syn::Type::BareFn(ref bare_fn) => Type::Fn(Ptr::new(fn_sig_from_syn(bare_fn)?)),
+        syn::Type::TraitObject(ref trait_object) => {
+            let mut traits = Vec::new();
+            for bound in trait_object.bounds.iter() {
+                if let syn::TypeParamBound::Trait(ref trait_bound) = *bound {
+                    traits.push(dyn_trait_from_syn(trait_bound)?);
+                }
+            }
+            Type::DynTrait(
+                Ptr::new(DynBounds {
+                    binder: Binder {
+                        count: LifetimeCount(0),
+                    },
+                    traits,
+                }),
+                erased_ast_lifetime(),
+            )
+        }
+        syn::Type::Never(_) => Type::BasicType(BasicType::Never),
+        syn::Type::Infer(_) => Type::BasicType(BasicType::Placeholder),
+        syn::Type::Paren(ref paren) => type_from_syn(&paren.elem)?,
+        syn::Type::Group(ref group) => type_from_syn(&group.elem)?,
+        _ => return Err("type has no symbol-level representation".to_string()),
+    })
+}
+
+fn basic_type_from_name(name: &str) -> Option<BasicType> {
+    Some(match name {
+        "bool" => BasicType::Bool,
+        "char" => BasicType::Char,
+        "str" => BasicType::Str,
+        "i8" => BasicType::I8,
+        "i16" => BasicType::I16,
+        "i32" => BasicType::I32,
+        "i64" => BasicType::I64,
+        "i128" => BasicType::I128,
+        "isize" => BasicType::Isize,
+        "u8" => BasicType::U8,
+        "u16" => BasicType::U16,
+        "u32" => BasicType::U32,
+        "u64" => BasicType::U64,
+        "u128" => BasicType::U128,
+        "usize" => BasicType::Usize,
+        "f32" => BasicType::F32,
+        "f64" => BasicType::F64,
+        _ => return None,
+    })
+}
+
+fn path_from_syn<'a, I>(mut path: Option<Path>, segments: I) -> Result<Path, String>
+where
+    I: Iterator<Item = &'a syn::PathSegment>,
+{
+    for segment in segments {
+        let ident = Ident {
+            dis: Disambiguator(0),
+            u_ident: UIdent(segment.ident.unraw().to_string().into()),
+        };
+
+        let base = match path {
+            None => Path::CrateRoot { id: ident },
+            Some(inner) => Path::Nested {
+                ns: TYPE_NAMESPACE,
+                inner: Ptr::new(inner),
+                ident,
+            },
+        };
+
+        path = Some(match segment.arguments {
+            syn::PathArguments::None => base,
+            syn::PathArguments::AngleBracketed(ref args) => Path::Generic {
+                inner: Ptr::new(base),
+                args: args
+                    .args
+                    .iter()
+                    .map(generic_arg_from_syn)
+                    .collect::<Result<_, _>>()?,
+            },
+            syn::PathArguments::Parenthesized(_) => {
+                return Err("parenthesized generic arguments are not supported".to_string());
+            }
+        });
+    }
+
+    path.ok_or_else(|| "empty path".to_string())
+}
+
+fn generic_arg_from_syn(arg: &syn::GenericArgument) -> Result<GenericArg, String> {
+    Ok(match *arg {
+        syn::GenericArgument::Lifetime(_) => GenericArg::Lifetime(erased_ast_lifetime()),
+        syn::GenericArgument::Type(ref ty) => GenericArg::Type(type_from_syn(ty)?),
+        syn::GenericArgument::Const(ref expr) => GenericArg::Const(const_from_syn(expr)?),
+        _ => return Err("generic argument has no symbol-level representation".to_string()),
+    })
+}
+
+fn const_from_syn(expr: &syn::Expr) -> Result<Const, String> {
+    match *expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(ref lit),
+            ..
+        }) => {
+            let value = lit.base10_parse::<u64>().map_err(|e| e.to_string())?;
+            let ty = match basic_type_from_name(lit.suffix()) {
+                Some(bt) => bt,
+                None => BasicType::Usize,
+            };
+            Ok(Const::Value(Type::BasicType(ty), value))
+        }
+        _ => Err("only integer literals are supported as const values".to_string()),
+    }
+}
+
+fn fn_sig_from_syn(bare_fn: &syn::TypeBareFn) -> Result<FnSig, String> {
+    let mut param_types = bare_fn
+        .inputs
+        .iter()
+        .map(|arg| type_from_syn(&arg.ty))
+        .collect::<Result<TypeList, _>>()?;
+
+    if bare_fn.variadic.is_some() {
+        param_types.push(Type::BasicType(BasicType::Ellipsis));
+    }
+
+    let abi = bare_fn.abi.as_ref().map(|abi| match abi.name {
+        Some(ref name) if name.value() != "C" => Abi::Named(UIdent(name.value().into())),
+        _ => Abi::C,
+    });
+
+    let return_type = match bare_fn.output {
+        syn::ReturnType::Default => Type::BasicType(BasicType::Unit),
+        syn::ReturnType::Type(_, ref ty) => type_from_syn(ty)?,
+    };
+
+    let bound_lifetimes = bare_fn
+        .lifetimes
+        .as_ref()
+        .map_or(0, |lifetimes| lifetimes.lifetimes.len());
+
+    Ok(FnSig {
+        binder: Binder {
+            count: LifetimeCount(bound_lifetimes as u64),
+        },
+        is_unsafe: bare_fn.unsafety.is_some(),
+        abi,
+        param_types,
+        return_type,
+    })
+}
+
+fn dyn_trait_from_syn(trait_bound: &syn::TraitBound) -> Result<DynTrait, String> {
+    let mut path = trait_bound.path.clone();
+    let mut assoc_type_bindings = Vec::new();
+
+    if let Some(last) = path.segments.last_mut() {
+        if let syn::PathArguments::AngleBracketed(ref mut args) = last.arguments {
+            let mut other_args = Punctuated::new();
+            for arg in args.args.iter() {
+                match *arg {
+                    syn::GenericArgument::AssocType(ref assoc) => {
+                        assoc_type_bindings.push(DynTraitAssocBinding {
+                            ident: UIdent(assoc.ident.unraw().to_string().into()),
+                            ty: type_from_syn(&assoc.ty)?,
+                        });
+                    }
+                    ref other => other_args.push(other.clone()),
+                }
+            }
+            args.args = other_args;
+        }
+
+        let no_args_left = match last.arguments {
+            syn::PathArguments::AngleBracketed(ref args) => args.args.is_empty(),
+            _ => false,
+        };
+        if no_args_left {
+            last.arguments = syn::PathArguments::None;
+        }
+    }
+
+    Ok(DynTrait {
+        path: path_from_syn(None, path.segments.iter())?,
+        assoc_type_bindings,
+    })
+}
+
+fn erased_ast_lifetime() -> Lifetime {
+    Lifetime {
+        debruijn_index: BinderIndex(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Type;
+    use ast_demangle::AstDemangle;
+    use syn;
+
+    fn round_trip(source: &str) -> syn::Type {
+        let ty = Type::from_syn(&syn::parse_str(source).unwrap()).unwrap();
+        ty.to_syn().unwrap()
+    }
+
+    #[test]
+    fn syn_round_trip() {
+        for source in &[
+            "u32",
+            "()",
+            "(char,)",
+            "&'a mut [u8; 16]",
+            "*const std::vec::Vec<i8>",
+            "unsafe extern \"C\" fn(u8, ...) -> !",
+            "dyn std::ops::Fn<(u8,), Output = bool> + Send",
+            "<T as core::iter::Iterator>::Item",
+        ] {
+            let expected: syn::Type = syn::parse_str(&source.replace("'a", "'_")).unwrap();
+            assert_eq!(expected, round_trip(source), "{}", source);
+        }
+    }
+
+    #[test]
+    fn from_syn_demangles_like_source() {
+        let ty: syn::Type = syn::parse_str("std::collections::HashMap<&str, [u16]>").unwrap();
+        assert_eq!(
+            "std::collections::HashMap<&str,[u16]>",
+            Type::from_syn(&ty).unwrap().demangle()
+        );
+    }
+}
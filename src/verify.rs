@@ -0,0 +1,152 @@
+//! Checks of symbols as produced by a compiler: that they parse, that they
+//! survive a round trip through the AST, and that they are in canonical
+//! form, i.e. exactly as [`Symbol::mangle`] would produce them.
+
+use ast::Symbol;
+use parse::{self, Parser};
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// The symbol is well-formed.
+    Parse,
+    /// Mangling the parsed symbol, with and without compression, and
+    /// parsing the result gives the same symbol again.
+    RoundTrip,
+    /// The symbol uses backrefs exactly where they are possible and has
+    /// nothing after the instantiating crate.
+    Canonical,
+}
+
+impl Check {
+    /// A stable code for the check, for tools that process the results.
+    pub fn code(self) -> &'static str {
+        match self {
+            Check::Parse => "V001",
+            Check::RoundTrip => "V002",
+            Check::Canonical => "V003",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Parse => "parse",
+            Check::RoundTrip => "round-trip",
+            Check::Canonical => "canonical",
+        }
+    }
+}
+
+/// A failed check of a symbol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub check: Check,
+    /// The byte offset within the symbol where the problem was found, if it
+    /// has one.
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.check.code(), self.check.name())?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Runs all checks on `mangled` and returns those that fail, which is none
+/// for a valid symbol. If the symbol does not parse, the other checks are
+/// not run.
+pub fn verify(mangled: &str) -> Vec<Failure> {
+    let symbol = match parse::parse_at(Parser::new(mangled.as_bytes())) {
+        Ok(symbol) => symbol,
+        Err((offset, message)) => {
+            // The parser may have moved past the end of the input to find
+            // that it ended too early.
+            return vec![Failure {
+                check: Check::Parse,
+                offset: Some(offset.min(mangled.len())),
+                message,
+            }];
+        }
+    };
+
+    let mut failures = Vec::new();
+    let compressed = symbol.mangle();
+
+    for remangled in &[&compressed, &symbol.mangle_uncompressed()] {
+        let message = match Symbol::parse(remangled) {
+            Ok(ref reparsed) if *reparsed == symbol => continue,
+            Ok(reparsed) => format!(
+                "`{}` is mangled as `{}`, which is `{}`",
+                symbol.demangle(),
+                remangled,
+                reparsed.demangle()
+            ),
+            Err(e) => format!("`{}` does not parse: {}", remangled, e),
+        };
+        failures.push(Failure {
+            check: Check::RoundTrip,
+            offset: None,
+            message,
+        });
+    }
+
+    if compressed != mangled {
+        let offset = compressed
+            .bytes()
+            .zip(mangled.bytes())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| compressed.len().min(mangled.len()));
+        let message = if mangled.starts_with(&compressed[..]) {
+            "unexpected data after the end of the symbol".to_string()
+        } else {
+            format!("expected `{}`", compressed)
+        };
+        failures.push(Failure {
+            check: Check::Canonical,
+            offset: Some(offset),
+            message,
+        });
+    }
+
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, Check};
+
+    #[test]
+    fn verify_symbols() {
+        assert_eq!(Vec::<super::Failure>::new(), verify("_RIC3fooSThhERB6_E"));
+
+        let failures = verify("_RIC3fooSThhERThhEE");
+        assert_eq!(1, failures.len());
+        assert_eq!(Check::Canonical, failures[0].check);
+        assert_eq!(Some(14), failures[0].offset);
+        assert_eq!(
+            "V003 canonical at offset 14: expected `_RIC3fooSThhERB6_E`",
+            failures[0].to_string()
+        );
+
+        let failures = verify("_RNvC3foo3barC3bazC3qux");
+        assert_eq!(Some(18), failures[0].offset);
+        assert_eq!(
+            "unexpected data after the end of the symbol",
+            failures[0].message
+        );
+
+        let failures = verify("_RNvC3fooX");
+        assert_eq!(Check::Parse, failures[0].check);
+        assert_eq!(Some(9), failures[0].offset);
+
+        // Errors at the end of the input are reported at its length.
+        assert_eq!(Some(14), verify("_RNvC3foo3barX")[0].offset);
+    }
+}
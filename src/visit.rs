@@ -0,0 +1,188 @@
+//! Read-only traversal of the AST.
+//!
+//! Implementors of `Visitor` override the `visit_*` methods they are
+//! interested in and call the corresponding `walk_*` function to continue
+//! into the children of a node.
+
+use ast::*;
+
+pub trait Visitor<'ast> {
+    fn visit_symbol(&mut self, symbol: &'ast Symbol) {
+        walk_symbol(self, symbol);
+    }
+
+    fn visit_path(&mut self, path: &'ast Path) {
+        walk_path(self, path);
+    }
+
+    fn visit_impl_path(&mut self, impl_path: &'ast ImplPath) {
+        walk_impl_path(self, impl_path);
+    }
+
+    fn visit_ident(&mut self, _ident: &'ast Ident) {}
+
+    fn visit_generic_arg(&mut self, arg: &'ast GenericArg) {
+        walk_generic_arg(self, arg);
+    }
+
+    fn visit_lifetime(&mut self, _lifetime: &'ast Lifetime) {}
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        walk_type(self, ty);
+    }
+
+    fn visit_fn_sig(&mut self, fn_sig: &'ast FnSig) {
+        walk_fn_sig(self, fn_sig);
+    }
+
+    fn visit_dyn_bounds(&mut self, bounds: &'ast DynBounds) {
+        walk_dyn_bounds(self, bounds);
+    }
+
+    fn visit_dyn_trait(&mut self, dyn_trait: &'ast DynTrait) {
+        walk_dyn_trait(self, dyn_trait);
+    }
+
+    fn visit_dyn_trait_assoc_binding(&mut self, binding: &'ast DynTraitAssocBinding) {
+        walk_dyn_trait_assoc_binding(self, binding);
+    }
+
+    fn visit_const(&mut self, k: &'ast Const) {
+        walk_const(self, k);
+    }
+}
+
+pub fn walk_symbol<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, symbol: &'ast Symbol) {
+    v.visit_path(&symbol.path);
+
+    if let Some(ref instantiating_crate) = symbol.instantiating_crate {
+        v.visit_path(instantiating_crate);
+    }
+}
+
+pub fn walk_path<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, path: &'ast Path) {
+    match *path {
+        Path::CrateRoot { ref id } => {
+            v.visit_ident(id);
+        }
+        Path::InherentImpl {
+            ref impl_path,
+            ref self_type,
+        } => {
+            v.visit_impl_path(impl_path);
+            v.visit_type(self_type);
+        }
+        Path::TraitImpl {
+            ref impl_path,
+            ref self_type,
+            ref trait_name,
+        } => {
+            v.visit_impl_path(impl_path);
+            v.visit_type(self_type);
+            v.visit_path(trait_name);
+        }
+        Path::TraitDef {
+            ref self_type,
+            ref trait_name,
+        } => {
+            v.visit_type(self_type);
+            v.visit_path(trait_name);
+        }
+        Path::Nested {
+            ref inner,
+            ref ident,
+            ..
+        } => {
+            v.visit_path(inner);
+            v.visit_ident(ident);
+        }
+        Path::Generic {
+            ref inner,
+            ref args,
+        } => {
+            v.visit_path(inner);
+            for arg in args {
+                v.visit_generic_arg(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_impl_path<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, impl_path: &'ast ImplPath) {
+    v.visit_path(&impl_path.path);
+}
+
+pub fn walk_generic_arg<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, arg: &'ast GenericArg) {
+    match *arg {
+        GenericArg::Lifetime(ref lt) => v.visit_lifetime(lt),
+        GenericArg::Type(ref ty) => v.visit_type(ty),
+        GenericArg::Const(ref k) => v.visit_const(k),
+    }
+}
+
+pub fn walk_type<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, ty: &'ast Type) {
+    match *ty {
+        Type::BasicType(_) => {}
+        Type::Array(ref inner, ref len) => {
+            v.visit_type(inner);
+            v.visit_const(len);
+        }
+        Type::Slice(ref inner) | Type::RawPtrConst(ref inner) | Type::RawPtrMut(ref inner) => {
+            v.visit_type(inner);
+        }
+        Type::Named(ref path) => {
+            v.visit_path(path);
+        }
+        Type::Tuple(ref components) => {
+            for ty in components {
+                v.visit_type(ty);
+            }
+        }
+        Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+            if let Some(ref lifetime) = *lifetime {
+                v.visit_lifetime(lifetime);
+            }
+            v.visit_type(inner);
+        }
+        Type::Fn(ref fn_sig) => {
+            v.visit_fn_sig(fn_sig);
+        }
+        Type::DynTrait(ref bounds, ref lifetime) => {
+            v.visit_dyn_bounds(bounds);
+            v.visit_lifetime(lifetime);
+        }
+    }
+}
+
+pub fn walk_fn_sig<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, fn_sig: &'ast FnSig) {
+    for param_type in fn_sig.param_types.iter() {
+        v.visit_type(param_type);
+    }
+    v.visit_type(&fn_sig.return_type);
+}
+
+pub fn walk_dyn_bounds<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, bounds: &'ast DynBounds) {
+    for tr in bounds.traits.iter() {
+        v.visit_dyn_trait(tr);
+    }
+}
+
+pub fn walk_dyn_trait<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, dyn_trait: &'ast DynTrait) {
+    v.visit_path(&dyn_trait.path);
+    for binding in dyn_trait.assoc_type_bindings.iter() {
+        v.visit_dyn_trait_assoc_binding(binding);
+    }
+}
+
+pub fn walk_dyn_trait_assoc_binding<'ast, V: Visitor<'ast> + ?Sized>(
+    v: &mut V,
+    binding: &'ast DynTraitAssocBinding,
+) {
+    v.visit_type(&binding.ty);
+}
+
+pub fn walk_const<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, k: &'ast Const) {
+    match *k {
+        Const::Value(ref ty, _) | Const::Placeholder(ref ty) => v.visit_type(ty),
+    }
+}
@@ -0,0 +1,130 @@
+//! JavaScript bindings for WebAssembly, so that web-based crash viewers and
+//! similar tools can demangle in the browser. Build them with
+//! `cargo rustc --release --lib --crate-type cdylib --features wasm
+//! --target wasm32-unknown-unknown` and run `wasm-bindgen` on the result.
+
+use ast::Symbol;
+use ffi::{self, SmrOptions};
+use js_sys::{Object, Reflect};
+use legacy;
+use wasm_bindgen::prelude::*;
+
+/// The demangled form of a symbol together with what `structured` output
+/// adds to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Demangled {
+    demangled: String,
+    krate: String,
+    skeleton: String,
+}
+
+/// Demangles the v0 or legacy Rust symbol `input`.
+///
+/// `options` may be `undefined` or an object with the boolean fields
+/// `stripDisambiguators`, `stripGenerics`, and `legacy`, which mean the same
+/// as the fields of `SmrOptions` in the C interface but with `legacy` on by
+/// default, and `structured`. Returns the demangled string, or, with
+/// `structured`, an object with the fields `demangled`, `crate`, and
+/// `skeleton`. Throws an `Error` if `input` is not a valid symbol.
+#[wasm_bindgen]
+pub fn demangle(input: &str, options: JsValue) -> Result<JsValue, JsError> {
+    let flag = |name: &str, default: bool| {
+        if options.is_object() {
+            Reflect::get(&options, &JsValue::from_str(name))
+                .ok()
+                .and_then(|value| value.as_bool())
+                .unwrap_or(default)
+        } else {
+            default
+        }
+    };
+    let smr_options = SmrOptions {
+        strip_disambiguators: flag("stripDisambiguators", false),
+        strip_generics: flag("stripGenerics", false),
+        legacy: flag("legacy", true),
+    };
+
+    let demangled = demangle_with(input, &smr_options).map_err(|e| JsError::new(&e))?;
+    if !flag("structured", false) {
+        return Ok(demangled.demangled.into());
+    }
+
+    let object = Object::new();
+    for &(name, value) in &[
+        ("demangled", &demangled.demangled),
+        ("crate", &demangled.krate),
+        ("skeleton", &demangled.skeleton),
+    ] {
+        Reflect::set(&object, &JsValue::from_str(name), &JsValue::from_str(value))
+            .map_err(|_| JsError::new("failed to build the result"))?;
+    }
+    Ok(object.into())
+}
+
+fn demangle_with(input: &str, options: &SmrOptions) -> Result<Demangled, String> {
+    let error = match Symbol::parse(input) {
+        Ok(symbol) => {
+            let skeleton = if options.strip_disambiguators || options.strip_generics {
+                let options = SmrOptions {
+                    strip_generics: true,
+                    ..*options
+                };
+                ffi::demangle_with(input, &options).unwrap_or_default()
+            } else {
+                symbol.skeleton().demangle()
+            };
+
+            return Ok(Demangled {
+                demangled: ffi::demangle_with(input, options).unwrap_or_default(),
+                krate: symbol.defining_crate().u_ident.0.to_string(),
+                skeleton,
+            });
+        }
+        Err(e) => e,
+    };
+
+    if !options.legacy {
+        return Err(error);
+    }
+
+    // Legacy symbols do not encode generic arguments, so they are their own
+    // skeleton.
+    let demangled = legacy::demangle(input)?;
+    Ok(Demangled {
+        krate: demangled.split("::").next().unwrap_or_default().to_string(),
+        skeleton: demangled.clone(),
+        demangled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle_with, Demangled};
+    use ffi::SmrOptions;
+
+    #[test]
+    fn demangle_structured() {
+        let options = SmrOptions {
+            strip_disambiguators: true,
+            legacy: true,
+            ..SmrOptions::default()
+        };
+        assert_eq!(
+            Ok(Demangled {
+                demangled: "mycrate::foo<u8>".to_string(),
+                krate: "mycrate".to_string(),
+                skeleton: "mycrate::foo<_>".to_string(),
+            }),
+            demangle_with("_RINvCs0_7mycrate3foohE", &options)
+        );
+        assert_eq!(
+            "std",
+            demangle_with("_ZN3std3foo17h0123456789abcdefE", &options)
+                .unwrap()
+                .krate
+        );
+
+        let options = SmrOptions::default();
+        assert!(demangle_with("_ZN3std3foo17h0123456789abcdefE", &options).is_err());
+    }
+}
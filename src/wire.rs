@@ -0,0 +1,663 @@
+//! A compact binary form of `ast::Symbol`, for caching parsed symbols and
+//! sending them between machines. Decoding it is cheaper than parsing the
+//! mangled symbol again because it needs no punycode decoding and no
+//! backref resolution.
+//!
+//! The first byte is `FORMAT_VERSION`, and `from_wire()` refuses any other
+//! version. The AST follows in prefix order:
+//!
+//! - Integers are unsigned LEB128, and strings their length followed by their
+//!   UTF-8 bytes.
+//! - Options are a `0` byte or a `1` byte followed by the value, lists their
+//!   length followed by their elements, and booleans one byte.
+//! - Enums start with one byte giving the index of the variant in the order
+//!   of `ast`, followed by the fields of the variant. Namespaces are their
+//!   tag byte.
+//! - A path or type that the AST shares through a `Ptr` is written once. Later
+//!   occurrences are a `0xff` byte followed by the index of the first one
+//!   among all shared paths or, respectively, types.
+
+use ast::*;
+use collections::Map;
+use parse::MAX_DEPTH;
+#[cfg(not(feature = "std"))]
+use std::prelude::v1::*;
+
+/// The version of the format that `to_wire()` writes.
+pub const FORMAT_VERSION: u8 = 1;
+
+const SHARED: u8 = 0xff;
+
+const BASIC_TYPES: [BasicType; 21] = [
+    BasicType::Bool,
+    BasicType::Char,
+    BasicType::Str,
+    BasicType::Unit,
+    BasicType::I8,
+    BasicType::I16,
+    BasicType::I32,
+    BasicType::I64,
+    BasicType::I128,
+    BasicType::Isize,
+    BasicType::U8,
+    BasicType::U16,
+    BasicType::U32,
+    BasicType::U64,
+    BasicType::U128,
+    BasicType::Usize,
+    BasicType::F32,
+    BasicType::F64,
+    BasicType::Never,
+    BasicType::Ellipsis,
+    BasicType::Placeholder,
+];
+
+impl Symbol {
+    /// Encodes the AST of this symbol in the binary wire format.
+    pub fn to_wire(&self) -> Vec<u8> {
+        let mut encoder = Encoder {
+            out: vec![FORMAT_VERSION],
+            paths: Map::default(),
+            types: Map::default(),
+        };
+        encoder.symbol(self);
+        encoder.out
+    }
+
+    /// Decodes the AST of a symbol, as encoded by `to_wire()`.
+    pub fn from_wire(data: &[u8]) -> Result<Symbol, String> {
+        match data.first() {
+            Some(&FORMAT_VERSION) => {}
+            Some(&version) => return Err(format!("unsupported wire format version {}", version)),
+            None => return Err("empty input".to_string()),
+        }
+
+        let mut decoder = Decoder {
+            data,
+            pos: 1,
+            depth: 0,
+            paths: Vec::new(),
+            types: Vec::new(),
+        };
+        let symbol = decoder.symbol()?;
+        if decoder.pos != data.len() {
+            return Err(format!(
+                "unexpected data after the end of the symbol at byte {}",
+                decoder.pos
+            ));
+        }
+        Ok(symbol)
+    }
+}
+
+struct Encoder {
+    out: Vec<u8>,
+    /// The index of every shared path and type written so far, by address.
+    paths: Map<usize, usize>,
+    types: Map<usize, usize>,
+}
+
+impl Encoder {
+    fn number(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.out.push(byte);
+                return;
+            }
+            self.out.push(byte | 0x80);
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.number(s.len() as u64);
+        self.out.extend_from_slice(s.as_bytes());
+    }
+
+    fn symbol(&mut self, symbol: &Symbol) {
+        self.opt_number(symbol.version.map(|version| version.0));
+        self.path(&symbol.path);
+        match symbol.instantiating_crate {
+            Some(ref path) => {
+                self.out.push(1);
+                self.path(path);
+            }
+            None => self.out.push(0),
+        }
+    }
+
+    fn opt_number(&mut self, value: Option<u64>) {
+        match value {
+            Some(value) => {
+                self.out.push(1);
+                self.number(value);
+            }
+            None => self.out.push(0),
+        }
+    }
+
+    fn ident(&mut self, ident: &Ident) {
+        self.number(ident.dis.0);
+        self.string(&ident.u_ident.0);
+    }
+
+    fn shared_path(&mut self, path: &Ptr<Path>) {
+        let address = &**path as *const Path as usize;
+        if let Some(&index) = self.paths.get(&address) {
+            self.out.push(SHARED);
+            self.number(index as u64);
+            return;
+        }
+
+        self.path(path);
+        let index = self.paths.len();
+        self.paths.insert(address, index);
+    }
+
+    fn path(&mut self, path: &Path) {
+        match *path {
+            Path::CrateRoot { ref id } => {
+                self.out.push(0);
+                self.ident(id);
+            }
+            Path::InherentImpl {
+                ref impl_path,
+                ref self_type,
+            } => {
+                self.out.push(1);
+                self.impl_path(impl_path);
+                self.ty(self_type);
+            }
+            Path::TraitImpl {
+                ref impl_path,
+                ref self_type,
+                ref trait_name,
+            } => {
+                self.out.push(2);
+                self.impl_path(impl_path);
+                self.ty(self_type);
+                self.shared_path(trait_name);
+            }
+            Path::TraitDef {
+                ref self_type,
+                ref trait_name,
+            } => {
+                self.out.push(3);
+                self.ty(self_type);
+                self.shared_path(trait_name);
+            }
+            Path::Nested {
+                ref ns,
+                ref inner,
+                ref ident,
+            } => {
+                self.out.push(4);
+                self.out.push(ns.0);
+                self.shared_path(inner);
+                self.ident(ident);
+            }
+            Path::Generic {
+                ref inner,
+                ref args,
+            } => {
+                self.out.push(5);
+                self.shared_path(inner);
+                self.number(args.len() as u64);
+                for arg in args {
+                    self.generic_arg(arg);
+                }
+            }
+        }
+    }
+
+    fn impl_path(&mut self, impl_path: &ImplPath) {
+        self.opt_number(impl_path.dis.map(|dis| dis.0));
+        self.shared_path(&impl_path.path);
+    }
+
+    fn generic_arg(&mut self, arg: &GenericArg) {
+        match *arg {
+            GenericArg::Lifetime(ref lifetime) => {
+                self.out.push(0);
+                self.number(lifetime.debruijn_index.0);
+            }
+            GenericArg::Type(ref ty) => {
+                self.out.push(1);
+                self.ty(ty);
+            }
+            GenericArg::Const(ref c) => {
+                self.out.push(2);
+                self.konst(c);
+            }
+        }
+    }
+
+    fn shared_type(&mut self, ty: &Ptr<Type>) {
+        let address = &**ty as *const Type as usize;
+        if let Some(&index) = self.types.get(&address) {
+            self.out.push(SHARED);
+            self.number(index as u64);
+            return;
+        }
+
+        self.ty(ty);
+        let index = self.types.len();
+        self.types.insert(address, index);
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match *ty {
+            Type::BasicType(basic_type) => {
+                self.out.push(0);
+                self.out.push(basic_type as u8);
+            }
+            Type::Array(ref inner, ref len) => {
+                self.out.push(1);
+                self.shared_type(inner);
+                self.konst(len);
+            }
+            Type::Slice(ref inner) => {
+                self.out.push(2);
+                self.shared_type(inner);
+            }
+            Type::Named(ref path) => {
+                self.out.push(3);
+                self.shared_path(path);
+            }
+            Type::Tuple(ref components) => {
+                self.out.push(4);
+                self.number(components.len() as u64);
+                for component in components {
+                    self.ty(component);
+                }
+            }
+            Type::Ref(ref lifetime, ref inner) | Type::RefMut(ref lifetime, ref inner) => {
+                self.out.push(if let Type::Ref(..) = *ty { 5 } else { 6 });
+                self.opt_number(lifetime.as_ref().map(|l| l.debruijn_index.0));
+                self.shared_type(inner);
+            }
+            Type::RawPtrConst(ref inner) => {
+                self.out.push(7);
+                self.shared_type(inner);
+            }
+            Type::RawPtrMut(ref inner) => {
+                self.out.push(8);
+                self.shared_type(inner);
+            }
+            Type::Fn(ref fn_sig) => {
+                self.out.push(9);
+                self.number(fn_sig.binder.count.0);
+                self.out.push(fn_sig.is_unsafe as u8);
+                match fn_sig.abi {
+                    None => self.out.push(0),
+                    Some(Abi::C) => self.out.push(1),
+                    Some(Abi::Named(ref name)) => {
+                        self.out.push(2);
+                        self.string(&name.0);
+                    }
+                }
+                self.number(fn_sig.param_types.len() as u64);
+                for param_type in &fn_sig.param_types {
+                    self.ty(param_type);
+                }
+                self.ty(&fn_sig.return_type);
+            }
+            Type::DynTrait(ref bounds, ref lifetime) => {
+                self.out.push(10);
+                self.number(bounds.binder.count.0);
+                self.number(bounds.traits.len() as u64);
+                for dyn_trait in &bounds.traits {
+                    self.path(&dyn_trait.path);
+                    self.number(dyn_trait.assoc_type_bindings.len() as u64);
+                    for binding in &dyn_trait.assoc_type_bindings {
+                        self.string(&binding.ident.0);
+                        self.ty(&binding.ty);
+                    }
+                }
+                self.number(lifetime.debruijn_index.0);
+            }
+        }
+    }
+
+    fn konst(&mut self, c: &Const) {
+        match *c {
+            Const::Value(ref ty, value) => {
+                self.out.push(0);
+                self.ty(ty);
+                self.number(value);
+            }
+            Const::Placeholder(ref ty) => {
+                self.out.push(1);
+                self.ty(ty);
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    depth: u32,
+    paths: Vec<Ptr<Path>>,
+    types: Vec<Ptr<Type>>,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| format!("unexpected end of input at byte {}", self.pos))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn invalid<T>(&self, what: &str, value: u64) -> Result<T, String> {
+        Err(format!(
+            "invalid {} {} at byte {}",
+            what,
+            value,
+            self.pos - 1
+        ))
+    }
+
+    fn number(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift == 63 && byte > 1 {
+                return self.invalid("number byte", byte as u64);
+            }
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+            if shift > 63 {
+                return self.invalid("number byte", byte as u64);
+            }
+        }
+    }
+
+    /// A list length, which cannot exceed the remaining input because every
+    /// element takes up at least one byte.
+    fn len(&mut self) -> Result<usize, String> {
+        let len = self.number()?;
+        if len > (self.data.len() - self.pos) as u64 {
+            return self.invalid("length", len);
+        }
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> Result<Ptr<str>, String> {
+        let len = self.len()?;
+        let bytes = &self.data[self.pos..self.pos + len];
+        let s = ::std::str::from_utf8(bytes)
+            .map_err(|e| format!("invalid UTF-8 at byte {}: {}", self.pos, e))?;
+        self.pos += len;
+        Ok(s.into())
+    }
+
+    fn flag(&mut self) -> Result<bool, String> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => self.invalid("flag", byte as u64),
+        }
+    }
+
+    fn opt_number(&mut self) -> Result<Option<u64>, String> {
+        if self.flag()? {
+            self.number().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("nesting too deep at byte {}", self.pos));
+        }
+        Ok(())
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, String> {
+        let version = self.opt_number()?.map(DecimalNumber);
+        let path = self.path()?;
+        let instantiating_crate = if self.flag()? {
+            Some(self.path()?)
+        } else {
+            None
+        };
+        Ok(Symbol {
+            version,
+            path,
+            instantiating_crate,
+        })
+    }
+
+    fn ident(&mut self) -> Result<Ident, String> {
+        Ok(Ident {
+            dis: Disambiguator(self.number()?),
+            u_ident: UIdent(self.string()?),
+        })
+    }
+
+    fn shared_path(&mut self) -> Result<Ptr<Path>, String> {
+        if self.data.get(self.pos) == Some(&SHARED) {
+            self.pos += 1;
+            let index = self.number()?;
+            return match self.paths.get(index as usize) {
+                Some(path) => Ok(path.clone()),
+                None => self.invalid("shared path index", index),
+            };
+        }
+
+        let path = Ptr::new(self.path()?);
+        self.paths.push(path.clone());
+        Ok(path)
+    }
+
+    fn path(&mut self) -> Result<Path, String> {
+        self.enter()?;
+        let path = match self.byte()? {
+            0 => Path::CrateRoot { id: self.ident()? },
+            1 => Path::InherentImpl {
+                impl_path: self.impl_path()?,
+                self_type: self.ty()?,
+            },
+            2 => Path::TraitImpl {
+                impl_path: self.impl_path()?,
+                self_type: self.ty()?,
+                trait_name: self.shared_path()?,
+            },
+            3 => Path::TraitDef {
+                self_type: self.ty()?,
+                trait_name: self.shared_path()?,
+            },
+            4 => Path::Nested {
+                ns: Namespace(self.byte()?),
+                inner: self.shared_path()?,
+                ident: self.ident()?,
+            },
+            5 => {
+                let inner = self.shared_path()?;
+                let len = self.len()?;
+                let mut args = GenericArgs::with_capacity(len);
+                for _ in 0..len {
+                    args.push(self.generic_arg()?);
+                }
+                Path::Generic { inner, args }
+            }
+            tag => return self.invalid("path tag", tag as u64),
+        };
+        self.depth -= 1;
+        Ok(path)
+    }
+
+    fn impl_path(&mut self) -> Result<ImplPath, String> {
+        Ok(ImplPath {
+            dis: self.opt_number()?.map(Disambiguator),
+            path: self.shared_path()?,
+        })
+    }
+
+    fn lifetime(&mut self) -> Result<Lifetime, String> {
+        Ok(Lifetime {
+            debruijn_index: BinderIndex(self.number()?),
+        })
+    }
+
+    fn generic_arg(&mut self) -> Result<GenericArg, String> {
+        match self.byte()? {
+            0 => Ok(GenericArg::Lifetime(self.lifetime()?)),
+            1 => Ok(GenericArg::Type(self.ty()?)),
+            2 => Ok(GenericArg::Const(self.konst()?)),
+            tag => self.invalid("generic argument tag", tag as u64),
+        }
+    }
+
+    fn shared_type(&mut self) -> Result<Ptr<Type>, String> {
+        if self.data.get(self.pos) == Some(&SHARED) {
+            self.pos += 1;
+            let index = self.number()?;
+            return match self.types.get(index as usize) {
+                Some(ty) => Ok(ty.clone()),
+                None => self.invalid("shared type index", index),
+            };
+        }
+
+        let ty = Ptr::new(self.ty()?);
+        self.types.push(ty.clone());
+        Ok(ty)
+    }
+
+    fn ty(&mut self) -> Result<Type, String> {
+        self.enter()?;
+        let ty = match self.byte()? {
+            0 => {
+                let byte = self.byte()?;
+                match BASIC_TYPES.get(byte as usize) {
+                    Some(&basic_type) => Type::BasicType(basic_type),
+                    None => return self.invalid("basic type", byte as u64),
+                }
+            }
+            1 => Type::Array(self.shared_type()?, Ptr::new(self.konst()?)),
+            2 => Type::Slice(self.shared_type()?),
+            3 => Type::Named(self.shared_path()?),
+            4 => {
+                let len = self.len()?;
+                let mut components = Vec::with_capacity(len);
+                for _ in 0..len {
+                    components.push(self.ty()?);
+                }
+                Type::Tuple(components)
+            }
+            tag @ 5 | tag @ 6 => {
+                let lifetime = self.opt_number()?.map(|index| Lifetime {
+                    debruijn_index: BinderIndex(index),
+                });
+                let inner = self.shared_type()?;
+                if tag == 5 {
+                    Type::Ref(lifetime, inner)
+                } else {
+                    Type::RefMut(lifetime, inner)
+                }
+            }
+            7 => Type::RawPtrConst(self.shared_type()?),
+            8 => Type::RawPtrMut(self.shared_type()?),
+            9 => {
+                let binder = Binder {
+                    count: LifetimeCount(self.number()?),
+                };
+                let is_unsafe = self.flag()?;
+                let abi = match self.byte()? {
+                    0 => None,
+                    1 => Some(Abi::C),
+                    2 => Some(Abi::Named(UIdent(self.string()?))),
+                    tag => return self.invalid("ABI tag", tag as u64),
+                };
+                let len = self.len()?;
+                let mut param_types = TypeList::with_capacity(len);
+                for _ in 0..len {
+                    param_types.push(self.ty()?);
+                }
+                Type::Fn(Ptr::new(FnSig {
+                    binder,
+                    is_unsafe,
+                    abi,
+                    param_types,
+                    return_type: self.ty()?,
+                }))
+            }
+            10 => {
+                let binder = Binder {
+                    count: LifetimeCount(self.number()?),
+                };
+                let len = self.len()?;
+                let mut traits = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let path = self.path()?;
+                    let len = self.len()?;
+                    let mut assoc_type_bindings = Vec::with_capacity(len);
+                    for _ in 0..len {
+                        assoc_type_bindings.push(DynTraitAssocBinding {
+                            ident: UIdent(self.string()?),
+                            ty: self.ty()?,
+                        });
+                    }
+                    traits.push(DynTrait {
+                        path,
+                        assoc_type_bindings,
+                    });
+                }
+                Type::DynTrait(Ptr::new(DynBounds { binder, traits }), self.lifetime()?)
+            }
+            tag => return self.invalid("type tag", tag as u64),
+        };
+        self.depth -= 1;
+        Ok(ty)
+    }
+
+    fn konst(&mut self) -> Result<Const, String> {
+        match self.byte()? {
+            0 => Ok(Const::Value(self.ty()?, self.number()?)),
+            1 => Ok(Const::Placeholder(self.ty()?)),
+            tag => self.invalid("const tag", tag as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Symbol;
+    use generate::{GeneratorConfig, SymbolGenerator};
+
+    #[test]
+    fn wire_round_trip() {
+        let generator = SymbolGenerator::new(GeneratorConfig::default()).with_seed(7);
+        for symbol in generator.take(1000) {
+            let wire = symbol.to_wire();
+            assert_eq!(Ok(&symbol), Symbol::from_wire(&wire).as_ref());
+        }
+
+        // The second use of a backref shares the node of the first one.
+        let symbol = Symbol::parse("_RINvC7mycrate3fooRNtC5other3BarBf_Bf_E").unwrap();
+        let wire = symbol.to_wire();
+        assert_eq!(Ok(&symbol), Symbol::from_wire(&wire).as_ref());
+        let unshared =
+            Symbol::parse("_RINvC7mycrate3fooRNtC5other3BarRNtC5other3BarRNtC5other3BarE").unwrap();
+        assert_eq!(symbol, unshared);
+        assert!(wire.len() < unshared.to_wire().len());
+
+        assert!(Symbol::from_wire(&[]).is_err());
+        assert!(Symbol::from_wire(&[2]).is_err());
+        assert!(Symbol::from_wire(&wire[..wire.len() - 1]).is_err());
+        let mut trailing = wire.clone();
+        trailing.push(0);
+        assert!(Symbol::from_wire(&trailing).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! Tests of the `std-mangle` binary, run as a child process.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+const BIN: &str = env!("CARGO_BIN_EXE_std-mangle");
+
+/// Feeds many symbols to `std-mangle <args>`, closes its stdout after the
+/// first line, and checks that it exits with status 0 without a panic.
+fn assert_quiet_on_broken_pipe(args: &[&str]) {
+    let mut child = Command::new(BIN)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        for _ in 0..200_000 {
+            if stdin.write_all(b"_RNvC7mycrate3foo\n").is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!("mycrate::foo\n", line);
+    drop(stdout);
+
+    writer.join().unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!("", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(Some(0), output.status.code());
+}
+
+#[test]
+fn filter_exits_quietly_on_broken_pipe() {
+    assert_quiet_on_broken_pipe(&[]);
+}
+
+#[test]
+fn stream_exits_quietly_on_broken_pipe() {
+    assert_quiet_on_broken_pipe(&["--unbuffered"]);
+}
+
+#[test]
+fn batch_exits_quietly_on_broken_pipe() {
+    assert_quiet_on_broken_pipe(&["demangle"]);
+    assert_quiet_on_broken_pipe(&["demangle", "--jobs", "4"]);
+}
+
+/// Runs `std-mangle <args>` with `stdin` and returns its stdout, stderr and
+/// exit status.
+fn run(args: &[&str], stdin: &str) -> (String, String, i32) {
+    let mut child = Command::new(BIN)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut input = child.stdin.take().unwrap();
+    let stdin = stdin.to_string();
+    let writer = thread::spawn(move || input.write_all(stdin.as_bytes()).unwrap());
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.code().unwrap(),
+    )
+}
+
+/// Runs `std-mangle <args>` without input and returns its stdout after
+/// checking that it succeeded.
+fn stdout_of(args: &[&str]) -> String {
+    let (stdout, stderr, code) = run(args, "");
+    assert_eq!("", stderr);
+    assert_eq!(0, code);
+    stdout
+}
+
+#[test]
+fn reject_bad_arguments() {
+    for args in [
+        &["demangle", "--bogus"][..],
+        &["demangle", "--jobs"],
+        &["demangle", "--jobs", "x"],
+        &["demangle", "--output", "xml"],
+        &["demangle", "--crate"],
+    ] {
+        let (stdout, stderr, code) = run(args, "");
+        assert_eq!("", stdout, "{:?}", args);
+        assert!(stderr.contains("\nusage: std-mangle"), "{:?}", args);
+        assert_eq!(2, code, "{:?}", args);
+    }
+}
+
+#[test]
+fn demangle_arguments_and_stdin() {
+    assert_eq!(
+        "mycrate::foo\nother::bar\n",
+        stdout_of(&["demangle", "_RNvC7mycrate3foo", "_RNvC5other3bar"])
+    );
+
+    let (stdout, stderr, code) = run(&["demangle"], "_RNvC7mycrate3foo\n_RXX\n");
+    assert_eq!("mycrate::foo\n", stdout);
+    assert!(stderr.starts_with("_RXX: at position 5: "));
+    assert_eq!(1, code);
+}
+
+#[test]
+fn demangle_legacy_symbols() {
+    let legacy = "_ZN4core3fmt5write17h0123456789abcdefE";
+    assert_eq!("core::fmt::write\n", stdout_of(&["demangle", legacy]));
+    assert_eq!("core::fmt::write\n", run(&[], &format!("{}\n", legacy)).0);
+}
+
+#[test]
+fn demangle_nul_separated_input() {
+    let input = "_RNvC7mycrate3foo\0_RNvC5other3bar\0";
+    let (stdout, stderr, code) = run(&["demangle", "-0"], input);
+    assert_eq!("mycrate::foo\nother::bar\n", stdout);
+    assert_eq!("", stderr);
+    assert_eq!(0, code);
+}
+
+#[test]
+fn demangle_stripped() {
+    assert_eq!(
+        "mycrate::foo\n",
+        stdout_of(&["demangle", "--strip", "_RNvCs1234_7mycrate3foo"])
+    );
+    assert_eq!(
+        "foo::bar<_>\n",
+        stdout_of(&["demangle", "--strip-generics", "_RINxC3foo3barmE"])
+    );
+}
+
+#[test]
+fn filter_by_crate() {
+    let symbols = ["_RNvC7mycrate3foo", "_RNvC5other3bar", "_RXX"];
+
+    let (stdout, stderr, code) = run(
+        &[&["demangle", "--crate", "mycrate"][..], &symbols].concat(),
+        "",
+    );
+    assert_eq!("mycrate::foo\n", stdout);
+    assert_eq!("", stderr);
+    assert_eq!(0, code);
+
+    let (stdout, stderr, code) = run(
+        &[&["demangle", "--crate", "!mycrate"][..], &symbols].concat(),
+        "",
+    );
+    assert_eq!("other::bar\n", stdout);
+    assert!(stderr.starts_with("_RXX: "));
+    assert_eq!(1, code);
+}
+
+#[test]
+fn unique_skeleton() {
+    assert_eq!(
+        "       2  foo::bar<u32>\n       1  other::bar\n",
+        stdout_of(&[
+            "demangle",
+            "--unique-skeleton",
+            "_RINxC3foo3barmE",
+            "_RINxC3foo3barhE",
+            "_RNvC5other3bar",
+        ])
+    );
+}
+
+#[test]
+fn csv_and_tsv_output() {
+    let (stdout, _, code) = run(
+        &["demangle", "--output", "csv", "_RINxC3foo3barTmhEE", "_RXX"],
+        "",
+    );
+    assert_eq!(
+        "mangled,demangled,crate,skeleton\n\
+         _RINxC3foo3barTmhEE,\"foo::bar<(u32,u8)>\",foo,foo::bar<_>\n\
+         _RXX,,,\n",
+        stdout
+    );
+    assert_eq!(1, code);
+
+    let (stdout, _, _) = run(
+        &[
+            "demangle",
+            "--output",
+            "csv",
+            "--columns",
+            "mangled,error",
+            "a,\"b",
+        ],
+        "",
+    );
+    assert_eq!(
+        "mangled,error\n\"a,\"\"b\",at position 0: Not a Rust symbol\n",
+        stdout
+    );
+
+    assert_eq!(
+        "mangled,count\n_RINxC3foo3barmE,2\n",
+        stdout_of(&[
+            "demangle",
+            "--output",
+            "csv",
+            "--columns",
+            "mangled,count",
+            "--unique-skeleton",
+            "_RINxC3foo3barmE",
+            "_RINxC3foo3barhE",
+        ])
+    );
+
+    assert_eq!(
+        "mangled\tdemangled\tcrate\tskeleton\n\
+         _RNvC7mycrate3foo\tmycrate::foo\tmycrate\tmycrate::foo\n",
+        stdout_of(&["demangle", "--output", "tsv", "_RNvC7mycrate3foo"])
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_output() {
+    let (stdout, _, code) = run(&["demangle", "--json", "_RNvC7mycrate3foo", "_RXX"], "");
+    let mut lines = stdout.lines();
+    assert_eq!(
+        Some(
+            "{\"input\":\"_RNvC7mycrate3foo\",\"output\":\"mycrate::foo\",\
+             \"demangled\":\"mycrate::foo\",\"crate\":\"mycrate\",\
+             \"skeleton\":\"mycrate::foo\",\"error\":null,\"size\":null,\"count\":null}"
+        ),
+        lines.next()
+    );
+    let error = lines.next().unwrap();
+    assert!(error.starts_with(
+        "{\"input\":\"_RXX\",\"output\":null,\"demangled\":null,\"crate\":null,\
+         \"skeleton\":null,\"error\":\"at position 5: "
+    ));
+    assert_eq!(None, lines.next());
+    assert_eq!(1, code);
+
+    assert_eq!(
+        stdout_of(&["demangle", "--json", "_RNvC7mycrate3foo"]),
+        stdout_of(&["demangle", "--output", "json", "_RNvC7mycrate3foo"])
+    );
+}
+
+#[test]
+fn jobs_keep_input_order() {
+    let input: String = (0..2000)
+        .map(|i| format!("_RNvC7mycrate{}f{}\n", 1 + i.to_string().len(), i))
+        .collect();
+    let expected: String = (0..2000).map(|i| format!("mycrate::f{}\n", i)).collect();
+
+    for jobs in ["1", "4", "0"] {
+        let (stdout, stderr, code) = run(&["demangle", "--jobs", jobs], &input);
+        assert_eq!(expected, stdout, "--jobs {}", jobs);
+        assert_eq!("", stderr);
+        assert_eq!(0, code);
+    }
+}
+
+#[test]
+fn verify_exit_status() {
+    let (stdout, stderr, code) = run(&["verify", "_RNvC7mycrate3foo"], "");
+    assert_eq!("", stdout);
+    assert_eq!("0 of 1 symbols failed verification\n", stderr);
+    assert_eq!(0, code);
+
+    let (stdout, stderr, code) = run(&["verify", "_RNvC7mycrate3fooXX"], "");
+    assert!(stdout.starts_with("_RNvC7mycrate3fooXX: V001 parse at offset 19: "));
+    assert_eq!("1 of 1 symbols failed verification\n", stderr);
+    assert_eq!(1, code);
+}
+
+#[test]
+fn reject_identifier_amplification() {
+    // foo::bar<a::xxx.., (T0, T0), (T1, T1), ..>, where every `Tn` is a
+    // backref to the previous argument, demangles to almost 1 GB.
+    let mut mangled = String::from("_RINvC3foo3bar");
+    let mut prev = mangled.len() - 2;
+    mangled.push_str(&format!("NvC1a60000{}", "x".repeat(60000)));
+    for _ in 0..14 {
+        let pos = mangled.len() - 2;
+        let arg = format!("B{}_", std_mangle_rs::int_radix::radix(62, prev as u64 - 1));
+        mangled.push_str(&format!("T{}{}E", arg, arg));
+        prev = pos;
+    }
+    mangled.push_str("E\n");
+
+    let (stdout, stderr, code) = run(&["demangle"], &mangled);
+    assert_eq!("", stdout);
+    assert!(stderr.contains("longer than 16777216 bytes"));
+    assert_eq!(1, code);
+}
+
+#[test]
+fn jobs_handle_deepest_symbols() {
+    // foo::bar<fn() -> fn() -> .. ()>, as deeply nested as is accepted.
+    let deepest = format!("_RINxC3foo3bar{}uE\n", "FG_E".repeat(254));
+    let (stdout, stderr, code) = run(&["demangle", "--jobs", "2"], &deepest.repeat(2));
+    assert_eq!(2, stdout.lines().count());
+    assert!(stdout.starts_with("foo::bar<fn() -> fn() -> "));
+    assert_eq!("", stderr);
+    assert_eq!(0, code);
+}
+
+/// Runs `std-mangle <args>` with `stdin` and `stdout`, and checks that it
+/// fails with `message` instead of panicking.
+#[cfg(target_os = "linux")]
+fn assert_io_error(args: &[&str], stdin: Stdio, stdout: Stdio, message: &str) {
+    let output = Command::new(BIN)
+        .args(args)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with(message), "{:?}: {}", args, stderr);
+    assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    assert_eq!(Some(1), output.status.code(), "{:?}", args);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn report_io_errors() {
+    use std::fs::{self, File};
+    use std::path::Path;
+
+    let symbols = Path::new(env!("CARGO_TARGET_TMPDIR")).join("symbols.txt");
+    fs::write(&symbols, "_RNvC7mycrate3foo\n".repeat(10_000)).unwrap();
+
+    for args in [&[][..], &["--unbuffered"], &["demangle"]] {
+        // Reading a directory fails with EISDIR.
+        assert_io_error(
+            args,
+            Stdio::from(File::open("/").unwrap()),
+            Stdio::piped(),
+            "std-mangle: failed to read stdin: ",
+        );
+        // Writing to /dev/full fails with ENOSPC.
+        assert_io_error(
+            args,
+            Stdio::from(File::open(&symbols).unwrap()),
+            Stdio::from(File::create("/dev/full").unwrap()),
+            "std-mangle: failed to write stdout: ",
+        );
+    }
+}